@@ -0,0 +1,54 @@
+-- The SQLite schema plus token transfers, with amounts as NUMERIC instead of decimal text
+CREATE TABLE blocks (
+    hash TEXT PRIMARY KEY,
+    number BIGINT NOT NULL,
+    parent_hash TEXT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    miner TEXT NOT NULL,
+    gas_used BIGINT NOT NULL,
+    gas_limit BIGINT NOT NULL,
+    base_fee_per_gas NUMERIC(78, 0),
+    burned_base_fee NUMERIC(78, 0),
+    state_diff_source TEXT NOT NULL,
+    replaced BOOLEAN NOT NULL DEFAULT FALSE
+);
+CREATE INDEX blocks_number ON blocks(number);
+
+CREATE TABLE transactions (
+    block_hash TEXT NOT NULL REFERENCES blocks(hash) ON DELETE CASCADE,
+    hash TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    from_address TEXT,
+    to_address TEXT,
+    created_contract TEXT,
+    value NUMERIC(78, 0) NOT NULL,
+    status TEXT NOT NULL,
+    gas_used NUMERIC(78, 0),
+    fee_paid NUMERIC(78, 0),
+    PRIMARY KEY (block_hash, hash)
+);
+
+CREATE TABLE state_changes (
+    block_hash TEXT NOT NULL REFERENCES blocks(hash) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    prev_balance NUMERIC(78, 0),
+    new_balance NUMERIC(78, 0),
+    balance_change NUMERIC(78, 0),
+    prev_nonce NUMERIC(78, 0),
+    new_nonce NUMERIC(78, 0),
+    nonce_change NUMERIC(78, 0),
+    PRIMARY KEY (block_hash, address)
+);
+CREATE INDEX state_changes_address ON state_changes(address);
+
+CREATE TABLE token_transfers (
+    block_hash TEXT NOT NULL REFERENCES blocks(hash) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    transaction_hash TEXT NOT NULL,
+    token TEXT NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT NOT NULL,
+    amount NUMERIC(78, 0) NOT NULL,
+    PRIMARY KEY (block_hash, position)
+);
+CREATE INDEX token_transfers_token ON token_transfers(token);
@@ -0,0 +1,283 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use web3::ethabi::{Contract, Event, ParamType, RawLog, Token};
+use web3::types::{Bytes, Log, H160, H256, U256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use crate::{format_address, BlockAnalysis};
+
+/// A receipt log from a contract with a known ABI, decoded where its signature matched an event
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedEvent {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    /// `None` when no event in the contract's ABI matches the log
+    pub name: Option<String>,
+    /// Canonical signature of the matched event, e.g. `Approval(address,address,uint256)`
+    pub signature: Option<String>,
+    pub params: Vec<DecodedParam>,
+    /// Raw topics, kept only when the log couldn't be decoded
+    pub topics: Vec<H256>,
+    /// Raw data, kept only when the log couldn't be decoded
+    pub data: Option<Bytes>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub indexed: bool,
+    /// Addresses and bytes are 0x-prefixed hex, integers decimal strings, tuples and arrays JSON arrays
+    pub value: Value,
+    /// Set when the value isn't what was emitted, as for hashed indexed parameters
+    pub note: Option<String>,
+}
+
+/// Contract ABIs by address, used to decode the logs those contracts emit
+#[derive(Debug, Clone, Default)]
+pub struct AbiRegistry {
+    contracts: HashMap<H160, Contract>,
+}
+
+impl AbiRegistry {
+    /// Read the ABI for `address` from a JSON file: either a plain ABI array or a build artifact
+    /// with an `abi` field, as Hardhat and Foundry write them
+    pub fn load_file(&mut self, address: H160, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+        let mut abi: Value = serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if let Some(inner) = abi.get_mut("abi") {
+            abi = inner.take();
+        }
+        let contract: Contract = serde_json::from_value(abi).map_err(|e| format!("{}: invalid ABI: {}", path.display(), e))?;
+        if self.contracts.insert(address, contract).is_some() {
+            return Err(format!("more than one ABI given for {}", format_address(&address)).into());
+        }
+        Ok(())
+    }
+
+    /// Read every `<address>.json` file in `dir`; other files are skipped
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("reading {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry?.path();
+            let address = path
+                .extension()
+                .filter(|extension| *extension == "json")
+                .and(path.file_stem())
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.to_lowercase().parse::<H160>().ok());
+            if let Some(address) = address {
+                self.load_file(address, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contracts.is_empty()
+    }
+
+    /// Fill in `decoded_events` on every transaction in `analysis` from the logs of contracts with an ABI
+    pub fn apply(&self, analysis: &mut BlockAnalysis) {
+        for tx in &mut analysis.block_info.transactions {
+            tx.decoded_events = tx
+                .logs
+                .iter()
+                .filter_map(|log| self.contracts.get(&log.address).map(|contract| decode_log(contract, log)))
+                .collect();
+        }
+    }
+}
+
+fn decode_log(contract: &Contract, log: &Log) -> DecodedEvent {
+    // Anonymous events have no signature topic to check, so they're only tried when nothing else matches
+    let named = contract.events().filter(|event| !event.anonymous);
+    let anonymous = contract.events().filter(|event| event.anonymous);
+    let matched = named.chain(anonymous).find_map(|event| {
+        if !event.anonymous && log.topics.first() != Some(&event.signature()) {
+            return None;
+        }
+        let raw = RawLog { topics: log.topics.clone(), data: log.data.0.clone() };
+        event.parse_log(raw).ok().map(|parsed| (event, parsed))
+    });
+
+    match matched {
+        Some((event, parsed)) => DecodedEvent {
+            address: log.address,
+            name: Some(event.name.clone()),
+            signature: Some(signature(event)),
+            params: event
+                .inputs
+                .iter()
+                .zip(parsed.params)
+                .map(|(input, param)| {
+                    // Only the Keccak-256 hash of an indexed dynamic value is logged
+                    let hashed = input.indexed && is_dynamic(&input.kind);
+                    DecodedParam {
+                        name: param.name,
+                        kind: input.kind.to_string(),
+                        indexed: input.indexed,
+                        value: token_to_json(&param.value),
+                        note: hashed.then(|| format!("indexed {}: topic is the Keccak-256 hash of the value", input.kind)),
+                    }
+                })
+                .collect(),
+            topics: Vec::new(),
+            data: None,
+        },
+        None => DecodedEvent {
+            address: log.address,
+            name: None,
+            signature: None,
+            params: Vec::new(),
+            topics: log.topics.clone(),
+            data: Some(log.data.clone()),
+        },
+    }
+}
+
+fn signature(event: &Event) -> String {
+    let kinds: Vec<String> = event.inputs.iter().map(|input| input.kind.to_string()).collect();
+    format!("{}({})", event.name, kinds.join(","))
+}
+
+fn is_dynamic(kind: &ParamType) -> bool {
+    matches!(kind, ParamType::String | ParamType::Bytes | ParamType::Array(_) | ParamType::FixedArray(..) | ParamType::Tuple(_))
+}
+
+fn token_to_json(token: &Token) -> Value {
+    match token {
+        Token::Address(address) => json!(format_address(address)),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => json!(Bytes(bytes.clone())),
+        // Signed values come sign-extended to 256 bits
+        Token::Int(value) if value.bit(255) => json!(format!("-{}", (!*value).overflowing_add(U256::one()).0)),
+        Token::Int(value) | Token::Uint(value) => json!(value.to_string()),
+        Token::Bool(value) => json!(value),
+        Token::String(value) => json!(value),
+        Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => Value::Array(tokens.iter().map(token_to_json).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::signing::keccak256;
+    use web3::Web3;
+
+    use crate::{analyze_block, MockTransport};
+
+    const ALICE: u64 = 0xa11ce;
+    const BOB: u64 = 0xb0b;
+    /// Has the ABI of `tests/fixtures/abi`: ERC-20's `Approval`, an `OrderFilled` with a tuple and a
+    /// `Tagged` with an indexed string
+    const EXCHANGE: u64 = 0xe20;
+
+    fn address(low: u64) -> H160 {
+        H160::from_low_u64_be(low)
+    }
+
+    fn word(value: U256) -> Vec<u8> {
+        let mut word = vec![0; 32];
+        value.to_big_endian(&mut word);
+        word
+    }
+
+    fn topic(address: H160) -> H256 {
+        address.into()
+    }
+
+    fn log(emitter: u64, topics: Vec<H256>, data: Vec<u8>) -> Log {
+        serde_json::from_value(json!({ "address": address(emitter), "topics": topics, "data": Bytes(data) })).unwrap()
+    }
+
+    fn registry() -> AbiRegistry {
+        let mut registry = AbiRegistry::default();
+        registry.load_dir("tests/fixtures/abi").unwrap();
+        registry
+    }
+
+    /// Block 3 of the fixture, its first transaction emitting `logs`, decoded with the fixture ABIs
+    async fn decode(logs: Vec<Log>) -> Vec<DecodedEvent> {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        let mut analysis = analyze_block(&web3, Some(3), 4).await.unwrap();
+        analysis.block_info.transactions[0].logs = logs;
+        registry().apply(&mut analysis);
+        analysis.block_info.transactions.swap_remove(0).decoded_events
+    }
+
+    fn params(event: &DecodedEvent) -> Vec<(&str, &str, bool, &Value)> {
+        event.params.iter().map(|param| (param.name.as_str(), param.kind.as_str(), param.indexed, &param.value)).collect()
+    }
+
+    #[tokio::test]
+    async fn decodes_an_erc20_approval() {
+        let approval = H256(keccak256(b"Approval(address,address,uint256)"));
+        let events = decode(vec![log(EXCHANGE, vec![approval, topic(address(ALICE)), topic(address(BOB))], word(1000.into()))]).await;
+
+        let [event] = events.as_slice() else { panic!("{:?}", events) };
+        assert_eq!((event.name.as_deref(), event.signature.as_deref()), (Some("Approval"), Some("Approval(address,address,uint256)")));
+        let (alice, bob) = (json!(format_address(&address(ALICE))), json!(format_address(&address(BOB))));
+        assert_eq!(params(event), [("owner", "address", true, &alice), ("spender", "address", true, &bob), ("value", "uint256", false, &json!("1000"))]);
+        assert!(event.params.iter().all(|param| param.note.is_none()));
+        assert!(event.topics.is_empty() && event.data.is_none());
+    }
+
+    #[tokio::test]
+    async fn decodes_a_custom_event_with_a_tuple() {
+        let order_filled = H256(keccak256(b"OrderFilled(bytes32,address,(address,uint256,int256),string)"));
+        let order_id = H256::from_low_u64_be(0x0bde);
+        // The tuple is static so it sits in the head, then the string's offset, length and bytes
+        let mut data = [word(U256::from(BOB)), word(250.into()), word(U256::MAX - 4), word(0x80.into()), word(6.into())].concat();
+        data.extend(b"filled");
+        data.resize(data.len() + 26, 0);
+        let tagged = H256(keccak256(b"Tagged(string,uint256)"));
+        let tag = H256(keccak256(b"urgent"));
+        let events = decode(vec![
+            log(EXCHANGE, vec![order_filled, order_id, topic(address(ALICE))], data),
+            log(EXCHANGE, vec![tagged, tag], word(9.into())),
+        ])
+        .await;
+
+        let [filled, tagged] = events.as_slice() else { panic!("{:?}", events) };
+        assert_eq!(filled.signature.as_deref(), Some("OrderFilled(bytes32,address,(address,uint256,int256),string)"));
+        let fill = json!([format_address(&address(BOB)), "250", "-5"]);
+        assert_eq!(
+            params(filled),
+            [
+                ("orderId", "bytes32", true, &json!(order_id)),
+                ("maker", "address", true, &json!(format_address(&address(ALICE)))),
+                ("fill", "(address,uint256,int256)", false, &fill),
+                ("memo", "string", false, &json!("filled")),
+            ]
+        );
+
+        // Only the hash of an indexed string is logged, and it's reported as such
+        assert_eq!(params(tagged), [("tag", "string", true, &json!(tag)), ("value", "uint256", false, &json!("9"))]);
+        assert_eq!(tagged.params[0].note.as_deref(), Some("indexed string: topic is the Keccak-256 hash of the value"));
+    }
+
+    #[tokio::test]
+    async fn logs_that_match_no_event_keep_their_raw_topics_and_data() {
+        let unknown = H256(keccak256(b"Unknown(uint256)"));
+        let events = decode(vec![log(EXCHANGE, vec![unknown], word(1.into())), log(BOB, vec![unknown], word(1.into()))]).await;
+
+        // The log from a contract without an ABI isn't decoded at all
+        let [event] = events.as_slice() else { panic!("{:?}", events) };
+        assert_eq!((event.address, event.name.as_ref(), event.params.len()), (address(EXCHANGE), None, 0));
+        assert_eq!(event.topics, [unknown]);
+        assert_eq!(event.data, Some(Bytes(word(1.into()))));
+    }
+
+    #[test]
+    fn an_address_is_given_one_abi() {
+        let mut registry = registry();
+        assert!(registry.contracts.contains_key(&address(EXCHANGE)));
+        let error = registry.load_file(address(EXCHANGE), "tests/fixtures/abi/0x0000000000000000000000000000000000000e20.json").unwrap_err();
+        assert!(error.to_string().contains("more than one ABI given"), "{}", error);
+    }
+}
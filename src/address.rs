@@ -0,0 +1,100 @@
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+use web3::signing::keccak256;
+use web3::types::{H160, H256};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once at startup by `--lowercase-addresses`; every printed and serialized address follows it
+static LOWERCASE_ADDRESSES: AtomicBool = AtomicBool::new(false);
+
+/// Print and serialize addresses as plain lowercase hex instead of EIP-55, for systems that
+/// normalize addresses differently. Applies to the whole process.
+pub fn set_lowercase_addresses(lowercase: bool) {
+    LOWERCASE_ADDRESSES.store(lowercase, Ordering::Relaxed);
+}
+
+/// The EIP-55 mixed-case form of `address`: each letter is uppercased when the matching nibble
+/// of the keccak256 of the lowercase hex is 8 or more
+pub fn to_checksum_address(address: &H160) -> String {
+    let hex: String = address.as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    let hash = keccak256(hex.as_bytes());
+    let mut checksummed = String::with_capacity(42);
+    checksummed.push_str("0x");
+    for (i, c) in hex.chars().enumerate() {
+        let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+        checksummed.push(if nibble >= 8 { c.to_ascii_uppercase() } else { c });
+    }
+    checksummed
+}
+
+/// `address` the way this process prints it: checksummed, unless lowercase was asked for
+pub fn format_address(address: &H160) -> String {
+    if LOWERCASE_ADDRESSES.load(Ordering::Relaxed) {
+        format!("{:?}", address)
+    } else {
+        to_checksum_address(address)
+    }
+}
+
+// `serialize_with` helpers, since H160's own Serialize is always lowercase
+
+pub(crate) fn serialize<S: Serializer>(address: &H160, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_address(address))
+}
+
+pub(crate) fn serialize_option<S: Serializer>(address: &Option<H160>, serializer: S) -> Result<S::Ok, S::Error> {
+    address.map(|address| format_address(&address)).serialize(serializer)
+}
+
+pub(crate) fn serialize_keys<S: Serializer, V: Serialize>(map: &BTreeMap<H160, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut entries = serializer.serialize_map(Some(map.len()))?;
+    for (address, value) in map {
+        entries.serialize_entry(&format_address(address), value)?;
+    }
+    entries.end()
+}
+
+pub(crate) fn serialize_access_list<S: Serializer>(
+    access_list: &Option<Vec<(H160, Vec<H256>)>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let Some(access_list) = access_list else { return serializer.serialize_none() };
+    let mut entries = serializer.serialize_seq(Some(access_list.len()))?;
+    for (address, keys) in access_list {
+        entries.serialize_element(&(format_address(address), keys))?;
+    }
+    entries.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The test cases of EIP-55: all caps, all lowercase, and mixed
+    const EIP55_VECTORS: [&str; 8] = [
+        "0x52908400098527886E0F7030069857D2E4169EE7",
+        "0x8617E340B3D01FA5F11F306F4090FD50E238070D",
+        "0xde709f2102306220921060314715629080e2fb77",
+        "0x27b1fdb04752bbc536007a920d24acb045561c26",
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn checksums_the_eip55_test_vectors() {
+        for vector in EIP55_VECTORS {
+            let address: H160 = vector.to_lowercase().parse().unwrap();
+            assert_eq!(to_checksum_address(&address), vector);
+        }
+    }
+
+    #[test]
+    fn leading_zeros_are_kept() {
+        let address = H160::from_low_u64_be(0xa11ce);
+        assert_eq!(to_checksum_address(&address).len(), 42);
+        assert!(to_checksum_address(&address).starts_with("0x000000000000000000000000000000000"));
+    }
+}
@@ -0,0 +1,413 @@
+use serde::{Serialize, Serializer};
+use web3::types::H160;
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::{parse_units, BlockAnalysis, StateChange, Wei};
+
+/// What an [`AlertRule`] looks at: a figure from the block summary, checked once per block, or a
+/// figure of each state change, checked for every changed address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AlertField {
+    Transactions,
+    RevertedTransactions,
+    ContractCreations,
+    UniqueSenders,
+    UniqueRecipients,
+    ChangedAddresses,
+    GasUsed,
+    GasUsedPercent,
+    ValueTransferred,
+    FeesPaid,
+    BaseFeeBurned,
+    PriorityFees,
+    MevPayment,
+    ProxyUpgrades,
+    CodeChanges,
+    BalanceIncrease,
+    BalanceDecrease,
+    NonceChange,
+}
+
+const FIELDS: &[(&str, AlertField)] = &[
+    ("transactions", AlertField::Transactions),
+    ("reverted_transactions", AlertField::RevertedTransactions),
+    ("contract_creations", AlertField::ContractCreations),
+    ("unique_senders", AlertField::UniqueSenders),
+    ("unique_recipients", AlertField::UniqueRecipients),
+    ("changed_addresses", AlertField::ChangedAddresses),
+    ("gas_used", AlertField::GasUsed),
+    ("gas_used_percent", AlertField::GasUsedPercent),
+    ("value_transferred", AlertField::ValueTransferred),
+    ("fees_paid", AlertField::FeesPaid),
+    ("base_fee_burned", AlertField::BaseFeeBurned),
+    ("priority_fees", AlertField::PriorityFees),
+    ("mev_payment", AlertField::MevPayment),
+    ("proxy_upgrades", AlertField::ProxyUpgrades),
+    ("code_changes", AlertField::CodeChanges),
+    ("balance_increase", AlertField::BalanceIncrease),
+    ("balance_decrease", AlertField::BalanceDecrease),
+    ("nonce_change", AlertField::NonceChange),
+];
+
+impl AlertField {
+    fn name(self) -> &'static str {
+        FIELDS.iter().find(|(_, field)| *field == self).map(|(name, _)| *name).expect("every field is listed")
+    }
+
+    /// Whether the field is checked per changed address rather than once per block
+    fn per_address(self) -> bool {
+        matches!(self, AlertField::BalanceIncrease | AlertField::BalanceDecrease | AlertField::NonceChange)
+    }
+
+    fn parse_value(self, s: &str) -> Result<AlertValue, String> {
+        match self {
+            AlertField::GasUsedPercent => s
+                .trim_end_matches('%')
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|percent| percent.is_finite())
+                .map(AlertValue::Percent)
+                .ok_or_else(|| format!("invalid percentage '{}' for {}: expected a number such as 95 or 95%", s, self.name())),
+            AlertField::ValueTransferred
+            | AlertField::FeesPaid
+            | AlertField::BaseFeeBurned
+            | AlertField::PriorityFees
+            | AlertField::MevPayment
+            | AlertField::BalanceIncrease
+            | AlertField::BalanceDecrease => parse_units(s).map(AlertValue::Amount),
+            _ => s
+                .parse::<u64>()
+                .map(AlertValue::Count)
+                .map_err(|_| format!("invalid count '{}' for {}: expected a whole number", s, self.name())),
+        }
+    }
+
+    fn block_value(self, analysis: &BlockAnalysis) -> Option<AlertValue> {
+        let summary = &analysis.summary;
+        let count = |n: usize| Some(AlertValue::Count(n as u64));
+        match self {
+            AlertField::Transactions => count(summary.transaction_count),
+            AlertField::RevertedTransactions => count(summary.reverted_transactions),
+            AlertField::ContractCreations => count(summary.contract_creations),
+            AlertField::UniqueSenders => count(summary.unique_senders),
+            AlertField::UniqueRecipients => count(summary.unique_recipients),
+            AlertField::ChangedAddresses => count(summary.changed_addresses),
+            AlertField::GasUsed => Some(AlertValue::Count(summary.gas_used)),
+            AlertField::GasUsedPercent => Some(AlertValue::Percent(summary.gas_used_percent)),
+            AlertField::ValueTransferred => Some(AlertValue::Amount(summary.value_transferred)),
+            AlertField::FeesPaid => Some(AlertValue::Amount(summary.fees_paid)),
+            AlertField::BaseFeeBurned => Some(AlertValue::Amount(summary.total_base_fee_burned)),
+            AlertField::PriorityFees => Some(AlertValue::Amount(summary.total_priority_fees)),
+            AlertField::MevPayment => summary.mev_payment.map(AlertValue::Amount),
+            AlertField::ProxyUpgrades => count(analysis.proxy_upgrades.len()),
+            AlertField::CodeChanges => count(analysis.code_changes.len()),
+            AlertField::BalanceIncrease | AlertField::BalanceDecrease | AlertField::NonceChange => None,
+        }
+    }
+
+    /// `None` when the change doesn't have the field: no balance queried, or a move the other way
+    fn change_value(self, change: &StateChange) -> Option<AlertValue> {
+        match self {
+            AlertField::BalanceIncrease => {
+                change.balance_change.filter(|delta| !delta.is_negative()).map(|delta| AlertValue::Amount(delta.magnitude))
+            }
+            AlertField::BalanceDecrease => {
+                change.balance_change.filter(|delta| delta.is_negative()).map(|delta| AlertValue::Amount(delta.magnitude))
+            }
+            AlertField::NonceChange => change.nonce_change.map(|delta| AlertValue::Count(delta.magnitude.low_u64())),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for AlertField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FIELDS.iter().find(|(name, _)| *name == s).map(|(_, field)| *field).ok_or_else(|| {
+            let names: Vec<&str> = FIELDS.iter().map(|(name, _)| *name).collect();
+            format!("unknown alert field '{}': expected one of {}", s, names.join(", "))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+/// Longest first, so `>=` isn't read as `>` followed by `=...`
+const COMPARISONS: &[(&str, Comparison)] = &[
+    (">=", Comparison::GreaterOrEqual),
+    ("<=", Comparison::LessOrEqual),
+    ("==", Comparison::Equal),
+    ("!=", Comparison::NotEqual),
+    (">", Comparison::Greater),
+    ("<", Comparison::Less),
+];
+
+impl Comparison {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::NotEqual => ordering != Ordering::Equal,
+        }
+    }
+}
+
+/// A figure an alert compares: counts and gas, a percentage of the gas limit, or an amount in wei
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertValue {
+    Count(u64),
+    Percent(f64),
+    Amount(Wei),
+}
+
+impl AlertValue {
+    /// Values of one field are always of the same kind
+    fn compare(self, threshold: AlertValue) -> Option<Ordering> {
+        match (self, threshold) {
+            (AlertValue::Count(value), AlertValue::Count(threshold)) => Some(value.cmp(&threshold)),
+            (AlertValue::Percent(value), AlertValue::Percent(threshold)) => value.partial_cmp(&threshold),
+            (AlertValue::Amount(value), AlertValue::Amount(threshold)) => Some(value.cmp(&threshold)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AlertValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertValue::Count(count) => write!(f, "{}", count),
+            AlertValue::Percent(percent) => write!(f, "{:.2}%", percent),
+            AlertValue::Amount(wei) => write!(f, "{} wei", wei),
+        }
+    }
+}
+
+impl Serialize for AlertValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            AlertValue::Count(count) => serializer.serialize_u64(*count),
+            AlertValue::Percent(percent) => serializer.serialize_f64(*percent),
+            AlertValue::Amount(wei) => wei.serialize(serializer),
+        }
+    }
+}
+
+/// A `field op value` condition such as `balance_decrease>100eth`, `reverted_transactions>10` or
+/// `gas_used_percent>=95`. Amounts take the unit suffixes `--min-balance-delta` does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    field: AlertField,
+    comparison: Comparison,
+    threshold: AlertValue,
+    /// As written, for messages
+    source: String,
+}
+
+impl std::str::FromStr for AlertRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = s.trim();
+        let position = COMPARISONS
+            .iter()
+            .filter_map(|(symbol, comparison)| source.find(symbol).map(|at| (at, *symbol, *comparison)))
+            .min_by_key(|(at, symbol, _)| (*at, std::cmp::Reverse(symbol.len())));
+        let Some((at, symbol, comparison)) = position else {
+            return Err(format!(
+                "invalid alert '{}': expected <field><op><value> such as balance_decrease>100eth, with op one of >, >=, <, <=, ==, !=",
+                s
+            ));
+        };
+        let (field, value) = (source[..at].trim(), source[at + symbol.len()..].trim());
+        if field.is_empty() || value.is_empty() {
+            return Err(format!("invalid alert '{}': both a field and a value are needed around '{}'", s, symbol));
+        }
+        let field: AlertField = field.parse()?;
+        let threshold = field.parse_value(value).map_err(|e| format!("invalid alert '{}': {}", s, e))?;
+        Ok(AlertRule { field, comparison, threshold, source: source.to_string() })
+    }
+}
+
+impl AlertRule {
+    fn matches(&self, value: AlertValue) -> bool {
+        value.compare(self.threshold).is_some_and(|ordering| self.comparison.holds(ordering))
+    }
+}
+
+/// One rule matched by one block, or by one address in it for per-address fields
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub block_number: u64,
+    #[serde(serialize_with = "crate::address::serialize_option")]
+    pub address: Option<H160>,
+    pub field: &'static str,
+    /// What the block or address had for `field`
+    pub value: AlertValue,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.address {
+            Some(address) => write!(
+                f,
+                "block {}: {} has {} {} ({})",
+                self.block_number,
+                crate::format_address(address),
+                self.field,
+                self.value,
+                self.rule
+            ),
+            None => write!(f, "block {}: {} is {} ({})", self.block_number, self.field, self.value, self.rule),
+        }
+    }
+}
+
+/// Every match of `rules` in `analysis`, rule by rule; per-address matches follow `state_changes` order
+pub fn evaluate_alerts(rules: &[AlertRule], analysis: &BlockAnalysis) -> Vec<Alert> {
+    let block_number = analysis.block_info.block_number;
+    let mut alerts = Vec::new();
+    for rule in rules {
+        let alert = |address: Option<H160>, value: AlertValue| Alert {
+            rule: rule.source.clone(),
+            block_number,
+            address,
+            field: rule.field.name(),
+            value,
+        };
+        if rule.field.per_address() {
+            for change in analysis.state_changes.iter().flatten() {
+                if let Some(value) = rule.field.change_value(change).filter(|value| rule.matches(*value)) {
+                    alerts.push(alert(Some(change.address), value));
+                }
+            }
+        } else if let Some(value) = rule.field.block_value(analysis).filter(|value| rule.matches(*value)) {
+            alerts.push(alert(None, value));
+        }
+    }
+    alerts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::types::U256;
+    use web3::Web3;
+
+    use crate::{analyze_block, MockTransport};
+
+    const ALICE: u64 = 0xa11ce;
+    const BOB: u64 = 0xb0b;
+    const CREATED: u64 = 0xc0ffee03;
+
+    /// Block 3 of the fixture: Alice sends Bob 0.5 ether and creates a contract with 0.1, and Bob's token
+    /// transfer reverts
+    async fn analysis() -> BlockAnalysis {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        analyze_block(&web3, Some(3), 4).await.unwrap()
+    }
+
+    fn rule(s: &str) -> AlertRule {
+        s.parse().unwrap()
+    }
+
+    fn ether(tenths: u64) -> Wei {
+        Wei::from(U256::from(tenths) * U256::exp10(17))
+    }
+
+    #[test]
+    fn parses_rules_of_each_value_kind() {
+        let parsed = rule("balance_decrease>100eth");
+        assert_eq!((parsed.field, parsed.comparison, parsed.threshold), (AlertField::BalanceDecrease, Comparison::Greater, AlertValue::Amount(ether(1000))));
+        let parsed = rule(" gas_used_percent >= 95% ");
+        assert_eq!((parsed.field, parsed.comparison, parsed.threshold), (AlertField::GasUsedPercent, Comparison::GreaterOrEqual, AlertValue::Percent(95.0)));
+        assert_eq!(parsed.source, "gas_used_percent >= 95%");
+        let parsed = rule("reverted_transactions!=0");
+        assert_eq!((parsed.field, parsed.comparison, parsed.threshold), (AlertField::RevertedTransactions, Comparison::NotEqual, AlertValue::Count(0)));
+        assert_eq!(rule("nonce_change<=2").comparison, Comparison::LessOrEqual);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_operators() {
+        let error = "balanse_decrease>1eth".parse::<AlertRule>().unwrap_err();
+        assert!(error.starts_with("unknown alert field 'balanse_decrease': expected one of transactions, "), "{}", error);
+        assert!(error.contains("balance_decrease"), "{}", error);
+
+        // A single '=' isn't an operator
+        for source in ["gas_used=5", "gas_used~5", "gas_used"] {
+            let error = source.parse::<AlertRule>().unwrap_err();
+            assert!(error.contains("with op one of >, >=, <, <=, ==, !="), "{}", error);
+        }
+        assert!(">5".parse::<AlertRule>().unwrap_err().contains("both a field and a value are needed around '>'"));
+        assert!("gas_used>".parse::<AlertRule>().unwrap_err().contains("both a field and a value"));
+    }
+
+    #[test]
+    fn rejects_values_of_the_wrong_kind() {
+        let error = "reverted_transactions>1.5".parse::<AlertRule>().unwrap_err();
+        assert_eq!(error, "invalid alert 'reverted_transactions>1.5': invalid count '1.5' for reverted_transactions: expected a whole number");
+        let error = "gas_used_percent>most".parse::<AlertRule>().unwrap_err();
+        assert!(error.contains("invalid percentage 'most' for gas_used_percent"), "{}", error);
+        assert!("gas_used_percent>inf".parse::<AlertRule>().is_err());
+        assert!("balance_increase>10lightyears".parse::<AlertRule>().is_err());
+    }
+
+    #[test]
+    fn compares_against_the_threshold() {
+        let cases = [
+            (">", [false, false, true]),
+            (">=", [false, true, true]),
+            ("<", [true, false, false]),
+            ("<=", [true, true, false]),
+            ("==", [false, true, false]),
+            ("!=", [true, false, true]),
+        ];
+        for (symbol, expected) in cases {
+            let rule = rule(&format!("transactions{}10", symbol));
+            let matched = [9, 10, 11].map(|count| rule.matches(AlertValue::Count(count)));
+            assert_eq!(matched, expected, "transactions{}10", symbol);
+        }
+
+        let rule = rule("fees_paid>=1gwei");
+        assert!(rule.matches(AlertValue::Amount(Wei::from(U256::exp10(9)))));
+        assert!(!rule.matches(AlertValue::Amount(Wei::from(U256::exp10(9) - 1))));
+        // A value of another kind never matches
+        assert!(!rule.matches(AlertValue::Count(u64::MAX)));
+    }
+
+    #[tokio::test]
+    async fn block_rules_match_once_and_address_rules_once_per_address() {
+        let analysis = analysis().await;
+        let rules = [rule("reverted_transactions>=1"), rule("transactions>3"), rule("balance_decrease>0.5eth"), rule("nonce_change>=1")];
+        let alerts = evaluate_alerts(&rules, &analysis);
+
+        let matched: Vec<(&str, Option<H160>)> = alerts.iter().map(|alert| (alert.rule.as_str(), alert.address)).collect();
+        let (alice, bob, created) = (H160::from_low_u64_be(ALICE), H160::from_low_u64_be(BOB), H160::from_low_u64_be(CREATED));
+        // Bob sent the reverted transaction, and the created contract starts at nonce 1
+        let expected = [
+            ("reverted_transactions>=1", None),
+            ("balance_decrease>0.5eth", Some(alice)),
+            ("nonce_change>=1", Some(bob)),
+            ("nonce_change>=1", Some(alice)),
+            ("nonce_change>=1", Some(created)),
+        ];
+        assert_eq!(matched, expected);
+
+        assert_eq!(alerts[0].to_string(), "block 3: reverted_transactions is 1 (reverted_transactions>=1)");
+        assert_eq!(alerts[1].field, "balance_decrease");
+    }
+}
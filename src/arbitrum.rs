@@ -0,0 +1,37 @@
+use web3::types::H160;
+
+use crate::BlockInfo;
+
+/// Arbitrum One and Arbitrum Sepolia
+const CHAIN_IDS: [u64; 2] = [42161, 421614];
+
+/// The transaction types ArbOS and its L1 bridge insert unsigned: deposits, unsigned and contract
+/// transactions from L1, retryable submissions and redemptions, and ArbOS's own internal ones
+pub(crate) const UNSIGNED_TX_TYPES: [u64; 6] = [0x64, 0x65, 0x66, 0x68, 0x69, 0x6a];
+
+/// Accounts ArbOS moves ether through in most blocks: its own sender address, its state account, the
+/// L1 pricer's funds pool, the batch poster standing in as coinbase, and the precompiles
+const SYSTEM_ADDRESSES: [&str; 4] = [
+    "00000000000000000000000000000000000a4b05",
+    "a4b05fffffffffffffffffffffffffffffffffff",
+    "a4b00000000000000000000000000000000000f6",
+    "a4b000000000000000000073657175656e636572",
+];
+
+/// The precompiles from ArbSys at 0x64 on, then NodeInterface and ArbDebug
+const PRECOMPILES: [u8; 17] = [0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f, 0x70, 0x71, 0x72, 0x73, 0xc8, 0xff];
+
+/// Whether the block is from an Arbitrum chain: a known chain id, or the L1 block number Arbitrum headers
+/// carry, as any Orbit chain's do
+pub(crate) fn is_arbitrum(chain_id: u64, block_info: &BlockInfo) -> bool {
+    CHAIN_IDS.contains(&chain_id) || block_info.l1_block_number.is_some()
+}
+
+/// Whether `address` is one of the ArbOS bookkeeping accounts `hide_system_addresses` leaves out
+pub(crate) fn is_system_address(address: &H160) -> bool {
+    let bytes = address.as_bytes();
+    if bytes[..19].iter().all(|byte| *byte == 0) && PRECOMPILES.contains(&bytes[19]) {
+        return true;
+    }
+    SYSTEM_ADDRESSES.contains(&format!("{:x}", address).as_str())
+}
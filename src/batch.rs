@@ -0,0 +1,196 @@
+use futures::stream::{self, StreamExt, TryStreamExt};
+use jsonrpc_core::Value;
+use web3::{BatchTransport, Web3};
+use std::error::Error;
+
+/// Send `calls` as JSON-RPC batches of at most `batch_size` requests, keeping at most `concurrency`
+/// batches in flight. Results come back in the order of `calls`; if any entry of any batch fails,
+/// the error names every failed call. `on_batch` is told how many calls have completed, in order.
+pub(crate) async fn call_batched<T: BatchTransport>(
+    web3: &Web3<T>,
+    calls: Vec<(&'static str, Vec<Value>)>,
+    batch_size: usize,
+    concurrency: usize,
+    mut on_batch: impl FnMut(usize),
+) -> Result<Vec<Value>, Box<dyn Error>> {
+    let transport = web3.transport();
+    let mut completed = 0;
+
+    let batches: Vec<Vec<web3::Result<Value>>> = stream::iter(calls.chunks(batch_size.max(1)))
+        .map(|chunk| {
+            let requests: Vec<_> = chunk.iter().map(|(method, params)| transport.prepare(method, params.clone())).collect();
+            transport.send_batch(requests)
+        })
+        .buffered(concurrency.max(1))
+        .inspect_ok(|batch| {
+            completed += batch.len();
+            on_batch(completed);
+        })
+        .try_collect()
+        .await?;
+
+    let mut values = Vec::with_capacity(calls.len());
+    let mut failures = Vec::new();
+    for ((method, params), result) in calls.iter().zip(batches.into_iter().flatten()) {
+        match result {
+            Ok(value) => values.push(value),
+            Err(e) => failures.push(format!("{}({}): {}", method, join_params(params), e)),
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(format!("{} of {} batched calls failed:\n  {}", failures.len(), calls.len(), failures.join("\n  ")).into());
+    }
+    Ok(values)
+}
+
+fn join_params(params: &[Value]) -> String {
+    params.iter().map(Value::to_string).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::BoxFuture;
+    use jsonrpc_core::Call;
+    use serde_json::json;
+    use web3::types::H160;
+    use web3::{RequestId, Transport};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::{analyze_block_with, AnalysisOptions, BlockRef, MockTransport};
+
+    /// Answers from a mock after a pause, counting the requests and batches awaiting an answer
+    #[derive(Debug, Clone)]
+    struct CountingTransport {
+        inner: MockTransport,
+        in_flight: Arc<AtomicUsize>,
+        most_in_flight: Arc<AtomicUsize>,
+    }
+
+    impl CountingTransport {
+        fn new(inner: MockTransport) -> Self {
+            CountingTransport { inner, in_flight: Arc::default(), most_in_flight: Arc::default() }
+        }
+
+        fn most_in_flight(&self) -> usize {
+            self.most_in_flight.load(Ordering::SeqCst)
+        }
+
+        fn counted<R: Send + 'static>(&self, answer: impl std::future::Future<Output = R> + Send + 'static) -> BoxFuture<'static, R> {
+            let this = self.clone();
+            Box::pin(async move {
+                let in_flight = this.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                this.most_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                this.in_flight.fetch_sub(1, Ordering::SeqCst);
+                answer.await
+            })
+        }
+    }
+
+    impl Transport for CountingTransport {
+        type Out = BoxFuture<'static, web3::Result<Value>>;
+
+        fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+            self.inner.prepare(method, params)
+        }
+
+        fn send(&self, id: RequestId, request: Call) -> Self::Out {
+            let inner = self.inner.clone();
+            self.counted(async move { inner.send(id, request).await })
+        }
+    }
+
+    impl BatchTransport for CountingTransport {
+        type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+        fn send_batch<I>(&self, requests: I) -> Self::Batch
+        where
+            I: IntoIterator<Item = (RequestId, Call)>,
+        {
+            let (inner, requests) = (self.inner.clone(), requests.into_iter().collect::<Vec<_>>());
+            self.counted(async move { inner.send_batch(requests).await })
+        }
+    }
+
+    fn balance_call(address: u64) -> (&'static str, Vec<Value>) {
+        ("eth_getBalance", vec![json!(H160::from_low_u64_be(address)), json!("0x1")])
+    }
+
+    fn mock(addresses: std::ops::RangeInclusive<u64>) -> MockTransport {
+        addresses.fold(MockTransport::new(), |mock, address| {
+            let (method, params) = balance_call(address);
+            mock.with_response(method, json!(params), json!(format!("0x{:x}", address * 10)))
+        })
+    }
+
+    #[tokio::test]
+    async fn sends_one_round_trip_per_batch_and_keeps_the_order() {
+        let mock = mock(1..=250);
+        let web3 = Web3::new(mock.clone());
+        let mut progress = Vec::new();
+        let calls = (1..=250).map(balance_call).collect();
+        let values = call_batched(&web3, calls, 100, 2, |completed| progress.push(completed)).await.unwrap();
+
+        assert_eq!(mock.round_trips(), 3);
+        assert_eq!(progress, [100, 200, 250]);
+        let expected: Vec<Value> = (1..=250u64).map(|address| json!(format!("0x{:x}", address * 10))).collect();
+        assert_eq!(values, expected);
+    }
+
+    #[tokio::test]
+    async fn names_every_failed_entry_of_a_partly_failed_batch() {
+        let (method, params) = balance_call(4);
+        let mock = mock(1..=5).with_error(method, json!(params), jsonrpc_core::Error::internal_error());
+        let web3 = Web3::new(mock);
+        let mut calls: Vec<_> = (1..=5).map(balance_call).collect();
+        calls.push(balance_call(6));
+
+        let error = call_batched(&web3, calls, 4, 1, |_| {}).await.unwrap_err().to_string();
+        let mut lines = error.lines();
+        assert_eq!(lines.next(), Some("2 of 6 batched calls failed:"));
+        let failed: Vec<&str> = lines.collect();
+        assert_eq!(failed.len(), 2, "{}", error);
+        assert!(failed[0].starts_with(r#"  eth_getBalance("0x0000000000000000000000000000000000000004", "0x1"): "#), "{}", failed[0]);
+        assert!(failed[1].contains(r#""0x0000000000000000000000000000000000000006""#) && failed[1].contains("no response"), "{}", failed[1]);
+    }
+
+    /// Block 3 of the fixture touches six addresses, each looked up at two blocks with two methods
+    #[tokio::test]
+    async fn state_queries_go_out_in_batches() {
+        let mock = MockTransport::load("tests/fixtures/block_3.json").unwrap();
+        let web3 = Web3::new(mock.clone());
+        let options = AnalysisOptions::new().with_batch_size(10);
+        analyze_block_with(&web3, BlockRef::Number(3), &options).await.unwrap();
+
+        let state_queries = mock.calls("eth_getBalance") + mock.calls("eth_getTransactionCount");
+        assert_eq!(state_queries, 24);
+        let single_requests = mock.requests().len() - state_queries;
+        assert_eq!(mock.round_trips(), single_requests + 3);
+    }
+
+    #[tokio::test]
+    async fn keeps_at_most_concurrency_batches_in_flight() {
+        for concurrency in [1, 3, 8] {
+            let transport = CountingTransport::new(mock(1..=100));
+            let calls = (1..=100).map(balance_call).collect();
+            let values = call_batched(&Web3::new(transport.clone()), calls, 10, concurrency, |_| {}).await.unwrap();
+            assert_eq!(values.len(), 100);
+            // Ten batches are enough to fill every slot
+            assert_eq!(transport.most_in_flight(), concurrency);
+        }
+    }
+
+    #[tokio::test]
+    async fn an_analysis_keeps_to_its_concurrency() {
+        for concurrency in [1, 2] {
+            let transport = CountingTransport::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+            let options = AnalysisOptions::new().with_batch_size(2).with_concurrency(concurrency);
+            analyze_block_with(&Web3::new(transport.clone()), BlockRef::Number(3), &options).await.unwrap();
+            assert_eq!(transport.most_in_flight(), concurrency);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use futures::future::BoxFuture;
+use jsonrpc_core::{Call, Params, Value};
+use web3::signing::keccak256;
+use web3::{BatchTransport, RequestId, Transport};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Blocks this far below the head are taken to be final, and responses pinned to them are cached
+pub const FINALITY_DEPTH: u64 = 64;
+
+#[derive(Debug, Default)]
+struct Stats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug)]
+struct DiskCache {
+    /// Already specific to the chain
+    dir: PathBuf,
+    stats: Stats,
+    /// Highest block treated as final, looked up on the first response that could be cached
+    horizon: Mutex<Option<u64>>,
+}
+
+/// Transport wrapper that keeps responses which can't change on disk: blocks, receipts, and balances,
+/// nonces, code, calls and traces pinned to a final block. Anything asked at a tag such as `latest`,
+/// and any `null` or error response, always goes to the node.
+#[derive(Debug, Clone)]
+pub struct CachingTransport<T> {
+    inner: T,
+    cache: Option<Arc<DiskCache>>,
+}
+
+impl<T> CachingTransport<T> {
+    /// Cache responses for `chain_id` under `dir`, which is created if needed
+    pub fn new(inner: T, dir: impl AsRef<Path>, chain_id: u64) -> std::io::Result<Self> {
+        let dir = dir.as_ref().join(chain_id.to_string());
+        std::fs::create_dir_all(&dir)?;
+        let cache = DiskCache { dir, stats: Stats::default(), horizon: Mutex::new(None) };
+        Ok(CachingTransport { inner, cache: Some(Arc::new(cache)) })
+    }
+
+    /// Pass every request through, for `--no-cache`
+    pub fn disabled(inner: T) -> Self {
+        CachingTransport { inner, cache: None }
+    }
+
+    /// `(hits, misses)` so far, counting only requests that could have been answered from the cache;
+    /// `None` when caching is disabled
+    pub fn stats(&self) -> Option<(u64, u64)> {
+        self.cache.as_ref().map(|cache| (cache.stats.hits.load(Ordering::Relaxed), cache.stats.misses.load(Ordering::Relaxed)))
+    }
+}
+
+/// The default cache location, `$XDG_CACHE_HOME/state-diff` or `~/.cache/state-diff`
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("state-diff"))
+}
+
+/// What a cacheable request is pinned to
+#[derive(Debug, Clone, Copy)]
+enum Pinned {
+    /// A block number, which must be final before the response is kept
+    Block(u64),
+    /// A block hash, whose response never changes
+    Hash,
+    /// A transaction hash; whether the response is final depends on the block it names
+    Transaction,
+}
+
+impl DiskCache {
+    fn path(&self, call: &Call) -> Option<(PathBuf, Pinned)> {
+        let Call::MethodCall(call) = call else { return None };
+        let Params::Array(params) = &call.params else { return None };
+        let block = match call.method.as_str() {
+            "eth_getBalance" | "eth_getTransactionCount" | "eth_getCode" | "eth_getStorageAt" | "eth_call" => params.last(),
+            "eth_getBlockByNumber" | "eth_getBlockReceipts" | "trace_replayBlockTransactions" | "trace_block" | "debug_traceBlockByNumber" => {
+                params.first()
+            }
+            _ => None,
+        };
+        let pin = match (call.method.as_str(), block) {
+            // Tags such as `latest` don't parse as numbers, so they're never cached
+            (_, Some(block)) => Pinned::Block(block.as_str().and_then(parse_number)?),
+            ("eth_getBlockByHash" | "eth_getUncleByBlockHashAndIndex", _) => Pinned::Hash,
+            ("eth_getTransactionReceipt", _) => Pinned::Transaction,
+            _ => return None,
+        };
+
+        let key = format!("{}{}", call.method, Value::Array(params.clone()));
+        let name: String = keccak256(key.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect();
+        Some((self.dir.join(format!("{}.json", name)), pin))
+    }
+
+    fn read(&self, path: &Path) -> Option<Value> {
+        let value = std::fs::read(path).ok().and_then(|contents| serde_json::from_slice(&contents).ok());
+        let counter = if value.is_some() { &self.stats.hits } else { &self.stats.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// Keep `response` if it's final; failing to write only loses the cache entry
+    async fn write<T: Transport>(&self, inner: &T, path: &Path, pin: Pinned, response: &Value) {
+        if response.is_null() {
+            return;
+        }
+        let block = match pin {
+            Pinned::Block(number) => Some(number),
+            Pinned::Hash => None,
+            Pinned::Transaction => match response.get("blockNumber").and_then(Value::as_str).and_then(parse_number) {
+                Some(number) => Some(number),
+                None => return,
+            },
+        };
+        if let Some(number) = block {
+            match self.horizon(inner).await {
+                Some(horizon) if number <= horizon => {}
+                _ => return,
+            }
+        }
+
+        // Write to a temporary file first so a concurrent reader never sees half an entry
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        if std::fs::write(&temporary, response.to_string()).is_ok() && std::fs::rename(&temporary, path).is_err() {
+            let _ = std::fs::remove_file(&temporary);
+        }
+    }
+
+    async fn horizon<T: Transport>(&self, inner: &T) -> Option<u64> {
+        if let Some(horizon) = *self.horizon.lock().expect("cache horizon lock poisoned") {
+            return Some(horizon);
+        }
+        let head = inner.execute("eth_blockNumber", vec![]).await.ok()?;
+        let horizon = head.as_str().and_then(parse_number)?.checked_sub(FINALITY_DEPTH)?;
+        *self.horizon.lock().expect("cache horizon lock poisoned") = Some(horizon);
+        Some(horizon)
+    }
+}
+
+impl<T> Transport for CachingTransport<T>
+where
+    T: Transport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+{
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        let inner = self.inner.clone();
+        let Some((cache, (path, pin))) = self.cache.clone().and_then(|cache| cache.path(&request).map(|key| (cache, key))) else {
+            return Box::pin(inner.send(id, request));
+        };
+        Box::pin(async move {
+            if let Some(response) = cache.read(&path) {
+                return Ok(response);
+            }
+            let response = inner.send(id, request).await?;
+            cache.write(&inner, &path, pin, &response).await;
+            Ok(response)
+        })
+    }
+}
+
+impl<T> BatchTransport for CachingTransport<T>
+where
+    T: BatchTransport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    /// Only the requests the cache can't answer are sent, still as one batch
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let inner = self.inner.clone();
+        let requests: Vec<(RequestId, Call)> = requests.into_iter().collect();
+        let Some(cache) = self.cache.clone() else {
+            return Box::pin(inner.send_batch(requests));
+        };
+        Box::pin(async move {
+            let keys: Vec<Option<(PathBuf, Pinned)>> = requests.iter().map(|(_, call)| cache.path(call)).collect();
+            let mut responses: Vec<Option<web3::Result<Value>>> =
+                keys.iter().map(|key| key.as_ref().and_then(|(path, _)| cache.read(path)).map(Ok)).collect();
+
+            let misses: Vec<usize> = (0..requests.len()).filter(|index| responses[*index].is_none()).collect();
+            if !misses.is_empty() {
+                let fetched = inner.send_batch(misses.iter().map(|index| requests[*index].clone())).await?;
+                for (index, response) in misses.into_iter().zip(fetched) {
+                    if let (Some((path, pin)), Ok(value)) = (&keys[index], &response) {
+                        cache.write(&inner, path, *pin, value).await;
+                    }
+                    responses[index] = Some(response);
+                }
+            }
+            Ok(responses.into_iter().map(|response| response.unwrap_or(Err(web3::Error::Unreachable))).collect())
+        })
+    }
+}
+
+fn parse_number(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex.strip_prefix("0x")?, 16).ok()
+}
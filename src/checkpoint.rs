@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Progress of a block-range scan, saved after every block so an interrupted run can pick up
+/// where it left off. Any SQLite rows for a block are written before its checkpoint is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub rpc_url: String,
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Highest block analyzed successfully so far; blocks before it that failed are not retried
+    pub last_block: Option<u64>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    /// Load the checkpoint at `path`, or start a new one if there is none yet.
+    /// A checkpoint written for another endpoint or range is rejected rather than silently reused.
+    pub fn resume(path: impl AsRef<Path>, rpc_url: &str, from_block: u64, to_block: u64) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Checkpoint { rpc_url: rpc_url.to_string(), from_block, to_block, last_block: None, path: path.into() });
+        }
+
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if checkpoint.rpc_url != rpc_url || checkpoint.from_block != from_block || checkpoint.to_block != to_block {
+            return Err(format!(
+                "checkpoint {} is for blocks {}..={} on {}, not {}..={} on {}; use another file or delete it to start over",
+                path.display(),
+                checkpoint.from_block,
+                checkpoint.to_block,
+                checkpoint.rpc_url,
+                from_block,
+                to_block,
+                rpc_url
+            )
+            .into());
+        }
+        Ok(Checkpoint { path: path.into(), ..checkpoint })
+    }
+
+    /// First block still to analyze; past `to_block` once the scan is complete
+    pub fn next_block(&self) -> u64 {
+        self.last_block.map_or(self.from_block, |last| last + 1)
+    }
+
+    /// Mark `block_number` as done and save. The file is replaced atomically, so an interrupted
+    /// write leaves the previous checkpoint intact.
+    pub fn record(&mut self, block_number: u64) -> Result<(), Box<dyn Error>> {
+        self.last_block = Some(block_number);
+        let temp = self.path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use serde::Serialize;
+use serde_json::json;
+use web3::signing::keccak256;
+use web3::types::{BlockNumber, Bytes, H160, H256, U64};
+use web3::{BatchTransport, Web3};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+use crate::{batch, AnalysisOptions, BlockInfo, StateChange};
+
+/// A touched address whose code differs between the baseline and the analyzed block: a contract created,
+/// self-destructed, or redeployed with different code at the same address
+#[derive(Debug, Clone, Serialize)]
+pub struct CodeChange {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    /// The keccak256 of the code; that of empty code when there was none
+    pub old_code_hash: H256,
+    pub new_code_hash: H256,
+    /// In bytes
+    pub old_size: usize,
+    pub new_size: usize,
+}
+
+/// An account's code at one block, as much of it as needs remembering
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CodeHash {
+    hash: H256,
+    size: usize,
+}
+
+/// Compare the code of every address the block touched, at `baseline` and at the block, with `eth_getCode`
+/// batched. `known` holds the hashes from earlier blocks of a run, which cover the baseline of the next
+/// one; it's left with the analyzed block's.
+pub(crate) async fn detect_code_changes<T: BatchTransport>(
+    web3: &Web3<T>,
+    baseline: u64,
+    block_info: &BlockInfo,
+    state_changes: &[StateChange],
+    options: &AnalysisOptions,
+    known: &mut HashMap<(H160, u64), CodeHash>,
+) -> Result<Vec<CodeChange>, Box<dyn Error>> {
+    let block = block_info.block_number;
+    let touched: BTreeSet<H160> = block_info
+        .transactions
+        .iter()
+        .flat_map(|tx| [tx.from, tx.to, tx.created_contract])
+        .flatten()
+        .chain(state_changes.iter().map(|change| change.address))
+        .filter(|address| options.includes(address))
+        .collect();
+
+    let lookups: Vec<(H160, u64)> = touched
+        .iter()
+        .flat_map(|address| [(*address, baseline), (*address, block)])
+        .filter(|key| !known.contains_key(key))
+        .collect();
+    if !lookups.is_empty() {
+        let calls = lookups
+            .iter()
+            .map(|(address, block)| ("eth_getCode", vec![json!(address), json!(BlockNumber::Number(U64::from(*block)))]))
+            .collect();
+        let codes = batch::call_batched(web3, calls, options.batch_size, options.concurrency, |_| {}).await?;
+        for (key, code) in lookups.into_iter().zip(codes) {
+            let code: Bytes = serde_json::from_value(code).map_err(|e| format!("invalid code for {:?}: {}", key.0, e))?;
+            known.insert(key, CodeHash { hash: H256(keccak256(&code.0)), size: code.0.len() });
+        }
+    }
+
+    let mut changes = Vec::new();
+    for address in &touched {
+        let (old, new) = (known[&(*address, baseline)], known[&(*address, block)]);
+        if old.hash != new.hash {
+            changes.push(CodeChange {
+                address: *address,
+                old_code_hash: old.hash,
+                new_code_hash: new.hash,
+                old_size: old.size,
+                new_size: new.size,
+            });
+        }
+    }
+    // Only this block's hashes can serve the next one
+    known.retain(|(_, at), _| *at == block);
+    Ok(changes)
+}
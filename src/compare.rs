@@ -0,0 +1,70 @@
+use serde::Serialize;
+use web3::types::H160;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+
+use crate::{format_address, BlockAnalysis, StateChange};
+
+/// A field on which two analyses of the same block disagree
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    /// Path of the field, e.g. `transactions[3].status` or `state_changes[0xab…].new_balance`
+    pub field: String,
+    pub primary: String,
+    pub secondary: String,
+}
+
+/// Every field-level difference between two analyses of the same block, typically from two providers.
+/// Transactions are matched by position and state changes by address.
+pub fn compare_analyses(primary: &BlockAnalysis, secondary: &BlockAnalysis) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+    let mut check = |field: String, a: &dyn Debug, b: &dyn Debug| {
+        let (a, b) = (format!("{:?}", a), format!("{:?}", b));
+        if a != b {
+            divergences.push(Divergence { field, primary: a, secondary: b });
+        }
+    };
+
+    let (a, b) = (&primary.block_info, &secondary.block_info);
+    check("hash".to_string(), &a.hash, &b.hash);
+    check("parent_hash".to_string(), &a.parent_hash, &b.parent_hash);
+    check("timestamp".to_string(), &a.timestamp, &b.timestamp);
+    check("miner".to_string(), &a.miner, &b.miner);
+    check("gas_used".to_string(), &a.gas_used, &b.gas_used);
+    check("gas_limit".to_string(), &a.gas_limit, &b.gas_limit);
+    check("base_fee_per_gas".to_string(), &a.base_fee_per_gas, &b.base_fee_per_gas);
+    check("transaction_count".to_string(), &a.transactions.len(), &b.transactions.len());
+    check("withdrawal_count".to_string(), &a.withdrawals.len(), &b.withdrawals.len());
+    check("uncle_count".to_string(), &a.uncles.len(), &b.uncles.len());
+
+    for (index, (a, b)) in a.transactions.iter().zip(&b.transactions).enumerate() {
+        let field = |name: &str| format!("transactions[{}].{}", index, name);
+        check(field("hash"), &a.hash, &b.hash);
+        check(field("status"), &a.status, &b.status);
+        check(field("gas_used"), &a.gas_used, &b.gas_used);
+        check(field("effective_gas_price"), &a.effective_gas_price, &b.effective_gas_price);
+        check(field("created_contract"), &a.created_contract, &b.created_contract);
+        check(field("log_count"), &a.logs.len(), &b.logs.len());
+    }
+
+    let (a, b) = (by_address(primary.state_changes.as_deref().unwrap_or_default()), by_address(secondary.state_changes.as_deref().unwrap_or_default()));
+    for address in a.keys().chain(b.keys()).collect::<BTreeSet<_>>() {
+        let field = |name: &str| format!("state_changes[{}].{}", format_address(address), name);
+        match (a.get(address), b.get(address)) {
+            (Some(a), Some(b)) => {
+                check(field("prev_balance"), &a.prev_balance, &b.prev_balance);
+                check(field("new_balance"), &a.new_balance, &b.new_balance);
+                check(field("prev_nonce"), &a.prev_nonce, &b.prev_nonce);
+                check(field("new_nonce"), &a.new_nonce, &b.new_nonce);
+                check(field("storage_change_count"), &a.storage_changes.len(), &b.storage_changes.len());
+            }
+            (a, b) => check(field("present"), &a.is_some(), &b.is_some()),
+        }
+    }
+
+    divergences
+}
+
+fn by_address(changes: &[StateChange]) -> BTreeMap<H160, &StateChange> {
+    changes.iter().map(|change| (change.address, change)).collect()
+}
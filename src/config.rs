@@ -0,0 +1,62 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Named sets of CLI defaults, read from a `state-diff.toml` like
+///
+/// ```toml
+/// [profiles.mainnet]
+/// rpc_url = "https://eth.example.com"
+/// units = "eth"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Defaults for one endpoint; anything left out falls back to the built-in default
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub rpc_url: Option<String>,
+    pub concurrency: Option<usize>,
+    pub max_retries: Option<u32>,
+    /// eth, gwei or wei
+    pub units: Option<String>,
+    pub native_symbol: Option<String>,
+    /// text, json or ndjson
+    pub format: Option<String>,
+    /// Rules as `--alert` takes them, e.g. `["balance_decrease>100eth"]`; an `--alert` flag replaces them all
+    pub alerts: Option<Vec<String>>,
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+        toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// `$XDG_CONFIG_HOME/state-diff.toml`, or `~/.config/state-diff.toml` when that isn't set
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("state-diff.toml"))
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, Box<dyn Error>> {
+        self.profiles.get(name).ok_or_else(|| {
+            let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            if available.is_empty() {
+                format!("no profile named '{}'; the config file defines no profiles", name).into()
+            } else {
+                format!("no profile named '{}'; available profiles: {}", name, available.join(", ")).into()
+            }
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use futures::stream::{self, StreamExt};
+use web3::ethabi::{self, ParamType, Token};
+use web3::signing::keccak256;
+use web3::types::{Bytes, CallRequest, H160, H256};
+use web3::Web3;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::RpcTransport;
+
+/// The ENS registry on Ethereum mainnet
+pub const MAINNET_ENS_REGISTRY: &str = "0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e";
+
+/// `resolver(bytes32)` on the registry
+const RESOLVER_SELECTOR: [u8; 4] = [0x01, 0x78, 0xb8, 0xbf];
+/// `name(bytes32)` on a reverse resolver
+const NAME_SELECTOR: [u8; 4] = [0x69, 0x1f, 0x34, 0x31];
+/// `addr(bytes32)` on a public resolver
+const ADDR_SELECTOR: [u8; 4] = [0x3b, 0x3b, 0x57, 0xde];
+
+/// A single lookup shouldn't hold up the analysis for long
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reverse ENS lookups against a registry on any chain, cached for as long as the resolver
+/// (or any clone of it) lives. Names are only accepted if they resolve back to the same address.
+#[derive(Debug, Clone)]
+pub struct EnsResolver {
+    web3: Web3<RpcTransport>,
+    registry: H160,
+    names: Arc<Mutex<HashMap<H160, Option<String>>>>,
+}
+
+impl EnsResolver {
+    pub fn new(web3: Web3<RpcTransport>, registry: H160) -> Self {
+        EnsResolver { web3, registry, names: Arc::default() }
+    }
+
+    /// Primary names for whichever of `addresses` have one. Failed or timed-out lookups are
+    /// treated as "no name" and never fail the caller.
+    pub async fn resolve_all(&self, addresses: impl IntoIterator<Item = H160>, concurrency: usize) -> BTreeMap<H160, String> {
+        let addresses: Vec<H160> = addresses.into_iter().collect();
+        let pending: Vec<H160> = {
+            let names = self.names.lock().expect("ENS cache lock poisoned");
+            addresses.iter().filter(|address| !names.contains_key(address)).copied().collect()
+        };
+
+        let resolved: Vec<(H160, Option<String>)> = stream::iter(pending)
+            .map(|address| async move {
+                let name = tokio::time::timeout(LOOKUP_TIMEOUT, self.reverse_lookup(address)).await.ok().flatten();
+                (address, name)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut names = self.names.lock().expect("ENS cache lock poisoned");
+        names.extend(resolved);
+        addresses
+            .into_iter()
+            .filter_map(|address| names.get(&address).cloned().flatten().map(|name| (address, name)))
+            .collect()
+    }
+
+    async fn reverse_lookup(&self, address: H160) -> Option<String> {
+        let reverse_node = namehash(&format!("{:x}.addr.reverse", address));
+        let reverse_resolver = self.resolver(reverse_node).await?;
+        let name = match self.call(reverse_resolver, NAME_SELECTOR, reverse_node, ParamType::String).await? {
+            Token::String(name) if !name.is_empty() => name,
+            _ => return None,
+        };
+
+        // Anyone can claim any name in their reverse record, so check the forward record agrees
+        let node = namehash(&name);
+        let resolver = self.resolver(node).await?;
+        match self.call(resolver, ADDR_SELECTOR, node, ParamType::Address).await? {
+            Token::Address(forward) if forward == address => Some(name),
+            _ => None,
+        }
+    }
+
+    async fn resolver(&self, node: H256) -> Option<H160> {
+        match self.call(self.registry, RESOLVER_SELECTOR, node, ParamType::Address).await? {
+            Token::Address(resolver) if !resolver.is_zero() => Some(resolver),
+            _ => None,
+        }
+    }
+
+    async fn call(&self, to: H160, selector: [u8; 4], node: H256, output: ParamType) -> Option<Token> {
+        let data = [&selector[..], node.as_bytes()].concat();
+        let request = CallRequest { to: Some(to), data: Some(Bytes(data)), ..CallRequest::default() };
+        let result = self.web3.eth().call(request, None).await.ok()?;
+        ethabi::decode(&[output], &result.0).ok()?.into_iter().next()
+    }
+}
+
+/// EIP-137 namehash
+fn namehash(name: &str) -> H256 {
+    let mut node = [0u8; 32];
+    for label in name.rsplit('.').filter(|label| !label.is_empty()) {
+        node = keccak256(&[node, keccak256(label.as_bytes())].concat());
+    }
+    H256(node)
+}
@@ -0,0 +1,261 @@
+use futures::future::BoxFuture;
+use jsonrpc_core::{Call, Params, Value};
+use web3::helpers::build_request;
+use web3::types::H256;
+use web3::{BatchTransport, RequestId, Transport};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::is_retryable;
+
+/// Consecutive failures after which an endpoint is benched
+const BENCH_AFTER_FAILURES: u32 = 3;
+/// How long a benched endpoint is skipped before it gets another chance
+const BENCH_DURATION: Duration = Duration::from_secs(30);
+/// Block hashes remembered for cross-endpoint checks
+const TRACKED_BLOCKS: usize = 256;
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    benched_until: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    health: Vec<Health>,
+    /// Endpoint that answered last; requests start there
+    preferred: usize,
+    /// Block hash by number, and the endpoint that reported it
+    hashes: BTreeMap<u64, (usize, H256)>,
+    /// Whether an endpoint was found to agree with `hashes` about a block
+    verified: HashMap<(usize, u64), bool>,
+}
+
+/// Transport over several endpoints for the same chain. A request that fails with a transient error
+/// (after any retries the endpoint's own transport does) moves on to the next endpoint, and an endpoint
+/// that keeps failing is benched for a while.
+///
+/// Block-scoped state queries are only sent to an endpoint other than the one the block came from if it
+/// reports the same hash for that block, so one analysis never mixes data from diverging chains.
+#[derive(Debug, Clone)]
+pub struct FailoverTransport<T> {
+    endpoints: Arc<Vec<(String, T)>>,
+    state: Arc<Mutex<State>>,
+    next_id: Arc<AtomicUsize>,
+}
+
+impl<T> FailoverTransport<T> {
+    /// `endpoints` are `(url, transport)` pairs, tried in order; the URL is only used in messages
+    pub fn new(endpoints: Vec<(String, T)>) -> Self {
+        let state = State { health: endpoints.iter().map(|_| Health::default()).collect(), ..State::default() };
+        FailoverTransport { endpoints: Arc::new(endpoints), state: Arc::new(Mutex::new(state)), next_id: Arc::default() }
+    }
+}
+
+impl<T: Clone> FailoverTransport<T> {
+    /// Healthy endpoints from the preferred one onwards, then benched ones as a last resort
+    fn order(&self) -> Vec<usize> {
+        let state = self.state.lock().expect("failover state lock poisoned");
+        let now = Instant::now();
+        let count = self.endpoints.len();
+        let (healthy, benched): (Vec<usize>, Vec<usize>) = (0..count)
+            .map(|offset| (state.preferred + offset) % count)
+            .partition(|&index| state.health[index].benched_until.is_none_or(|until| until <= now));
+        healthy.into_iter().chain(benched).collect()
+    }
+
+    fn succeeded(&self, index: usize, requests: &[Call], responses: &[&Value]) {
+        let mut state = self.state.lock().expect("failover state lock poisoned");
+        state.health[index] = Health::default();
+        state.preferred = index;
+
+        for (request, response) in requests.iter().zip(responses) {
+            if !matches!(method(request), Some("eth_getBlockByNumber" | "eth_getBlockByHash")) {
+                continue;
+            }
+            let number = response.get("number").and_then(Value::as_str).and_then(parse_number);
+            let hash = response.get("hash").and_then(|hash| serde_json::from_value::<H256>(hash.clone()).ok());
+            if let (Some(number), Some(hash)) = (number, hash) {
+                if state.hashes.insert(number, (index, hash)).map(|(_, previous)| previous) != Some(hash) {
+                    state.verified.retain(|(_, verified), _| *verified != number);
+                }
+            }
+        }
+        while state.hashes.len() > TRACKED_BLOCKS {
+            if let Some((number, _)) = state.hashes.pop_first() {
+                state.verified.retain(|(_, verified), _| *verified != number);
+            }
+        }
+    }
+
+    fn failed(&self, index: usize, error: &web3::Error) {
+        let mut state = self.state.lock().expect("failover state lock poisoned");
+        let health = &mut state.health[index];
+        health.consecutive_failures += 1;
+        let benched = health.benched_until.is_some_and(|until| until > Instant::now());
+        if health.consecutive_failures >= BENCH_AFTER_FAILURES && !benched {
+            health.benched_until = Some(Instant::now() + BENCH_DURATION);
+            tracing::warn!(
+                endpoint = %self.endpoints[index].0,
+                benched_for = ?BENCH_DURATION,
+                failures = health.consecutive_failures,
+                "benching endpoint after repeated failures"
+            );
+        }
+        if self.endpoints.len() > 1 {
+            tracing::warn!(endpoint = %self.endpoints[index].0, error = %error, "endpoint failed; trying the next one");
+        }
+    }
+}
+
+impl<T> FailoverTransport<T>
+where
+    T: Transport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+{
+    /// Whether endpoint `index` agrees on the hash of every tracked block that `requests` query state at
+    async fn agrees(&self, index: usize, requests: &[Call]) -> web3::Result<bool> {
+        let mut to_check: Vec<(u64, H256)> = Vec::new();
+        {
+            let state = self.state.lock().expect("failover state lock poisoned");
+            for number in requests.iter().filter_map(state_block) {
+                match (state.hashes.get(&number), state.verified.get(&(index, number))) {
+                    (Some((source, _)), _) if *source == index => {}
+                    (Some(_), Some(false)) => return Ok(false),
+                    (Some((_, hash)), None) => to_check.push((number, *hash)),
+                    _ => {}
+                }
+            }
+        }
+
+        let transport = &self.endpoints[index].1;
+        for (number, expected) in to_check {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let request = build_request(id, "eth_getBlockByNumber", vec![format!("{:#x}", number).into(), false.into()]);
+            let block = transport.send(id, request).await?;
+            let hash = block.get("hash").and_then(|hash| serde_json::from_value::<H256>(hash.clone()).ok());
+            let agrees = hash == Some(expected);
+            self.state.lock().expect("failover state lock poisoned").verified.insert((index, number), agrees);
+            if !agrees {
+                tracing::warn!(
+                    endpoint = %self.endpoints[index].0,
+                    block = number,
+                    "endpoint has a different block; not using it for this block's state"
+                );
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl<T> Transport for FailoverTransport<T>
+where
+    T: Transport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+{
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        (id, build_request(id, method, params))
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        let this = self.clone();
+        Box::pin(async move {
+            let requests = [request];
+            let mut last_error = None;
+            for index in this.order() {
+                if !this.agrees(index, &requests).await.unwrap_or(false) {
+                    continue;
+                }
+                match this.endpoints[index].1.send(id, requests[0].clone()).await {
+                    Ok(response) => {
+                        this.succeeded(index, &requests, &[&response]);
+                        return Ok(response);
+                    }
+                    Err(e) if is_retryable(&e) => {
+                        this.failed(index, &e);
+                        last_error = Some(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(last_error.unwrap_or_else(no_consistent_endpoint))
+        })
+    }
+}
+
+impl<T> BatchTransport for FailoverTransport<T>
+where
+    T: BatchTransport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    /// A batch always goes to a single endpoint as a whole
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let this = self.clone();
+        let requests: Vec<(RequestId, Call)> = requests.into_iter().collect();
+        Box::pin(async move {
+            let calls: Vec<Call> = requests.iter().map(|(_, call)| call.clone()).collect();
+            let mut last_error = None;
+            for index in this.order() {
+                if !this.agrees(index, &calls).await.unwrap_or(false) {
+                    continue;
+                }
+                match this.endpoints[index].1.send_batch(requests.clone()).await {
+                    Ok(responses) => {
+                        let values: Vec<&Value> = responses.iter().map(|response| response.as_ref().unwrap_or(&Value::Null)).collect();
+                        this.succeeded(index, &calls, &values);
+                        return Ok(responses);
+                    }
+                    Err(e) if is_retryable(&e) => {
+                        this.failed(index, &e);
+                        last_error = Some(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(last_error.unwrap_or_else(no_consistent_endpoint))
+        })
+    }
+}
+
+fn no_consistent_endpoint() -> web3::Error {
+    web3::Error::Transport(web3::error::TransportError::Message(
+        "no endpoint agrees with the block hash this analysis started from".to_string(),
+    ))
+}
+
+fn method(request: &Call) -> Option<&str> {
+    match request {
+        Call::MethodCall(call) => Some(&call.method),
+        _ => None,
+    }
+}
+
+/// The block number a state query is pinned to, if it is one
+fn state_block(request: &Call) -> Option<u64> {
+    let Call::MethodCall(call) = request else { return None };
+    let Params::Array(params) = &call.params else { return None };
+    let block = match call.method.as_str() {
+        "eth_getBalance" | "eth_getTransactionCount" | "eth_getCode" | "eth_getStorageAt" | "eth_call" => params.last(),
+        "trace_replayBlockTransactions" | "debug_traceBlockByNumber" | "eth_getBlockReceipts" => params.first(),
+        _ => None,
+    };
+    block.and_then(Value::as_str).and_then(parse_number)
+}
+
+fn parse_number(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex.strip_prefix("0x")?, 16).ok()
+}
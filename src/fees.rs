@@ -0,0 +1,190 @@
+use web3::types::{H160, U256};
+use std::collections::HashMap;
+
+use crate::{BlockInfo, InternalTransfer, StateChange, TransactionInfo, TransactionStatus, Wei};
+
+/// EIP-2718 type of OP Stack deposit transactions, which L1 sends and pays for: they carry no signature
+/// or gas price, and may mint ether to their sender
+pub(crate) const DEPOSIT_TX_TYPE: u64 = 0x7e;
+
+/// OP Stack predeploys collecting the base fee, which isn't burned there, and the L1 data fee
+pub(crate) const BASE_FEE_VAULT: H160 = H160([0x42, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x19]);
+pub(crate) const L1_FEE_VAULT: H160 = H160([0x42, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x1a]);
+
+/// Whether the transactions are an OP Stack block's: every one starts with a deposit setting the L1
+/// attributes, and user transactions' receipts carry the L1 fee
+pub(crate) fn is_op_stack(transactions: &[TransactionInfo]) -> bool {
+    transactions.iter().any(|tx| tx.tx_type == Some(DEPOSIT_TX_TYPE) || tx.l1_fee.is_some())
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Components {
+    value_in: Wei,
+    value_out: Wei,
+    fees_paid: Wei,
+    priority_fees_received: Wei,
+    vault_fees_received: Wei,
+}
+
+/// Split each balance change into value transferred by `transactions` and the calls they made, and the
+/// fees they paid. Reverted transactions still pay fees but move no value. On OP Stack chains the base
+/// and L1 fees are credited to their vaults.
+pub(crate) fn decompose_balance_changes<'a>(
+    changes: &mut [StateChange],
+    transactions: impl IntoIterator<Item = &'a TransactionInfo>,
+    internal_transfers: impl IntoIterator<Item = &'a InternalTransfer>,
+    block_info: &BlockInfo,
+) {
+    let op_stack = block_info.is_op_stack();
+    let mut components: HashMap<H160, Components> = HashMap::new();
+    for tx in transactions {
+        // A deposit's mint is credited before it runs, so it stays even if the deposit reverts
+        if let (Some(from), Some(mint)) = (tx.from, tx.mint) {
+            let entry = components.entry(from).or_default();
+            entry.value_in = entry.value_in.saturating_add(mint);
+        }
+
+        if tx.status != TransactionStatus::Reverted {
+            if let Some(from) = tx.from {
+                let entry = components.entry(from).or_default();
+                entry.value_out = entry.value_out.saturating_add(tx.value);
+            }
+            if let Some(to) = tx.to.or(tx.created_contract) {
+                let entry = components.entry(to).or_default();
+                entry.value_in = entry.value_in.saturating_add(tx.value);
+            }
+        }
+
+        // Blob fees are burned in full, so only the sender sees them
+        if let (Some(from), Some(blob_fee)) = (tx.from, tx.blob_fee_paid) {
+            let entry = components.entry(from).or_default();
+            entry.fees_paid = entry.fees_paid.saturating_add(blob_fee);
+        }
+
+        // The L1 data fee is charged on top of the gas
+        if let Some(l1_fee) = tx.l1_fee {
+            if let Some(from) = tx.from {
+                let entry = components.entry(from).or_default();
+                entry.fees_paid = entry.fees_paid.saturating_add(l1_fee);
+            }
+            let entry = components.entry(L1_FEE_VAULT).or_default();
+            entry.vault_fees_received = entry.vault_fees_received.saturating_add(l1_fee);
+        }
+
+        let Some(fee) = tx.fee_paid else { continue };
+        if let Some(from) = tx.from {
+            let entry = components.entry(from).or_default();
+            entry.fees_paid = entry.fees_paid.saturating_add(fee);
+        }
+
+        // Everything above the base fee goes to the coinbase; the base fee itself is burned, or paid into
+        // the BaseFeeVault on OP Stack chains
+        let base_fee = base_fee_paid(block_info, tx);
+        let entry = components.entry(block_info.miner_address).or_default();
+        entry.priority_fees_received = entry.priority_fees_received.saturating_add(fee.saturating_sub(base_fee));
+        if op_stack {
+            let entry = components.entry(BASE_FEE_VAULT).or_default();
+            entry.vault_fees_received = entry.vault_fees_received.saturating_add(base_fee);
+        }
+    }
+
+    for transfer in internal_transfers {
+        let entry = components.entry(transfer.from).or_default();
+        entry.value_out = entry.value_out.saturating_add(transfer.value);
+        let entry = components.entry(transfer.to).or_default();
+        entry.value_in = entry.value_in.saturating_add(transfer.value);
+    }
+
+    for change in changes {
+        let parts = components.get(&change.address).copied().unwrap_or_default();
+        change.value_in = parts.value_in;
+        change.value_out = parts.value_out;
+        change.fees_paid = parts.fees_paid;
+        change.priority_fees_received = parts.priority_fees_received;
+        change.vault_fees_received = parts.vault_fees_received;
+    }
+}
+
+/// The part of `tx`'s fee that was the base fee, for each unit of gas used: nothing before London, or
+/// for a deposit, whose gas L1 paid for
+pub(crate) fn base_fee_paid(block_info: &BlockInfo, tx: &TransactionInfo) -> Wei {
+    match (block_info.base_fee_per_gas, tx.gas_used) {
+        _ if tx.tx_type == Some(DEPOSIT_TX_TYPE) => Wei::zero(),
+        (Some(base_fee), Some(gas_used)) => base_fee.saturating_mul_gas(gas_used),
+        _ => Wei::zero(),
+    }
+}
+
+/// The part of `tx`'s fee that was burned: its base fee, except on OP Stack chains
+pub(crate) fn burned_fee(block_info: &BlockInfo, tx: &TransactionInfo) -> Wei {
+    match block_info.is_op_stack() {
+        true => Wei::zero(),
+        false => base_fee_paid(block_info, tx),
+    }
+}
+
+/// New ether a proof-of-work block issues: the block reward plus 1/32 of it per uncle for the miner,
+/// and each uncle's reward for its miner. `None` when the chain's reward schedule isn't known.
+pub(crate) fn expected_rewards(chain_id: u64, block_info: &BlockInfo) -> Option<Wei> {
+    // Proof-of-stake blocks issue nothing on the execution layer
+    if block_info.is_post_merge() {
+        return Some(Wei::zero());
+    }
+    let number = block_info.block_number;
+    let ether = U256::exp10(18);
+    let (block_reward, era) = match chain_id {
+        // Byzantium and Constantinople cut the reward
+        1 if number >= 7_280_000 => (ether * 2, 0),
+        1 if number >= 4_370_000 => (ether * 3, 0),
+        1 => (ether * 5, 0),
+        // Ethereum Classic (ECIP-1017): the reward drops by a fifth every five million blocks
+        61 => {
+            let era = number.saturating_sub(1) / 5_000_000;
+            let reward = (0..era).fold(ether * 5, |reward, _| reward * 4 / 5);
+            (reward, era)
+        }
+        _ => return None,
+    };
+
+    let uncle_count = U256::from(block_info.uncles.len());
+    let mut total = block_reward + block_reward * uncle_count / 32;
+    for uncle in &block_info.uncles {
+        total += match era {
+            // Uncles earn less the further back they are, down to 1/8 of the reward seven blocks back
+            0 => block_reward * U256::from((uncle.number + 8).saturating_sub(number)) / 8,
+            _ => block_reward / 32,
+        };
+    }
+    Some(Wei(total))
+}
+
+/// Balances across the block should only grow by withdrawals, issuance and deposit mints, and shrink by the
+/// burned base and blob fees. Anything else means a balance changed at an address the scan didn't include.
+/// Proof-of-work blocks on chains with an unknown reward schedule will always look off by their rewards.
+pub(crate) fn check_balance_consistency(chain_id: u64, block_info: &BlockInfo, changes: &[StateChange]) {
+    let (mut gained, mut lost) = (Wei::zero(), Wei::zero());
+    for delta in changes.iter().filter_map(|change| change.balance_change) {
+        if delta.decreased {
+            lost = lost.saturating_add(delta.magnitude);
+        } else {
+            gained = gained.saturating_add(delta.magnitude);
+        }
+    }
+
+    let withdrawn: Wei = block_info.withdrawals.iter().map(|withdrawal| withdrawal.amount).sum();
+    let minted: Wei = block_info.transactions.iter().filter_map(|tx| tx.mint).sum();
+    let issued = expected_rewards(chain_id, block_info).unwrap_or_default().saturating_add(minted);
+    let blob_fees: Wei = block_info.transactions.iter().filter_map(|tx| tx.blob_fee_paid).sum();
+    let burned = block_info.burned_base_fee.unwrap_or_default().saturating_add(blob_fees);
+
+    // Compare gained - lost against withdrawn + issued - burned without going negative
+    let (left, right) = (gained.saturating_add(burned), lost.saturating_add(withdrawn).saturating_add(issued));
+    if left != right {
+        let (sign, unexplained) = if left > right { ("+", left.saturating_sub(right)) } else { ("-", right.saturating_sub(left)) };
+        tracing::warn!(
+            block = block_info.block_number,
+            unexplained_wei = %format!("{}{}", sign, unexplained),
+            "balance changes don't net out; internal transfers or rewards may have been missed"
+        );
+    }
+}
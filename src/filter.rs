@@ -0,0 +1,33 @@
+use web3::types::H160;
+
+use crate::TransactionInfo;
+
+/// Which transactions reports list. Hidden transactions still count everywhere else: in the summary,
+/// the gas figures and the accounts queried for state changes.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionFilter {
+    /// Hide transactions that move no value and carry no input, like rollup spam
+    pub skip_zero_value: bool,
+    /// List only transactions to one of these, counting a creation as sent to the contract it created
+    pub only_to: Vec<H160>,
+    /// List only transactions from one of these
+    pub only_from: Vec<H160>,
+}
+
+impl TransactionFilter {
+    /// Whether `tx` is listed; every filter set has to let it through
+    pub fn matches(&self, tx: &TransactionInfo) -> bool {
+        let spam = tx.value.is_zero() && tx.input.0.is_empty();
+        let to = tx.created_contract.or(tx.to);
+        !(self.skip_zero_value && spam)
+            && (self.only_to.is_empty() || to.is_some_and(|to| self.only_to.contains(&to)))
+            && (self.only_from.is_empty() || tx.from.is_some_and(|from| self.only_from.contains(&from)))
+    }
+
+    /// The listed transactions with their index in the block, and how many were hidden
+    pub fn apply<'a>(&self, transactions: &'a [TransactionInfo]) -> (Vec<(usize, &'a TransactionInfo)>, usize) {
+        let listed: Vec<(usize, &TransactionInfo)> = transactions.iter().enumerate().filter(|(_, tx)| self.matches(tx)).collect();
+        let hidden = transactions.len() - listed.len();
+        (listed, hidden)
+    }
+}
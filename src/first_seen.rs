@@ -0,0 +1,71 @@
+use serde_json::{json, Value};
+use web3::types::{BlockNumber, U256, U64};
+use web3::{BatchTransport, Web3};
+use std::collections::HashSet;
+use std::error::Error;
+
+use crate::{batch, AnalysisOptions, StateChange};
+
+/// Flag the changes of accounts the block brought to life: empty at `baseline`, with no nonce, balance
+/// or code there. With `deep_first_seen`, balances at earlier blocks are probed too, and an account that
+/// held ether at any of them isn't new.
+pub(crate) async fn mark_first_activity<T: BatchTransport>(
+    web3: &Web3<T>,
+    baseline: u64,
+    changes: &mut [StateChange],
+    options: &AnalysisOptions,
+) -> Result<(), Box<dyn Error>> {
+    let empty = |change: &StateChange| change.prev_nonce == Some(U256::zero()) && change.prev_balance.is_some_and(|balance| balance.is_zero());
+    let mut candidates: Vec<usize> = changes.iter().enumerate().filter(|(_, change)| empty(change)).map(|(index, _)| index).collect();
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    let block = json!(BlockNumber::Number(U64::from(baseline)));
+    let calls = candidates.iter().map(|index| ("eth_getCode", vec![json!(changes[*index].address), block.clone()])).collect();
+    let codes = batch::call_batched(web3, calls, options.batch_size, options.concurrency, |_| {}).await?;
+    let mut without_code = Vec::with_capacity(candidates.len());
+    for (index, code) in candidates.into_iter().zip(codes) {
+        if code.as_str().is_none_or(|code| code == "0x") {
+            without_code.push(index);
+        }
+    }
+    candidates = without_code;
+
+    if let Some(budget) = options.deep_first_seen {
+        let mut spent = 0;
+        // One batch per round, each still-new account probed at its next block
+        for block in probe_blocks(baseline) {
+            if candidates.is_empty() || spent + candidates.len() > budget {
+                break;
+            }
+            spent += candidates.len();
+            let tag = json!(BlockNumber::Number(U64::from(block)));
+            let calls = candidates.iter().map(|index| ("eth_getBalance", vec![json!(changes[*index].address), tag.clone()])).collect();
+            let balances = batch::call_batched(web3, calls, options.batch_size, options.concurrency, |_| {}).await?;
+            candidates = candidates.into_iter().zip(balances).filter(|(_, balance)| is_zero_quantity(balance)).map(|(index, _)| index).collect();
+        }
+    }
+
+    for index in candidates {
+        changes[index].first_activity = true;
+    }
+    Ok(())
+}
+
+/// The blocks before `baseline` in the order they're probed: its middle, then the middles of each half,
+/// and so on, so a bounded number of probes spreads over the whole history
+fn probe_blocks(baseline: u64) -> impl Iterator<Item = u64> {
+    let levels = (1..=64u32).take_while(move |level| 1u128 << (level - 1) <= u128::from(baseline));
+    let mut seen = HashSet::new();
+    levels
+        .flat_map(move |level| {
+            let parts = 1u128 << level;
+            (1..parts).step_by(2).map(move |part| (u128::from(baseline) * part / parts) as u64)
+        })
+        .filter(move |block| seen.insert(*block))
+}
+
+fn is_zero_quantity(value: &Value) -> bool {
+    serde_json::from_value::<U256>(value.clone()).is_ok_and(|quantity| quantity.is_zero())
+}
@@ -0,0 +1,210 @@
+use futures::future::BoxFuture;
+use jsonrpc_core::{Call, Params, Value};
+use serde::{Deserialize, Serialize};
+use web3::error::TransportError;
+use web3::{BatchTransport, RequestId, Transport};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// One request and the node's answer, stored one per line in a fixture file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Exchange {
+    pub method: String,
+    pub params: Value,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Outcome {
+    Result(Value),
+    /// Errors the node returned, such as an unsupported method; transport failures aren't recorded
+    Error(jsonrpc_core::Error),
+}
+
+impl Outcome {
+    fn from_response(response: &web3::Result<Value>) -> Option<Self> {
+        match response {
+            Ok(value) => Some(Outcome::Result(value.clone())),
+            Err(web3::Error::Rpc(e)) => Some(Outcome::Error(e.clone())),
+            Err(_) => None,
+        }
+    }
+
+    pub fn into_response(self) -> web3::Result<Value> {
+        match self {
+            Outcome::Result(value) => Ok(value),
+            Outcome::Error(e) => Err(web3::Error::Rpc(e)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Recorded {
+    /// Outcomes in the order they were recorded, and how many of them have been served
+    outcomes: Vec<Outcome>,
+    served: usize,
+}
+
+#[derive(Debug)]
+enum Mode {
+    Passthrough,
+    Record(Mutex<File>),
+    Replay { path: PathBuf, recorded: Mutex<HashMap<String, Recorded>> },
+}
+
+/// Transport wrapper that writes every request and response to a fixture file, or serves responses
+/// from one without touching the inner transport. Replay fails any request that wasn't recorded, so
+/// an analysis that has drifted from the recording is caught.
+#[derive(Debug, Clone)]
+pub struct FixtureTransport<T> {
+    inner: T,
+    mode: Arc<Mode>,
+}
+
+impl<T> FixtureTransport<T> {
+    pub fn passthrough(inner: T) -> Self {
+        FixtureTransport { inner, mode: Arc::new(Mode::Passthrough) }
+    }
+
+    /// Record into `path`, replacing whatever it held
+    pub fn record(inner: T, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("creating {}: {}", path.display(), e))?;
+        Ok(FixtureTransport { inner, mode: Arc::new(Mode::Record(Mutex::new(file))) })
+    }
+
+    /// Serve the responses recorded in `path`. A request made more often than it was recorded gets
+    /// the last recorded response again.
+    pub fn replay(inner: T, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let mut recorded: HashMap<String, Recorded> = HashMap::new();
+        for exchange in read_exchanges(path)? {
+            recorded
+                .entry(key(&exchange.method, &exchange.params))
+                .or_insert_with(|| Recorded { outcomes: Vec::new(), served: 0 })
+                .outcomes
+                .push(exchange.outcome);
+        }
+        let mode = Mode::Replay { path: path.to_path_buf(), recorded: Mutex::new(recorded) };
+        Ok(FixtureTransport { inner, mode: Arc::new(mode) })
+    }
+}
+
+impl Mode {
+    fn record(&self, request: &Call, response: &web3::Result<Value>) {
+        let (Mode::Record(file), Some((method, params)), Some(outcome)) = (self, method_call(request), Outcome::from_response(response)) else {
+            return;
+        };
+        let exchange = Exchange { method: method.to_string(), params, outcome };
+        let mut file = file.lock().expect("fixture file lock poisoned");
+        if let Err(e) = writeln!(file, "{}", serde_json::to_string(&exchange).unwrap_or_default()) {
+            tracing::warn!(method = %exchange.method, error = %e, "failed to record fixture exchange");
+        }
+    }
+
+    fn replay(&self, request: &Call) -> Option<web3::Result<Value>> {
+        let Mode::Replay { path, recorded } = self else { return None };
+        let Some((method, params)) = method_call(request) else {
+            return Some(Err(unrecorded(path, "a notification or invalid request")));
+        };
+        let mut recorded = recorded.lock().expect("fixture replay lock poisoned");
+        let Some(entry) = recorded.get_mut(&key(method, &params)) else {
+            return Some(Err(unrecorded(path, &format!("{}({})", method, params))));
+        };
+        let outcome = entry.outcomes[entry.served.min(entry.outcomes.len() - 1)].clone();
+        entry.served += 1;
+        Some(outcome.into_response())
+    }
+}
+
+impl<T> Transport for FixtureTransport<T>
+where
+    T: Transport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+{
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        if let Some(response) = self.mode.replay(&request) {
+            return Box::pin(futures::future::ready(response));
+        }
+        let inner = self.inner.clone();
+        let mode = self.mode.clone();
+        Box::pin(async move {
+            let response = inner.send(id, request.clone()).await;
+            mode.record(&request, &response);
+            response
+        })
+    }
+}
+
+impl<T> BatchTransport for FixtureTransport<T>
+where
+    T: BatchTransport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    /// Each request in a batch is recorded and replayed on its own, so batch sizes may differ between runs
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let requests: Vec<(RequestId, Call)> = requests.into_iter().collect();
+        if matches!(*self.mode, Mode::Replay { .. }) {
+            let responses = requests.iter().map(|(_, call)| self.mode.replay(call).unwrap_or(Err(web3::Error::Unreachable))).collect();
+            return Box::pin(futures::future::ready(Ok(responses)));
+        }
+        let inner = self.inner.clone();
+        let mode = self.mode.clone();
+        Box::pin(async move {
+            let responses = inner.send_batch(requests.clone()).await?;
+            for ((_, request), response) in requests.iter().zip(&responses) {
+                mode.record(request, response);
+            }
+            Ok(responses)
+        })
+    }
+}
+
+/// The exchanges recorded in a fixture file, in order
+pub(crate) fn read_exchanges(path: &Path) -> Result<Vec<Exchange>, Box<dyn Error>> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+    let mut exchanges = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        exchanges.push(serde_json::from_str(line).map_err(|e| format!("{} line {}: {}", path.display(), index + 1, e))?);
+    }
+    Ok(exchanges)
+}
+
+pub(crate) fn method_call(request: &Call) -> Option<(&str, Value)> {
+    let Call::MethodCall(call) = request else { return None };
+    let params = match &call.params {
+        Params::Array(params) => Value::Array(params.clone()),
+        Params::Map(params) => Value::Object(params.clone()),
+        Params::None => Value::Array(Vec::new()),
+    };
+    Some((&call.method, params))
+}
+
+pub(crate) fn key(method: &str, params: &Value) -> String {
+    format!("{}{}", method, params)
+}
+
+fn unrecorded(path: &Path, request: &str) -> web3::Error {
+    web3::Error::Transport(TransportError::Message(format!("{} has no recorded response for {}", path.display(), request)))
+}
@@ -0,0 +1,161 @@
+use serde::Serialize;
+use web3::types::{H160, H256};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::BlockInfo;
+
+/// Where a block's gas went, from [`gas_report`]
+#[derive(Debug, Clone, Serialize)]
+pub struct GasReport {
+    /// Most gas first; transactions without a receipt are left out
+    pub transactions: Vec<TransactionGas>,
+    /// Gas summed per recipient, most first
+    pub recipients: Vec<RecipientGas>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionGas {
+    pub hash: H256,
+    /// The called address, or the created contract for a creation
+    #[serde(serialize_with = "crate::address::serialize_option")]
+    pub recipient: Option<H160>,
+    pub gas_used: u64,
+    /// Share of the block's gas used
+    pub percent: f64,
+    /// Share used by this transaction and every one ranked above it
+    pub cumulative_percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecipientGas {
+    #[serde(serialize_with = "crate::address::serialize_option")]
+    pub address: Option<H160>,
+    pub transactions: usize,
+    pub gas_used: u64,
+    pub percent: f64,
+}
+
+/// Rank the block's transactions, and their recipients, by gas used. Equal amounts keep block order,
+/// so the report is the same on every run.
+pub(crate) fn gas_report(block_info: &BlockInfo) -> GasReport {
+    let percent = |gas: u64| if block_info.gas_used == 0 { 0.0 } else { gas as f64 * 100.0 / block_info.gas_used as f64 };
+
+    let mut transactions: Vec<TransactionGas> = block_info
+        .transactions
+        .iter()
+        .filter_map(|tx| {
+            let gas_used = tx.gas_used?.low_u64();
+            Some(TransactionGas {
+                hash: tx.hash,
+                recipient: tx.created_contract.or(tx.to),
+                gas_used,
+                percent: percent(gas_used),
+                cumulative_percent: 0.0,
+            })
+        })
+        .collect();
+    transactions.sort_by_key(|tx| Reverse(tx.gas_used));
+    let mut cumulative = 0;
+    for tx in &mut transactions {
+        cumulative += tx.gas_used;
+        tx.cumulative_percent = percent(cumulative);
+    }
+
+    let mut by_recipient: HashMap<Option<H160>, usize> = HashMap::new();
+    let mut recipients: Vec<RecipientGas> = Vec::new();
+    for tx in block_info.transactions.iter().filter(|tx| tx.gas_used.is_some()) {
+        let address = tx.created_contract.or(tx.to);
+        let index = *by_recipient.entry(address).or_insert_with(|| {
+            recipients.push(RecipientGas { address, transactions: 0, gas_used: 0, percent: 0.0 });
+            recipients.len() - 1
+        });
+        recipients[index].transactions += 1;
+        recipients[index].gas_used += tx.gas_used.unwrap_or_default().low_u64();
+    }
+    for recipient in &mut recipients {
+        recipient.percent = percent(recipient.gas_used);
+    }
+    recipients.sort_by_key(|recipient| Reverse(recipient.gas_used));
+
+    GasReport { transactions, recipients }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::types::U256;
+    use web3::Web3;
+
+    use crate::{get_block_info, AnalysisOptions, BlockRef, MockTransport};
+
+    const BOB: u64 = 0xb0b;
+    const TOKEN: u64 = 0x70ce;
+    const CREATED: u64 = 0xc0ffee03;
+
+    /// Block 3 of the fixture: 21,000 gas sent to Bob, 50,000 to the token and 60,000 creating a contract
+    async fn block_info() -> BlockInfo {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        get_block_info(&web3, BlockRef::Number(3), &AnalysisOptions::new()).await.unwrap().0
+    }
+
+    fn ranked(report: &GasReport) -> Vec<(u64, Option<H160>, u64, f64)> {
+        report.transactions.iter().map(|tx| (tx.hash.to_low_u64_be(), tx.recipient, tx.gas_used, tx.cumulative_percent)).collect()
+    }
+
+    fn address(low: u64) -> Option<H160> {
+        Some(H160::from_low_u64_be(low))
+    }
+
+    #[tokio::test]
+    async fn ranks_transactions_by_gas_with_their_shares() {
+        let report = gas_report(&block_info().await);
+        let share = |gas: f64| gas * 100.0 / 131_000.0;
+        assert_eq!(
+            ranked(&report),
+            [
+                (0x409, address(CREATED), 60_000, share(60_000.0)),
+                (0x408, address(TOKEN), 50_000, share(110_000.0)),
+                (0x407, address(BOB), 21_000, 100.0),
+            ]
+        );
+        let percents: Vec<f64> = report.transactions.iter().map(|tx| tx.percent).collect();
+        assert_eq!(percents, [share(60_000.0), share(50_000.0), share(21_000.0)]);
+        assert_eq!(report.recipients.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn equal_amounts_keep_block_order_and_recipients_add_up() {
+        let mut block_info = block_info().await;
+        // Both transfers call the token with the same gas, and the creation has no receipt
+        block_info.transactions[0].to = address(TOKEN);
+        block_info.transactions[0].gas_used = Some(U256::from(50_000));
+        block_info.transactions[2].gas_used = None;
+        block_info.gas_used = 100_000;
+
+        let report = gas_report(&block_info);
+        assert_eq!(ranked(&report), [(0x407, address(TOKEN), 50_000, 50.0), (0x408, address(TOKEN), 50_000, 100.0)]);
+        let recipients: Vec<_> = report.recipients.iter().map(|recipient| (recipient.address, recipient.transactions, recipient.gas_used, recipient.percent)).collect();
+        assert_eq!(recipients, [(address(TOKEN), 2, 100_000, 100.0)]);
+    }
+
+    #[tokio::test]
+    async fn an_empty_block_has_an_empty_report() {
+        let mut block_info = block_info().await;
+        block_info.transactions.clear();
+        block_info.gas_used = 0;
+        let report = gas_report(&block_info);
+        assert!(report.transactions.is_empty() && report.recipients.is_empty());
+    }
+
+    #[tokio::test]
+    async fn shares_of_a_block_that_used_no_gas_are_zero() {
+        let mut block_info = block_info().await;
+        block_info.transactions.truncate(1);
+        block_info.transactions[0].gas_used = Some(U256::zero());
+        block_info.gas_used = 0;
+        let report = gas_report(&block_info);
+        assert_eq!(ranked(&report), [(0x407, address(BOB), 0, 0.0)]);
+        assert_eq!(report.recipients[0].percent, 0.0);
+    }
+}
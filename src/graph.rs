@@ -0,0 +1,198 @@
+use serde::Serialize;
+use web3::types::H160;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::fees::base_fee_paid;
+use crate::{format_address, AmountFormat, BlockAnalysis, TransactionStatus, Wei};
+
+/// Renders the ether an analysis moved as a graph: addresses are nodes, and value transfers, contract
+/// creations and the fees paid to the coinbase are edges between them
+#[derive(Debug, Clone, Default)]
+pub struct GraphReport {
+    pub amounts: AmountFormat,
+    /// Edges carrying less than this in total are left out, as are the nodes only they connected
+    pub min_edge_value: Option<Wei>,
+}
+
+/// The value flows of one block, as `--format graph-json` writes them
+#[derive(Debug, Clone, Serialize)]
+pub struct ValueGraph {
+    pub block_number: u64,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphNode {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    /// The address's label or ENS name
+    pub label: Option<String>,
+    /// Whether this is the block's coinbase
+    pub coinbase: bool,
+}
+
+/// Every transfer of one kind from one address to another in the block, summed
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphEdge {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub from: H160,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub to: H160,
+    pub kind: EdgeKind,
+    pub value: Wei,
+    /// `value` in the selected unit, with its symbol
+    pub label: String,
+    /// How many transfers were summed
+    pub transfers: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// A transaction's value
+    Transaction,
+    /// Value moved by a call inside a transaction, from the traces
+    Internal,
+    /// A transaction creating a contract, with whatever value it was given
+    Creation,
+    /// What a transaction paid the coinbase above the base fee
+    CoinbaseFee,
+}
+
+impl GraphReport {
+    /// Reverted transactions pay fees but move no value, so only their fee edges are drawn. Internal
+    /// transfers are only there when the node could trace the block.
+    pub fn graph(&self, analysis: &BlockAnalysis) -> ValueGraph {
+        let info = &analysis.block_info;
+        let mut flows: Vec<(H160, H160, EdgeKind, Wei)> = Vec::new();
+        for tx in &info.transactions {
+            let Some(from) = tx.from else { continue };
+            if tx.status != TransactionStatus::Reverted {
+                match (tx.created_contract, tx.to) {
+                    (Some(created), _) => flows.push((from, created, EdgeKind::Creation, tx.value)),
+                    (None, Some(to)) if !tx.value.is_zero() => flows.push((from, to, EdgeKind::Transaction, tx.value)),
+                    _ => {}
+                }
+            }
+            if let Some(fee) = tx.fee_paid {
+                let priority_fee = fee.saturating_sub(base_fee_paid(info, tx));
+                if !priority_fee.is_zero() {
+                    flows.push((from, info.miner_address, EdgeKind::CoinbaseFee, priority_fee));
+                }
+            }
+        }
+        for transfer in analysis.internal_transfers.iter().flatten() {
+            flows.push((transfer.from, transfer.to, EdgeKind::Internal, transfer.value));
+        }
+
+        // Edges are summed per pair and kind, in the order they first appear
+        let mut edges: Vec<GraphEdge> = Vec::new();
+        let mut positions: HashMap<(H160, H160, EdgeKind), usize> = HashMap::new();
+        for (from, to, kind, value) in flows {
+            match positions.get(&(from, to, kind)) {
+                Some(&position) => {
+                    let edge = &mut edges[position];
+                    edge.value = edge.value.saturating_add(value);
+                    edge.transfers += 1;
+                }
+                None => {
+                    positions.insert((from, to, kind), edges.len());
+                    edges.push(GraphEdge { from, to, kind, value, label: String::new(), transfers: 1 });
+                }
+            }
+        }
+        if let Some(min) = self.min_edge_value {
+            edges.retain(|edge| edge.value >= min);
+        }
+        for edge in &mut edges {
+            edge.label = self.amounts.format(edge.value);
+        }
+
+        let names = names(analysis);
+        let mut nodes: Vec<GraphNode> = Vec::new();
+        for address in edges.iter().flat_map(|edge| [edge.from, edge.to]) {
+            if !nodes.iter().any(|node| node.address == address) {
+                let label = names.get(&address).cloned();
+                nodes.push(GraphNode { address, label, coinbase: address == info.miner_address });
+            }
+        }
+        ValueGraph { block_number: info.block_number, nodes, edges }
+    }
+
+    /// A Graphviz digraph: creation edges are dashed, coinbase fee edges dotted and grey, internal
+    /// transfers blue, and each edge's width grows with its share of the largest
+    pub fn render_dot(&self, analysis: &BlockAnalysis) -> String {
+        let graph = self.graph(analysis);
+        let largest = graph.edges.iter().map(|edge| edge.value).max().unwrap_or_default();
+        let mut out = String::new();
+
+        // Writing to a String can't fail
+        let _ = writeln!(out, "digraph \"block {}\" {{", graph.block_number);
+        let _ = writeln!(out, "  rankdir=LR;");
+        let _ = writeln!(out, "  node [shape=box, fontname=\"monospace\"];");
+        for node in &graph.nodes {
+            let address = format_address(&node.address);
+            let label = match &node.label {
+                Some(name) => format!("{}\\n{}", escape(name), address),
+                None => address.clone(),
+            };
+            let style = if node.coinbase { ", style=filled, fillcolor=\"lightgrey\"" } else { "" };
+            let _ = writeln!(out, "  \"{}\" [label=\"{}\"{}];", address, label, style);
+        }
+        for edge in &graph.edges {
+            let style = match edge.kind {
+                EdgeKind::Transaction => "",
+                EdgeKind::Internal => ", color=\"blue\"",
+                EdgeKind::Creation => ", style=dashed, color=\"darkgreen\"",
+                EdgeKind::CoinbaseFee => ", style=dotted, color=\"grey50\"",
+            };
+            let _ = writeln!(
+                out,
+                "  \"{}\" -> \"{}\" [label=\"{}\", penwidth={:.2}{}];",
+                format_address(&edge.from),
+                format_address(&edge.to),
+                escape(&edge.label),
+                pen_width(edge.value, largest),
+                style
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Labels win over ENS names, as in the other reports
+fn names(analysis: &BlockAnalysis) -> BTreeMap<H160, String> {
+    let mut names = analysis.ens.clone();
+    for tx in &analysis.block_info.transactions {
+        let labeled = [(tx.from, &tx.from_label), (tx.to.or(tx.created_contract), &tx.to_label)];
+        for (address, label) in labeled {
+            if let (Some(address), Some(label)) = (address, label) {
+                names.insert(address, label.clone());
+            }
+        }
+    }
+    let per_tx = analysis.per_tx_changes.iter().flat_map(|tx_changes| &tx_changes.state_changes);
+    for change in analysis.state_changes.iter().flatten().chain(per_tx) {
+        if let Some(label) = &change.label {
+            names.insert(change.address, label.clone());
+        }
+    }
+    names
+}
+
+/// From 1 for the smallest edges to 5 for the largest
+fn pen_width(value: Wei, largest: Wei) -> f64 {
+    if largest.is_zero() {
+        return 1.0;
+    }
+    // In hundredths; an amount too large to scale is as wide as the largest
+    let share = value.0.checked_mul(100.into()).map_or(100, |scaled| (scaled / largest.0).low_u64());
+    1.0 + 4.0 * share as f64 / 100.0
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,224 @@
+use serde::Serialize;
+use serde_json::json;
+use web3::types::{BlockNumber, H160, U256, U64};
+use web3::{BatchTransport, Web3};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::{batch, format_address, serialize_u256, sort_state_changes, with_deadline, AnalysisOptions, NonceDelta, StateChange, Wei};
+
+/// Interior blocks sampled per segment in each round; a segment with no more than this many is scanned block by block
+const SAMPLES_PER_SEGMENT: u64 = 16;
+
+/// A block after which an address's balance or nonce differs from the block before
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangePoint {
+    pub block_number: u64,
+    pub balance: Wei,
+    #[serde(serialize_with = "serialize_u256")]
+    pub nonce: U256,
+}
+
+/// Blocks in `from..=to` where the balance or nonce of `address` changed, with the values after each.
+///
+/// Only the address's balance and nonce are queried: the range is split into segments, and a segment
+/// whose two ends agree is assumed to hold no change, so a change that is undone inside one segment
+/// can be missed. Segments whose ends differ are sampled again until the change points are found;
+/// short or dense segments are scanned block by block, in batches.
+pub async fn address_history<T: BatchTransport>(
+    web3: &Web3<T>,
+    address: H160,
+    from: u64,
+    to: u64,
+    options: &AnalysisOptions,
+) -> Result<Vec<ChangePoint>, Box<dyn Error>> {
+    if from > to {
+        return Err(format!("invalid block range: from-block {} is after to-block {}", from, to).into());
+    }
+
+    with_deadline(options.deadline, || format!("history of {} from block {} to {}", format_address(&address), from, to), async {
+        // Compare block `from` against the block before it; the genesis block has nothing before it
+        let start = from.saturating_sub(1);
+        let mut states = fetch_states(web3, address, &[start, to], options).await?;
+        let mut changed: Vec<u64> = Vec::new();
+        if from == 0 && states[&0] != (Wei::zero(), U256::zero()) {
+            changed.push(0);
+        }
+
+        let mut segments = vec![(start, to)];
+        while !segments.is_empty() {
+            let mut samples: Vec<u64> = Vec::new();
+            let mut splits: Vec<Vec<u64>> = Vec::new();
+            for &(low, high) in &segments {
+                let interior = interior_samples(low, high);
+                samples.extend(&interior);
+                splits.push([vec![low], interior, vec![high]].concat());
+            }
+            states.extend(fetch_states(web3, address, &samples, options).await?);
+
+            segments = Vec::new();
+            for points in splits {
+                for pair in points.windows(2) {
+                    let (low, high) = (pair[0], pair[1]);
+                    if states[&low] == states[&high] {
+                        continue;
+                    }
+                    if high == low + 1 {
+                        changed.push(high);
+                    } else {
+                        segments.push((low, high));
+                    }
+                }
+            }
+        }
+
+        changed.sort_unstable();
+        Ok(changed
+            .into_iter()
+            .map(|block_number| {
+                let (balance, nonce) = states[&block_number];
+                ChangePoint { block_number, balance, nonce }
+            })
+            .collect())
+    })
+    .await
+}
+
+/// Net change of each of `addresses` between the states after blocks `from` and `to`, from two snapshots;
+/// the blocks in between aren't looked at. Every address is reported, changed or not, and only the
+/// fields in `options.fields` are queried. The transfer and fee breakdown is left at zero.
+pub async fn diff_addresses<T: BatchTransport>(
+    web3: &Web3<T>,
+    from: u64,
+    to: u64,
+    addresses: &[H160],
+    options: &AnalysisOptions,
+) -> Result<Vec<StateChange>, Box<dyn Error>> {
+    if from > to {
+        return Err(format!("invalid block range: from-block {} is after to-block {}", from, to).into());
+    }
+
+    with_deadline(options.deadline, || format!("diff of {} addresses from block {} to {}", addresses.len(), from, to), async {
+        let states = fetch_accounts(web3, addresses, &[from, to], options).await?;
+        let mut changes: Vec<StateChange> = addresses
+            .iter()
+            .zip(states)
+            .map(|(address, states)| net_change(*address, states[0], states[1]))
+            .collect();
+        sort_state_changes(&mut changes, options.sort);
+        Ok(changes)
+    })
+    .await
+}
+
+/// An account's balance and nonce at one block; a field left out of `options.fields` is `None`
+pub(crate) type AccountState = (Option<Wei>, Option<U256>);
+
+/// The state of each of `addresses` at each of `blocks`, indexed by address then block, batched.
+/// Only the fields in `options.fields` are queried.
+pub(crate) async fn fetch_accounts<T: BatchTransport>(
+    web3: &Web3<T>,
+    addresses: &[H160],
+    blocks: &[u64],
+    options: &AnalysisOptions,
+) -> Result<Vec<Vec<AccountState>>, Box<dyn Error>> {
+    let fields = options.fields;
+    let methods: Vec<&'static str> = [(fields.balance, "eth_getBalance"), (fields.nonce, "eth_getTransactionCount")]
+        .into_iter()
+        .filter_map(|(selected, method)| selected.then_some(method))
+        .collect();
+    let blocks: Vec<BlockNumber> = blocks.iter().map(|block| BlockNumber::Number(U64::from(*block))).collect();
+    let mut calls = Vec::new();
+    for address in addresses {
+        for block in &blocks {
+            calls.extend(methods.iter().map(|method| (*method, vec![json!(address), json!(block)])));
+        }
+    }
+    let values = batch::call_batched(web3, calls, options.batch_size, options.concurrency, |_| {}).await?;
+
+    let parse = |values: &[serde_json::Value]| -> Result<AccountState, Box<dyn Error>> {
+        let mut values = values.iter().cloned();
+        let balance = fields.balance.then(|| serde_json::from_value(values.next().unwrap_or_default())).transpose()?;
+        let nonce = fields.nonce.then(|| serde_json::from_value(values.next().unwrap_or_default())).transpose()?;
+        Ok((balance.map(Wei), nonce))
+    };
+    let mut states = Vec::with_capacity(addresses.len());
+    let mut values = values.chunks(methods.len().max(1));
+    for _ in addresses {
+        let mut at_blocks = Vec::with_capacity(blocks.len());
+        for _ in &blocks {
+            at_blocks.push(match methods.is_empty() {
+                true => (None, None),
+                false => parse(values.next().unwrap_or_default())?,
+            });
+        }
+        states.push(at_blocks);
+    }
+    Ok(states)
+}
+
+/// The change of `address` from `before` to `after`, with the transfer and fee breakdown left at zero
+pub(crate) fn net_change(address: H160, before: AccountState, after: AccountState) -> StateChange {
+    let ((prev_balance, prev_nonce), (new_balance, new_nonce)) = (before, after);
+    StateChange {
+        address,
+        label: None,
+        prev_balance,
+        new_balance,
+        prev_nonce,
+        new_nonce,
+        balance_change: prev_balance.zip(new_balance).map(|(prev, new)| Wei::checked_delta(prev, new)),
+        nonce_change: prev_nonce.zip(new_nonce).map(|(prev, new)| NonceDelta::between(prev, new)),
+        value_in: Wei::zero(),
+        value_out: Wei::zero(),
+        fees_paid: Wei::zero(),
+        priority_fees_received: Wei::zero(),
+        vault_fees_received: Wei::zero(),
+        code_changed: false,
+        is_contract: None,
+        code_size: None,
+        storage_changes: Vec::new(),
+        proof_verified: false,
+        first_activity: false,
+    }
+}
+
+/// Blocks strictly between `low` and `high` to query next: all of them for a short segment,
+/// otherwise evenly spaced samples
+fn interior_samples(low: u64, high: u64) -> Vec<u64> {
+    let gap = high - low;
+    if gap <= SAMPLES_PER_SEGMENT + 1 {
+        return (low + 1..high).collect();
+    }
+    let mut samples: Vec<u64> = (1..=SAMPLES_PER_SEGMENT).map(|i| low + gap * i / (SAMPLES_PER_SEGMENT + 1)).collect();
+    samples.dedup();
+    samples
+}
+
+/// Balance and nonce of `address` at each of `blocks`, batched
+async fn fetch_states<T: BatchTransport>(
+    web3: &Web3<T>,
+    address: H160,
+    blocks: &[u64],
+    options: &AnalysisOptions,
+) -> Result<BTreeMap<u64, (Wei, U256)>, Box<dyn Error>> {
+    let calls = blocks
+        .iter()
+        .flat_map(|block| {
+            let block = BlockNumber::Number(U64::from(*block));
+            [
+                ("eth_getBalance", vec![json!(address), json!(block)]),
+                ("eth_getTransactionCount", vec![json!(address), json!(block)]),
+            ]
+        })
+        .collect();
+    let values = batch::call_batched(web3, calls, options.batch_size, options.concurrency, |_| {}).await?;
+
+    let mut states = BTreeMap::new();
+    for (block, pair) in blocks.iter().zip(values.chunks(2)) {
+        let balance = Wei(serde_json::from_value(pair[0].clone())?);
+        let nonce = serde_json::from_value(pair[1].clone())?;
+        states.insert(*block, (balance, nonce));
+    }
+    Ok(states)
+}
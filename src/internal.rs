@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use web3::types::{H160, H256, U256};
+use web3::{Web3, Transport};
+use std::error::Error;
+
+use crate::trace::is_method_unsupported;
+use crate::{BlockInfo, BlockRef, DestroyedContract, Wei};
+
+/// Value moved by a call a transaction made along the way, rather than by the transaction itself
+#[derive(Debug, Clone, Serialize)]
+pub struct InternalTransfer {
+    pub transaction_hash: H256,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub from: H160,
+    /// The callee, or the contract created with the value
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub to: H160,
+    pub value: Wei,
+    /// 1 for a call made by the transaction's target, 2 for one made by that callee, and so on
+    pub depth: usize,
+}
+
+/// What the block's call traces show beyond its transactions and receipts. Calls that were reverted,
+/// or sit under one that was, are left out.
+#[derive(Debug, Default)]
+pub(crate) struct TracedCalls {
+    pub(crate) internal_transfers: Vec<InternalTransfer>,
+    pub(crate) destroyed_contracts: Vec<DestroyedContract>,
+    /// Contracts created by calls inside transactions, which no receipt names
+    pub(crate) created_contracts: Vec<H160>,
+}
+
+impl TracedCalls {
+    /// Every account the traces show gaining or losing ether, or being created or destroyed
+    pub(crate) fn addresses(&self) -> impl Iterator<Item = H160> + '_ {
+        let transfers = self.internal_transfers.iter().flat_map(|transfer| [transfer.from, transfer.to]);
+        let destroyed = self.destroyed_contracts.iter().flat_map(|destroyed| [Some(destroyed.address), destroyed.beneficiary]).flatten();
+        transfers.chain(destroyed).chain(self.created_contracts.iter().copied())
+    }
+
+    fn push(&mut self, transaction_hash: H256, call: Call) {
+        match call {
+            Call::Transfer { from, to, value, depth, created } => {
+                if created {
+                    self.created_contracts.push(to);
+                }
+                if !value.is_zero() {
+                    self.internal_transfers.push(InternalTransfer { transaction_hash, from, to, value: Wei(value), depth });
+                }
+            }
+            Call::Destroy { address, beneficiary, balance } => self.destroyed_contracts.push(DestroyedContract {
+                address,
+                beneficiary,
+                balance: balance.map(Wei),
+                transaction_hash: Some(transaction_hash),
+            }),
+        }
+    }
+}
+
+/// A traced frame in either node's format, reduced to what it did with ether
+enum Call {
+    Transfer { from: H160, to: H160, value: U256, depth: usize, created: bool },
+    Destroy { address: H160, beneficiary: Option<H160>, balance: Option<U256> },
+}
+
+/// One entry of `trace_block`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    action: TraceAction,
+    result: Option<TraceResult>,
+    error: Option<String>,
+    #[serde(default)]
+    trace_address: Vec<usize>,
+    /// Absent for block and uncle rewards
+    transaction_hash: Option<H256>,
+}
+
+/// The fields of call, create and suicide actions that matter here; each kind fills in some of them
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceAction {
+    call_type: Option<String>,
+    from: Option<H160>,
+    to: Option<H160>,
+    value: Option<U256>,
+    address: Option<H160>,
+    refund_address: Option<H160>,
+    balance: Option<U256>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceResult {
+    /// The created contract, for creates
+    address: Option<H160>,
+}
+
+/// One transaction of `debug_traceBlockByNumber` with the call tracer
+#[derive(Debug, Deserialize)]
+struct TracedTransaction {
+    result: Option<CallFrame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CallFrame {
+    #[serde(rename = "type")]
+    kind: String,
+    from: Option<H160>,
+    to: Option<H160>,
+    value: Option<U256>,
+    error: Option<String>,
+    #[serde(default)]
+    calls: Vec<CallFrame>,
+}
+
+/// Internal transfers, contract creations and self-destructs in the block, in block order, from
+/// `trace_block` or else geth's call tracer. Returns `None` when the node exposes neither.
+pub(crate) async fn trace_calls<T: Transport>(web3: &Web3<T>, block_info: &BlockInfo) -> Result<Option<TracedCalls>, Box<dyn Error>> {
+    let block = json!(BlockRef::Number(block_info.block_number));
+    match web3.transport().execute("trace_block", vec![block.clone()]).await {
+        Ok(traces) => return Ok(Some(from_traces(serde_json::from_value(traces)?))),
+        Err(e) if is_method_unsupported(&e) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let tracer = json!({ "tracer": "callTracer" });
+    match web3.transport().execute("debug_traceBlockByNumber", vec![block, tracer]).await {
+        Ok(traced) => Ok(Some(from_call_frames(block_info, serde_json::from_value(traced)?))),
+        Err(e) if is_method_unsupported(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn from_traces(traces: Vec<Trace>) -> TracedCalls {
+    // A frame that failed undoes everything below it, and its trace address prefixes theirs
+    let failed: Vec<(H256, &[usize])> = traces
+        .iter()
+        .filter(|trace| trace.error.is_some())
+        .filter_map(|trace| Some((trace.transaction_hash?, trace.trace_address.as_slice())))
+        .collect();
+
+    let mut traced = TracedCalls::default();
+    for trace in &traces {
+        let Some(hash) = trace.transaction_hash else { continue };
+        if failed.iter().any(|(failed_hash, path)| *failed_hash == hash && trace.trace_address.starts_with(path)) {
+            continue;
+        }
+        let depth = trace.trace_address.len();
+        let action = &trace.action;
+        let call = match trace.kind.as_str() {
+            // The transaction's own value is already accounted for; delegate and static calls move none
+            "call" if depth > 0 && action.call_type.as_deref().is_none_or(|call_type| call_type == "call") => {
+                let (Some(from), Some(to)) = (action.from, action.to) else { continue };
+                Call::Transfer { from, to, value: action.value.unwrap_or_default(), depth, created: false }
+            }
+            "create" if depth > 0 => {
+                let (Some(from), Some(to)) = (action.from, trace.result.as_ref().and_then(|result| result.address)) else { continue };
+                Call::Transfer { from, to, value: action.value.unwrap_or_default(), depth, created: true }
+            }
+            "suicide" | "selfdestruct" => {
+                let Some(address) = action.address else { continue };
+                Call::Destroy { address, beneficiary: action.refund_address, balance: action.balance }
+            }
+            _ => continue,
+        };
+        traced.push(hash, call);
+    }
+    traced
+}
+
+fn from_call_frames(block_info: &BlockInfo, transactions: Vec<TracedTransaction>) -> TracedCalls {
+    let mut traced = TracedCalls::default();
+
+    // Results come in block order, and not every geth version names the transaction
+    for (tx, transaction) in block_info.transactions.iter().zip(transactions) {
+        let Some(root) = transaction.result else { continue };
+        if root.error.is_some() {
+            continue;
+        }
+        // Reversed children so the stack pops them in call order
+        let mut frames: Vec<(CallFrame, usize)> = root.calls.into_iter().rev().map(|frame| (frame, 1)).collect();
+        while let Some((frame, depth)) = frames.pop() {
+            if frame.error.is_some() {
+                continue;
+            }
+            let value = frame.value.unwrap_or_default();
+            let call = match (frame.kind.to_ascii_uppercase().as_str(), frame.from, frame.to) {
+                ("CALL", Some(from), Some(to)) => Some(Call::Transfer { from, to, value, depth, created: false }),
+                ("CREATE" | "CREATE2", Some(from), Some(to)) => Some(Call::Transfer { from, to, value, depth, created: true }),
+                ("SELFDESTRUCT", Some(address), beneficiary) => Some(Call::Destroy { address, beneficiary, balance: frame.value }),
+                _ => None,
+            };
+            if let Some(call) = call {
+                traced.push(tx.hash, call);
+            }
+            frames.extend(frame.calls.into_iter().rev().map(|frame| (frame, depth + 1)));
+        }
+    }
+    traced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::Web3;
+
+    use crate::{get_block_info, AnalysisOptions, MockTransport};
+
+    const BOB: u64 = 0xb0b;
+    const CREATED: u64 = 0xc0ffee03;
+    const VAULT: u64 = 0x5afe;
+    const CAROL: u64 = 0xca401;
+    const CHILD: u64 = 0xc41d;
+
+    fn address(low: u64) -> H160 {
+        H160::from_low_u64_be(low)
+    }
+
+    fn milliether(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(15)
+    }
+
+    fn fixture(name: &str) -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(format!("tests/fixtures/traces/{}", name)).unwrap()).unwrap()
+    }
+
+    /// Block 3 of the fixture, with its calls traced in `method`'s format and the other method missing.
+    /// Its second transaction reverts after moving ether, and its third, the creation, calls out from
+    /// the constructor: 10 milliether to Bob, 20 to a vault that passes 5 on to Carol and delegate- and
+    /// static-calls out, 30 to a pool that pays Dave and reverts, and 4 to a child contract it creates,
+    /// which self-destructs to Bob.
+    async fn traced(method: &str, file: &str) -> TracedCalls {
+        let mock = MockTransport::load("tests/fixtures/block_3.json").unwrap();
+        let params = match method {
+            "trace_block" => json!(["0x3"]),
+            _ => json!(["0x3", { "tracer": "callTracer" }]),
+        };
+        let web3 = Web3::new(mock.with_response(method, params, fixture(file)));
+        let (block_info, _) = get_block_info(&web3, BlockRef::Number(3), &AnalysisOptions::new()).await.unwrap();
+        trace_calls(&web3, &block_info).await.unwrap().unwrap()
+    }
+
+    fn assert_nested_calls(traced: &TracedCalls) {
+        let creation = H256::from_low_u64_be(0x409);
+        let transfers: Vec<_> =
+            traced.internal_transfers.iter().map(|transfer| (transfer.transaction_hash, transfer.from, transfer.to, transfer.value.0, transfer.depth)).collect();
+        assert_eq!(
+            transfers,
+            [
+                (creation, address(CREATED), address(BOB), milliether(10), 1),
+                (creation, address(CREATED), address(VAULT), milliether(20), 1),
+                (creation, address(VAULT), address(CAROL), milliether(5), 2),
+                (creation, address(CREATED), address(CHILD), milliether(4), 1),
+            ]
+        );
+        // The transaction's own creation is in its receipt
+        assert_eq!(traced.created_contracts, [address(CHILD)]);
+        let destroyed: Vec<_> = traced
+            .destroyed_contracts
+            .iter()
+            .map(|destroyed| (destroyed.address, destroyed.beneficiary, destroyed.balance, destroyed.transaction_hash))
+            .collect();
+        assert_eq!(destroyed, [(address(CHILD), Some(address(BOB)), Some(Wei(milliether(4))), Some(creation))]);
+
+        let mut addresses: Vec<_> = traced.addresses().collect();
+        addresses.sort();
+        addresses.dedup();
+        let mut expected = vec![address(BOB), address(CREATED), address(VAULT), address(CAROL), address(CHILD)];
+        expected.sort();
+        assert_eq!(addresses, expected);
+    }
+
+    #[tokio::test]
+    async fn follows_a_nested_call_tree_from_trace_block() {
+        assert_nested_calls(&traced("trace_block", "nested_trace_block.json").await);
+    }
+
+    #[tokio::test]
+    async fn follows_a_nested_call_tree_from_the_call_tracer() {
+        assert_nested_calls(&traced("debug_traceBlockByNumber", "nested_call_tracer.json").await);
+    }
+
+    #[tokio::test]
+    async fn a_node_without_either_trace_method_has_no_traced_calls() {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        let (block_info, _) = get_block_info(&web3, BlockRef::Number(3), &AnalysisOptions::new()).await.unwrap();
+        assert!(trace_calls(&web3, &block_info).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,108 @@
+use web3::types::H160;
+
+/// Addresses, lowercase, with their labels
+type Labels = &'static [(&'static str, &'static str)];
+
+/// Labeled on every chain: the zero and burn addresses, the precompiles, the EIP system contracts and
+/// contracts deployed at the same address everywhere
+const EVERY_CHAIN: Labels = &[
+    ("0x0000000000000000000000000000000000000000", "Zero Address"),
+    ("0x000000000000000000000000000000000000dead", "Burn Address"),
+    ("0x0000000000000000000000000000000000000001", "ecRecover Precompile"),
+    ("0x0000000000000000000000000000000000000002", "SHA-256 Precompile"),
+    ("0x0000000000000000000000000000000000000003", "RIPEMD-160 Precompile"),
+    ("0x0000000000000000000000000000000000000004", "Identity Precompile"),
+    ("0x0000000000000000000000000000000000000005", "ModExp Precompile"),
+    ("0x0000000000000000000000000000000000000006", "BN254 Add Precompile"),
+    ("0x0000000000000000000000000000000000000007", "BN254 Mul Precompile"),
+    ("0x0000000000000000000000000000000000000008", "BN254 Pairing Precompile"),
+    ("0x0000000000000000000000000000000000000009", "BLAKE2f Precompile"),
+    ("0x000000000000000000000000000000000000000a", "Point Evaluation Precompile"),
+    ("0x000f3df6d732807ef1319fb7b8bb8522d0beac02", "EIP-4788 Beacon Roots"),
+    ("0x0000f90827f1c53a10cb7a02335b175320002935", "EIP-2935 Block Hashes"),
+    ("0x00000961ef480eb55e80d19ad83579a64c007002", "EIP-7002 Withdrawal Requests"),
+    ("0x0000bbddc7ce488642fb579f8b00f3a590007251", "EIP-7251 Consolidation Requests"),
+    ("0xca11bde05977b3631167028862be2a173976ca11", "Multicall3"),
+    ("0x000000000022d473030f116ddee9f6b43ac78ba3", "Uniswap Permit2"),
+    ("0x4e59b44847b379578588920ca78fbf26c0b4956c", "CREATE2 Deployer"),
+];
+
+const ETHEREUM: Labels = &[
+    ("0x00000000219ab540356cbb839cbe05303d7705fa", "Beacon Deposit Contract"),
+    ("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", "WETH"),
+    ("0x7a250d5630b4cf539739df2c5dacb4c659f2488d", "Uniswap V2 Router"),
+    ("0xe592427a0aece92de3edee1f18e0157c05861564", "Uniswap V3 Router"),
+    ("0x68b3465833fb72a70ecdf485e0e4c7bd8665fc45", "Uniswap V3 Router 2"),
+    ("0x3fc91a3afd70395cd496c647d5a6cc9d4b2b7fad", "Uniswap Universal Router"),
+    ("0x1111111254eeb25477b68fb85ed929f73a960582", "1inch V5 Router"),
+    ("0x111111125421ca6dc452d289314280a0f8842a65", "1inch V6 Router"),
+    ("0xdef1c0ded9bec7f1a1670819833240f027b25eff", "0x Exchange Proxy"),
+    ("0x28c6c06298d514db089934071355e5743bf21d60", "Binance 14"),
+    ("0xbe0eb53f46cd790cd13851d5eff43d12404d33e8", "Binance 7"),
+    ("0xf977814e90da44bfa03b6295a0616a897441acec", "Binance 8"),
+    ("0x71660c4005ba85c37ccec55d0c4493e66fe775d3", "Coinbase 1"),
+    ("0x503828976d22510aad0201ac7ec88293211d23da", "Coinbase 2"),
+    ("0xa9d1e08c7793af67e9d92fe308d5697fb81d3e43", "Coinbase 10"),
+    ("0x2910543af39aba0cd09dbb2d50200b3e800a63d2", "Kraken 1"),
+    ("0x4dbd4fc535ac27206064b68ffcf827b0a60bab3f", "Arbitrum Delayed Inbox"),
+    ("0x8315177ab297ba92a06054ce80a67ed4dbd7ed3a", "Arbitrum Bridge"),
+    ("0x99c9fc46f92e8a1c0dec1b1747d010903e884be1", "Optimism L1 Standard Bridge"),
+    ("0xbeb5fc579115071764c7423a4f12edde41f106ed", "Optimism Portal"),
+    ("0x3154cf16ccdb4c6d922629664174b904d80f2c35", "Base L1 Standard Bridge"),
+    ("0x49048044d57e1c92a77f79988d21fa8faf74e97e", "Base Portal"),
+    ("0xa0c68c638235ee32657e8f720a23cec1bfc77c77", "Polygon Root Chain Manager"),
+    ("0x40ec5b33f54e0e8a33a975908c5ba1c14e5bbbdf", "Polygon ERC20 Predicate"),
+];
+
+/// The predeploys every OP Stack chain has
+const OP_STACK: Labels = &[
+    ("0xdeaddeaddeaddeaddeaddeaddeaddeaddead0001", "L1 Attributes Depositor"),
+    ("0x4200000000000000000000000000000000000006", "WETH"),
+    ("0x4200000000000000000000000000000000000007", "L2 Cross Domain Messenger"),
+    ("0x420000000000000000000000000000000000000f", "Gas Price Oracle"),
+    ("0x4200000000000000000000000000000000000010", "L2 Standard Bridge"),
+    ("0x4200000000000000000000000000000000000011", "Sequencer Fee Vault"),
+    ("0x4200000000000000000000000000000000000015", "L1 Block Attributes"),
+    ("0x4200000000000000000000000000000000000016", "L2 To L1 Message Passer"),
+    ("0x4200000000000000000000000000000000000019", "Base Fee Vault"),
+    ("0x420000000000000000000000000000000000001a", "L1 Fee Vault"),
+];
+
+/// ArbOS and the precompiles every Arbitrum chain has
+const ARBITRUM: Labels = &[
+    ("0x00000000000000000000000000000000000a4b05", "ArbOS"),
+    ("0x0000000000000000000000000000000000000064", "ArbSys"),
+    ("0x0000000000000000000000000000000000000065", "ArbInfo"),
+    ("0x0000000000000000000000000000000000000066", "ArbAddressTable"),
+    ("0x000000000000000000000000000000000000006c", "ArbGasInfo"),
+    ("0x000000000000000000000000000000000000006d", "ArbAggregator"),
+    ("0x000000000000000000000000000000000000006e", "ArbRetryableTx"),
+    ("0x000000000000000000000000000000000000006f", "ArbStatistics"),
+    ("0x0000000000000000000000000000000000000070", "ArbOwner"),
+    ("0x00000000000000000000000000000000000000c8", "NodeInterface"),
+];
+
+const ARBITRUM_ONE: Labels = &[
+    ("0x82af49447d8a07e3bd95bd0d56f35241523fbab1", "WETH"),
+    ("0x5288c571fd7ad117bea99bf60fe0846c4e84f933", "Arbitrum L2 Gateway Router"),
+];
+
+/// Each chain's labels, by chain id; they win over those in `EVERY_CHAIN`
+const CHAINS: &[(u64, &[Labels])] = &[
+    (1, &[ETHEREUM]),
+    (10, &[OP_STACK]),
+    (8453, &[OP_STACK]),
+    (11155420, &[OP_STACK]),
+    (84532, &[OP_STACK]),
+    (42161, &[ARBITRUM, ARBITRUM_ONE]),
+    (421614, &[ARBITRUM]),
+];
+
+/// The built-in labels of `chain_id`, with those of every chain; a chain it doesn't know gets only the latter
+pub(crate) fn for_chain(chain_id: u64) -> impl Iterator<Item = (H160, &'static str)> {
+    let chain = CHAINS.iter().filter(move |(id, _)| *id == chain_id).flat_map(|(_, lists)| lists.iter().copied());
+    std::iter::once(EVERY_CHAIN)
+        .chain(chain)
+        .flatten()
+        .map(|(address, label)| (address.parse().expect("known label addresses are valid"), *label))
+}
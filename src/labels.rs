@@ -0,0 +1,98 @@
+use web3::types::H160;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use crate::{format_address, BlockAnalysis};
+
+/// Human labels for known addresses, read from `address,label` lines
+#[derive(Debug, Clone, Default)]
+pub struct AddressLabels {
+    labels: HashMap<H160, String>,
+}
+
+impl AddressLabels {
+    /// The built-in registry's labels for `chain_id`, with the chain-independent ones such as the zero and
+    /// burn addresses. A build without the `known-labels` feature has none.
+    pub fn known(chain_id: u64) -> Self {
+        #[cfg(feature = "known-labels")]
+        let labels = crate::known_labels::for_chain(chain_id).map(|(address, label)| (address, label.to_string())).collect();
+        #[cfg(not(feature = "known-labels"))]
+        let labels = {
+            let _ = chain_id;
+            HashMap::new()
+        };
+        AddressLabels { labels }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+        Self::parse(&contents)
+    }
+
+    /// Blank lines, `#` comments and an `address,label` header are skipped.
+    /// Hex is matched case-insensitively, and an address listed twice is an error.
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let mut labels = HashMap::new();
+        let mut seen_on: HashMap<H160, usize> = HashMap::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (address, label) = line
+                .split_once(',')
+                .ok_or_else(|| format!("labels line {}: expected address,label", line_number))?;
+            let address = address.trim();
+            if line_number == 1 && address.eq_ignore_ascii_case("address") {
+                continue;
+            }
+
+            let address: H160 = address
+                .to_lowercase()
+                .parse()
+                .map_err(|_| format!("labels line {}: invalid address '{}'", line_number, address))?;
+            if let Some(first) = seen_on.insert(address, line_number) {
+                return Err(format!("labels: {} is listed on both line {} and line {}", format_address(&address), first, line_number).into());
+            }
+            labels.insert(address, label.trim().trim_matches('"').to_string());
+        }
+
+        Ok(AddressLabels { labels })
+    }
+
+    pub fn get(&self, address: &H160) -> Option<&String> {
+        self.labels.get(address)
+    }
+
+    /// These labels with `overrides` on top, which win where both label an address
+    pub fn merged(mut self, overrides: AddressLabels) -> Self {
+        self.labels.extend(overrides.labels);
+        self
+    }
+
+    /// Every label, by address
+    pub fn entries(&self) -> Vec<(H160, &str)> {
+        let mut entries: Vec<_> = self.labels.iter().map(|(address, label)| (*address, label.as_str())).collect();
+        entries.sort();
+        entries
+    }
+
+    /// Fill in the label fields of every transaction and state change in `analysis`
+    pub fn apply(&self, analysis: &mut BlockAnalysis) {
+        let lookup = |address: Option<H160>| address.and_then(|address| self.get(&address).cloned());
+
+        for tx in &mut analysis.block_info.transactions {
+            tx.from_label = lookup(tx.from);
+            tx.to_label = lookup(tx.to.or(tx.created_contract));
+        }
+        let per_tx = analysis.per_tx_changes.iter_mut().flat_map(|tx_changes| &mut tx_changes.state_changes);
+        for change in analysis.state_changes.iter_mut().flatten().chain(per_tx) {
+            change.label = lookup(Some(change.address));
+        }
+    }
+}
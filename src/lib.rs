@@ -0,0 +1,2328 @@
+//! Block and state-diff analysis over Ethereum JSON-RPC.
+//!
+//! Every analysis function is generic over `web3::Transport` and only talks to the node through it,
+//! so a transport that answers from canned JSON responses can stand in for a live node.
+
+use futures::future;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::{Serialize, Serializer};
+use web3::types::{Block, BlockId, BlockNumber, Bytes, Log, Transaction, TransactionReceipt, U64, H160, H256, U256};
+use serde_json::json;
+use web3::{BatchTransport, Transport, Web3};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod abi;
+mod address;
+mod alert;
+mod arbitrum;
+mod batch;
+mod cache;
+mod checkpoint;
+mod code_changes;
+mod compare;
+mod config;
+mod ens;
+mod failover;
+mod fees;
+mod filter;
+mod first_seen;
+mod fixture;
+mod gas;
+mod graph;
+mod history;
+mod internal;
+#[cfg(feature = "known-labels")]
+mod known_labels;
+mod labels;
+mod markdown;
+mod metrics;
+mod mock;
+mod notify;
+#[cfg(feature = "parquet")]
+mod parquet_export;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod proof;
+mod proxy;
+mod reorg;
+mod retry;
+mod rpc;
+mod revert;
+mod selectors;
+mod selfdestruct;
+mod snapshot;
+mod sqlite;
+mod state_diff;
+mod summary;
+mod table;
+mod telemetry;
+mod throughput;
+mod timestamp;
+mod token_metadata;
+mod tokens;
+mod trace;
+mod transaction;
+mod transport;
+mod units;
+mod verify;
+mod watch;
+mod watched_slots;
+mod wei;
+
+pub use abi::{AbiRegistry, DecodedEvent, DecodedParam};
+pub use address::{format_address, set_lowercase_addresses, to_checksum_address};
+pub use alert::{evaluate_alerts, Alert, AlertRule, AlertValue};
+pub use cache::{default_cache_dir, CachingTransport, FINALITY_DEPTH};
+pub use checkpoint::Checkpoint;
+pub use code_changes::CodeChange;
+pub use compare::{compare_analyses, Divergence};
+pub use config::{Config, Profile};
+pub use ens::{EnsResolver, MAINNET_ENS_REGISTRY};
+pub use failover::FailoverTransport;
+pub use filter::TransactionFilter;
+pub use fixture::FixtureTransport;
+pub use gas::{GasReport, RecipientGas, TransactionGas};
+pub use graph::{EdgeKind, GraphEdge, GraphNode, GraphReport, ValueGraph};
+pub use history::{address_history, diff_addresses, ChangePoint};
+pub use internal::InternalTransfer;
+pub use labels::AddressLabels;
+pub use markdown::{MarkdownReport, DEFAULT_MAX_ROWS};
+pub use metrics::{MetricsServer, WatchMetrics};
+pub use mock::MockTransport;
+pub use notify::{AddressNotification, NotifyFormat, WebhookNotifier};
+#[cfg(feature = "parquet")]
+pub use parquet_export::{blocks_schema, state_changes_schema, transactions_schema, ParquetExport};
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresSink;
+pub use proxy::{ProxySlot, ProxyUpgrade};
+pub use retry::{is_retryable, RetryPolicy, RetryTransport};
+pub use selectors::SelectorRegistry;
+pub use selfdestruct::DestroyedContract;
+pub use snapshot::{compare_snapshots, AccountSnapshot, Snapshot};
+pub use sqlite::SqliteSink;
+pub use table::{Column, TableReport};
+pub use telemetry::{MethodStats, RpcStats};
+pub use throughput::{block_timestamp, BlockTiming, Spread, ThroughputSummary, ThroughputTracker};
+pub use timestamp::{format_age, format_timestamp, Timezone};
+pub use token_metadata::{TokenMetadata, TokenMetadataCache};
+pub use transaction::{analyze_transaction, TransactionAnalysis};
+pub use transport::{connect, RpcTransport};
+pub use units::{format_units, parse_units, AmountFormat, Unit};
+pub use verify::{Verification, VerificationCheck};
+pub use watch::{new_heads, watch_blocks};
+pub use watched_slots::{parse_watched_slot, SlotChange};
+pub use wei::{Wei, WeiDelta};
+
+/// Public endpoints rate-limit aggressively, so keep the default fan-out modest
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+/// Large enough to cut round trips sharply, small enough for providers' batch limits
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// Enough to hide per-block latency without multiplying the request rate too far
+pub const DEFAULT_BLOCK_CONCURRENCY: usize = 4;
+
+/// Knobs for a single analysis run; everything defaults to the cheapest behavior
+#[derive(Debug, Clone)]
+pub struct AnalysisOptions {
+    /// Maximum number of RPC requests (or batches) in flight at once
+    pub concurrency: usize,
+    /// Maximum number of blocks analyzed at once in range mode; each has its own `concurrency` budget
+    pub block_concurrency: usize,
+    /// Maximum number of balance and nonce queries sent in one JSON-RPC batch
+    pub batch_size: usize,
+    /// Trace transactions for storage slot changes (needs the debug_ namespace)
+    pub include_storage: bool,
+    /// Re-run reverted transactions with eth_call to recover their revert reasons
+    pub revert_reasons: bool,
+    /// Order of the reported state changes
+    pub sort: SortKey,
+    /// Account fields to query; leaving one out halves the balance and nonce calls
+    pub fields: StateFields,
+    /// Only query and report these addresses' state changes
+    pub address_filter: Option<HashSet<H160>>,
+    /// Also attribute state changes to individual transactions (needs the trace_ or debug_ namespace)
+    pub per_transaction: bool,
+    /// Look up each changed address's code to tell contracts from EOAs
+    pub classify_addresses: bool,
+    /// Reverse-resolve ENS names for the addresses in the analysis
+    pub ens: Option<EnsResolver>,
+    /// Look up the symbol, name and decimals of each token moved, remembering them in this cache
+    pub token_metadata: Option<TokenMetadataCache>,
+    /// Give up on a whole single-block or range run after this long
+    pub deadline: Option<Duration>,
+    /// Told how far the slow phases of each block's analysis have got
+    pub progress: Option<Arc<dyn ProgressReporter>>,
+    /// What to do when the node has no receipt for a transaction in a mined block
+    pub missing_receipts: MissingReceiptPolicy,
+    /// How many blocks back range and watch modes can follow a reorg
+    pub reorg_depth: usize,
+    /// Attach each block's RPC call counts and timings to its analysis
+    pub rpc_stats: bool,
+    /// Rank each block's transactions and recipients by gas used
+    pub gas_report: bool,
+    /// Also query the addresses named in transactions' access lists, which usually mark touched state
+    pub access_list_addresses: bool,
+    /// Query account state at all; without it only the block, its transactions and their receipts are fetched
+    pub state_changes: bool,
+    /// Compare against the state at this block instead of the parent, e.g. when the node lacks the parent's
+    /// state. It must come before the analyzed block, so it's meant for single-block analyses. Changes over
+    /// several blocks aren't broken down into transfers and fees, since this block's transactions don't explain them.
+    pub baseline_block: Option<u64>,
+    /// Leave out state changes whose balance moved by less than this, unless their nonce meets `min_nonce_delta`
+    pub min_balance_delta: Option<Wei>,
+    /// Leave out state changes whose nonce moved by less than this, unless their balance meets `min_balance_delta`
+    pub min_nonce_delta: Option<u64>,
+    /// Check each block's transactions and receipts against the roots in its header, and the header against
+    /// the block hash, recording the results in [`BlockAnalysis::verification`]
+    pub verify: bool,
+    /// Prove every changed account's balance and nonce with `eth_getProof` against the state roots of the
+    /// baseline and analyzed blocks, using the proven values; see [`StateChange::proof_verified`]
+    pub verify_state: bool,
+    /// Recover each transaction's sender from its signature and warn where it isn't the `from` the node reports
+    pub check_senders: bool,
+    /// Leave the ArbOS bookkeeping accounts, whose balances churn in every block, out of the state changes
+    /// of Arbitrum blocks; see [`BlockSummary::hidden_system_addresses`]
+    pub hide_system_addresses: bool,
+    /// Also probe balances before the baseline of addresses that look new, with at most this many extra
+    /// `eth_getBalance` calls per block; see [`StateChange::first_activity`]
+    pub deep_first_seen: Option<usize>,
+    /// Storage slots to read at the baseline and analyzed blocks with `eth_getStorageAt`, by contract,
+    /// reported in [`BlockAnalysis::slot_changes`] when they change; no tracing needed
+    pub watched_slots: Vec<(H160, H256)>,
+    /// Read the EIP-1967 implementation, admin and beacon slots of every transaction recipient and filtered
+    /// address at the baseline and analyzed blocks; see [`BlockAnalysis::proxy_upgrades`]
+    pub check_proxies: bool,
+    /// Compare the code of every touched address at the baseline and analyzed blocks, two `eth_getCode`
+    /// calls per address; see [`BlockAnalysis::code_changes`]
+    pub detect_code_changes: bool,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        AnalysisOptions {
+            concurrency: DEFAULT_CONCURRENCY,
+            block_concurrency: DEFAULT_BLOCK_CONCURRENCY,
+            batch_size: DEFAULT_BATCH_SIZE,
+            include_storage: false,
+            revert_reasons: false,
+            sort: SortKey::Address,
+            fields: StateFields::default(),
+            address_filter: None,
+            per_transaction: false,
+            classify_addresses: false,
+            ens: None,
+            token_metadata: None,
+            deadline: None,
+            progress: None,
+            missing_receipts: MissingReceiptPolicy::Warn,
+            reorg_depth: 64,
+            rpc_stats: false,
+            gas_report: false,
+            access_list_addresses: false,
+            state_changes: true,
+            baseline_block: None,
+            min_balance_delta: None,
+            min_nonce_delta: None,
+            verify: false,
+            verify_state: false,
+            check_senders: false,
+            hide_system_addresses: false,
+            deep_first_seen: None,
+            watched_slots: Vec::new(),
+            check_proxies: false,
+            detect_code_changes: false,
+        }
+    }
+}
+
+/// Builder-style setters, so options read as a chain starting from [`AnalysisOptions::new`]
+impl AnalysisOptions {
+    /// The defaults, same as [`AnalysisOptions::default`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    pub fn with_block_concurrency(mut self, block_concurrency: usize) -> Self {
+        self.block_concurrency = block_concurrency;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn include_storage(mut self, include_storage: bool) -> Self {
+        self.include_storage = include_storage;
+        self
+    }
+
+    pub fn revert_reasons(mut self, revert_reasons: bool) -> Self {
+        self.revert_reasons = revert_reasons;
+        self
+    }
+
+    pub fn with_sort(mut self, sort: SortKey) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn with_fields(mut self, fields: StateFields) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn with_address_filter(mut self, addresses: impl IntoIterator<Item = H160>) -> Self {
+        self.address_filter = Some(addresses.into_iter().collect());
+        self
+    }
+
+    pub fn per_transaction(mut self, per_transaction: bool) -> Self {
+        self.per_transaction = per_transaction;
+        self
+    }
+
+    pub fn classify_addresses(mut self, classify_addresses: bool) -> Self {
+        self.classify_addresses = classify_addresses;
+        self
+    }
+
+    pub fn with_ens(mut self, resolver: EnsResolver) -> Self {
+        self.ens = Some(resolver);
+        self
+    }
+
+    pub fn with_token_metadata(mut self, cache: TokenMetadataCache) -> Self {
+        self.token_metadata = Some(cache);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressReporter>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn with_missing_receipts(mut self, policy: MissingReceiptPolicy) -> Self {
+        self.missing_receipts = policy;
+        self
+    }
+
+    pub fn with_reorg_depth(mut self, reorg_depth: usize) -> Self {
+        self.reorg_depth = reorg_depth;
+        self
+    }
+
+    pub fn rpc_stats(mut self, rpc_stats: bool) -> Self {
+        self.rpc_stats = rpc_stats;
+        self
+    }
+
+    pub fn gas_report(mut self, gas_report: bool) -> Self {
+        self.gas_report = gas_report;
+        self
+    }
+
+    pub fn access_list_addresses(mut self, access_list_addresses: bool) -> Self {
+        self.access_list_addresses = access_list_addresses;
+        self
+    }
+
+    pub fn state_changes(mut self, state_changes: bool) -> Self {
+        self.state_changes = state_changes;
+        self
+    }
+
+    pub fn with_baseline_block(mut self, baseline_block: u64) -> Self {
+        self.baseline_block = Some(baseline_block);
+        self
+    }
+
+    pub fn with_min_balance_delta(mut self, min_balance_delta: Wei) -> Self {
+        self.min_balance_delta = Some(min_balance_delta);
+        self
+    }
+
+    pub fn with_min_nonce_delta(mut self, min_nonce_delta: u64) -> Self {
+        self.min_nonce_delta = Some(min_nonce_delta);
+        self
+    }
+
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    pub fn verify_state(mut self, verify_state: bool) -> Self {
+        self.verify_state = verify_state;
+        self
+    }
+
+    pub fn check_senders(mut self, check_senders: bool) -> Self {
+        self.check_senders = check_senders;
+        self
+    }
+
+    pub fn hide_system_addresses(mut self, hide_system_addresses: bool) -> Self {
+        self.hide_system_addresses = hide_system_addresses;
+        self
+    }
+
+    pub fn with_deep_first_seen(mut self, max_calls: usize) -> Self {
+        self.deep_first_seen = Some(max_calls);
+        self
+    }
+
+    pub fn with_watched_slot(mut self, address: H160, slot: H256) -> Self {
+        self.watched_slots.push((address, slot));
+        self
+    }
+
+    pub fn check_proxies(mut self, check_proxies: bool) -> Self {
+        self.check_proxies = check_proxies;
+        self
+    }
+
+    pub fn detect_code_changes(mut self, detect_code_changes: bool) -> Self {
+        self.detect_code_changes = detect_code_changes;
+        self
+    }
+
+    /// Whether `change` is big enough to report: it meets one of the thresholds set, or none are set.
+    /// Storage and code changes don't count, so a threshold hides accounts that only changed those.
+    fn meets_thresholds(&self, change: &StateChange) -> bool {
+        let balance = self.min_balance_delta.map(|min| change.balance_change.map_or(Wei::zero(), |delta| delta.magnitude) >= min);
+        let nonce = self.min_nonce_delta.map(|min| change.nonce_change.is_some_and(|delta| delta.magnitude >= U256::from(min)));
+        match (balance, nonce) {
+            (None, None) => true,
+            (balance, nonce) => balance.unwrap_or(false) || nonce.unwrap_or(false),
+        }
+    }
+
+    /// Whether `address` is one the analysis queries
+    fn includes(&self, address: &H160) -> bool {
+        self.address_filter.as_ref().is_none_or(|filter| filter.contains(address))
+    }
+
+    /// The calls an analysis of `block` with these options plans to make, judged from the block body
+    /// alone. Kept next to the options so a new option that adds calls adds them here too.
+    pub(crate) fn planned_calls(&self, block: &Block<Transaction>, extras: &rpc::BlockExtras, by_hash: bool) -> CallEstimate {
+        let mut estimate = CallEstimate::default();
+        let transactions = block.transactions.len() as u64;
+
+        estimate.add(if by_hash { "eth_getBlockByHash" } else { "eth_getBlockByNumber" }, 1);
+        if by_hash {
+            // The canonical check
+            estimate.add("eth_getBlockByNumber", 1);
+        }
+        estimate.add("eth_getUncleByBlockHashAndIndex", block.uncles.len() as u64);
+        estimate.add("eth_getBlockReceipts", 1);
+        estimate.add_fallback("eth_getTransactionReceipt", transactions);
+        estimate.add("trace_block", 1);
+        estimate.add_fallback("debug_traceBlockByNumber", 1);
+        if self.revert_reasons {
+            estimate.add_up_to("eth_call", transactions);
+        }
+
+        // Every account the block body names; accounts reached only by internal calls come on top
+        let mut addresses: HashSet<H160> = block.transactions.iter().flat_map(|tx| [tx.from, tx.to]).flatten().collect();
+        addresses.insert(block.author);
+        addresses.extend(extras.withdrawals.iter().map(|withdrawal| withdrawal.address));
+        if self.access_list_addresses {
+            addresses.extend(block.transactions.iter().flat_map(|tx| tx.access_list.iter().flatten()).map(|item| item.address));
+        }
+        addresses.retain(|address| self.includes(address));
+        let created = block.transactions.iter().filter(|tx| tx.to.is_none()).count() as u64;
+        let accounts = addresses.len() as u64 + created + block.uncles.len() as u64;
+
+        if self.token_metadata.is_some() {
+            // Tokens are only known from the receipts' logs, so count every called contract as one:
+            // symbol, name and decimals each
+            let called: HashSet<H160> =
+                block.transactions.iter().filter(|tx| tx.input.0.len() >= 4).filter_map(|tx| tx.to).collect();
+            estimate.add_up_to("eth_call", 3 * called.len() as u64);
+        }
+        if self.ens.is_some() {
+            // A reverse resolver, name, forward resolver and address lookup per new address
+            estimate.add_up_to("eth_call", 4 * accounts);
+        }
+        if !self.state_changes {
+            return estimate;
+        }
+
+        if self.baseline_block.is_some() {
+            estimate.add("eth_getBlockByNumber", 1);
+        }
+        estimate.add("trace_replayBlockTransactions", 1);
+        if self.include_storage {
+            estimate.add_fallback("debug_traceTransaction", transactions);
+        }
+        if self.per_transaction {
+            estimate.add_fallback("debug_traceTransaction", transactions);
+        }
+        let blocks = if self.baseline_block.is_some() || block.number.is_some_and(|number| number > U64::zero()) { 2 } else { 1 };
+        if self.fields.balance {
+            estimate.add("eth_getBalance", blocks * accounts);
+        }
+        if self.fields.nonce {
+            estimate.add("eth_getTransactionCount", blocks * accounts);
+        }
+        if self.classify_addresses {
+            estimate.add_up_to("eth_getCode", accounts);
+            // The code at the parent, looking for self-destructs without traces
+            estimate.add_fallback("eth_getCode", accounts);
+        }
+        if !self.watched_slots.is_empty() {
+            estimate.add("eth_getStorageAt", blocks * self.watched_slots.len() as u64);
+        }
+        if self.check_proxies && blocks == 2 {
+            // Three slots per recipient and filtered address, less the contracts the block creates
+            let mut proxies: HashSet<H160> = block.transactions.iter().filter_map(|tx| tx.to).collect();
+            proxies.extend(self.address_filter.iter().flatten());
+            estimate.add("eth_getStorageAt", 2 * 3 * proxies.len() as u64);
+        }
+        if self.detect_code_changes && blocks == 2 {
+            // Fewer in a range, where a block's code serves as the next one's baseline
+            estimate.add_up_to("eth_getCode", 2 * accounts);
+        }
+        if self.fields.balance && self.fields.nonce && blocks == 2 {
+            // The code at the baseline of accounts that were empty there
+            estimate.add_up_to("eth_getCode", accounts);
+            if let Some(max_calls) = self.deep_first_seen {
+                estimate.add_up_to("eth_getBalance", max_calls as u64);
+            }
+        }
+        if self.verify_state {
+            // The state roots, and a proof per changed account and block after one to check support
+            estimate.add("eth_getBlockByHash", 1);
+            estimate.add("eth_getBlockByNumber", blocks - 1);
+            estimate.add_up_to("eth_getProof", blocks * accounts + 1);
+        }
+        estimate
+    }
+}
+
+/// Planned RPC calls per method, from [`estimate_calls`] or [`estimate_range_calls`]. Nothing is
+/// cached in the estimate, and accounts only internal calls reach aren't known up front, so state
+/// queries can run higher.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CallEstimate {
+    /// Blocks the estimate covers
+    pub blocks: u64,
+    /// Blocks whose bodies were fetched to make it; the rest are extrapolated from them
+    pub sampled_blocks: u64,
+    /// Calls made when the node supports the preferred methods
+    pub calls: BTreeMap<String, u64>,
+    /// Calls that depend on what the receipts show, counted at their most, e.g. revert reasons
+    pub up_to: BTreeMap<String, u64>,
+    /// Calls made instead when the node lacks a preferred method, e.g. a receipt per transaction
+    /// without `eth_getBlockReceipts`
+    pub fallback: BTreeMap<String, u64>,
+}
+
+impl CallEstimate {
+    fn add(&mut self, method: &str, calls: u64) {
+        if calls > 0 {
+            *self.calls.entry(method.to_string()).or_default() += calls;
+        }
+    }
+
+    fn add_up_to(&mut self, method: &str, calls: u64) {
+        if calls > 0 {
+            *self.up_to.entry(method.to_string()).or_default() += calls;
+        }
+    }
+
+    fn add_fallback(&mut self, method: &str, calls: u64) {
+        if calls > 0 {
+            *self.fallback.entry(method.to_string()).or_default() += calls;
+        }
+    }
+
+    /// Calls made when every preferred method is supported and nothing more is needed
+    pub fn total(&self) -> u64 {
+        self.calls.values().sum()
+    }
+}
+
+/// The phases of an analysis that scale with the size of the block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Transaction receipts fetched, out of the block's transactions
+    Receipts,
+    /// Addresses whose balance and nonce have been fetched, out of all candidate addresses
+    StateDiff,
+}
+
+/// Receives progress updates during an analysis, e.g. to drive a progress bar.
+/// Each phase starts with `done == 0` and ends with `done == total`.
+pub trait ProgressReporter: Debug + Send + Sync {
+    fn update(&self, phase: Phase, done: usize, total: usize);
+}
+
+/// A mined block's transactions should all have receipts; a missing one usually means the
+/// endpoint is unhealthy or has pruned them, and the transaction's gas and status are unknown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingReceiptPolicy {
+    /// Fail the analysis with a [`MissingReceiptsError`]
+    Error,
+    /// Carry on, noting the affected transactions in [`BlockAnalysis::warnings`]
+    #[default]
+    Warn,
+    /// Carry on silently
+    Skip,
+}
+
+impl std::str::FromStr for MissingReceiptPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(MissingReceiptPolicy::Error),
+            "warn" => Ok(MissingReceiptPolicy::Warn),
+            "skip" => Ok(MissingReceiptPolicy::Skip),
+            _ => Err(format!("invalid missing receipt policy '{}': expected error, warn or skip", s)),
+        }
+    }
+}
+
+/// Raised under [`MissingReceiptPolicy::Error`] when the node returned no receipt for some transactions
+#[derive(Debug, Clone)]
+pub struct MissingReceiptsError {
+    pub block_number: u64,
+    pub transactions: Vec<H256>,
+}
+
+impl std::fmt::Display for MissingReceiptsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "block {} is missing {} receipts:", self.block_number, self.transactions.len())?;
+        for hash in &self.transactions {
+            write!(f, "\n  {:?}", hash)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for MissingReceiptsError {}
+
+/// The node has no block for the requested number, hash or tag
+#[derive(Debug, Clone, Copy)]
+pub struct BlockNotFoundError {
+    pub block: BlockRef,
+}
+
+impl std::fmt::Display for BlockNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.block {
+            BlockRef::Number(number) => write!(f, "block {} not found", number),
+            BlockRef::Hash(hash) => write!(f, "block {:?} not found", hash),
+            tag => write!(f, "no {} block found", format!("{:?}", tag).to_lowercase()),
+        }
+    }
+}
+
+impl Error for BlockNotFoundError {}
+
+/// A request that got no answer within [`RetryPolicy::request_timeout`] on its last attempt, or a whole
+/// run that outlasted [`AnalysisOptions::deadline`]. A request's timeout reaches the caller as the payload
+/// of a `web3::Error::Io` of kind `TimedOut`, which [`TimeoutError::find`] looks inside.
+#[derive(Debug, Clone)]
+pub struct TimeoutError {
+    /// The stalled request, e.g. `eth_getBalance("0xab…", "0x710c1f")`, or the run that overran
+    pub call: String,
+    pub after: Duration,
+}
+
+impl TimeoutError {
+    /// The timeout behind `error`, anywhere in its source chain
+    pub fn find<'a>(error: &'a (dyn Error + 'static)) -> Option<&'a TimeoutError> {
+        let mut error = Some(error);
+        while let Some(e) = error {
+            let timeout = match e.downcast_ref::<web3::Error>() {
+                Some(web3::Error::Io(io)) => io.get_ref().and_then(|inner| inner.downcast_ref::<TimeoutError>()),
+                _ => e.downcast_ref::<TimeoutError>(),
+            };
+            if timeout.is_some() {
+                return timeout;
+            }
+            error = e.source();
+        }
+        None
+    }
+}
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} timed out after {:?}", self.call, self.after)
+    }
+}
+
+impl Error for TimeoutError {}
+
+/// How state changes are ordered; ties always fall back to address order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// Ascending address
+    #[default]
+    Address,
+    /// Largest balance change first, regardless of sign
+    BalanceDelta,
+    /// Largest nonce change first
+    NonceDelta,
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(SortKey::Address),
+            "balance-delta" => Ok(SortKey::BalanceDelta),
+            "nonce-delta" => Ok(SortKey::NonceDelta),
+            _ => Err(format!("invalid sort key '{}': expected address, balance-delta or nonce-delta", s)),
+        }
+    }
+}
+
+/// Which account fields are queried for state changes; an excluded field's `Option`s in [`StateChange`]
+/// stay `None`, and a change to it alone doesn't make an address count as changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateFields {
+    pub balance: bool,
+    pub nonce: bool,
+}
+
+impl Default for StateFields {
+    fn default() -> Self {
+        StateFields { balance: true, nonce: true }
+    }
+}
+
+impl std::str::FromStr for StateFields {
+    type Err = String;
+
+    /// A comma-separated list such as `balance,nonce` or `nonce`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = StateFields { balance: false, nonce: false };
+        for field in s.split(',').map(str::trim) {
+            match field {
+                "balance" => fields.balance = true,
+                "nonce" => fields.nonce = true,
+                _ => return Err(format!("invalid field '{}': expected balance or nonce", field)),
+            }
+        }
+        Ok(fields)
+    }
+}
+
+/// Which block to analyze: a number, a hash, or one of the JSON-RPC block tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRef {
+    Number(u64),
+    Hash(H256),
+    Latest,
+    Pending,
+    Safe,
+    Finalized,
+    Earliest,
+}
+
+impl From<u64> for BlockRef {
+    fn from(number: u64) -> Self {
+        BlockRef::Number(number)
+    }
+}
+
+impl std::str::FromStr for BlockRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latest" => Ok(BlockRef::Latest),
+            "pending" => Ok(BlockRef::Pending),
+            "safe" => Ok(BlockRef::Safe),
+            "finalized" => Ok(BlockRef::Finalized),
+            "earliest" => Ok(BlockRef::Earliest),
+            _ => s.parse().map(BlockRef::Number).map_err(|_| {
+                format!("invalid block '{}': expected a number or one of latest, pending, safe, finalized, earliest", s)
+            }),
+        }
+    }
+}
+
+impl Serialize for BlockRef {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            BlockRef::Number(number) => serializer.serialize_str(&format!("{:#x}", number)),
+            BlockRef::Hash(hash) => hash.serialize(serializer),
+            BlockRef::Latest => serializer.serialize_str("latest"),
+            BlockRef::Pending => serializer.serialize_str("pending"),
+            BlockRef::Safe => serializer.serialize_str("safe"),
+            BlockRef::Finalized => serializer.serialize_str("finalized"),
+            BlockRef::Earliest => serializer.serialize_str("earliest"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockAnalysis {
+    /// As reported by `eth_chainId`
+    pub chain_id: u64,
+    pub block_info: BlockInfo,
+    /// Block whose state the changes are measured from: the parent unless another baseline was asked for,
+    /// and `None` for the genesis block
+    pub baseline_block: Option<u64>,
+    pub receipt_source: ReceiptSource,
+    /// `None` when account state wasn't queried
+    pub state_diff_source: Option<StateDiffSource>,
+    /// `None` when account state wasn't queried, as opposed to empty when nothing changed
+    pub state_changes: Option<Vec<StateChange>>,
+    /// Only populated when per-transaction attribution was requested and the node supports tracing
+    pub per_tx_changes: Vec<TransactionChanges>,
+    pub token_transfers: Vec<TokenTransfer>,
+    pub nft_transfers: Vec<NftTransfer>,
+    /// Ether moved by calls within transactions; `None` when account state wasn't queried or the node has
+    /// neither `trace_block` nor `debug_traceBlockByNumber`, as opposed to empty when there were none
+    pub internal_transfers: Option<Vec<InternalTransfer>>,
+    /// Self-destructs found in traces, or without traces contracts whose code disappeared; the latter
+    /// are only looked for when addresses are classified
+    pub destroyed_contracts: Vec<DestroyedContract>,
+    /// The watched storage slots that changed, read directly rather than traced
+    pub slot_changes: Vec<SlotChange>,
+    /// Proxies whose EIP-1967 implementation, admin or beacon changed; only looked for when requested,
+    /// and warned about as they're high-severity
+    pub proxy_upgrades: Vec<ProxyUpgrade>,
+    /// Touched addresses whose code was created, destroyed or replaced; only looked for when requested
+    pub code_changes: Vec<CodeChange>,
+    /// Metadata of the tokens in `token_transfers` and `nft_transfers`; only populated when requested
+    #[serde(serialize_with = "crate::address::serialize_keys")]
+    pub token_metadata: BTreeMap<H160, TokenMetadata>,
+    /// Primary ENS names of addresses in the analysis; only populated when ENS resolution was requested
+    #[serde(serialize_with = "crate::address::serialize_keys")]
+    pub ens: BTreeMap<H160, String>,
+    pub summary: BlockSummary,
+    /// Problems that didn't stop the analysis but may make it incomplete
+    pub warnings: Vec<String>,
+    /// Only populated when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_report: Option<GasReport>,
+    /// RPC calls made for this block; only populated when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_stats: Option<RpcStats>,
+    /// The header, transactions and receipts checked against each other; only populated with `verify`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
+}
+
+impl BlockAnalysis {
+    /// The `n` largest balance increases and the `n` largest decreases, biggest first.
+    /// Equal changes are ordered by address so the report is the same on every run.
+    pub fn top_movers(&self, n: usize) -> TopMovers<'_> {
+        let mut movers: Vec<(&StateChange, WeiDelta)> = self
+            .state_changes
+            .iter()
+            .flatten()
+            .filter_map(|change| change.balance_change.map(|delta| (change, delta)))
+            .filter(|(_, delta)| !delta.magnitude.is_zero())
+            .collect();
+        movers.sort_by_key(|(change, delta)| (Reverse(delta.magnitude), change.address));
+
+        let (mut losers, mut gainers): (Vec<_>, Vec<_>) = movers.into_iter().partition(|(_, delta)| delta.decreased);
+        losers.truncate(n);
+        gainers.truncate(n);
+        TopMovers {
+            gainers: gainers.into_iter().map(|(change, _)| change).collect(),
+            losers: losers.into_iter().map(|(change, _)| change).collect(),
+        }
+    }
+}
+
+/// Addresses with the largest balance changes in a block, from [`BlockAnalysis::top_movers`]
+#[derive(Debug)]
+pub struct TopMovers<'a> {
+    pub gainers: Vec<&'a StateChange>,
+    pub losers: Vec<&'a StateChange>,
+}
+
+/// What range and watch modes hand to their callback, in chain order
+#[derive(Debug)]
+pub enum BlockEvent {
+    Analyzed(Box<BlockAnalysis>),
+    /// Blocks handed over earlier are no longer canonical; analyses of their replacements follow
+    Reorg(Reorg),
+}
+
+/// A reorg spotted because a new block's parent wasn't the block delivered before it
+#[derive(Debug, Clone, Serialize)]
+pub struct Reorg {
+    /// Newest block still on the canonical chain
+    pub common_ancestor: u64,
+    /// Earlier analyses that no longer hold, oldest first
+    pub invalidated: Vec<InvalidatedBlock>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InvalidatedBlock {
+    pub number: u64,
+    pub hash: H256,
+}
+
+/// Block-wide totals for dashboards, computed from the rest of the analysis
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockSummary {
+    pub transaction_count: usize,
+    pub unique_senders: usize,
+    /// Counts created contracts as the recipients of their creation transactions
+    pub unique_recipients: usize,
+    /// Value moved by successful transactions
+    pub value_transferred: Wei,
+    pub gas_used: u64,
+    /// `gas_used` as a percentage of the block gas limit
+    pub gas_used_percent: f64,
+    pub fees_paid: Wei,
+    /// Base fee times gas used, summed over the transactions; zero before London, and on OP Stack chains,
+    /// which pay it into the BaseFeeVault
+    pub total_base_fee_burned: Wei,
+    /// The part of the fees above the base fee, paid to the coinbase
+    pub total_priority_fees: Wei,
+    /// Blob gas fees of EIP-4844 transactions, burned on top of `fees_paid`
+    #[serde(skip_serializing_if = "is_zero_wei")]
+    pub total_blob_fees: Wei,
+    /// L1 data fees of an OP Stack block's transactions, charged on top of `fees_paid`
+    #[serde(skip_serializing_if = "is_zero_wei")]
+    pub total_l1_fees: Wei,
+    /// The coinbase's balance change beyond its priority fees, transfers and withdrawals. Non-zero
+    /// after the merge usually means direct payments to the builder, as MEV bundles make; before it,
+    /// the block reward shows up here too.
+    pub unexplained_coinbase_change: WeiDelta,
+    /// `unexplained_coinbase_change` when it is an increase on a proof-of-stake block: what was paid to
+    /// the coinbase directly from inside transactions
+    pub mev_payment: Option<Wei>,
+    /// Transactions whose own state diff shows them paying the coinbase; empty without trace support
+    pub mev_candidates: Vec<H256>,
+    /// Transactions per EIP-2718 type; untyped transactions count as legacy (0)
+    pub transactions_by_type: BTreeMap<u64, usize>,
+    pub contract_creations: usize,
+    pub reverted_transactions: usize,
+    pub changed_addresses: usize,
+    /// Of `changed_addresses`, those left out of `state_changes` for falling under the delta thresholds
+    pub filtered_state_changes: usize,
+    /// Of `changed_addresses`, the ArbOS system accounts left out of `state_changes` by `hide_system_addresses`
+    #[serde(skip_serializing_if = "is_zero")]
+    pub hidden_system_addresses: usize,
+    pub gas_price_stats: GasPriceStats,
+}
+
+/// How the block's transactions priced their gas, from what the receipts say was charged, or the
+/// legacy gas price without one. Every figure is `None` for a block without priced transactions.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GasPriceStats {
+    /// Transactions with a known price
+    pub transactions: usize,
+    pub min: Option<Wei>,
+    pub p25: Option<Wei>,
+    pub median: Option<Wei>,
+    pub p75: Option<Wei>,
+    pub max: Option<Wei>,
+    /// Transactions that paid the base fee and no priority fee; always zero before London
+    pub base_fee_only: usize,
+    /// Equal-width price ranges from `min` to `max`, cheapest first
+    pub histogram: Vec<GasPriceBucket>,
+}
+
+/// Transactions priced from `low` up to and including `high`
+#[derive(Debug, Clone, Serialize)]
+pub struct GasPriceBucket {
+    pub low: Wei,
+    pub high: Wei,
+    pub transactions: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockInfo {
+    pub block_number: u64,
+    /// Seconds since the epoch
+    pub timestamp: u64,
+    /// `timestamp` as RFC 3339 UTC
+    pub timestamp_utc: String,
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub nonce: Option<String>,
+    pub miner: String,
+    #[serde(skip)]
+    pub miner_address: H160,
+    pub difficulty: String,
+    /// Many providers no longer report it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_difficulty: Option<String>,
+    /// The beacon chain's randomness, carried in `mixHash` after the merge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev_randao: Option<H256>,
+    pub size: u64,
+    pub gas_used: u64,
+    pub gas_limit: u64,
+    /// Absent before London
+    pub base_fee_per_gas: Option<Wei>,
+    /// `base_fee_per_gas * gas_used`, destroyed rather than paid to the miner; zero on OP Stack chains,
+    /// which pay it into the BaseFeeVault
+    pub burned_base_fee: Option<Wei>,
+    /// Absent before Dencun
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excess_blob_gas: Option<u64>,
+    /// The L1 block Arbitrum's sequencer last synced to; only on Arbitrum chains
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_block_number: Option<u64>,
+    pub transactions: Vec<TransactionInfo>,
+    /// Beacon chain withdrawals credited in this block; empty before Shanghai
+    pub withdrawals: Vec<WithdrawalInfo>,
+    /// Ommers this block includes, whose miners are rewarded by it; only proof-of-work blocks have any
+    pub uncles: Vec<UncleInfo>,
+}
+
+impl BlockInfo {
+    /// Whether the block was proposed by a validator rather than mined. Difficulty is zero after the merge,
+    /// so the total difficulty stays at the terminal value, when the node reports it at all.
+    pub fn is_post_merge(&self) -> bool {
+        self.difficulty == "0"
+    }
+
+    /// Whether the block is an OP Stack rollup's, told by its deposit transactions and L1 fees
+    pub fn is_op_stack(&self) -> bool {
+        fees::is_op_stack(&self.transactions)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UncleInfo {
+    pub hash: H256,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub miner: H160,
+    pub number: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WithdrawalInfo {
+    pub index: u64,
+    pub validator_index: u64,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    /// Converted to wei from the Gwei the RPC reports
+    pub amount: Wei,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionInfo {
+    pub hash: H256,
+    /// Some L2 system transactions (e.g. OP Stack deposits) come back without a sender
+    #[serde(serialize_with = "crate::address::serialize_option")]
+    pub from: Option<H160>,
+    #[serde(serialize_with = "crate::address::serialize_option")]
+    pub to: Option<H160>,
+    /// EIP-2718 type: 0 legacy, 1 access list, 2 EIP-1559, 3 blob, 0x7e an OP Stack deposit; `None` when
+    /// the node doesn't say
+    pub tx_type: Option<u64>,
+    /// Addresses and storage keys declared up front; `None` on legacy transactions
+    #[serde(serialize_with = "crate::address::serialize_access_list")]
+    pub access_list: Option<Vec<(H160, Vec<H256>)>>,
+    /// Labels from an address label file, when one was applied
+    pub from_label: Option<String>,
+    pub to_label: Option<String>,
+    pub value: Wei,
+    /// Gas limit set by the sender
+    #[serde(serialize_with = "serialize_u256")]
+    pub gas: U256,
+    /// `transfer`, `create` or the called function's signature, when a selector registry was applied
+    /// and knows it
+    pub method: Option<String>,
+    /// Bytes of calldata
+    pub input_size: usize,
+    pub status: TransactionStatus,
+    /// Only looked up when revert reasons were requested
+    pub revert_reason: Option<String>,
+    #[serde(serialize_with = "serialize_opt_u256")]
+    pub gas_used: Option<U256>,
+    /// Address of the contract deployed by this transaction, if it was a creation
+    #[serde(serialize_with = "crate::address::serialize_option")]
+    pub created_contract: Option<H160>,
+    pub gas_price: Option<Wei>,
+    /// EIP-1559 fee caps; `None` on legacy and access-list transactions
+    pub max_fee_per_gas: Option<Wei>,
+    pub max_priority_fee_per_gas: Option<Wei>,
+    /// Price actually charged per unit of gas, from the receipt
+    pub effective_gas_price: Option<Wei>,
+    /// `gas_used * effective_gas_price`
+    pub fee_paid: Option<Wei>,
+    /// The blob fields are only present on EIP-4844 transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_versioned_hashes: Option<Vec<H256>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_blob_gas: Option<Wei>,
+    #[serde(serialize_with = "serialize_opt_u256", skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_gas_price: Option<Wei>,
+    /// `blob_gas_used * blob_gas_price`, burned on top of `fee_paid`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_fee_paid: Option<Wei>,
+    /// The L1 data fee fields are only present on OP Stack chains' non-deposit transactions
+    #[serde(serialize_with = "serialize_opt_u256", skip_serializing_if = "Option::is_none")]
+    pub l1_gas_used: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_gas_price: Option<Wei>,
+    /// Charged on top of `fee_paid` and credited to the L1FeeVault
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l1_fee: Option<Wei>,
+    /// Ether an OP Stack deposit credits its sender from L1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mint: Option<Wei>,
+    /// Of `gas_used`, what Arbitrum charged for posting the transaction's data to L1
+    #[serde(serialize_with = "serialize_opt_u256", skip_serializing_if = "Option::is_none")]
+    pub gas_used_for_l1: Option<U256>,
+    /// Logs from contracts with a known ABI, when an ABI registry was applied
+    pub decoded_events: Vec<DecodedEvent>,
+    /// Receipt logs, kept for decoding rather than output
+    #[serde(skip)]
+    pub logs: Vec<Log>,
+    #[serde(skip)]
+    pub input: Bytes,
+}
+
+/// Outcome of a transaction according to its receipt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    Success,
+    Reverted,
+    /// No receipt, or a pre-Byzantium receipt without a status field
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StateChange {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    /// From an address label file, when one was applied
+    pub label: Option<String>,
+    pub prev_balance: Option<Wei>,
+    pub new_balance: Option<Wei>,
+    #[serde(serialize_with = "serialize_opt_u256")]
+    pub prev_nonce: Option<U256>,
+    #[serde(serialize_with = "serialize_opt_u256")]
+    pub new_nonce: Option<U256>,
+    pub balance_change: Option<WeiDelta>,
+    pub nonce_change: Option<NonceDelta>,
+    /// Value received from successful transactions in the block (or in the transaction, per-tx)
+    pub value_in: Wei,
+    /// Value sent by successful transactions
+    pub value_out: Wei,
+    /// Gas fees paid as a sender, reverted transactions included
+    pub fees_paid: Wei,
+    /// Fees above the base fee, credited to the coinbase
+    pub priority_fees_received: Wei,
+    /// Base and L1 fees credited to an OP Stack fee vault
+    #[serde(skip_serializing_if = "is_zero_wei")]
+    pub vault_fees_received: Wei,
+    /// Only detected from trace state diffs
+    pub code_changed: bool,
+    /// Only populated when address classification was requested
+    pub is_contract: Option<bool>,
+    /// Bytes of code at the analyzed block; only populated with `is_contract`
+    pub code_size: Option<usize>,
+    /// Only populated when storage diffs were requested and the node supports tracing
+    pub storage_changes: Vec<StorageChange>,
+    /// The balances and nonces were proven against the state roots with `eth_getProof`; only with `verify_state`
+    pub proof_verified: bool,
+    /// No nonce, balance or code at the baseline, nor a balance at any earlier block `deep_first_seen`
+    /// probed: the block is the account's first activity. Only set when both balances and nonces were queried.
+    pub first_activity: bool,
+}
+
+/// Signed difference between two nonces. A nonce only goes down when the account was deleted and
+/// created again, as a self-destructed contract redeployed at the same address is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceDelta {
+    pub decreased: bool,
+    pub magnitude: U256,
+}
+
+impl NonceDelta {
+    pub fn between(prev: U256, new: U256) -> Self {
+        match new >= prev {
+            true => NonceDelta { decreased: false, magnitude: new - prev },
+            false => NonceDelta { decreased: true, magnitude: prev - new },
+        }
+    }
+
+    /// A zero change is never negative
+    pub fn is_negative(self) -> bool {
+        self.decreased && !self.magnitude.is_zero()
+    }
+}
+
+impl std::fmt::Display for NonceDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.is_negative() {
+            true => write!(f, "-{}", self.magnitude),
+            false => write!(f, "{}", self.magnitude),
+        }
+    }
+}
+
+// A decimal string with a leading '-' for a decrease, like balance changes
+impl Serialize for NonceDelta {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// How the transaction receipts were fetched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReceiptSource {
+    /// All at once with `eth_getBlockReceipts`
+    Block,
+    /// One `eth_getTransactionReceipt` per transaction, for nodes without `eth_getBlockReceipts`
+    PerTransaction,
+}
+
+/// How the set of accounts in `state_changes` was found, and so how complete it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateDiffSource {
+    /// Replayed state diffs: every account touched, including through internal calls
+    Trace,
+    /// Senders, recipients, created contracts, the miner and withdrawal recipients only
+    Heuristic,
+}
+
+/// State changes caused by a single transaction, in the same shape as the block-wide ones
+#[derive(Debug, Serialize)]
+pub struct TransactionChanges {
+    pub transaction_hash: H256,
+    pub state_changes: Vec<StateChange>,
+}
+
+/// An ERC-20 `Transfer` event decoded from a receipt log
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenTransfer {
+    pub transaction_hash: H256,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub token: H160,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub from: H160,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub to: H160,
+    #[serde(serialize_with = "serialize_u256")]
+    pub amount: U256,
+}
+
+/// An ERC-721 or ERC-1155 token movement decoded from a receipt log. An ERC-1155 `TransferBatch`
+/// is expanded into one entry per id and amount pair, in the order the event lists them.
+#[derive(Debug, Clone, Serialize)]
+pub struct NftTransfer {
+    pub transaction_hash: H256,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub token: H160,
+    pub standard: NftStandard,
+    /// The account that moved the tokens; ERC-1155 only
+    #[serde(serialize_with = "crate::address::serialize_option")]
+    pub operator: Option<H160>,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub from: H160,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub to: H160,
+    #[serde(serialize_with = "serialize_u256")]
+    pub token_id: U256,
+    /// Always 1 for ERC-721
+    #[serde(serialize_with = "serialize_u256")]
+    pub amount: U256,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NftStandard {
+    Erc721,
+    Erc1155,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageChange {
+    pub slot: H256,
+    pub before: H256,
+    pub after: H256,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct AccountState {
+    balance: Wei,
+    nonce: U256,
+}
+
+/// Account states observed at one block, reused as the baseline when the next block is analyzed
+#[derive(Debug, Default, Clone)]
+pub(crate) struct AccountStateCache {
+    block_number: Option<u64>,
+    accounts: HashMap<H160, AccountState>,
+    /// Code sizes by address, kept for a whole run since code rarely changes
+    code_sizes: HashMap<H160, usize>,
+    /// Code hashes by address and block, for `detect_code_changes`; only the last block's are kept
+    code_hashes: HashMap<(H160, u64), code_changes::CodeHash>,
+    chain_id: Option<u64>,
+}
+
+impl AccountStateCache {
+    /// Take in what a block analyzed alongside others learned. Its states and code hashes replace these
+    /// only if it's a later block, so one that finishes out of order can't set the cache back.
+    fn merge(&mut self, finished: AccountStateCache) {
+        self.chain_id = self.chain_id.or(finished.chain_id);
+        self.code_sizes.extend(finished.code_sizes);
+        if finished.block_number > self.block_number {
+            self.block_number = finished.block_number;
+            self.accounts = finished.accounts;
+            self.code_hashes = finished.code_hashes;
+        }
+    }
+}
+
+fn is_zero_wei(value: &Wei) -> bool {
+    value.is_zero()
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+// U256 values are written as decimal strings so consumers like jq don't lose precision
+fn serialize_u256<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+fn serialize_opt_u256<S: Serializer>(value: &Option<U256>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(v) => serialize_u256(v, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub async fn analyze_block<T: BatchTransport>(
+    web3: &Web3<T>,
+    block_number: Option<u64>,
+    concurrency: usize,
+) -> Result<BlockAnalysis, Box<dyn Error>> {
+    let options = AnalysisOptions::new().with_concurrency(concurrency);
+    analyze_block_with(web3, block_number.map_or(BlockRef::Latest, BlockRef::Number), &options).await
+}
+
+/// Like `analyze_block`, but also traces each transaction with the prestate tracer to report
+/// storage slot changes. Falls back to plain balance/nonce diffs if the node has no debug namespace.
+pub async fn analyze_block_with_storage<T: BatchTransport>(
+    web3: &Web3<T>,
+    block_number: Option<u64>,
+    concurrency: usize,
+) -> Result<BlockAnalysis, Box<dyn Error>> {
+    let options = AnalysisOptions::new().with_concurrency(concurrency).include_storage(true);
+    analyze_block_with(web3, block_number.map_or(BlockRef::Latest, BlockRef::Number), &options).await
+}
+
+/// Analyze a single block with every knob in `options` applied.
+pub async fn analyze_block_with<T: BatchTransport>(
+    web3: &Web3<T>,
+    block: BlockRef,
+    options: &AnalysisOptions,
+) -> Result<BlockAnalysis, Box<dyn Error>> {
+    let mut cache = AccountStateCache::default();
+    with_deadline(options.deadline, || analysis_of(block), analyze_block_cached(web3, block, options, &mut cache)).await
+}
+
+/// Analyze every block in `from..=to`, returning the analyses in ascending block order.
+pub async fn analyze_block_range<T: BatchTransport>(
+    web3: &Web3<T>,
+    from: u64,
+    to: u64,
+    concurrency: usize,
+) -> Result<Vec<BlockAnalysis>, Box<dyn Error>> {
+    let options = AnalysisOptions::new().with_concurrency(concurrency);
+    let mut analyses: Vec<BlockAnalysis> = Vec::new();
+    analyze_block_range_streaming(web3, from, to, &options, |_, event| {
+        match event? {
+            BlockEvent::Analyzed(analysis) => analyses.push(*analysis),
+            BlockEvent::Reorg(reorg) => analyses.retain(|analysis| analysis.block_info.block_number <= reorg.common_ancestor),
+        }
+        Ok(())
+    }).await?;
+
+    Ok(analyses)
+}
+
+/// Like `analyze_block_range`, but hands each block's outcome to `on_block` in ascending order as soon
+/// as it is ready instead of buffering the whole range. Up to `options.block_concurrency` blocks are
+/// analyzed at once. A failed block stops the run only if `on_block` returns the error, which also
+/// abandons the blocks in flight. A block that doesn't build on the one before it is reported as a
+/// [`BlockEvent::Reorg`], after which the range is re-analyzed from the fork.
+pub async fn analyze_block_range_streaming<T, F>(
+    web3: &Web3<T>,
+    from: u64,
+    to: u64,
+    options: &AnalysisOptions,
+    mut on_block: F,
+) -> Result<(), Box<dyn Error>>
+where
+    T: BatchTransport,
+    F: FnMut(u64, Result<BlockEvent, Box<dyn Error>>) -> Result<(), Box<dyn Error>>,
+{
+    if from > to {
+        return Err(format!("invalid block range: from-block {} is after to-block {}", from, to).into());
+    }
+
+    with_deadline(options.deadline, || format!("analysis of blocks {} to {}", from, to), async {
+        let mut tracker = reorg::ChainTracker::new(options.reorg_depth);
+        // Block N's "current" state is block N+1's "previous" state, so carry it across iterations
+        let mut cache = AccountStateCache::default();
+
+        if options.block_concurrency <= 1 {
+            for block_number in from..=to {
+                let analysis = analyze_block_cached(web3, BlockRef::Number(block_number), options, &mut cache).await;
+                reorg::deliver(web3, options, &mut tracker, &mut cache, block_number, analysis, &mut on_block).await?;
+            }
+        } else {
+            // Each block in flight starts from a copy of the shared cache, so it gets the chain id, the
+            // code sizes seen so far and, when the block before it has finished, its baseline states.
+            // `buffered` holds early finishers until the blocks before them are done, and never starts
+            // more than the window allows.
+            let shared = Mutex::new(cache);
+            let lock = || shared.lock().expect("account state cache lock poisoned");
+            let mut analyses = stream::iter(from..=to)
+                .map(|block_number| async move {
+                    let mut cache = lock().clone();
+                    let analysis = analyze_block_cached(web3, BlockRef::Number(block_number), options, &mut cache).await;
+                    lock().merge(cache);
+                    (block_number, analysis)
+                })
+                .buffered(options.block_concurrency);
+            while let Some((block_number, analysis)) = analyses.next().await {
+                // Blocks in flight make no progress while this one is delivered, so the cache can be lent out
+                let mut cache = std::mem::take(&mut *lock());
+                let delivered = reorg::deliver(web3, options, &mut tracker, &mut cache, block_number, analysis, &mut on_block).await;
+                *lock() = cache;
+                delivered?;
+            }
+        }
+
+        Ok(())
+    }).await
+}
+
+/// Run `run`, failing with a [`TimeoutError`] naming it as `call` once `deadline` passes
+async fn with_deadline<R>(
+    deadline: Option<Duration>,
+    call: impl FnOnce() -> String,
+    run: impl std::future::Future<Output = Result<R, Box<dyn Error>>>,
+) -> Result<R, Box<dyn Error>> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout(deadline, run).await.map_err(|_| TimeoutError { call: call(), after: deadline })?,
+        None => run.await,
+    }
+}
+
+/// `block` as a deadline's [`TimeoutError`] names it
+fn analysis_of(block: BlockRef) -> String {
+    match block {
+        BlockRef::Number(number) => format!("analysis of block {}", number),
+        BlockRef::Hash(hash) => format!("analysis of block {:?}", hash),
+        tag => format!("analysis of the {} block", format!("{:?}", tag).to_lowercase()),
+    }
+}
+
+#[tracing::instrument(name = "analyze_block", skip_all, fields(block = ?block))]
+pub(crate) async fn analyze_block_cached<T: BatchTransport>(
+    web3: &Web3<T>,
+    block: BlockRef,
+    options: &AnalysisOptions,
+    cache: &mut AccountStateCache,
+) -> Result<BlockAnalysis, Box<dyn Error>> {
+    let started = Instant::now();
+    let (result, stats) = telemetry::with_rpc_summary(block, run_block_analysis(web3, block, options, cache)).await;
+    metrics::record_analysis_duration(started.elapsed());
+    let mut analysis = result?;
+    if options.rpc_stats {
+        analysis.rpc_stats = Some(stats);
+    }
+    Ok(analysis)
+}
+
+async fn run_block_analysis<T: BatchTransport>(
+    web3: &Web3<T>,
+    block: BlockRef,
+    options: &AnalysisOptions,
+    cache: &mut AccountStateCache,
+) -> Result<BlockAnalysis, Box<dyn Error>> {
+    let concurrency = options.concurrency;
+
+    // The chain can't change under a connection, so ask once per run
+    let chain_id = match cache.chain_id {
+        Some(chain_id) => chain_id,
+        None => *cache.chain_id.insert(web3.eth().chain_id().await?.as_u64()),
+    };
+
+    // Get block info
+    let FetchedBlock { mut block_info, receipt_source, missing_receipts, verification, sender_warnings } =
+        fetch_block_info(web3, block, concurrency, options.progress.as_deref(), options.verify, options.check_senders).await?;
+
+    // Deal with transactions the node had no receipt for
+    let mut warnings = sender_warnings;
+    if !missing_receipts.is_empty() {
+        match options.missing_receipts {
+            MissingReceiptPolicy::Error => {
+                return Err(MissingReceiptsError { block_number: block_info.block_number, transactions: missing_receipts }.into())
+            }
+            MissingReceiptPolicy::Warn => warnings.extend(missing_receipts.iter().map(missing_receipt_warning)),
+            MissingReceiptPolicy::Skip => {}
+        }
+    }
+
+    // A baseline other than the parent must exist before hundreds of state queries are made against it
+    let baseline_block = options.baseline_block.or(block_info.block_number.checked_sub(1));
+    if let Some(baseline) = options.baseline_block.filter(|_| options.state_changes) {
+        if baseline >= block_info.block_number {
+            return Err(format!("baseline block {} must come before the analyzed block {}", baseline, block_info.block_number).into());
+        }
+        if web3.eth().block(BlockId::Number(BlockNumber::Number(U64::from(baseline)))).await?.is_none() {
+            return Err(BlockNotFoundError { block: BlockRef::Number(baseline) }.into());
+        }
+    }
+    // Over several blocks, this block's transactions and rewards no longer account for the whole change
+    let spans_one_block = baseline_block == block_info.block_number.checked_sub(1);
+
+    // Recover revert reasons, if requested
+    if options.revert_reasons {
+        revert::fill_revert_reasons(web3, &mut block_info, concurrency).await;
+    }
+
+    // Replay the block for authoritative state diffs, if the node supports the trace_ namespace
+    let replayed = match options.state_changes {
+        true => state_diff::replay_block(web3, block_info.block_number).await?,
+        false => None,
+    };
+    let state_diff_source =
+        options.state_changes.then_some(if replayed.is_some() { StateDiffSource::Trace } else { StateDiffSource::Heuristic });
+
+    // Trace calls for the ether moved inside transactions, so every account involved is queried
+    let mut traced = match options.state_changes {
+        true => internal::trace_calls(web3, &block_info).await?,
+        false => None,
+    };
+    let mut destroyed_contracts = Vec::new();
+
+    let mut state_changes = if options.state_changes {
+        // Get storage changes, if requested and supported
+        let storage_changes = match &replayed {
+            _ if !options.include_storage => HashMap::new(),
+            Some(replayed) => state_diff::merge_storage(replayed),
+            None => trace::get_storage_changes(web3, &block_info, concurrency).await?.unwrap_or_default(),
+        };
+
+        // Get state changes
+        let mut state_changes =
+            get_state_changes(web3, &block_info, options, storage_changes, replayed.as_deref(), traced.as_ref(), cache).await?;
+        if options.verify_state {
+            proof::prove_state_changes(web3, &block_info, baseline_block, &mut state_changes, options, &mut warnings).await?;
+        }
+        if let Some(baseline) = baseline_block {
+            first_seen::mark_first_activity(web3, baseline, &mut state_changes, options).await?;
+        }
+        sort_state_changes(&mut state_changes, options.sort);
+
+        // Break balance changes down into transfers and fees, and check they add up
+        if spans_one_block {
+            let internal_transfers = traced.iter().flat_map(|traced| &traced.internal_transfers);
+            fees::decompose_balance_changes(&mut state_changes, &block_info.transactions, internal_transfers, &block_info);
+            // Balances only net out when every changed account was queried
+            if options.fields.balance && options.address_filter.is_none() {
+                fees::check_balance_consistency(chain_id, &block_info, &state_changes);
+            }
+        }
+
+        // Tell contracts from EOAs, if requested
+        if options.classify_addresses {
+            classify_addresses(web3, &block_info, &mut state_changes, concurrency, cache).await?;
+        }
+
+        // Without traces, a contract whose code vanished is the only sign of a self-destruct
+        destroyed_contracts = match &mut traced {
+            Some(traced) => std::mem::take(&mut traced.destroyed_contracts),
+            None if options.classify_addresses => {
+                selfdestruct::find_emptied_contracts(web3, &block_info, &state_changes, concurrency).await?
+            }
+            None => Vec::new(),
+        };
+        destroyed_contracts.retain(|destroyed| options.includes(&destroyed.address));
+        Some(state_changes)
+    } else {
+        None
+    };
+
+    // Look for transactions paying the coinbase directly while the replayed diffs are still at hand
+    let replayed_candidates = replayed.as_deref().map(|replayed| summary::mev_candidates(&block_info, replayed));
+
+    // Attribute changes to transactions, if requested and supported
+    let mut per_tx_changes = match replayed {
+        _ if !options.per_transaction || !options.state_changes => Vec::new(),
+        Some(replayed) => replayed,
+        None => state_diff::get_transaction_changes(web3, &block_info, concurrency).await?.unwrap_or_default(),
+    };
+    for tx_changes in &mut per_tx_changes {
+        tx_changes.state_changes.retain(|change| options.includes(&change.address));
+        sort_state_changes(&mut tx_changes.state_changes, options.sort);
+        let hash = tx_changes.transaction_hash;
+        let tx = block_info.transactions.iter().filter(|tx| tx.hash == hash);
+        let internal_transfers =
+            traced.iter().flat_map(|traced| &traced.internal_transfers).filter(|transfer| transfer.transaction_hash == hash);
+        fees::decompose_balance_changes(&mut tx_changes.state_changes, tx, internal_transfers, &block_info);
+    }
+
+    // Decode token transfers from the receipt logs
+    let token_transfers = tokens::extract_token_transfers(&block_info);
+    let nft_transfers = tokens::extract_nft_transfers(&block_info);
+
+    // Symbols and decimals for the tokens moved, if requested; lookups never fail the analysis
+    let token_metadata = match &options.token_metadata {
+        Some(cache) => {
+            let tokens: HashSet<H160> =
+                token_transfers.iter().map(|transfer| transfer.token).chain(nft_transfers.iter().map(|transfer| transfer.token)).collect();
+            cache.fetch_all(web3, tokens, block_info.block_number, concurrency).await
+        }
+        None => BTreeMap::new(),
+    };
+
+    // Name the addresses involved, if requested; lookups never fail the analysis
+    let ens = match &options.ens {
+        Some(resolver) => {
+            let addresses = block_info
+                .transactions
+                .iter()
+                .flat_map(|tx| [tx.from, tx.to, tx.created_contract])
+                .flatten()
+                .chain(state_changes.iter().flatten().map(|change| change.address));
+            resolver.resolve_all(addresses, concurrency).await
+        }
+        None => BTreeMap::new(),
+    };
+
+    let mev_candidates = replayed_candidates.unwrap_or_else(|| summary::mev_candidates(&block_info, &per_tx_changes));
+    let mut summary = summary::summarize(&block_info, state_changes.as_deref().unwrap_or_default(), mev_candidates);
+
+    // Drop the changes under the thresholds only now, so the checks and totals above saw every account
+    let hide_system_addresses = options.hide_system_addresses && arbitrum::is_arbitrum(chain_id, &block_info);
+    if let Some(changes) = &mut state_changes {
+        if hide_system_addresses {
+            changes.retain(|change| !arbitrum::is_system_address(&change.address));
+            summary.hidden_system_addresses = summary.changed_addresses - changes.len();
+        }
+        changes.retain(|change| options.meets_thresholds(change));
+        summary.filtered_state_changes = summary.changed_addresses - summary.hidden_system_addresses - changes.len();
+    }
+    if hide_system_addresses {
+        for tx_changes in &mut per_tx_changes {
+            tx_changes.state_changes.retain(|change| !arbitrum::is_system_address(&change.address));
+        }
+    }
+    // Without the coinbase's balance there's no observed change to reconcile
+    if state_changes.is_none() || !spans_one_block || !options.fields.balance || !options.includes(&block_info.miner_address) {
+        summary.unexplained_coinbase_change = WeiDelta { decreased: false, magnitude: Wei::zero() };
+        summary.mev_payment = None;
+    }
+    if let Some(payment) = summary.mev_payment {
+        warnings.push(format!(
+            "coinbase received {} wei beyond its priority fees; likely a direct payment, e.g. from an MEV bundle",
+            payment
+        ));
+    }
+
+    let gas_report = options.gas_report.then(|| gas::gas_report(&block_info));
+    let slot_changes = match options.state_changes {
+        true => watched_slots::read_watched_slots(web3, baseline_block, block_info.block_number, options).await?,
+        false => Vec::new(),
+    };
+    let proxy_upgrades = match baseline_block {
+        Some(baseline) if options.state_changes && options.check_proxies => {
+            proxy::find_proxy_upgrades(web3, baseline, &block_info, options).await?
+        }
+        _ => Vec::new(),
+    };
+    warnings.extend(proxy_upgrades.iter().map(|upgrade| upgrade.to_string()));
+    let code_changes = match (baseline_block, &state_changes) {
+        (Some(baseline), Some(changes)) if options.detect_code_changes => {
+            code_changes::detect_code_changes(web3, baseline, &block_info, changes, options, &mut cache.code_hashes).await?
+        }
+        _ => Vec::new(),
+    };
+
+    Ok(BlockAnalysis {
+        chain_id,
+        block_info,
+        baseline_block,
+        receipt_source,
+        state_diff_source,
+        state_changes,
+        per_tx_changes,
+        token_transfers,
+        nft_transfers,
+        internal_transfers: traced.map(|traced| traced.internal_transfers),
+        destroyed_contracts,
+        slot_changes,
+        proxy_upgrades,
+        code_changes,
+        token_metadata,
+        ens,
+        summary,
+        warnings,
+        gas_report,
+        rpc_stats: None,
+        verification,
+    })
+}
+
+/// Fetch a block with its transactions and their receipts, without querying any account state. This is
+/// the cheap part of an analysis; `concurrency`, `progress`, `missing_receipts`, `check_senders` and
+/// `deadline` in `options` apply, and missing receipts are only an error under [`MissingReceiptPolicy::Error`].
+/// Comes with the warnings an analysis would carry in [`BlockAnalysis::warnings`]: senders that don't match
+/// their signatures, and transactions without a receipt under [`MissingReceiptPolicy::Warn`].
+pub async fn get_block_info<T: Transport>(
+    web3: &Web3<T>,
+    block: BlockRef,
+    options: &AnalysisOptions,
+) -> Result<(BlockInfo, Vec<String>), Box<dyn Error>> {
+    with_deadline(options.deadline, || analysis_of(block), async {
+        let FetchedBlock { block_info, missing_receipts, verification, sender_warnings, .. } =
+            fetch_block_info(web3, block, options.concurrency, options.progress.as_deref(), options.verify, options.check_senders)
+                .await?;
+        let mut warnings = sender_warnings;
+        if !missing_receipts.is_empty() {
+            match options.missing_receipts {
+                MissingReceiptPolicy::Error => {
+                    return Err(MissingReceiptsError { block_number: block_info.block_number, transactions: missing_receipts }.into())
+                }
+                MissingReceiptPolicy::Warn => warnings.extend(missing_receipts.iter().map(missing_receipt_warning)),
+                MissingReceiptPolicy::Skip => {}
+            }
+        }
+        if let Some(verification) = verification.filter(|verification| !verification.passed()) {
+            return Err(format!("block {} failed verification: {}", block_info.block_number, verification.failures().join("; ")).into());
+        }
+        Ok((block_info, warnings))
+    })
+    .await
+}
+
+fn missing_receipt_warning(hash: &H256) -> String {
+    format!("no receipt for transaction {:?}; its gas, fee and status are unknown", hash)
+}
+
+/// The RPC calls analyzing `block` with `options` would make, fetching only the block body
+pub async fn estimate_calls<T: Transport>(
+    web3: &Web3<T>,
+    block: BlockRef,
+    options: &AnalysisOptions,
+) -> Result<CallEstimate, Box<dyn Error>> {
+    let (body, extras) = rpc::fetch_block(web3, block).await?.ok_or(BlockNotFoundError { block })?;
+    let mut estimate = options.planned_calls(&body, &extras, matches!(block, BlockRef::Hash(_)));
+    estimate.add("eth_chainId", 1);
+    estimate.blocks = 1;
+    estimate.sampled_blocks = 1;
+    Ok(estimate)
+}
+
+/// The RPC calls analyzing blocks `from` to `to` would make, extrapolated from the bodies of up to
+/// `samples` blocks spread evenly over the range
+pub async fn estimate_range_calls<T: Transport>(
+    web3: &Web3<T>,
+    from: u64,
+    to: u64,
+    samples: u64,
+    options: &AnalysisOptions,
+) -> Result<CallEstimate, Box<dyn Error>> {
+    if from > to {
+        return Err(format!("invalid block range: from-block {} is after to-block {}", from, to).into());
+    }
+    let blocks = to - from + 1;
+    let samples = samples.clamp(1, blocks);
+    let step = if samples > 1 { (blocks - 1) as f64 / (samples - 1) as f64 } else { 0.0 };
+    let sampled: Vec<u64> = (0..samples).map(|i| from + (i as f64 * step).round() as u64).collect();
+
+    let mut sum = CallEstimate::default();
+    for number in &sampled {
+        let block = BlockRef::Number(*number);
+        let (body, extras) = rpc::fetch_block(web3, block).await?.ok_or(BlockNotFoundError { block })?;
+        let planned = options.planned_calls(&body, &extras, false);
+        for (method, calls) in planned.calls {
+            sum.add(&method, calls);
+        }
+        for (method, calls) in planned.up_to {
+            sum.add_up_to(&method, calls);
+        }
+        for (method, calls) in planned.fallback {
+            sum.add_fallback(&method, calls);
+        }
+    }
+
+    // Per-block averages over the samples, times the length of the range
+    let scale = |counts: BTreeMap<String, u64>| -> BTreeMap<String, u64> {
+        counts.into_iter().map(|(method, calls)| (method, (calls as f64 * blocks as f64 / samples as f64).round() as u64)).collect()
+    };
+    let mut estimate = CallEstimate {
+        blocks,
+        sampled_blocks: samples,
+        calls: scale(sum.calls),
+        up_to: scale(sum.up_to),
+        fallback: scale(sum.fallback),
+    };
+    estimate.add("eth_chainId", 1);
+    Ok(estimate)
+}
+
+/// A block with its transactions and receipts, before any account state is queried
+struct FetchedBlock {
+    block_info: BlockInfo,
+    receipt_source: ReceiptSource,
+    /// Transactions the node had no receipt for
+    missing_receipts: Vec<H256>,
+    /// Only with `verify`
+    verification: Option<Verification>,
+    /// Transactions whose signature doesn't recover to their sender; only with `check_senders`
+    sender_warnings: Vec<String>,
+}
+
+#[tracing::instrument(skip_all, fields(block = ?block_ref))]
+async fn fetch_block_info<T: Transport>(
+    web3: &Web3<T>,
+    block_ref: BlockRef,
+    concurrency: usize,
+    progress: Option<&dyn ProgressReporter>,
+    verify: bool,
+    check_senders: bool,
+) -> Result<FetchedBlock, Box<dyn Error>> {
+    // Fetch block with full transaction objects
+    let (block, extras) = rpc::fetch_block(web3, block_ref).await?
+        .ok_or(BlockNotFoundError { block: block_ref })?;
+    // The receipts are checked once they're fetched, unless a transaction type rules that out
+    let header_checks = verify.then(|| {
+        let unencodable = verify::unencodable_transaction(&block.transactions);
+        (verify::check_transactions_root(&block, &extras), verify::check_block_hash(&block, &extras), unencodable)
+    });
+    let sender_warnings = if check_senders { verify::check_senders(&block, &extras) } else { Vec::new() };
+
+    // Pending blocks may come back without a number, and there is nothing to diff against then
+    let block_number = block.number
+        .ok_or("block has no number (pending?); analyze a mined block instead")?
+        .as_u64();
+
+    // State is queried by number, so a block looked up by hash must still be the canonical one
+    if let BlockRef::Hash(hash) = block_ref {
+        let canonical = web3.eth().block(BlockId::Number(BlockNumber::Number(U64::from(block_number)))).await?;
+        match canonical.and_then(|canonical| canonical.hash) {
+            Some(canonical) if canonical == hash => {}
+            Some(canonical) => {
+                return Err(format!(
+                    "block {:?} is no longer canonical: block {} is now {:?}",
+                    hash, block_number, canonical
+                ).into())
+            }
+            None => return Err(format!("block {:?} is no longer canonical", hash).into()),
+        }
+    }
+
+    // Uncle headers come one call each, so blocks without uncles cost nothing extra
+    let block_id = block.hash.map_or(BlockId::Number(BlockNumber::Number(U64::from(block_number))), BlockId::Hash);
+    let uncles = future::try_join_all(block.uncles.iter().enumerate().map(|(index, hash)| async move {
+        let uncle = web3.eth().uncle(block_id, index.into()).await?;
+        let uncle = uncle.ok_or_else(|| format!("block {} has no uncle {}", block_number, index))?;
+        Ok::<_, Box<dyn Error>>(UncleInfo {
+            hash: uncle.hash.unwrap_or(*hash),
+            miner: uncle.author,
+            number: uncle.number.unwrap_or_default().as_u64(),
+        })
+    }))
+    .await?;
+
+    // Get transaction receipts for gas used: all in one call if the node can, otherwise one request per
+    // transaction, keeping at most `concurrency` in flight. `buffered` yields results in transaction order.
+    let block_receipts = rpc::fetch_block_receipts(web3, block_number).await?;
+    let receipt_source = if block_receipts.is_some() { ReceiptSource::Block } else { ReceiptSource::PerTransaction };
+    let mut receipts_by_hash: HashMap<H256, (TransactionReceipt, rpc::ReceiptExtras)> = block_receipts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(receipt, extras)| (receipt.transaction_hash, (receipt, extras)))
+        .collect();
+    let mut tx_extras: HashMap<H256, rpc::TransactionExtras> = extras.transactions.into_iter().map(|tx| (tx.hash, tx)).collect();
+
+    let total = block.transactions.len();
+    let mut fetched = 0;
+    report(progress, Phase::Receipts, 0, total);
+    let fetched_transactions: Vec<(TransactionInfo, bool, Option<verify::EncodedReceipt>)> = stream::iter(block.transactions)
+        .map(|tx| (receipts_by_hash.remove(&tx.hash), tx_extras.remove(&tx.hash), tx))
+        .map(|(prefetched, tx_extras, tx)| async move {
+            let fetched = match (prefetched, receipt_source) {
+                (Some(receipt), _) => Some(receipt),
+                (None, ReceiptSource::Block) => None,
+                (None, ReceiptSource::PerTransaction) => rpc::fetch_receipt(web3, tx.hash).await?,
+            };
+            let missing = fetched.is_none();
+            let (receipt, receipt_extras) = match fetched {
+                Some((receipt, extras)) => (Some(receipt), extras),
+                None => (None, rpc::ReceiptExtras::default()),
+            };
+            let receipt_encoding = receipt.as_ref().filter(|_| verify).map(|receipt| verify::encode_receipt(receipt, &receipt_extras));
+
+            // Pre-London receipts have no effectiveGasPrice; the legacy gas price is what was charged
+            let gas_used = receipt.as_ref().and_then(|r| r.gas_used);
+            let effective_gas_price = receipt.as_ref().and_then(|r| r.effective_gas_price).map(Wei);
+            let gas_price = tx.gas_price.map(Wei);
+            let deposit = tx.transaction_type.map(|tx_type| tx_type.as_u64()) == Some(fees::DEPOSIT_TX_TYPE);
+            let fee_paid = match deposit {
+                // Deposits buy their gas on L1 and may come without any gas price
+                true => Some(Wei::zero()),
+                false => gas_used.zip(effective_gas_price.or(gas_price)).map(|(gas, price)| price.saturating_mul_gas(gas)),
+            };
+
+            let status = match receipt.as_ref().and_then(|r| r.status).map(|status| status.as_u64()) {
+                Some(1) => TransactionStatus::Success,
+                Some(0) => TransactionStatus::Reverted,
+                _ => TransactionStatus::Unknown,
+            };
+
+            // Blob gas is priced separately and burned in full
+            let blob_gas_price = receipt_extras.blob_gas_price.map(Wei);
+            let blob_fee_paid = receipt_extras.blob_gas_used.zip(blob_gas_price).map(|(gas, price)| price.saturating_mul_gas(gas));
+            let (max_fee_per_blob_gas, blob_versioned_hashes, mint) = match tx_extras {
+                Some(extras) => (extras.max_fee_per_blob_gas.map(Wei), extras.blob_versioned_hashes, extras.mint.map(Wei)),
+                None => (None, None, None),
+            };
+
+            let info = TransactionInfo {
+                hash: tx.hash,
+                // Fall back to the receipt's sender when the transaction object omits it
+                from: tx.from.or_else(|| receipt.as_ref().map(|r| r.from).filter(|from| !from.is_zero())),
+                to: tx.to,
+                tx_type: tx.transaction_type.map(|tx_type| tx_type.as_u64()),
+                access_list: tx.access_list.map(|list| list.into_iter().map(|item| (item.address, item.storage_keys)).collect()),
+                value: Wei(tx.value),
+                gas: tx.gas,
+                method: None,
+                input_size: tx.input.0.len(),
+                status,
+                revert_reason: None,
+                gas_used,
+                created_contract: receipt.as_ref().and_then(|r| r.contract_address),
+                gas_price,
+                max_fee_per_gas: tx.max_fee_per_gas.map(Wei),
+                max_priority_fee_per_gas: tx.max_priority_fee_per_gas.map(Wei),
+                effective_gas_price,
+                fee_paid,
+                blob_versioned_hashes,
+                max_fee_per_blob_gas,
+                blob_gas_used: receipt_extras.blob_gas_used,
+                blob_gas_price,
+                blob_fee_paid,
+                l1_gas_used: receipt_extras.l1_gas_used,
+                l1_gas_price: receipt_extras.l1_gas_price.map(Wei),
+                l1_fee: receipt_extras.l1_fee.map(Wei),
+                mint,
+                gas_used_for_l1: receipt_extras.gas_used_for_l1,
+                decoded_events: Vec::new(),
+                logs: receipt.map(|r| r.logs).unwrap_or_default(),
+                input: tx.input,
+                from_label: None,
+                to_label: None,
+            };
+            Ok::<_, Box<dyn Error>>((info, missing, receipt_encoding))
+        })
+        .buffered(concurrency.max(1))
+        .inspect(|_| {
+            fetched += 1;
+            report(progress, Phase::Receipts, fetched, total);
+        })
+        .try_collect()
+        .await?;
+    let missing_receipts = fetched_transactions.iter().filter(|(_, missing, _)| *missing).map(|(tx, _, _)| tx.hash).collect();
+    let mut receipt_encodings = Vec::with_capacity(total);
+    let transactions: Vec<TransactionInfo> = fetched_transactions
+        .into_iter()
+        .map(|(tx, _, encoding)| {
+            receipt_encodings.push(encoding);
+            tx
+        })
+        .collect();
+    let verification = header_checks.map(|(transactions_root, block_hash, unencodable)| Verification {
+        transactions_root,
+        receipts_root: match unencodable {
+            Some(reason) => VerificationCheck::skipped(block.receipts_root, reason),
+            None => verify::check_receipts_root(block.receipts_root, receipt_encodings),
+        },
+        block_hash,
+    });
+
+    let op_stack = fees::is_op_stack(&transactions);
+
+    // Create BlockInfo struct with fetched data
+    let block_info = BlockInfo {
+        block_number,
+        timestamp: block.timestamp.as_u64(),
+        timestamp_utc: format_timestamp(block.timestamp.as_u64()),
+        hash: block.hash.unwrap_or_default(),
+        parent_hash: block.parent_hash,
+        nonce: block.nonce.map(|n| format!("{:?}", n)),
+        miner: format_address(&block.author),
+        miner_address: block.author,
+        difficulty: block.difficulty.to_string(),
+        total_difficulty: block.total_difficulty.map(|td| td.to_string()),
+        prev_randao: if block.difficulty.is_zero() { block.mix_hash } else { None },
+        size: block.size.unwrap_or_default().as_u64(),
+        gas_used: block.gas_used.as_u64(),
+        gas_limit: block.gas_limit.as_u64(),
+        base_fee_per_gas: block.base_fee_per_gas.map(Wei),
+        burned_base_fee: block.base_fee_per_gas.map(|base_fee| match op_stack {
+            true => Wei::zero(),
+            false => Wei(base_fee).saturating_mul_gas(block.gas_used),
+        }),
+        blob_gas_used: extras.blob_gas_used.map(|gas| gas.as_u64()),
+        excess_blob_gas: extras.excess_blob_gas.map(|gas| gas.as_u64()),
+        l1_block_number: extras.l1_block_number.map(|number| number.as_u64()),
+        transactions,
+        uncles,
+        withdrawals: extras.withdrawals.into_iter().map(|w| WithdrawalInfo {
+            index: w.index.as_u64(),
+            validator_index: w.validator_index.as_u64(),
+            address: w.address,
+            amount: Wei(U256::from(w.amount.as_u64()) * U256::exp10(9)),
+        }).collect(),
+    };
+
+    Ok(FetchedBlock { block_info, receipt_source, missing_receipts, verification, sender_warnings })
+}
+
+#[tracing::instrument(skip_all, fields(block = block_info.block_number))]
+async fn get_state_changes<T: BatchTransport>(
+    web3: &Web3<T>,
+    block_info: &BlockInfo,
+    options: &AnalysisOptions,
+    mut storage_changes: HashMap<H160, Vec<StorageChange>>,
+    replayed: Option<&[TransactionChanges]>,
+    traced: Option<&internal::TracedCalls>,
+    cache: &mut AccountStateCache,
+) -> Result<Vec<StateChange>, Box<dyn Error>> {
+    let mut addresses = HashMap::new();
+    let mut code_changed = HashSet::new();
+
+    match replayed {
+        // Replayed state diffs list every account the transactions touched
+        Some(replayed) => {
+            for change in replayed.iter().flat_map(|tx_changes| &tx_changes.state_changes) {
+                addresses.insert(change.address, true);
+                if change.code_changed {
+                    code_changed.insert(change.address);
+                }
+            }
+        }
+        // Otherwise collect all addresses involved in transactions
+        None => {
+            for tx in &block_info.transactions {
+                if let Some(from) = tx.from {
+                    addresses.insert(from, true);
+                }
+                if let Some(to) = tx.to {
+                    addresses.insert(to, true);
+                }
+                if let Some(created) = tx.created_contract {
+                    addresses.insert(created, true);
+                }
+            }
+        }
+    }
+
+    // Add addresses the senders declared they would touch
+    if options.access_list_addresses {
+        for (address, _) in block_info.transactions.iter().flat_map(|tx| tx.access_list.iter().flatten()) {
+            addresses.insert(*address, true);
+        }
+    }
+
+    // Add accounts that internal calls paid, created or destroyed, which replayed diffs would already list
+    for address in traced.iter().flat_map(|traced| traced.addresses()) {
+        addresses.insert(address, true);
+    }
+
+    // Add miner address
+    addresses.insert(block_info.miner_address, true);
+
+    // Add uncle miners, rewarded by this block
+    for uncle in &block_info.uncles {
+        addresses.insert(uncle.miner, true);
+    }
+
+    // Add withdrawal recipients, whose balances change without any transaction
+    for withdrawal in &block_info.withdrawals {
+        addresses.insert(withdrawal.address, true);
+    }
+
+    // Add the OP Stack vaults the base and L1 fees are paid into
+    if block_info.is_op_stack() {
+        addresses.insert(fees::BASE_FEE_VAULT, true);
+        addresses.insert(fees::L1_FEE_VAULT, true);
+    }
+
+    // Add contracts whose storage changed, even if their balance and nonce didn't
+    for address in storage_changes.keys() {
+        addresses.insert(*address, true);
+    }
+
+    addresses.retain(|address, _| options.includes(address));
+
+    // Previous block number, unless another baseline was asked for; the genesis block has none, so
+    // everything starts from an empty account
+    let prev_block_number = options.baseline_block.or(block_info.block_number.checked_sub(1));
+    let prev_block = prev_block_number.map(|n| BlockNumber::Number(U64::from(n)));
+    let current_block = BlockNumber::Number(U64::from(block_info.block_number));
+
+    // States cached from the previous block stand in for the "previous" queries
+    let carried = match (cache.block_number, prev_block_number) {
+        (Some(cached), Some(prev)) if cached == prev => std::mem::take(&mut cache.accounts),
+        _ => HashMap::new(),
+    };
+
+    // A balance and a nonce query per address and block, or just the selected one, skipping whatever the
+    // cache already knows. Fields that aren't queried stay zero on both sides, so they never differ.
+    let fields = options.fields;
+    let methods: Vec<&str> = [(fields.balance, "eth_getBalance"), (fields.nonce, "eth_getTransactionCount")]
+        .into_iter()
+        .filter_map(|(selected, method)| selected.then_some(method))
+        .collect();
+    let addresses: Vec<H160> = addresses.into_keys().collect();
+    let mut lookups: Vec<(H160, BlockNumber, bool)> = Vec::new();
+    for address in &addresses {
+        if let (None, Some(prev_block)) = (carried.get(address), prev_block) {
+            lookups.push((*address, prev_block, false));
+        }
+        lookups.push((*address, current_block, true));
+    }
+    let calls = lookups
+        .iter()
+        .flat_map(|(address, block, _)| methods.iter().map(move |method| (*method, vec![json!(address), json!(block)])))
+        .collect();
+
+    // Batches complete in order, so an address is done once the calls for its current state are.
+    // Each lookup is a call per selected field.
+    let progress = options.progress.as_deref();
+    report(progress, Phase::StateDiff, 0, addresses.len());
+    let on_batch = |calls_done: usize| {
+        let done = lookups.iter().take(calls_done / methods.len().max(1)).filter(|(_, _, current)| *current).count();
+        report(progress, Phase::StateDiff, done, addresses.len());
+    };
+
+    // Get balances and nonces for all addresses at both blocks, batched to save round trips
+    let values = batch::call_batched(web3, calls, options.batch_size, options.concurrency, on_batch).await?;
+
+    let mut fetched: HashMap<(H160, bool), AccountState> = HashMap::new();
+    for ((address, _, current), values) in lookups.iter().zip(values.chunks(methods.len().max(1))) {
+        let mut values = values.iter();
+        let mut state = AccountState::default();
+        if fields.balance {
+            state.balance = Wei(serde_json::from_value(values.next().cloned().unwrap_or_default())?);
+        }
+        if fields.nonce {
+            state.nonce = serde_json::from_value(values.next().cloned().unwrap_or_default())?;
+        }
+        fetched.insert((*address, *current), state);
+    }
+
+    let states: Vec<(H160, AccountState, AccountState)> = addresses
+        .into_iter()
+        .map(|address| {
+            let prev = carried.get(&address).or_else(|| fetched.get(&(address, false))).copied().unwrap_or_default();
+            let current = fetched.get(&(address, true)).copied().unwrap_or_default();
+            (address, prev, current)
+        })
+        .collect();
+
+    cache.block_number = Some(block_info.block_number);
+    cache.accounts = states.iter().map(|(address, _, current)| (*address, *current)).collect();
+
+    // Check if state changed
+    let mut changes: Vec<StateChange> = states
+        .into_iter()
+        .filter_map(|(address, prev, current)| {
+            let storage_changes = storage_changes.remove(&address).unwrap_or_default();
+            let code_changed = code_changed.contains(&address);
+            (prev != current || code_changed || !storage_changes.is_empty()).then(|| StateChange {
+                address,
+                label: None,
+                prev_balance: fields.balance.then_some(prev.balance),
+                new_balance: fields.balance.then_some(current.balance),
+                prev_nonce: fields.nonce.then_some(prev.nonce),
+                new_nonce: fields.nonce.then_some(current.nonce),
+                balance_change: fields.balance.then(|| Wei::checked_delta(prev.balance, current.balance)),
+                nonce_change: fields.nonce.then(|| NonceDelta::between(prev.nonce, current.nonce)),
+                value_in: Wei::zero(),
+                value_out: Wei::zero(),
+                fees_paid: Wei::zero(),
+                priority_fees_received: Wei::zero(),
+                vault_fees_received: Wei::zero(),
+                code_changed,
+                is_contract: None,
+                code_size: None,
+                storage_changes,
+                proof_verified: false,
+                first_activity: false,
+            })
+        })
+        .collect();
+
+    // Completion order is arbitrary, so sort for stable output
+    changes.sort_by_key(|change| change.address);
+
+    Ok(changes)
+}
+
+async fn classify_addresses<T: Transport>(
+    web3: &Web3<T>,
+    block_info: &BlockInfo,
+    state_changes: &mut [StateChange],
+    concurrency: usize,
+    cache: &mut AccountStateCache,
+) -> Result<(), Box<dyn Error>> {
+    let block = Some(BlockNumber::Number(U64::from(block_info.block_number)));
+
+    // Cached sizes can't be trusted for accounts whose code this block may have changed. A nonce that
+    // went down means the account was deleted, code and all.
+    let stale = |change: &StateChange| {
+        change.code_changed
+            || block_info.transactions.iter().any(|tx| tx.created_contract == Some(change.address))
+            || matches!((change.prev_nonce, change.new_nonce), (Some(prev), Some(new)) if new < prev)
+    };
+    let lookups: Vec<H160> = state_changes
+        .iter()
+        .filter(|change| stale(change) || !cache.code_sizes.contains_key(&change.address))
+        .map(|change| change.address)
+        .collect();
+
+    let fetched: Vec<(H160, usize)> = stream::iter(lookups)
+        .map(|address| async move {
+            let code = web3.eth().code(address, block).await?;
+            Ok::<_, web3::Error>((address, code.0.len()))
+        })
+        .buffer_unordered(concurrency.max(1))
+        .try_collect()
+        .await?;
+    cache.code_sizes.extend(fetched);
+
+    for change in state_changes {
+        let code_size = cache.code_sizes.get(&change.address).copied();
+        change.is_contract = code_size.map(|size| size > 0);
+        change.code_size = code_size;
+    }
+
+    Ok(())
+}
+
+fn report(progress: Option<&dyn ProgressReporter>, phase: Phase, done: usize, total: usize) {
+    if let Some(progress) = progress {
+        progress.update(phase, done, total);
+    }
+}
+
+fn sort_state_changes(changes: &mut [StateChange], key: SortKey) {
+    // Stable sort over address-ordered input, so equal deltas stay in address order
+    match key {
+        SortKey::Address => changes.sort_by_key(|change| change.address),
+        SortKey::BalanceDelta => changes.sort_by_key(|change| Reverse(change.balance_change.map(|delta| delta.magnitude))),
+        SortKey::NonceDelta => changes.sort_by_key(|change| Reverse(change.nonce_change.map(|delta| delta.magnitude))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nonce_change(address: u64, prev: u64, new: u64) -> StateChange {
+        let state = |nonce: u64| (Some(Wei::zero()), Some(U256::from(nonce)));
+        history::net_change(H160::from_low_u64_be(address), state(prev), state(new))
+    }
+
+    #[test]
+    fn nonce_deltas_are_signed() {
+        assert_eq!(NonceDelta::between(U256::from(3), U256::from(5)), NonceDelta { decreased: false, magnitude: U256::from(2) });
+        assert_eq!(NonceDelta::between(U256::from(5), U256::from(1)), NonceDelta { decreased: true, magnitude: U256::from(4) });
+        assert_eq!(NonceDelta::between(U256::from(5), U256::from(1)).to_string(), "-4");
+        assert!(!NonceDelta::between(U256::from(2), U256::from(2)).is_negative());
+        assert_eq!(serde_json::to_value(nonce_change(1, 1, 0).nonce_change).unwrap(), serde_json::json!("-1"));
+    }
+
+    #[test]
+    fn a_dropped_nonce_ranks_by_how_far_it_dropped() {
+        // A contract self-destructed and created again starts over at nonce 1
+        let mut changes = vec![nonce_change(1, 1, 3), nonce_change(2, 9, 1), nonce_change(3, 4, 5)];
+        sort_state_changes(&mut changes, SortKey::NonceDelta);
+        let order: Vec<u64> = changes.iter().map(|change| change.address.to_low_u64_be()).collect();
+        assert_eq!(order, [2, 1, 3]);
+
+        let options = AnalysisOptions::new().with_min_nonce_delta(10);
+        assert!(!options.meets_thresholds(&nonce_change(2, 9, 1)));
+        let options = AnalysisOptions::new().with_min_nonce_delta(2);
+        assert!(options.meets_thresholds(&nonce_change(2, 9, 1)));
+        assert!(!options.meets_thresholds(&nonce_change(3, 4, 5)));
+    }
+
+    #[tokio::test]
+    async fn a_run_past_its_deadline_fails_with_a_timeout_naming_it() {
+        let stalled = future::pending::<Result<(), Box<dyn Error>>>();
+        let error = with_deadline(Some(Duration::from_millis(10)), || analysis_of(BlockRef::Number(7)), stalled).await.unwrap_err();
+        let timeout = TimeoutError::find(error.as_ref()).unwrap();
+        assert_eq!(timeout.to_string(), "analysis of block 7 timed out after 10ms");
+        assert_eq!(analysis_of(BlockRef::Latest), "analysis of the latest block");
+
+        // A request's timeout is found inside the web3 error carrying it, and only a typed one counts
+        let request = TimeoutError { call: "eth_chainId()".to_string(), after: Duration::from_secs(30) };
+        let wrapped: Box<dyn Error> = Box::new(web3::Error::Io(std::io::Error::new(std::io::ErrorKind::TimedOut, request)));
+        assert_eq!(TimeoutError::find(wrapped.as_ref()).map(|timeout| timeout.call.as_str()), Some("eth_chainId()"));
+        let message: Box<dyn Error> = "eth_chainId() timed out after 30s".into();
+        assert!(TimeoutError::find(message.as_ref()).is_none());
+    }
+
+    /// The fixtures' coinbase, whose leading zero nibbles trimming `0` and `x` off its string dropped
+    const MINER: &str = "0x00a329c0648769a73afac7f9381e08fb43dbea72";
+
+    #[test]
+    fn trimming_the_miner_string_lost_its_leading_zeros() {
+        let miner: H160 = MINER.parse().unwrap();
+        let trimmed = format!("{:?}", miner);
+        let trimmed = trimmed.trim_matches(|c| c == '"' || c == '0' || c == 'x');
+        assert_eq!(trimmed, "a329c0648769a73afac7f9381e08fb43dbea72");
+        assert!(trimmed.parse::<H160>().is_err());
+    }
+
+    #[tokio::test]
+    async fn the_miner_is_kept_as_an_address() {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        let (block_info, _) = get_block_info(&web3, BlockRef::Number(3), &AnalysisOptions::new()).await.unwrap();
+        assert_eq!(block_info.miner_address, MINER.parse().unwrap());
+
+        let analysis = analyze_block(&web3, Some(3), 4).await.unwrap();
+        assert!(analysis.state_changes.iter().flatten().any(|change| change.address == block_info.miner_address));
+    }
+}
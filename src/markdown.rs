@@ -0,0 +1,385 @@
+use web3::types::{H160, H256};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::{
+    format_address, AmountFormat, BlockAnalysis, StateChange, Timezone, TransactionAnalysis, TransactionFilter, TransactionInfo,
+    TransactionStatus,
+};
+
+/// Rows per table before the rest are summarized in a footer
+pub const DEFAULT_MAX_ROWS: usize = 50;
+
+/// Renders an analysis as a Markdown document for pasting into issues and notes
+#[derive(Debug, Clone)]
+pub struct MarkdownReport {
+    pub amounts: AmountFormat,
+    /// Block explorer base URL such as `https://etherscan.io`; addresses, transactions and the block link to it
+    pub explorer: Option<String>,
+    pub max_rows: usize,
+    pub timezone: Timezone,
+    /// Which transactions the transaction table lists
+    pub filter: TransactionFilter,
+}
+
+impl Default for MarkdownReport {
+    fn default() -> Self {
+        MarkdownReport { amounts: AmountFormat::default(), explorer: None, max_rows: DEFAULT_MAX_ROWS,
+            timezone: Timezone::default(),
+            filter: TransactionFilter::default(),
+        }
+    }
+}
+
+impl MarkdownReport {
+    pub fn render(&self, analysis: &BlockAnalysis) -> String {
+        let info = &analysis.block_info;
+        let amounts = &self.amounts;
+        let mut out = String::new();
+
+        // Writing to a String can't fail
+        let _ = writeln!(out, "## Block {}\n", self.link(&info.block_number.to_string(), "block", &info.block_number.to_string()));
+        let mut header = vec![
+            ("Chain ID", analysis.chain_id.to_string()),
+            ("Hash", format!("`{:?}`", info.hash)),
+            ("Parent Hash", format!("`{:?}`", info.parent_hash)),
+            ("Timestamp", self.timezone.describe(info.timestamp)),
+            (if info.is_post_merge() { "Fee Recipient" } else { "Miner" }, self.address(&analysis.ens, info.miner_address, None)),
+            ("Gas Used", format!("{} of {} ({:.2}%)", info.gas_used, info.gas_limit, analysis.summary.gas_used_percent)),
+            ("Base Fee Per Gas", info.base_fee_per_gas.map_or("none".to_string(), |fee| format!("{} wei", fee))),
+            ("Burned Base Fee", info.burned_base_fee.map_or("none".to_string(), |fee| amounts.format(fee))),
+            ("Value Transferred", amounts.format(analysis.summary.value_transferred)),
+            ("Fees Paid", amounts.format(analysis.summary.fees_paid)),
+            ("State Diff Source", analysis.state_diff_source.map_or("none".to_string(), |source| format!("{:?}", source))),
+        ];
+        if let Some(baseline) = analysis.baseline_block {
+            header.push(("Baseline Block", baseline.to_string()));
+        }
+        if let Some(prev_randao) = info.prev_randao {
+            header.push(("Prev Randao", format!("`{:?}`", prev_randao)));
+        }
+        if !info.uncles.is_empty() {
+            let uncles: Vec<String> = info.uncles.iter().map(|uncle| format!("{} `{:?}`", uncle.number, uncle.hash)).collect();
+            header.push(("Uncles", uncles.join(", ")));
+        }
+        if let Some(blob_gas_used) = info.blob_gas_used {
+            header.push(("Blob Gas Used", blob_gas_used.to_string()));
+        }
+        if let Some(l1_block_number) = info.l1_block_number {
+            header.push(("L1 Block Number", l1_block_number.to_string()));
+        }
+        if !analysis.summary.total_blob_fees.is_zero() {
+            header.push(("Blob Fees Burned", amounts.format(analysis.summary.total_blob_fees)));
+        }
+        if !analysis.summary.total_l1_fees.is_zero() {
+            header.push(("L1 Fees", amounts.format(analysis.summary.total_l1_fees)));
+        }
+        let prices = &analysis.summary.gas_price_stats;
+        if let (Some(min), Some(median), Some(max)) = (prices.min, prices.median, prices.max) {
+            let spread = format!("min {} / median {} / max {}", amounts.format(min), amounts.format(median), amounts.format(max));
+            header.push(("Gas Price", format!("{}; {} paid only the base fee", spread, prices.base_fee_only)));
+        }
+        for (term, definition) in header {
+            let _ = writeln!(out, "{}\n: {}\n", term, definition);
+        }
+
+        let (listed, hidden) = self.filter.apply(&info.transactions);
+        let _ = writeln!(out, "### Transactions ({})\n", info.transactions.len());
+        if hidden > 0 {
+            let _ = writeln!(out, "_{} of them hidden by the transaction filters_\n", hidden);
+        }
+        let mut rows = Vec::new();
+        for (index, tx) in listed {
+            let status = match tx.status {
+                TransactionStatus::Success => "success".to_string(),
+                TransactionStatus::Reverted => match &tx.revert_reason {
+                    Some(reason) => format!("reverted: {}", reason),
+                    None => "reverted".to_string(),
+                },
+                TransactionStatus::Unknown => "unknown".to_string(),
+            };
+            let to = match (tx.created_contract, tx.to) {
+                (Some(created), _) => format!("{} (created)", self.address(&analysis.ens, created, tx.to_label.as_ref())),
+                (None, Some(to)) => self.address(&analysis.ens, to, tx.to_label.as_ref()),
+                (None, None) => String::new(),
+            };
+            rows.push(vec![
+                index.to_string(),
+                self.transaction(&tx.hash),
+                tx.from.map_or(String::new(), |from| self.address(&analysis.ens, from, tx.from_label.as_ref())),
+                to,
+                method(tx),
+                amounts.format(tx.value),
+                tx.fee_paid.map_or(String::new(), |fee| amounts.format(fee)),
+                status,
+            ]);
+        }
+        self.table(&mut out, &["#", "Hash", "From", "To", "Method", "Value", "Fee", "Status"], rows);
+
+        match &analysis.state_changes {
+            Some(changes) => {
+                let _ = writeln!(out, "### State Changes ({})\n", changes.len());
+                if analysis.summary.filtered_state_changes > 0 {
+                    let _ = writeln!(out, "_{} smaller changes hidden by the delta thresholds_\n", analysis.summary.filtered_state_changes);
+                }
+                if analysis.summary.hidden_system_addresses > 0 {
+                    let _ = writeln!(out, "_{} ArbOS system addresses hidden_\n", analysis.summary.hidden_system_addresses);
+                }
+                self.state_changes(&mut out, &analysis.ens, changes);
+            }
+            None => out.push_str("### State Changes\n\n_Not queried_\n\n"),
+        }
+
+        match &analysis.internal_transfers {
+            Some(transfers) => {
+                let _ = writeln!(out, "### Internal Transfers ({})\n", transfers.len());
+                let rows = transfers
+                    .iter()
+                    .map(|transfer| {
+                        vec![
+                            self.transaction(&transfer.transaction_hash),
+                            self.address(&analysis.ens, transfer.from, None),
+                            self.address(&analysis.ens, transfer.to, None),
+                            amounts.format(transfer.value),
+                            transfer.depth.to_string(),
+                        ]
+                    })
+                    .collect();
+                self.table(&mut out, &["Transaction", "From", "To", "Value", "Depth"], rows);
+            }
+            None if analysis.state_changes.is_none() => out.push_str("### Internal Transfers\n\n_Not traced_\n\n"),
+            None => out.push_str("### Internal Transfers\n\n_Unavailable: the node has neither `trace_block` nor `debug_traceBlockByNumber`_\n\n"),
+        }
+
+        if !analysis.slot_changes.is_empty() {
+            let _ = writeln!(out, "### Watched Slot Changes ({})\n", analysis.slot_changes.len());
+            let rows = analysis
+                .slot_changes
+                .iter()
+                .map(|change| {
+                    vec![
+                        self.address(&analysis.ens, change.address, None),
+                        format!("`{:?}`", change.slot),
+                        format!("`{:?}`", change.before),
+                        format!("`{:?}`", change.after),
+                    ]
+                })
+                .collect();
+            self.table(&mut out, &["Contract", "Slot", "Before", "After"], rows);
+        }
+
+        if !analysis.proxy_upgrades.is_empty() {
+            let _ = writeln!(out, "### Proxy Upgrades ({})\n", analysis.proxy_upgrades.len());
+            let rows = analysis
+                .proxy_upgrades
+                .iter()
+                .map(|upgrade| {
+                    vec![
+                        self.address(&analysis.ens, upgrade.address, None),
+                        upgrade.slot.to_string(),
+                        self.address(&analysis.ens, upgrade.old, None),
+                        self.address(&analysis.ens, upgrade.new, None),
+                    ]
+                })
+                .collect();
+            self.table(&mut out, &["Proxy", "Slot", "Old", "New"], rows);
+        }
+
+        if !analysis.code_changes.is_empty() {
+            let _ = writeln!(out, "### Code Changes ({})\n", analysis.code_changes.len());
+            let rows = analysis
+                .code_changes
+                .iter()
+                .map(|change| {
+                    vec![
+                        self.address(&analysis.ens, change.address, None),
+                        format!("`{:?}`", change.old_code_hash),
+                        change.old_size.to_string(),
+                        format!("`{:?}`", change.new_code_hash),
+                        change.new_size.to_string(),
+                    ]
+                })
+                .collect();
+            self.table(&mut out, &["Address", "Old Code Hash", "Old Size", "New Code Hash", "New Size"], rows);
+        }
+
+        if !analysis.destroyed_contracts.is_empty() {
+            let _ = writeln!(out, "### Destroyed Contracts ({})\n", analysis.destroyed_contracts.len());
+            let rows = analysis
+                .destroyed_contracts
+                .iter()
+                .map(|destroyed| {
+                    vec![
+                        self.address(&analysis.ens, destroyed.address, None),
+                        destroyed.transaction_hash.map_or("unknown".to_string(), |hash| self.transaction(&hash)),
+                        destroyed.beneficiary.map_or(String::new(), |beneficiary| self.address(&analysis.ens, beneficiary, None)),
+                        destroyed.balance.map_or(String::new(), |balance| amounts.format(balance)),
+                    ]
+                })
+                .collect();
+            self.table(&mut out, &["Contract", "Transaction", "Beneficiary", "Balance Sent"], rows);
+        }
+
+        if let Some(report) = &analysis.gas_report {
+            let _ = writeln!(out, "### Gas Report\n");
+            let rows = report
+                .transactions
+                .iter()
+                .enumerate()
+                .map(|(rank, tx)| {
+                    vec![
+                        (rank + 1).to_string(),
+                        self.transaction(&tx.hash),
+                        tx.recipient.map_or(String::new(), |address| self.address(&analysis.ens, address, None)),
+                        tx.gas_used.to_string(),
+                        format!("{:.2}%", tx.percent),
+                        format!("{:.2}%", tx.cumulative_percent),
+                    ]
+                })
+                .collect();
+            self.table(&mut out, &["Rank", "Transaction", "Recipient", "Gas Used", "Share", "Cumulative"], rows);
+            let rows = report
+                .recipients
+                .iter()
+                .map(|recipient| {
+                    vec![
+                        recipient.address.map_or(String::new(), |address| self.address(&analysis.ens, address, None)),
+                        recipient.transactions.to_string(),
+                        recipient.gas_used.to_string(),
+                        format!("{:.2}%", recipient.percent),
+                    ]
+                })
+                .collect();
+            self.table(&mut out, &["Recipient", "Transactions", "Gas Used", "Share"], rows);
+        }
+
+        if let Some(verification) = &analysis.verification {
+            let _ = writeln!(out, "### Verification\n");
+            let rows = verification.checks().iter().map(|(name, check)| vec![name.to_string(), check.describe()]).collect();
+            self.table(&mut out, &["Check", "Result"], rows);
+        }
+
+        if !analysis.warnings.is_empty() {
+            let _ = writeln!(out, "### Warnings\n");
+            for warning in &analysis.warnings {
+                let _ = writeln!(out, "- {}", warning);
+            }
+            out.push('\n');
+        }
+
+        if let Some(stats) = &analysis.rpc_stats {
+            let _ = writeln!(
+                out,
+                "### Diagnostics\n\n{} calls in {} requests, {:.1} ms total, {} retries, {} failures\n",
+                stats.calls(),
+                stats.requests,
+                stats.total_ms(),
+                stats.retries,
+                stats.failures
+            );
+            let rows = stats
+                .calls_by_method
+                .iter()
+                .map(|(method, calls)| vec![format!("`{}`", method), calls.count.to_string(), format!("{:.1}", calls.total_ms)])
+                .collect();
+            self.table(&mut out, &["Method", "Calls", "Total ms"], rows);
+        }
+        out
+    }
+
+    /// The block report for the transaction's block, followed by the changes the transaction made on its own
+    pub fn render_transaction(&self, analysis: &TransactionAnalysis) -> String {
+        let mut out = self.render(&analysis.block);
+        if let Some(changes) = analysis.own_state_changes() {
+            let _ = writeln!(out, "### Changes Made By This Transaction ({})\n", changes.len());
+            self.state_changes(&mut out, &analysis.block.ens, changes);
+        }
+        out
+    }
+
+    /// The net changes from [`crate::diff_addresses`] between blocks `from` and `to`
+    pub fn render_address_diff(&self, from: u64, to: u64, changes: &[StateChange]) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "## State Changes from Block {} to Block {}\n",
+            self.link(&from.to_string(), "block", &from.to_string()),
+            self.link(&to.to_string(), "block", &to.to_string())
+        );
+        self.state_changes(&mut out, &BTreeMap::new(), changes);
+        out
+    }
+
+    fn state_changes(&self, out: &mut String, ens: &BTreeMap<H160, String>, changes: &[StateChange]) {
+        let amounts = &self.amounts;
+        let rows = changes
+            .iter()
+            .map(|change| {
+                let address = self.address(ens, change.address, change.label.as_ref());
+                vec![
+                    if change.first_activity { format!("{} **NEW**", address) } else { address },
+                    change.new_balance.map_or(String::new(), |balance| amounts.format(balance)),
+                    change.balance_change.map_or(String::new(), |delta| format!("{} {}", amounts.delta(delta), amounts.symbol())),
+                    change.nonce_change.map_or(String::new(), |delta| delta.to_string()),
+                    change.storage_changes.len().to_string(),
+                ]
+            })
+            .collect();
+        self.table(out, &["Address", "Balance", "Balance Change", "Nonce Change", "Storage Slots"], rows);
+    }
+
+    /// A table with at most `max_rows` rows, then a footer saying how many were left out
+    fn table(&self, out: &mut String, headers: &[&str], rows: Vec<Vec<String>>) {
+        if rows.is_empty() {
+            out.push_str("_None_\n\n");
+            return;
+        }
+        let _ = writeln!(out, "| {} |", headers.join(" | "));
+        let _ = writeln!(out, "|{}", "---|".repeat(headers.len()));
+        let omitted = rows.len().saturating_sub(self.max_rows);
+        for row in rows.iter().take(self.max_rows) {
+            let cells: Vec<String> = row.iter().map(|cell| escape(cell)).collect();
+            let _ = writeln!(out, "| {} |", cells.join(" | "));
+        }
+        if omitted > 0 {
+            let _ = writeln!(out, "\n… and {} more", omitted);
+        }
+        out.push('\n');
+    }
+
+    fn address(&self, ens: &BTreeMap<H160, String>, address: H160, file_label: Option<&String>) -> String {
+        let hex = format_address(&address);
+        let text = match file_label.or_else(|| ens.get(&address)) {
+            Some(name) => format!("{} ({}…)", name, &hex[..6]),
+            None => hex.clone(),
+        };
+        self.link(&text, "address", &hex)
+    }
+
+    fn transaction(&self, hash: &H256) -> String {
+        let hex = format!("{:?}", hash);
+        self.link(&format!("{}…", &hex[..10]), "tx", &hex)
+    }
+
+    /// `text` linked to the explorer page for `path/id`, or as code when there's no explorer
+    fn link(&self, text: &str, path: &str, id: &str) -> String {
+        match &self.explorer {
+            Some(base) => format!("[{}]({}/{}/{})", text, base.trim_end_matches('/'), path, id),
+            None => format!("`{}`", text),
+        }
+    }
+}
+
+/// The method name, or the raw selector when it isn't known
+pub(crate) fn method(tx: &TransactionInfo) -> String {
+    match (&tx.method, tx.input.0.get(..4)) {
+        (Some(method), _) => method.clone(),
+        (None, Some(selector)) => format!("0x{}", selector.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()),
+        (None, None) => String::new(),
+    }
+}
+
+/// Keep decoded strings from breaking out of their table cell
+fn escape(cell: &str) -> String {
+    cell.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
@@ -0,0 +1,248 @@
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use std::convert::Infallible;
+use std::error::Error;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::{BlockAnalysis, Reorg};
+
+/// Block analyses range from milliseconds on an empty devnet block to minutes with traces on mainnet
+const ANALYSIS_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Metrics fed from deep inside the analysis, where no `WatchMetrics` is at hand: the retry layer and
+/// the per-block analysis. Created by the first `WatchMetrics`; until then recording them is a no-op.
+struct SharedMetrics {
+    rpc_calls: IntCounterVec,
+    rpc_errors: IntCounterVec,
+    analysis_seconds: Histogram,
+}
+
+static SHARED: OnceLock<SharedMetrics> = OnceLock::new();
+
+impl SharedMetrics {
+    fn new() -> Self {
+        SharedMetrics {
+            rpc_calls: IntCounterVec::new(
+                Opts::new("state_diff_rpc_calls_total", "JSON-RPC calls sent to the node, counting each call in a batch"),
+                &["method"],
+            )
+            .expect("valid metric"),
+            rpc_errors: IntCounterVec::new(
+                Opts::new("state_diff_rpc_errors_total", "JSON-RPC calls that failed after retries"),
+                &["method"],
+            )
+            .expect("valid metric"),
+            analysis_seconds: Histogram::with_opts(
+                HistogramOpts::new("state_diff_analysis_duration_seconds", "Wall-clock time to analyze one block, failures included")
+                    .buckets(ANALYSIS_BUCKETS.to_vec()),
+            )
+            .expect("valid metric"),
+        }
+    }
+}
+
+pub(crate) fn record_rpc_calls(methods: &[String]) {
+    if let Some(shared) = SHARED.get() {
+        for method in methods {
+            shared.rpc_calls.with_label_values(&[method]).inc();
+        }
+    }
+}
+
+pub(crate) fn record_rpc_errors<'a>(methods: impl IntoIterator<Item = &'a String>) {
+    if let Some(shared) = SHARED.get() {
+        for method in methods {
+            shared.rpc_errors.with_label_values(&[method]).inc();
+        }
+    }
+}
+
+pub(crate) fn record_analysis_duration(elapsed: Duration) {
+    if let Some(shared) = SHARED.get() {
+        shared.analysis_seconds.observe(elapsed.as_secs_f64());
+    }
+}
+
+/// Prometheus metrics for `--watch`. Every metric is registered once, in [`WatchMetrics::new`], and
+/// updated as blocks are delivered; RPC traffic and analysis durations are counted as they happen.
+pub struct WatchMetrics {
+    registry: Registry,
+    last_block: IntGauge,
+    block_gas_used: IntGauge,
+    block_state_changes: IntGauge,
+    reorgs: IntCounter,
+    reorged_blocks: IntCounter,
+}
+
+impl Default for WatchMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WatchMetrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let last_block = IntGauge::new("state_diff_last_block", "Number of the last block analyzed").expect("valid metric");
+        let block_gas_used = IntGauge::new("state_diff_block_gas_used", "Gas used by the last block analyzed").expect("valid metric");
+        let block_state_changes = IntGauge::new("state_diff_block_state_changes", "State changes reported for the last block analyzed")
+            .expect("valid metric");
+        let reorgs = IntCounter::new("state_diff_reorgs_total", "Reorgs seen while watching").expect("valid metric");
+        let reorged_blocks = IntCounter::new("state_diff_reorged_blocks_total", "Analyzed blocks that a reorg took off the canonical chain")
+            .expect("valid metric");
+
+        let shared = SHARED.get_or_init(SharedMetrics::new);
+        for metric in [
+            Box::new(last_block.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(block_gas_used.clone()),
+            Box::new(block_state_changes.clone()),
+            Box::new(reorgs.clone()),
+            Box::new(reorged_blocks.clone()),
+            Box::new(shared.rpc_calls.clone()),
+            Box::new(shared.rpc_errors.clone()),
+            Box::new(shared.analysis_seconds.clone()),
+        ] {
+            registry.register(metric).expect("metric registered twice");
+        }
+
+        WatchMetrics { registry, last_block, block_gas_used, block_state_changes, reorgs, reorged_blocks }
+    }
+
+    pub fn record_block(&self, analysis: &BlockAnalysis) {
+        let info = &analysis.block_info;
+        self.last_block.set(i64::try_from(info.block_number).unwrap_or(i64::MAX));
+        self.block_gas_used.set(i64::try_from(info.gas_used).unwrap_or(i64::MAX));
+        self.block_state_changes.set(analysis.state_changes.as_ref().map_or(0, |changes| changes.len() as i64));
+    }
+
+    pub fn record_reorg(&self, reorg: &Reorg) {
+        self.reorgs.inc();
+        self.reorged_blocks.inc_by(reorg.invalidated.len() as u64);
+    }
+
+    /// Everything registered, in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).expect("text encoding can't fail");
+        String::from_utf8(buffer).expect("text encoding is UTF-8")
+    }
+}
+
+/// An HTTP listener serving `GET /metrics`, bound up front so a taken port fails before watching starts
+pub struct MetricsServer {
+    server: hyper::server::Builder<AddrIncoming>,
+    metrics: Arc<WatchMetrics>,
+}
+
+impl MetricsServer {
+    pub fn bind(addr: SocketAddr, metrics: Arc<WatchMetrics>) -> Result<Self, Box<dyn Error>> {
+        let server = Server::try_bind(&addr).map_err(|e| format!("cannot listen for metrics on {}: {}", addr, e))?;
+        Ok(MetricsServer { server, metrics })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.server.local_addr()
+    }
+
+    /// Serve until `shutdown` resolves, then finish the responses in flight and return
+    pub async fn serve(self, shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn Error>> {
+        let metrics = self.metrics;
+        let service = make_service_fn(move |_| {
+            let metrics = metrics.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |request| respond(metrics.clone(), request))) }
+        });
+        self.server.serve(service).with_graceful_shutdown(shutdown).await?;
+        Ok(())
+    }
+}
+
+async fn respond(metrics: Arc<WatchMetrics>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header("Content-Type", TextEncoder::new().format_type())
+            .body(Body::from(metrics.render())),
+        _ => Response::builder().status(StatusCode::NOT_FOUND).body(Body::from("try /metrics\n")),
+    };
+    Ok(response.expect("valid response"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analyze_block, InvalidatedBlock, MockTransport};
+    use web3::types::H256;
+    use web3::Web3;
+
+    async fn block_3() -> BlockAnalysis {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        analyze_block(&web3, Some(3), 4).await.unwrap()
+    }
+
+    /// The value of the sample named exactly `sample`, labels included
+    fn sample(rendered: &str, sample: &str) -> Option<f64> {
+        rendered.lines().find_map(|line| line.strip_prefix(sample)?.strip_prefix(' ')?.parse().ok())
+    }
+
+    #[tokio::test]
+    async fn blocks_and_reorgs_update_the_registered_metrics() {
+        let metrics = WatchMetrics::new();
+        let analysis = block_3().await;
+        metrics.record_block(&analysis);
+        let invalidated = (2..=3).map(|number| InvalidatedBlock { number, hash: H256::from_low_u64_be(number) }).collect();
+        metrics.record_reorg(&Reorg { common_ancestor: 1, invalidated });
+
+        let rendered = metrics.render();
+        assert_eq!(sample(&rendered, "state_diff_last_block"), Some(3.0));
+        assert_eq!(sample(&rendered, "state_diff_block_gas_used"), Some(0x1ffb8 as f64));
+        let changes = analysis.state_changes.as_ref().unwrap().len();
+        assert_eq!(sample(&rendered, "state_diff_block_state_changes"), Some(changes as f64));
+        assert_eq!(sample(&rendered, "state_diff_reorgs_total"), Some(1.0));
+        assert_eq!(sample(&rendered, "state_diff_reorged_blocks_total"), Some(2.0));
+        // Every analysis is timed, this one included
+        assert!(sample(&rendered, "state_diff_analysis_duration_seconds_count").unwrap() >= 1.0, "{}", rendered);
+    }
+
+    #[test]
+    fn rpc_traffic_is_counted_by_method_in_every_registry() {
+        let first = WatchMetrics::new();
+        // The shared metrics are created once and registered in each
+        let second = WatchMetrics::new();
+        let methods = ["test_countedCall".to_string(), "test_countedCall".to_string()];
+        record_rpc_calls(&methods);
+        record_rpc_errors(&methods[..1]);
+
+        for rendered in [first.render(), second.render()] {
+            assert_eq!(sample(&rendered, r#"state_diff_rpc_calls_total{method="test_countedCall"}"#), Some(2.0), "{}", rendered);
+            assert_eq!(sample(&rendered, r#"state_diff_rpc_errors_total{method="test_countedCall"}"#), Some(1.0), "{}", rendered);
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_metrics_until_shut_down() {
+        let metrics = Arc::new(WatchMetrics::new());
+        metrics.record_block(&block_3().await);
+        let server = MetricsServer::bind("127.0.0.1:0".parse().unwrap(), metrics).unwrap();
+        let addr = server.local_addr();
+        let taken = MetricsServer::bind(addr, Arc::new(WatchMetrics::new())).err().unwrap();
+        assert!(taken.to_string().starts_with(&format!("cannot listen for metrics on {}", addr)), "{}", taken);
+
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let scrape = async move {
+            let response = reqwest::get(format!("http://{}/metrics", addr)).await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], TextEncoder::new().format_type());
+            assert_eq!(sample(&response.text().await.unwrap(), "state_diff_last_block"), Some(3.0));
+            let elsewhere = reqwest::get(format!("http://{}/", addr)).await.unwrap();
+            assert_eq!(elsewhere.status(), reqwest::StatusCode::NOT_FOUND);
+            stop.send(()).unwrap();
+        };
+        let (served, ()) = tokio::join!(server.serve(async move { stopped.await.unwrap() }), scrape);
+        served.unwrap();
+        assert!(reqwest::get(format!("http://{}/metrics", addr)).await.is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use futures::future::{self, Ready};
+use jsonrpc_core::{Call, Value};
+use web3::error::TransportError;
+use web3::helpers::build_request;
+use web3::{BatchTransport, RequestId, Transport};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::fixture::{key, method_call, read_exchanges, Outcome};
+
+#[derive(Debug, Default)]
+struct MockState {
+    responses: HashMap<String, Outcome>,
+    /// Every request made, in order, as method and parameters
+    requests: Vec<(String, Value)>,
+    /// A batch counts once, however many requests it holds
+    round_trips: usize,
+    next_id: RequestId,
+}
+
+/// Transport answering from canned responses, for tests. Responses are matched on method and exact
+/// parameters, the same request always gets the same one, and a request without one fails, naming it.
+/// Clones share their responses and what they've been asked.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer with the responses of a fixture file, as [`FixtureTransport::record`] writes. A request
+    /// recorded more than once is answered with its last response.
+    ///
+    /// [`FixtureTransport::record`]: crate::FixtureTransport::record
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let mock = Self::new();
+        mock.lock().responses =
+            read_exchanges(path.as_ref())?.into_iter().map(|exchange| (key(&exchange.method, &exchange.params), exchange.outcome)).collect();
+        Ok(mock)
+    }
+
+    /// Answer `method` called with `params` with `result`, replacing any response it had
+    pub fn with_response(self, method: &str, params: Value, result: Value) -> Self {
+        self.lock().responses.insert(key(method, &params), Outcome::Result(result));
+        self
+    }
+
+    /// Answer `method` called with `params` with a JSON-RPC error, as a node does for an unsupported
+    /// method or a reverted call
+    pub fn with_error(self, method: &str, params: Value, error: jsonrpc_core::Error) -> Self {
+        self.lock().responses.insert(key(method, &params), Outcome::Error(error));
+        self
+    }
+
+    /// The requests made so far, in order, batched ones included
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.lock().requests.clone()
+    }
+
+    /// How many calls to `method` have been made
+    pub fn calls(&self, method: &str) -> usize {
+        self.lock().requests.iter().filter(|(called, _)| called == method).count()
+    }
+
+    /// How many times the node would have been contacted: once per request, and once per batch
+    pub fn round_trips(&self) -> usize {
+        self.lock().round_trips
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().expect("mock transport lock poisoned")
+    }
+
+    fn answer(state: &mut MockState, request: &Call) -> web3::Result<Value> {
+        let Some((method, params)) = method_call(request) else {
+            return Err(unanswered("a notification or invalid request"));
+        };
+        let response = state.responses.get(&key(method, &params)).cloned();
+        state.requests.push((method.to_string(), params.clone()));
+        match response {
+            Some(outcome) => outcome.into_response(),
+            None => Err(unanswered(&format!("{}({})", method, params))),
+        }
+    }
+}
+
+impl Transport for MockTransport {
+    type Out = Ready<web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        let mut state = self.lock();
+        state.next_id += 1;
+        (state.next_id, build_request(state.next_id, method, params))
+    }
+
+    fn send(&self, _id: RequestId, request: Call) -> Self::Out {
+        let mut state = self.lock();
+        state.round_trips += 1;
+        future::ready(Self::answer(&mut state, &request))
+    }
+}
+
+impl BatchTransport for MockTransport {
+    type Batch = Ready<web3::Result<Vec<web3::Result<Value>>>>;
+
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let mut state = self.lock();
+        state.round_trips += 1;
+        future::ready(Ok(requests.into_iter().map(|(_, request)| Self::answer(&mut state, &request)).collect()))
+    }
+}
+
+fn unanswered(request: &str) -> web3::Error {
+    web3::Error::Transport(TransportError::Message(format!("the mock has no response for {}", request)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use web3::types::{BlockNumber, H160};
+    use web3::Web3;
+
+    #[tokio::test]
+    async fn answers_on_exact_parameters_and_counts_round_trips() {
+        let mock = MockTransport::new().with_response("eth_getBalance", json!(["0x00000000000000000000000000000000000a11ce", "0x1"]), json!("0x64"));
+        let web3 = Web3::new(mock.clone());
+        let alice = H160::from_low_u64_be(0xa11ce);
+        let balance = web3.eth().balance(alice, Some(BlockNumber::Number(1.into()))).await.unwrap();
+        assert_eq!(balance, 100.into());
+
+        let error = web3.eth().balance(alice, Some(BlockNumber::Number(2.into()))).await.unwrap_err();
+        assert!(error.to_string().contains("no response for eth_getBalance"), "{}", error);
+
+        let requests = (1..=2).map(|block| mock.prepare("eth_getBalance", vec![json!(alice), json!(format!("0x{:x}", block))]));
+        let responses = mock.send_batch(requests.collect::<Vec<_>>()).await.unwrap();
+        assert!(responses[0].is_ok() && responses[1].is_err());
+        assert_eq!((mock.round_trips(), mock.calls("eth_getBalance")), (3, 4));
+    }
+}
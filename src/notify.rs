@@ -0,0 +1,345 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use web3::types::{H160, H256, U256};
+use std::collections::BTreeSet;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+use crate::address::format_address;
+use crate::{serialize_opt_u256, Alert, BlockAnalysis, ProxyUpgrade, RetryPolicy, Wei};
+
+/// Shape of the body POSTed to the webhook
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotifyFormat {
+    /// The [`AddressNotification`] as is
+    #[default]
+    Json,
+    /// A Slack incoming-webhook message: a one-line summary with the JSON payload in a code block
+    Slack,
+}
+
+impl std::str::FromStr for NotifyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(NotifyFormat::Json),
+            "slack" => Ok(NotifyFormat::Slack),
+            _ => Err(format!("invalid notification format '{}': expected json or slack", s)),
+        }
+    }
+}
+
+/// A watched address whose state changed in a newly analyzed block
+#[derive(Debug, Clone, Serialize)]
+pub struct AddressNotification {
+    pub block_number: u64,
+    pub block_hash: H256,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    pub label: Option<String>,
+    pub prev_balance: Option<Wei>,
+    pub new_balance: Option<Wei>,
+    #[serde(serialize_with = "serialize_opt_u256")]
+    pub prev_nonce: Option<U256>,
+    #[serde(serialize_with = "serialize_opt_u256")]
+    pub new_nonce: Option<U256>,
+    /// Transactions sent from, to or creating the address, moving ether to or from it internally, or
+    /// attributed a change to it per transaction; empty when the change can't be pinned on one, like a
+    /// withdrawal or a block reward
+    pub transactions: Vec<H256>,
+}
+
+impl AddressNotification {
+    /// One notification per watched address in `analysis.state_changes`, in address order
+    pub fn collect(analysis: &BlockAnalysis, watched: &BTreeSet<H160>) -> Vec<Self> {
+        let info = &analysis.block_info;
+        let changes = analysis.state_changes.iter().flatten().filter(|change| watched.contains(&change.address));
+        changes
+            .map(|change| {
+                let address = change.address;
+                let sent_or_received = info.transactions.iter().filter(|tx| {
+                    tx.from == Some(address) || tx.to == Some(address) || tx.created_contract == Some(address)
+                });
+                let internal = analysis.internal_transfers.iter().flatten().filter(|transfer| {
+                    transfer.from == address || transfer.to == address
+                });
+                let attributed = analysis
+                    .per_tx_changes
+                    .iter()
+                    .filter(|tx| tx.state_changes.iter().any(|change| change.address == address));
+
+                // Kept in block order
+                let mut hashes: Vec<H256> = sent_or_received
+                    .map(|tx| tx.hash)
+                    .chain(internal.map(|transfer| transfer.transaction_hash))
+                    .chain(attributed.map(|tx| tx.transaction_hash))
+                    .collect();
+                let position = |hash: &H256| info.transactions.iter().position(|tx| tx.hash == *hash);
+                hashes.sort_by_key(position);
+                hashes.dedup();
+
+                AddressNotification {
+                    block_number: info.block_number,
+                    block_hash: info.hash,
+                    address,
+                    label: change.label.clone(),
+                    prev_balance: change.prev_balance,
+                    new_balance: change.new_balance,
+                    prev_nonce: change.prev_nonce,
+                    new_nonce: change.new_nonce,
+                    transactions: hashes,
+                }
+            })
+            .collect()
+    }
+
+    fn body(&self, format: NotifyFormat) -> Value {
+        let payload = serde_json::to_value(self).expect("notification serializes");
+        match format {
+            NotifyFormat::Json => payload,
+            NotifyFormat::Slack => {
+                let name = self.label.clone().unwrap_or_else(|| format_address(&self.address));
+                let mut changed = Vec::new();
+                if self.prev_balance != self.new_balance {
+                    changed.push(format!("balance {} → {} wei", describe(self.prev_balance), describe(self.new_balance)));
+                }
+                if self.prev_nonce != self.new_nonce {
+                    changed.push(format!("nonce {} → {}", describe(self.prev_nonce), describe(self.new_nonce)));
+                }
+                let summary = match changed.is_empty() {
+                    true => format!("{} changed in block {}", name, self.block_number),
+                    false => format!("{} changed in block {}: {}", name, self.block_number, changed.join(", ")),
+                };
+                let details = serde_json::to_string_pretty(&payload).expect("notification serializes");
+                json!({ "text": format!("{}\n```{}```", summary, details) })
+            }
+        }
+    }
+}
+
+fn describe<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "?".to_string(), |value| value.to_string())
+}
+
+/// POSTs [`AddressNotification`]s, [`ProxyUpgrade`]s and [`Alert`]s to a webhook in the background, so a slow or failing endpoint never
+/// holds up or aborts the analysis. Undeliverable notifications are logged and dropped.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+    format: NotifyFormat,
+    policy: RetryPolicy,
+    deliveries: JoinSet<()>,
+}
+
+impl WebhookNotifier {
+    /// `timeout` bounds each delivery attempt; a failed attempt is retried with backoff
+    pub fn new(url: &str, format: NotifyFormat, timeout: Duration) -> Result<Self, String> {
+        let parsed: reqwest::Url = url.parse().map_err(|e| format!("invalid webhook URL '{}': {}", url, e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(format!("invalid webhook URL '{}': expected http or https", url));
+        }
+        let client = reqwest::Client::builder().timeout(timeout).build().map_err(|e| e.to_string())?;
+        let policy = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+            request_timeout: timeout,
+        };
+        Ok(WebhookNotifier { client, url: url.to_string(), format, policy, deliveries: JoinSet::new() })
+    }
+
+    /// Start delivering `notification`; it is sent while analysis carries on
+    pub fn send(&mut self, notification: AddressNotification) {
+        let what = format!("notification for {} in block {}", format_address(&notification.address), notification.block_number);
+        self.deliver(notification.body(self.format), what);
+    }
+
+    /// Start delivering `alert`, as it is in JSON or as a one-line Slack message
+    pub fn send_alert(&mut self, alert: &Alert) {
+        let body = match self.format {
+            NotifyFormat::Json => serde_json::to_value(alert).expect("alert serializes"),
+            NotifyFormat::Slack => json!({ "text": format!("Alert: {}", alert) }),
+        };
+        self.deliver(body, format!("alert '{}' for block {}", alert.rule, alert.block_number));
+    }
+
+    /// Start delivering a proxy upgrade of a watched contract in `analysis`, with the block it happened in
+    pub fn send_upgrade(&mut self, analysis: &BlockAnalysis, upgrade: &ProxyUpgrade) {
+        let info = &analysis.block_info;
+        let body = match self.format {
+            NotifyFormat::Json => {
+                let mut body = serde_json::to_value(upgrade).expect("proxy upgrade serializes");
+                body["block_number"] = json!(info.block_number);
+                body["block_hash"] = json!(info.hash);
+                body
+            }
+            NotifyFormat::Slack => json!({ "text": format!("block {}: {}", info.block_number, upgrade) }),
+        };
+        self.deliver(body, format!("upgrade of {} in block {}", format_address(&upgrade.address), info.block_number));
+    }
+
+    /// `what` names the payload in the warning logged if it can't be delivered
+    fn deliver(&mut self, body: Value, what: String) {
+        let (client, url, policy) = (self.client.clone(), self.url.clone(), self.policy);
+        self.deliveries.spawn(async move {
+            let mut retries = 0;
+            loop {
+                let error = match client.post(&url).json(&body).send().await {
+                    Ok(response) if response.status().is_success() => return,
+                    Ok(response) => {
+                        let status = response.status();
+                        let retryable = status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429;
+                        (format!("webhook answered {}", status), retryable)
+                    }
+                    Err(e) => (format!("webhook request failed: {}", e), true),
+                };
+                match error {
+                    (_, true) if retries < policy.max_retries => {
+                        tokio::time::sleep(policy.delay(retries)).await;
+                        retries += 1;
+                    }
+                    (message, _) => {
+                        tracing::warn!(payload = %what, error = %message, "webhook notification not delivered");
+                        return;
+                    }
+                }
+            }
+        });
+        // Reap deliveries that already finished so a long watch doesn't pile them up
+        while self.deliveries.try_join_next().is_some() {}
+    }
+
+    /// Wait for the notifications still being delivered
+    pub async fn finish(mut self) {
+        while self.deliveries.join_next().await.is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{analyze_block, MockTransport};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Response, Server, StatusCode};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use web3::Web3;
+
+    const ALICE: u64 = 0xa11ce;
+    const BOB: u64 = 0xb0b;
+
+    async fn block_3() -> BlockAnalysis {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        analyze_block(&web3, Some(3), 4).await.unwrap()
+    }
+
+    fn notifications(analysis: &BlockAnalysis, watched: &[u64]) -> Vec<AddressNotification> {
+        AddressNotification::collect(analysis, &watched.iter().copied().map(H160::from_low_u64_be).collect())
+    }
+
+    #[test]
+    fn formats_parse_by_name() {
+        assert_eq!("json".parse(), Ok(NotifyFormat::Json));
+        assert_eq!("slack".parse(), Ok(NotifyFormat::Slack));
+        assert_eq!("xml".parse::<NotifyFormat>(), Err("invalid notification format 'xml': expected json or slack".to_string()));
+    }
+
+    #[tokio::test]
+    async fn watched_addresses_are_notified_with_the_transactions_touching_them() {
+        let analysis = block_3().await;
+        // The third watched address didn't change
+        let notifications = notifications(&analysis, &[BOB, ALICE, 0xdead]);
+        let notified: Vec<_> = notifications.iter().map(|notification| notification.address).collect();
+        assert_eq!(notified, [H160::from_low_u64_be(BOB), H160::from_low_u64_be(ALICE)]);
+
+        let bob = &notifications[0];
+        assert_eq!((bob.block_number, bob.block_hash), (3, analysis.block_info.hash));
+        assert_eq!(bob.transactions, [H256::from_low_u64_be(0x407), H256::from_low_u64_be(0x408)]);
+        // Alice sent the transfer and created the contract
+        let alice = &notifications[1];
+        assert_eq!(alice.transactions, [H256::from_low_u64_be(0x407), H256::from_low_u64_be(0x409)]);
+        let change = analysis.state_changes.iter().flatten().find(|change| change.address == alice.address).unwrap();
+        assert_eq!((alice.prev_balance, alice.new_balance), (change.prev_balance, change.new_balance));
+        assert_eq!((alice.prev_nonce, alice.new_nonce), (change.prev_nonce, change.new_nonce));
+    }
+
+    #[tokio::test]
+    async fn the_json_payload_is_the_notification_and_slack_wraps_it() {
+        let analysis = block_3().await;
+        let alice = notifications(&analysis, &[ALICE]).remove(0);
+
+        let payload = alice.body(NotifyFormat::Json);
+        assert_eq!(payload["block_number"], json!(3));
+        assert_eq!(payload["address"], json!(format_address(&alice.address)));
+        assert_eq!(payload["transactions"], json!(alice.transactions));
+        assert_eq!(payload["new_nonce"], serde_json::to_value(&alice).unwrap()["new_nonce"]);
+
+        let slack = alice.body(NotifyFormat::Slack);
+        let text = slack["text"].as_str().unwrap();
+        let (summary, details) = text.split_once('\n').unwrap();
+        let expected = format!(
+            "{} changed in block 3: balance {} → {} wei, nonce {} → {}",
+            format_address(&alice.address),
+            alice.prev_balance.unwrap(),
+            alice.new_balance.unwrap(),
+            alice.prev_nonce.unwrap(),
+            alice.new_nonce.unwrap()
+        );
+        assert_eq!(summary, expected);
+        let details = details.strip_prefix("```").and_then(|details| details.strip_suffix("```")).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(details).unwrap(), payload);
+    }
+
+    /// A webhook answering with `statuses` in turn, then 200, recording the bodies POSTed to it
+    fn webhook(statuses: Vec<u16>) -> (SocketAddr, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(statuses.into_iter()));
+        let recorded = received.clone();
+        let service = make_service_fn(move |_| {
+            let (received, statuses) = (recorded.clone(), statuses.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                    let (received, statuses) = (received.clone(), statuses.clone());
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                        received.lock().unwrap().push(serde_json::from_slice(&body).unwrap());
+                        let status = statuses.lock().unwrap().next().unwrap_or(200);
+                        let mut response = Response::new(Body::empty());
+                        *response.status_mut() = StatusCode::from_u16(status).unwrap();
+                        Ok::<_, Infallible>(response)
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        (addr, received)
+    }
+
+    #[tokio::test]
+    async fn a_failed_delivery_is_retried_and_a_rejected_one_is_not() {
+        let alice = notifications(&block_3().await, &[ALICE]).remove(0);
+
+        let (addr, received) = webhook(vec![503]);
+        let mut notifier = WebhookNotifier::new(&format!("http://{}/hook", addr), NotifyFormat::Json, Duration::from_secs(5)).unwrap();
+        notifier.send(alice.clone());
+        notifier.finish().await;
+        assert_eq!(*received.lock().unwrap(), [alice.body(NotifyFormat::Json), alice.body(NotifyFormat::Json)]);
+
+        let (addr, received) = webhook(vec![400]);
+        let mut notifier = WebhookNotifier::new(&format!("http://{}/hook", addr), NotifyFormat::Slack, Duration::from_secs(5)).unwrap();
+        notifier.send(alice.clone());
+        notifier.finish().await;
+        assert_eq!(*received.lock().unwrap(), [alice.body(NotifyFormat::Slack)]);
+    }
+
+    #[test]
+    fn only_http_webhooks_are_accepted() {
+        let error = WebhookNotifier::new("ftp://example.com/hook", NotifyFormat::Json, Duration::from_secs(1)).err().unwrap();
+        assert_eq!(error, "invalid webhook URL 'ftp://example.com/hook': expected http or https");
+        assert!(WebhookNotifier::new("not a url", NotifyFormat::Json, Duration::from_secs(1)).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use arrow_array::builder::{FixedSizeBinaryBuilder, Int64Builder, StringBuilder, UInt32Builder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use web3::types::{H160, H256};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{BlockAnalysis, NonceDelta, Reorg};
+
+const HASH_BYTES: i32 = 32;
+const ADDRESS_BYTES: i32 = 20;
+
+fn hash_field(name: &str, nullable: bool) -> Field {
+    Field::new(name, DataType::FixedSizeBinary(HASH_BYTES), nullable)
+}
+
+fn address_field(name: &str, nullable: bool) -> Field {
+    Field::new(name, DataType::FixedSizeBinary(ADDRESS_BYTES), nullable)
+}
+
+/// U256 amounts don't fit any Arrow decimal, so they're decimal strings; gas and nonces fit u64
+fn amount_field(name: &str, nullable: bool) -> Field {
+    Field::new(name, DataType::Utf8, nullable)
+}
+
+/// `blocks.parquet`: one row per block
+pub fn blocks_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("number", DataType::UInt64, false),
+        hash_field("hash", false),
+        hash_field("parent_hash", false),
+        Field::new("timestamp", DataType::UInt64, false),
+        address_field("miner", false),
+        Field::new("gas_used", DataType::UInt64, false),
+        Field::new("gas_limit", DataType::UInt64, false),
+        amount_field("base_fee_per_gas", true),
+        amount_field("burned_base_fee", true),
+        Field::new("transaction_count", DataType::UInt64, false),
+    ]))
+}
+
+/// `transactions.parquet`: one row per transaction, in block order
+pub fn transactions_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        hash_field("block_hash", false),
+        Field::new("position", DataType::UInt32, false),
+        hash_field("hash", false),
+        address_field("from_address", true),
+        address_field("to_address", true),
+        address_field("created_contract", true),
+        amount_field("value", false),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("gas_used", DataType::UInt64, true),
+        amount_field("effective_gas_price", true),
+        amount_field("fee_paid", true),
+    ]))
+}
+
+/// `state_changes.parquet`: one row per changed address
+pub fn state_changes_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        hash_field("block_hash", false),
+        address_field("address", false),
+        amount_field("prev_balance", true),
+        amount_field("new_balance", true),
+        // Signed, with a leading '-' for a decrease
+        amount_field("balance_change", true),
+        Field::new("prev_nonce", DataType::UInt64, true),
+        Field::new("new_nonce", DataType::UInt64, true),
+        // Negative for an account deleted and created again
+        Field::new("nonce_change", DataType::Int64, true),
+    ]))
+}
+
+/// The three record batches of one block, kept back until a reorg can no longer replace it
+struct PendingBlock {
+    hash: H256,
+    batches: [RecordBatch; 3],
+}
+
+/// Writes analyses as `blocks.parquet`, `transactions.parquet` and `state_changes.parquet` in a directory,
+/// appending every block of a run to the same three files. A block is held back until `reorg_depth` newer
+/// ones arrive, so a reorg within that window drops its rows instead of leaving them behind. The files are
+/// written under temporary names and only take their final names in [`ParquetExport::finish`].
+pub struct ParquetExport {
+    /// Each file's writer, with the temporary path it writes to and the path it's renamed to
+    writers: [(ArrowWriter<File>, PathBuf, PathBuf); 3],
+    reorg_depth: usize,
+    pending: VecDeque<PendingBlock>,
+}
+
+const FILE_NAMES: [&str; 3] = ["blocks.parquet", "transactions.parquet", "state_changes.parquet"];
+
+impl ParquetExport {
+    /// Start the files in `dir`, creating it if needed
+    pub fn create(dir: impl AsRef<Path>, reorg_depth: usize) -> Result<Self, Box<dyn Error>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir).map_err(|e| format!("creating {}: {}", dir.display(), e))?;
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let schemas = [blocks_schema(), transactions_schema(), state_changes_schema()];
+
+        let mut writers = Vec::with_capacity(3);
+        for (name, schema) in FILE_NAMES.iter().zip(schemas) {
+            let path = dir.join(name);
+            let temporary = dir.join(format!(".{}.partial", name));
+            let file = File::create(&temporary).map_err(|e| format!("creating {}: {}", temporary.display(), e))?;
+            writers.push((ArrowWriter::try_new(file, schema, Some(properties.clone()))?, temporary, path));
+        }
+        let writers = writers.try_into().unwrap_or_else(|_| unreachable!("one writer per file"));
+        Ok(ParquetExport { writers, reorg_depth, pending: VecDeque::new() })
+    }
+
+    pub fn write(&mut self, analysis: &BlockAnalysis) -> Result<(), Box<dyn Error>> {
+        let batches = [block_batch(analysis)?, transactions_batch(analysis)?, state_changes_batch(analysis)?];
+        self.pending.push_back(PendingBlock { hash: analysis.block_info.hash, batches });
+        while self.pending.len() > self.reorg_depth {
+            let block = self.pending.pop_front().expect("more pending blocks than the depth");
+            self.append(block)?;
+        }
+        Ok(())
+    }
+
+    /// Drop the rows of the blocks a reorg invalidated; their replacements are written next
+    pub fn mark_replaced(&mut self, reorg: &Reorg) {
+        self.pending.retain(|block| !reorg.invalidated.iter().any(|invalidated| invalidated.hash == block.hash));
+    }
+
+    /// Write out the blocks still held back, close the files and give them their final names
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        while let Some(block) = self.pending.pop_front() {
+            self.append(block)?;
+        }
+        for (writer, temporary, path) in self.writers {
+            writer.close()?;
+            std::fs::rename(&temporary, &path).map_err(|e| format!("renaming {} to {}: {}", temporary.display(), path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Batches are buffered into row groups by the writer, so a long range doesn't get one per block
+    fn append(&mut self, block: PendingBlock) -> Result<(), Box<dyn Error>> {
+        for ((writer, _, _), batch) in self.writers.iter_mut().zip(block.batches) {
+            writer.write(&batch)?;
+        }
+        Ok(())
+    }
+}
+
+fn hashes<'a>(values: impl Iterator<Item = Option<&'a H256>>) -> Result<ArrayRef, Box<dyn Error>> {
+    let mut builder = FixedSizeBinaryBuilder::new(HASH_BYTES);
+    for value in values {
+        match value {
+            Some(hash) => builder.append_value(hash.as_bytes())?,
+            None => builder.append_null(),
+        }
+    }
+    Ok(Arc::new(builder.finish()))
+}
+
+fn addresses(values: impl Iterator<Item = Option<H160>>) -> Result<ArrayRef, Box<dyn Error>> {
+    let mut builder = FixedSizeBinaryBuilder::new(ADDRESS_BYTES);
+    for value in values {
+        match value {
+            Some(address) => builder.append_value(address.as_bytes())?,
+            None => builder.append_null(),
+        }
+    }
+    Ok(Arc::new(builder.finish()))
+}
+
+fn strings(values: impl Iterator<Item = Option<String>>) -> ArrayRef {
+    let mut builder = StringBuilder::new();
+    for value in values {
+        builder.append_option(value);
+    }
+    Arc::new(builder.finish())
+}
+
+fn numbers(values: impl Iterator<Item = Option<u64>>) -> ArrayRef {
+    let mut builder = UInt64Builder::new();
+    for value in values {
+        builder.append_option(value);
+    }
+    Arc::new(builder.finish())
+}
+
+fn signed_numbers(values: impl Iterator<Item = Option<i64>>) -> ArrayRef {
+    let mut builder = Int64Builder::new();
+    for value in values {
+        builder.append_option(value);
+    }
+    Arc::new(builder.finish())
+}
+
+fn signed_nonce_delta(delta: NonceDelta) -> i64 {
+    let magnitude = delta.magnitude.low_u64() as i64;
+    match delta.is_negative() {
+        true => -magnitude,
+        false => magnitude,
+    }
+}
+
+fn repeat<T: Clone>(value: T, count: usize) -> impl Iterator<Item = Option<T>> {
+    std::iter::repeat_n(Some(value), count)
+}
+
+fn block_batch(analysis: &BlockAnalysis) -> Result<RecordBatch, Box<dyn Error>> {
+    let block = &analysis.block_info;
+    let miner: H160 = block.miner.to_lowercase().parse().map_err(|_| format!("invalid miner address '{}'", block.miner))?;
+    let columns = vec![
+        numbers(repeat(block.block_number, 1)),
+        hashes(repeat(&block.hash, 1))?,
+        hashes(repeat(&block.parent_hash, 1))?,
+        numbers(repeat(block.timestamp, 1)),
+        addresses(repeat(miner, 1))?,
+        numbers(repeat(block.gas_used, 1)),
+        numbers(repeat(block.gas_limit, 1)),
+        strings(std::iter::once(block.base_fee_per_gas.map(|fee| fee.to_string()))),
+        strings(std::iter::once(block.burned_base_fee.map(|fee| fee.to_string()))),
+        numbers(repeat(block.transactions.len() as u64, 1)),
+    ];
+    Ok(RecordBatch::try_new(blocks_schema(), columns)?)
+}
+
+fn transactions_batch(analysis: &BlockAnalysis) -> Result<RecordBatch, Box<dyn Error>> {
+    let block = &analysis.block_info;
+    let transactions = &block.transactions;
+    let count = transactions.len();
+    let mut positions = UInt32Builder::with_capacity(count);
+    for position in 0..count {
+        positions.append_value(position as u32);
+    }
+    let columns = vec![
+        numbers(repeat(block.block_number, count)),
+        hashes(repeat(&block.hash, count))?,
+        Arc::new(positions.finish()) as ArrayRef,
+        hashes(transactions.iter().map(|tx| Some(&tx.hash)))?,
+        addresses(transactions.iter().map(|tx| tx.from))?,
+        addresses(transactions.iter().map(|tx| tx.to))?,
+        addresses(transactions.iter().map(|tx| tx.created_contract))?,
+        strings(transactions.iter().map(|tx| Some(tx.value.to_string()))),
+        strings(transactions.iter().map(|tx| serde_json::to_value(tx.status).ok().and_then(|status| status.as_str().map(String::from)))),
+        numbers(transactions.iter().map(|tx| tx.gas_used.map(|gas| gas.low_u64()))),
+        strings(transactions.iter().map(|tx| tx.effective_gas_price.map(|price| price.to_string()))),
+        strings(transactions.iter().map(|tx| tx.fee_paid.map(|fee| fee.to_string()))),
+    ];
+    Ok(RecordBatch::try_new(transactions_schema(), columns)?)
+}
+
+fn state_changes_batch(analysis: &BlockAnalysis) -> Result<RecordBatch, Box<dyn Error>> {
+    let block = &analysis.block_info;
+    let changes: Vec<_> = analysis.state_changes.iter().flatten().collect();
+    let count = changes.len();
+    let columns = vec![
+        numbers(repeat(block.block_number, count)),
+        hashes(repeat(&block.hash, count))?,
+        addresses(changes.iter().map(|change| Some(change.address)))?,
+        strings(changes.iter().map(|change| change.prev_balance.map(|balance| balance.to_string()))),
+        strings(changes.iter().map(|change| change.new_balance.map(|balance| balance.to_string()))),
+        strings(changes.iter().map(|change| change.balance_change.map(|delta| delta.to_string()))),
+        numbers(changes.iter().map(|change| change.prev_nonce.map(|nonce| nonce.low_u64()))),
+        numbers(changes.iter().map(|change| change.new_nonce.map(|nonce| nonce.low_u64()))),
+        signed_numbers(changes.iter().map(|change| change.nonce_change.map(signed_nonce_delta))),
+    ];
+    Ok(RecordBatch::try_new(state_changes_schema(), columns)?)
+}
@@ -0,0 +1,238 @@
+use postgres_native_tls::MakeTlsConnector;
+use tokio_postgres::Client;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::{BlockAnalysis, Reorg, RetryPolicy};
+
+/// Applied in order by `--migrate`, each in its own transaction, and recorded in `schema_migrations`
+const MIGRATIONS: &[(&str, &str)] = &[("0001_initial", include_str!("../migrations/0001_initial.sql"))];
+
+/// Writes analyses into PostgreSQL with the same tables as [`crate::SqliteSink`], plus token transfers.
+/// Each block is written in one transaction that first drops anything stored for its hash, so a write
+/// repeated after a lost connection neither duplicates nor half-writes a block. A lost connection is
+/// re-established with backoff and the interrupted write retried; a block that still can't be stored
+/// is an error, so nothing after it is checkpointed.
+pub struct PostgresSink {
+    url: String,
+    tls: MakeTlsConnector,
+    client: Client,
+    policy: RetryPolicy,
+}
+
+impl PostgresSink {
+    /// Connect to `url`, a `postgres://` URL or key=value connection string. With `migrate`, pending
+    /// migrations are applied first; without it, a database that needs them is an error.
+    pub async fn connect(url: &str, migrate: bool) -> Result<Self, Box<dyn Error>> {
+        let tls = MakeTlsConnector::new(native_tls::TlsConnector::new()?);
+        let mut client = open(url, &tls).await.map_err(|e| format!("connecting to postgres: {}", e))?;
+
+        let pending = pending_migrations(&client).await?;
+        if !pending.is_empty() && !migrate {
+            return Err(format!("the postgres database needs migrations {}; run with --migrate", pending.join(", ")).into());
+        }
+        if migrate {
+            apply_migrations(&mut client).await?;
+        }
+
+        let policy = RetryPolicy {
+            max_retries: 8,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            request_timeout: Duration::from_secs(30),
+        };
+        Ok(PostgresSink { url: url.to_string(), tls, client, policy })
+    }
+
+    /// Highest canonical block number stored so far, if any
+    pub async fn highest_block(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        let row = self.client.query_one("SELECT MAX(number) FROM blocks WHERE NOT replaced", &[]).await?;
+        Ok(row.get::<_, Option<i64>>(0).map(|number| number as u64))
+    }
+
+    /// Store an analysis, replacing anything stored for the same block hash. Other blocks stored at
+    /// the same height have been reorged out, and are flagged as replaced.
+    pub async fn write(&mut self, analysis: &BlockAnalysis) -> Result<(), Box<dyn Error>> {
+        let mut retries = 0;
+        loop {
+            match write_block(&mut self.client, analysis).await {
+                Ok(()) => return Ok(()),
+                Err(e) => self
+                    .recover(e, &mut retries)
+                    .await
+                    .map_err(|e| format!("storing block {} in postgres: {}", analysis.block_info.block_number, e))?,
+            }
+        }
+    }
+
+    /// Flag the blocks a reorg invalidated, keeping their rows
+    pub async fn mark_replaced(&mut self, reorg: &Reorg) -> Result<(), Box<dyn Error>> {
+        let hashes: Vec<String> = reorg.invalidated.iter().map(|block| format!("{:?}", block.hash)).collect();
+        let mut retries = 0;
+        loop {
+            match self.client.execute("UPDATE blocks SET replaced = TRUE WHERE hash = ANY($1)", &[&hashes]).await {
+                Ok(_) => return Ok(()),
+                Err(e) => self.recover(e, &mut retries).await.map_err(|e| format!("flagging reorged blocks in postgres: {}", e))?,
+            }
+        }
+    }
+
+    /// After `error`, wait and reconnect if the connection was lost, so the caller can try again.
+    /// Hands the error back for anything else, or once the retries are used up.
+    async fn recover(&mut self, error: tokio_postgres::Error, retries: &mut u32) -> Result<(), tokio_postgres::Error> {
+        let lost = error.is_closed() || self.client.is_closed() || error.source().is_some_and(|source| source.is::<std::io::Error>());
+        if !lost {
+            return Err(error);
+        }
+        let mut error = error;
+        while *retries < self.policy.max_retries {
+            tracing::warn!(error = %error, retries = *retries, "postgres connection lost; reconnecting");
+            tokio::time::sleep(self.policy.delay(*retries)).await;
+            *retries += 1;
+            match open(&self.url, &self.tls).await {
+                Ok(client) => {
+                    self.client = client;
+                    return Ok(());
+                }
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+}
+
+async fn open(url: &str, tls: &MakeTlsConnector) -> Result<Client, tokio_postgres::Error> {
+    let (client, connection) = tokio_postgres::connect(url, tls.clone()).await?;
+    // The connection runs until the client is dropped or the server goes away; a lost connection
+    // shows up as failing queries, which reconnect
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::debug!(error = %e, "postgres connection closed");
+        }
+    });
+    Ok(client)
+}
+
+async fn pending_migrations(client: &Client) -> Result<Vec<&'static str>, Box<dyn Error>> {
+    let exists: bool = client.query_one("SELECT to_regclass('schema_migrations') IS NOT NULL", &[]).await?.get(0);
+    let applied: Vec<String> = match exists {
+        true => client.query("SELECT version FROM schema_migrations", &[]).await?.iter().map(|row| row.get(0)).collect(),
+        false => Vec::new(),
+    };
+    Ok(MIGRATIONS.iter().map(|(version, _)| *version).filter(|version| !applied.iter().any(|applied| applied == version)).collect())
+}
+
+async fn apply_migrations(client: &mut Client) -> Result<(), Box<dyn Error>> {
+    client
+        .batch_execute("CREATE TABLE IF NOT EXISTS schema_migrations (version TEXT PRIMARY KEY, applied_at TIMESTAMPTZ NOT NULL DEFAULT now())")
+        .await?;
+    for (version, sql) in MIGRATIONS {
+        let tx = client.transaction().await?;
+        // Two analyzers migrating at once take turns
+        tx.batch_execute("LOCK TABLE schema_migrations IN EXCLUSIVE MODE").await?;
+        if tx.query_opt("SELECT 1 FROM schema_migrations WHERE version = $1", &[version]).await?.is_some() {
+            continue;
+        }
+        tx.batch_execute(sql).await.map_err(|e| format!("applying migration {}: {}", version, e))?;
+        tx.execute("INSERT INTO schema_migrations (version) VALUES ($1)", &[version]).await?;
+        tx.commit().await?;
+        tracing::info!(version = %version, "applied postgres migration");
+    }
+    Ok(())
+}
+
+/// One block with its transactions, state changes and token transfers, inserted a table at a time
+/// from arrays. Amounts travel as decimal text and are cast to NUMERIC by the server.
+async fn write_block(client: &mut Client, analysis: &BlockAnalysis) -> Result<(), tokio_postgres::Error> {
+    let block = &analysis.block_info;
+    let hash = format!("{:?}", block.hash);
+    let number = block.block_number as i64;
+    let state_diff_source = serde_json::to_value(analysis.state_diff_source).ok().and_then(|source| source.as_str().map(String::from));
+    let tx = client.transaction().await?;
+
+    // Child rows go with the block
+    tx.execute("DELETE FROM blocks WHERE hash = $1", &[&hash]).await?;
+    tx.execute("UPDATE blocks SET replaced = TRUE WHERE number = $1", &[&number]).await?;
+
+    tx.execute(
+        "INSERT INTO blocks (hash, number, parent_hash, timestamp, miner, gas_used, gas_limit, base_fee_per_gas, burned_base_fee, state_diff_source)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8::TEXT::NUMERIC, $9::TEXT::NUMERIC, $10)",
+        &[
+            &hash,
+            &number,
+            &format!("{:?}", block.parent_hash),
+            &(block.timestamp as i64),
+            &block.miner,
+            &(block.gas_used as i64),
+            &(block.gas_limit as i64),
+            &block.base_fee_per_gas.map(|fee| fee.to_string()),
+            &block.burned_base_fee.map(|fee| fee.to_string()),
+            &state_diff_source.unwrap_or_default(),
+        ],
+    )
+    .await?;
+
+    let transactions = &block.transactions;
+    let column = |value: &dyn Fn(&crate::TransactionInfo) -> Option<String>| transactions.iter().map(value).collect::<Vec<_>>();
+    tx.execute(
+        "INSERT INTO transactions (block_hash, hash, position, from_address, to_address, created_contract, value, status, gas_used, fee_paid)
+         SELECT $1, hash, position, from_address, to_address, created_contract, value::NUMERIC, status, gas_used::NUMERIC, fee_paid::NUMERIC
+         FROM UNNEST($2::TEXT[], $3::INT[], $4::TEXT[], $5::TEXT[], $6::TEXT[], $7::TEXT[], $8::TEXT[], $9::TEXT[], $10::TEXT[])
+             AS t (hash, position, from_address, to_address, created_contract, value, status, gas_used, fee_paid)",
+        &[
+            &hash,
+            &column(&|info| Some(format!("{:?}", info.hash))),
+            &(0..transactions.len() as i32).collect::<Vec<i32>>(),
+            &column(&|info| info.from.map(|from| format!("{:?}", from))),
+            &column(&|info| info.to.map(|to| format!("{:?}", to))),
+            &column(&|info| info.created_contract.map(|created| format!("{:?}", created))),
+            &column(&|info| Some(info.value.to_string())),
+            &column(&|info| serde_json::to_value(info.status).ok().and_then(|status| status.as_str().map(String::from))),
+            &column(&|info| info.gas_used.map(|gas| gas.to_string())),
+            &column(&|info| info.fee_paid.map(|fee| fee.to_string())),
+        ],
+    )
+    .await?;
+
+    let changes: Vec<&crate::StateChange> = analysis.state_changes.iter().flatten().collect();
+    let column = |value: &dyn Fn(&crate::StateChange) -> Option<String>| changes.iter().map(|change| value(change)).collect::<Vec<_>>();
+    tx.execute(
+        "INSERT INTO state_changes (block_hash, address, prev_balance, new_balance, balance_change, prev_nonce, new_nonce, nonce_change)
+         SELECT $1, address, prev_balance::NUMERIC, new_balance::NUMERIC, balance_change::NUMERIC,
+                prev_nonce::NUMERIC, new_nonce::NUMERIC, nonce_change::NUMERIC
+         FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TEXT[], $7::TEXT[], $8::TEXT[])
+             AS t (address, prev_balance, new_balance, balance_change, prev_nonce, new_nonce, nonce_change)",
+        &[
+            &hash,
+            &column(&|change| Some(format!("{:?}", change.address))),
+            &column(&|change| change.prev_balance.map(|balance| balance.to_string())),
+            &column(&|change| change.new_balance.map(|balance| balance.to_string())),
+            &column(&|change| change.balance_change.map(|delta| delta.to_string())),
+            &column(&|change| change.prev_nonce.map(|nonce| nonce.to_string())),
+            &column(&|change| change.new_nonce.map(|nonce| nonce.to_string())),
+            &column(&|change| change.nonce_change.map(|delta| delta.to_string())),
+        ],
+    )
+    .await?;
+
+    let transfers = &analysis.token_transfers;
+    let column = |value: &dyn Fn(&crate::TokenTransfer) -> String| transfers.iter().map(value).collect::<Vec<_>>();
+    tx.execute(
+        "INSERT INTO token_transfers (block_hash, position, transaction_hash, token, from_address, to_address, amount)
+         SELECT $1, position, transaction_hash, token, from_address, to_address, amount::NUMERIC
+         FROM UNNEST($2::INT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TEXT[], $7::TEXT[])
+             AS t (position, transaction_hash, token, from_address, to_address, amount)",
+        &[
+            &hash,
+            &(0..transfers.len() as i32).collect::<Vec<i32>>(),
+            &column(&|transfer| format!("{:?}", transfer.transaction_hash)),
+            &column(&|transfer| format!("{:?}", transfer.token)),
+            &column(&|transfer| format!("{:?}", transfer.from)),
+            &column(&|transfer| format!("{:?}", transfer.to)),
+            &column(&|transfer| transfer.amount.to_string()),
+        ],
+    )
+    .await?;
+
+    tx.commit().await
+}
@@ -0,0 +1,142 @@
+use serde_json::json;
+use web3::types::{BlockId, BlockNumber, Proof, H160, U64};
+use web3::{BatchTransport, Web3};
+use std::error::Error;
+use std::fmt::Display;
+
+use crate::trace::is_method_unsupported;
+use crate::verify::{verify_account_proof, ProvenAccount};
+use crate::{batch, AnalysisOptions, BlockInfo, BlockNotFoundError, BlockRef, NonceDelta, StateChange, Wei};
+
+/// Prove the balances and nonces of `changes` with `eth_getProof` at `baseline` and the analyzed block,
+/// against those blocks' state roots, and put the proven values in place of the queried ones. A change
+/// whose proofs all check out is flagged `proof_verified`; one whose proof doesn't keeps its queried
+/// values, with a warning. Without `eth_getProof` on the node, every change stays unproven, with one warning.
+pub(crate) async fn prove_state_changes<T: BatchTransport>(
+    web3: &Web3<T>,
+    block_info: &BlockInfo,
+    baseline: Option<u64>,
+    changes: &mut [StateChange],
+    options: &AnalysisOptions,
+    warnings: &mut Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(first) = changes.first() else {
+        return Ok(());
+    };
+    let current = block_info.block_number;
+
+    // One call on its own first, so a node without the method is one warning instead of a failed batch
+    match web3.eth().proof(first.address, Vec::new(), Some(BlockNumber::Number(U64::from(current)))).await {
+        Err(e) if is_method_unsupported(&e) => {
+            warnings.push(format!("the node doesn't support eth_getProof ({}); state changes are unproven", e));
+            return Ok(());
+        }
+        result => {
+            result?;
+        }
+    }
+
+    // The analyzed block's root is taken by hash, so proofs from a block that replaced it fail to verify
+    // instead of proving another block's state. The genesis block has no baseline; its accounts start empty.
+    let analyzed = web3.eth().block(BlockId::Hash(block_info.hash)).await?;
+    let mut roots = vec![(current, analyzed.ok_or(BlockNotFoundError { block: BlockRef::Hash(block_info.hash) })?.state_root)];
+    if let Some(baseline) = baseline {
+        let header = web3.eth().block(BlockId::Number(BlockNumber::Number(U64::from(baseline)))).await?;
+        roots.insert(0, (baseline, header.ok_or(BlockNotFoundError { block: BlockRef::Number(baseline) })?.state_root));
+    }
+
+    let calls = changes
+        .iter()
+        .flat_map(|change| {
+            roots.iter().map(move |(block, _)| {
+                ("eth_getProof", vec![json!(change.address), json!([]), json!(BlockNumber::Number(U64::from(*block)))])
+            })
+        })
+        .collect();
+    let values = batch::call_batched(web3, calls, options.batch_size, options.concurrency, |_| {}).await?;
+
+    for (change, values) in changes.iter_mut().zip(values.chunks(roots.len())) {
+        let mut proven = Vec::with_capacity(roots.len());
+        for ((block, state_root), value) in roots.iter().zip(values) {
+            let proof: Proof = serde_json::from_value(value.clone())?;
+            let nodes: Vec<Vec<u8>> = proof.account_proof.into_iter().map(|node| node.0).collect();
+            match verify_account_proof(*state_root, change.address, &nodes) {
+                Ok(account) => proven.push((*block, account.unwrap_or_default())),
+                Err(reason) => {
+                    warnings.push(format!(
+                        "the account proof of {:?} at block {} failed verification: {}; its state change is unproven",
+                        change.address, block, reason
+                    ));
+                    break;
+                }
+            }
+        }
+        if proven.len() == roots.len() {
+            apply_proven(change, baseline.is_some().then(|| proven[0]), proven[proven.len() - 1], warnings);
+        }
+    }
+    Ok(())
+}
+
+/// Put the proven state before and after the block in place of the queried one, for the fields queried
+fn apply_proven(change: &mut StateChange, before: Option<(u64, ProvenAccount)>, after: (u64, ProvenAccount), warnings: &mut Vec<String>) {
+    let address = change.address;
+    if let Some((block, (nonce, balance))) = before {
+        replace(&mut change.prev_balance, Wei(balance), "balance", address, block, warnings);
+        replace(&mut change.prev_nonce, nonce, "nonce", address, block, warnings);
+    }
+    let (block, (nonce, balance)) = after;
+    replace(&mut change.new_balance, Wei(balance), "balance", address, block, warnings);
+    replace(&mut change.new_nonce, nonce, "nonce", address, block, warnings);
+
+    change.balance_change = change.prev_balance.zip(change.new_balance).map(|(prev, new)| Wei::checked_delta(prev, new));
+    change.nonce_change = change.prev_nonce.zip(change.new_nonce).map(|(prev, new)| NonceDelta::between(prev, new));
+    change.proof_verified = true;
+}
+
+/// A field that wasn't queried stays empty
+fn replace<V: PartialEq + Display>(queried: &mut Option<V>, proven: V, field: &str, address: H160, block: u64, warnings: &mut Vec<String>) {
+    if let Some(queried) = queried {
+        if *queried != proven {
+            warnings.push(format!(
+                "the node reported a {} of {} for {:?} at block {}, but its proof gives {}; the proven value is used",
+                field, queried, address, block, proven
+            ));
+        }
+        *queried = proven;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::types::U256;
+
+    use crate::history::net_change;
+
+    #[test]
+    fn proven_values_replace_queried_ones_with_a_warning_where_they_differ() {
+        let address = H160::from_low_u64_be(0xa11ce);
+        let ether = |amount: u64| Wei(U256::from(amount) * U256::exp10(18));
+        let mut change = net_change(address, (Some(ether(10)), Some(U256::from(2))), (Some(ether(9)), Some(U256::from(3))));
+        let mut warnings = Vec::new();
+        apply_proven(&mut change, Some((4, (U256::from(2), ether(10).0))), (5, (U256::from(3), ether(8).0)), &mut warnings);
+
+        assert!(change.proof_verified);
+        assert_eq!(change.new_balance, Some(ether(8)));
+        assert_eq!(change.balance_change, Some(Wei::checked_delta(ether(10), ether(8))));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("at block 5, but its proof gives"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn a_field_that_wasnt_queried_stays_empty() {
+        let address = H160::from_low_u64_be(0xb0b);
+        let mut change = net_change(address, (Some(Wei::zero()), None), (Some(Wei::from(U256::one())), None));
+        let mut warnings = Vec::new();
+        apply_proven(&mut change, Some((4, (U256::zero(), U256::zero()))), (5, (U256::from(7), U256::one())), &mut warnings);
+
+        assert!(warnings.is_empty());
+        assert_eq!((change.prev_nonce, change.new_nonce, change.nonce_change), (None, None, None));
+    }
+}
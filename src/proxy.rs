@@ -0,0 +1,122 @@
+use serde::Serialize;
+use serde_json::json;
+use web3::types::{BlockNumber, H160, H256, U256, U64};
+use web3::{BatchTransport, Web3};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use crate::tokens::topic_to_address;
+use crate::watched_slots::storage_word;
+use crate::{batch, format_address, AnalysisOptions, BlockInfo};
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`
+const IMPLEMENTATION_SLOT: H256 = H256([
+    0x36, 0x08, 0x94, 0xa1, 0x3b, 0xa1, 0xa3, 0x21, 0x06, 0x67, 0xc8, 0x28, 0x49, 0x2d, 0xb9, 0x8d,
+    0xca, 0x3e, 0x20, 0x76, 0xcc, 0x37, 0x35, 0xa9, 0x20, 0xa3, 0xca, 0x50, 0x5d, 0x38, 0x2b, 0xbc,
+]);
+/// `bytes32(uint256(keccak256("eip1967.proxy.admin")) - 1)`
+const ADMIN_SLOT: H256 = H256([
+    0xb5, 0x31, 0x27, 0x68, 0x4a, 0x56, 0x8b, 0x31, 0x73, 0xae, 0x13, 0xb9, 0xf8, 0xa6, 0x01, 0x6e,
+    0x24, 0x3e, 0x63, 0xb6, 0xe8, 0xee, 0x11, 0x78, 0xd6, 0xa7, 0x17, 0x85, 0x0b, 0x5d, 0x61, 0x03,
+]);
+/// `bytes32(uint256(keccak256("eip1967.proxy.beacon")) - 1)`
+const BEACON_SLOT: H256 = H256([
+    0xa3, 0xf0, 0xad, 0x74, 0xe5, 0x42, 0x3a, 0xeb, 0xfd, 0x80, 0xd3, 0xef, 0x43, 0x46, 0x57, 0x83,
+    0x35, 0xa9, 0xa7, 0x2a, 0xea, 0xee, 0x59, 0xff, 0x6c, 0xb3, 0x58, 0x2b, 0x35, 0x13, 0x3d, 0x50,
+]);
+
+const SLOTS: [(ProxySlot, H256); 3] =
+    [(ProxySlot::Implementation, IMPLEMENTATION_SLOT), (ProxySlot::Admin, ADMIN_SLOT), (ProxySlot::Beacon, BEACON_SLOT)];
+
+/// Which EIP-1967 slot of a proxy changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxySlot {
+    Implementation,
+    Admin,
+    /// The beacon a beacon proxy asks for its implementation
+    Beacon,
+}
+
+impl fmt::Display for ProxySlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProxySlot::Implementation => "implementation",
+            ProxySlot::Admin => "admin",
+            ProxySlot::Beacon => "beacon",
+        })
+    }
+}
+
+/// A proxy whose implementation, admin or beacon was replaced in the block
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyUpgrade {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    pub slot: ProxySlot,
+    /// The zero address when the slot was empty at the baseline
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub old: H160,
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub new: H160,
+}
+
+impl fmt::Display for ProxyUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PROXY UPGRADED: {} {} {} -> {}",
+            format_address(&self.address),
+            self.slot,
+            format_address(&self.old),
+            format_address(&self.new)
+        )
+    }
+}
+
+/// Read the EIP-1967 slots of every transaction recipient and of `options.address_filter` at `baseline` and
+/// at the block, batched, and keep those that changed. Contracts created in the block are left out: filling
+/// their slots in the constructor isn't an upgrade.
+pub(crate) async fn find_proxy_upgrades<T: BatchTransport>(
+    web3: &Web3<T>,
+    baseline: u64,
+    block_info: &BlockInfo,
+    options: &AnalysisOptions,
+) -> Result<Vec<ProxyUpgrade>, Box<dyn Error>> {
+    let created: BTreeSet<H160> = block_info.transactions.iter().filter_map(|tx| tx.created_contract).collect();
+    let addresses: Vec<H160> = block_info
+        .transactions
+        .iter()
+        .filter_map(|tx| tx.to)
+        .chain(options.address_filter.iter().flatten().copied())
+        .filter(|address| !created.contains(address))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if addresses.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let blocks = [baseline, block_info.block_number];
+    let mut calls = Vec::with_capacity(addresses.len() * SLOTS.len() * blocks.len());
+    for address in &addresses {
+        for (_, slot) in &SLOTS {
+            for block in blocks {
+                let tag = json!(BlockNumber::Number(U64::from(block)));
+                calls.push(("eth_getStorageAt", vec![json!(address), json!(U256::from_big_endian(slot.as_bytes())), tag]));
+            }
+        }
+    }
+    let values = batch::call_batched(web3, calls, options.batch_size, options.concurrency, |_| {}).await?;
+
+    let mut upgrades = Vec::new();
+    let slots = addresses.iter().flat_map(|address| SLOTS.iter().map(move |(slot, _)| (*address, *slot)));
+    for ((address, slot), values) in slots.zip(values.chunks(blocks.len())) {
+        let (old, new) = (storage_word(&values[0])?, storage_word(&values[1])?);
+        if old != new {
+            upgrades.push(ProxyUpgrade { address, slot, old: topic_to_address(&old), new: topic_to_address(&new) });
+        }
+    }
+    Ok(upgrades)
+}
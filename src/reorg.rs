@@ -0,0 +1,98 @@
+use web3::types::{BlockId, BlockNumber, H256, U64};
+use web3::{BatchTransport, Transport, Web3};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::{analyze_block_cached, AccountStateCache, AnalysisOptions, BlockAnalysis, BlockEvent, BlockRef, InvalidatedBlock, Reorg};
+
+/// Hashes of the most recently delivered blocks, for spotting a new block that doesn't build on them
+#[derive(Debug)]
+pub(crate) struct ChainTracker {
+    hashes: BTreeMap<u64, H256>,
+    depth: usize,
+}
+
+impl ChainTracker {
+    /// Remember up to `depth` blocks, which is also as far back as a reorg can be followed
+    pub(crate) fn new(depth: usize) -> Self {
+        ChainTracker { hashes: BTreeMap::new(), depth: depth.max(1) }
+    }
+
+    /// Whether `analysis` builds on the recorded chain. With no record of its parent there is nothing to contradict.
+    fn extends(&self, analysis: &BlockAnalysis) -> bool {
+        let block = &analysis.block_info;
+        match block.block_number.checked_sub(1).and_then(|parent| self.hashes.get(&parent)) {
+            Some(parent_hash) => *parent_hash == block.parent_hash,
+            None => true,
+        }
+    }
+
+    fn record(&mut self, analysis: &BlockAnalysis) {
+        let block = &analysis.block_info;
+        // Anything recorded above this block belonged to a chain it has replaced
+        self.hashes.split_off(&block.block_number);
+        self.hashes.insert(block.block_number, block.hash);
+        while self.hashes.len() > self.depth {
+            self.hashes.pop_first();
+        }
+    }
+
+    /// Walk back from the top of the recorded chain to the newest block that is still canonical,
+    /// forgetting the ones that aren't
+    async fn unwind<T: Transport>(&mut self, web3: &Web3<T>) -> Result<Reorg, Box<dyn Error>> {
+        let mut invalidated = Vec::new();
+        while let Some((&number, &hash)) = self.hashes.last_key_value() {
+            let canonical = web3.eth().block(BlockId::Number(BlockNumber::Number(U64::from(number)))).await?;
+            if canonical.and_then(|canonical| canonical.hash) == Some(hash) {
+                invalidated.reverse();
+                return Ok(Reorg { common_ancestor: number, invalidated });
+            }
+            invalidated.push(InvalidatedBlock { number, hash });
+            self.hashes.pop_last();
+        }
+
+        Err(format!("reorg is deeper than the {} tracked blocks; no common ancestor found", self.depth).into())
+    }
+}
+
+/// Hand the outcome for `block_number` to `on_block`, first dealing with any reorg it reveals:
+/// the invalidated blocks are reported, then the new canonical ones are re-analyzed and delivered in order.
+pub(crate) async fn deliver<T, F>(
+    web3: &Web3<T>,
+    options: &AnalysisOptions,
+    tracker: &mut ChainTracker,
+    cache: &mut AccountStateCache,
+    block_number: u64,
+    analysis: Result<BlockAnalysis, Box<dyn Error>>,
+    on_block: &mut F,
+) -> Result<(), Box<dyn Error>>
+where
+    T: BatchTransport,
+    F: FnMut(u64, Result<BlockEvent, Box<dyn Error>>) -> Result<(), Box<dyn Error>>,
+{
+    let (mut number, mut analysis) = (block_number, analysis);
+    let mut redo = block_number + 1..=block_number;
+
+    loop {
+        match analysis {
+            Ok(analysis) if !tracker.extends(&analysis) => {
+                let reorg = tracker.unwind(web3).await?;
+                let fork = reorg.common_ancestor + 1;
+                on_block(fork, Ok(BlockEvent::Reorg(reorg)))?;
+
+                // Cached states came from the old chain
+                *cache = AccountStateCache::default();
+                redo = fork..=block_number;
+            }
+            Ok(analysis) => {
+                tracker.record(&analysis);
+                on_block(number, Ok(BlockEvent::Analyzed(Box::new(analysis))))?;
+            }
+            Err(e) => on_block(number, Err(e))?,
+        }
+
+        let Some(next) = redo.next() else { return Ok(()) };
+        number = next;
+        analysis = analyze_block_cached(web3, BlockRef::Number(next), options, cache).await;
+    }
+}
@@ -0,0 +1,407 @@
+use futures::future::BoxFuture;
+use jsonrpc_core::{Call, ErrorCode, Params, Value};
+use rand::Rng;
+use web3::error::TransportError;
+use web3::{BatchTransport, RequestId, Transport};
+use std::future::Future;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::{telemetry, TimeoutError};
+
+/// Requests are cut to this many characters in logs; calldata and batches can run to megabytes
+const LOGGED_REQUEST_CHARS: usize = 200;
+
+/// How many times, and how patiently, to retry a failed RPC call
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// How long a single attempt may take; a timed-out attempt is retried like any transient failure
+    pub request_timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter: somewhere between half and all of `base_delay * 2^attempt`
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(attempt)).min(self.max_delay);
+        let half = backoff / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+}
+
+/// Whether an error is worth retrying: rate limits, timeouts (including the [`TimeoutError`]s of attempts
+/// that overran), 5xx responses and dropped connections.
+/// Requests the node rejected outright (bad params, unknown method) fail the same way every time, as do
+/// transport messages other than the HTTP transport's failures to send or to read the response, such as
+/// an unparseable response or a canned transport with no answer.
+pub fn is_retryable(error: &web3::Error) -> bool {
+    match error {
+        web3::Error::Unreachable | web3::Error::Io(_) => true,
+        web3::Error::Transport(TransportError::Code(code)) => *code == 408 || *code == 429 || (500..600).contains(code),
+        web3::Error::Transport(TransportError::Message(message)) => {
+            message.starts_with("failed to send request") || message.starts_with("failed to read response bytes")
+        }
+        web3::Error::Rpc(e) => match e.code {
+            ErrorCode::ParseError | ErrorCode::InvalidRequest | ErrorCode::MethodNotFound | ErrorCode::InvalidParams => false,
+            ErrorCode::InternalError => true,
+            ErrorCode::ServerError(code) => {
+                let message = e.message.to_lowercase();
+                code == -32005
+                    || message.contains("rate limit")
+                    || message.contains("too many requests")
+                    || message.contains("timeout")
+                    || message.contains("timed out")
+                    || message.contains("try again")
+            }
+        },
+        _ => false,
+    }
+}
+
+/// Transport wrapper that retries transient failures according to a `RetryPolicy`
+#[derive(Debug, Clone)]
+pub struct RetryTransport<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T> RetryTransport<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        RetryTransport { inner, policy }
+    }
+}
+
+impl<T> Transport for RetryTransport<T>
+where
+    T: Transport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+{
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        let inner = self.inner.clone();
+        let policy = self.policy;
+
+        Box::pin(with_retries(policy, describe(&request), vec![method(&request)], move || inner.send(id, request.clone())))
+    }
+}
+
+impl<T> BatchTransport for RetryTransport<T>
+where
+    T: BatchTransport + Send + Sync + 'static,
+    T::Out: Send + 'static,
+    T::Batch: Send + 'static,
+{
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    /// Retries the whole batch on a transient transport failure; failed entries inside a
+    /// delivered batch are passed through to the caller.
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let inner = self.inner.clone();
+        let policy = self.policy;
+        let requests: Vec<(RequestId, Call)> = requests.into_iter().collect();
+        let description = match requests.first() {
+            Some((_, first)) => format!("batch of {} requests starting with {}", requests.len(), describe(first)),
+            None => "empty batch".to_string(),
+        };
+
+        let methods: Vec<String> = requests.iter().map(|(_, call)| method(call)).collect();
+        Box::pin(async move {
+            let responses = with_retries(policy, description, methods.clone(), move || inner.send_batch(requests.clone())).await?;
+            telemetry::record_failures(
+                responses.iter().zip(&methods).filter(|(response, _)| response.is_err()).map(|(_, method)| method),
+            );
+            Ok(responses)
+        })
+    }
+}
+
+/// Run `attempt` until it succeeds, fails with a permanent error, or runs out of retries.
+/// Each attempt gets `policy.request_timeout`; a timed-out attempt counts as one retryable failure, and
+/// the last one fails with a [`TimeoutError`] naming the request.
+/// The whole exchange, retries included, is logged at debug level and counted towards the block's RPC stats.
+async fn with_retries<R, F, Fut>(policy: RetryPolicy, description: String, methods: Vec<String>, mut attempt: F) -> web3::Result<R>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = web3::Result<R>>,
+{
+    let started = Instant::now();
+    let mut retries = 0;
+    loop {
+        let result = match tokio::time::timeout(policy.request_timeout, attempt()).await {
+            Ok(result) => result,
+            Err(_) => {
+                let timeout = TimeoutError { call: description.clone(), after: policy.request_timeout };
+                Err(web3::Error::Io(io::Error::new(io::ErrorKind::TimedOut, timeout)))
+            }
+        };
+        match result {
+            Err(e) if retries < policy.max_retries && is_retryable(&e) => {
+                tracing::debug!(request = %truncate(&description, LOGGED_REQUEST_CHARS), retries, error = %e, "retrying rpc request");
+                telemetry::record_retry();
+                tokio::time::sleep(policy.delay(retries)).await;
+                retries += 1;
+            }
+            result => {
+                let elapsed = started.elapsed();
+                telemetry::record_rpc(&methods, elapsed);
+                if result.is_err() {
+                    telemetry::record_failures(&methods);
+                }
+                tracing::debug!(
+                    request = %truncate(&description, LOGGED_REQUEST_CHARS),
+                    retries,
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    ok = result.is_ok(),
+                    "rpc request"
+                );
+                return result;
+            }
+        }
+    }
+}
+
+/// A short rendering of a request for error messages, e.g. `eth_getBalance("0xab…", "0x710c1f")`
+fn describe(request: &Call) -> String {
+    match request {
+        Call::MethodCall(call) => {
+            let params = match &call.params {
+                Params::Array(params) => params.iter().map(Value::to_string).collect::<Vec<_>>().join(", "),
+                Params::Map(params) => Value::Object(params.clone()).to_string(),
+                Params::None => String::new(),
+            };
+            format!("{}({})", call.method, params)
+        }
+        Call::Notification(notification) => notification.method.clone(),
+        Call::Invalid { .. } => "invalid request".to_string(),
+    }
+}
+
+fn method(request: &Call) -> String {
+    match request {
+        Call::MethodCall(call) => call.method.clone(),
+        Call::Notification(notification) => notification.method.clone(),
+        Call::Invalid { .. } => "invalid request".to_string(),
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_core::Error as RpcError;
+    use serde_json::json;
+    use web3::Web3;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::MockTransport;
+
+    /// Fails the first `failures` requests with what `error` makes, then answers from a mock; a failure
+    /// of `None` never answers at all
+    #[derive(Debug, Clone)]
+    struct FlakyTransport {
+        inner: MockTransport,
+        failures: usize,
+        error: Option<fn() -> web3::Error>,
+        attempts: Arc<AtomicUsize>,
+    }
+
+    impl FlakyTransport {
+        fn new(failures: usize, error: Option<fn() -> web3::Error>) -> Self {
+            let inner = MockTransport::new().with_response("eth_blockNumber", json!([]), json!("0x2a"));
+            FlakyTransport { inner, failures, error, attempts: Arc::default() }
+        }
+
+        fn attempts(&self) -> usize {
+            self.attempts.load(Ordering::SeqCst)
+        }
+
+        /// How the next attempt fails, or `None` once they succeed
+        fn failure(&self) -> Option<BoxFuture<'static, web3::Error>> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) >= self.failures {
+                return None;
+            }
+            Some(match self.error {
+                Some(error) => Box::pin(futures::future::ready(error())),
+                None => Box::pin(futures::future::pending()),
+            })
+        }
+    }
+
+    impl Transport for FlakyTransport {
+        type Out = BoxFuture<'static, web3::Result<Value>>;
+
+        fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+            self.inner.prepare(method, params)
+        }
+
+        fn send(&self, id: RequestId, request: Call) -> Self::Out {
+            match self.failure() {
+                Some(failure) => Box::pin(async move { Err(failure.await) }),
+                None => Box::pin(self.inner.send(id, request)),
+            }
+        }
+    }
+
+    impl BatchTransport for FlakyTransport {
+        type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+        fn send_batch<I>(&self, requests: I) -> Self::Batch
+        where
+            I: IntoIterator<Item = (RequestId, Call)>,
+        {
+            match self.failure() {
+                Some(failure) => Box::pin(async move { Err(failure.await) }),
+                None => Box::pin(self.inner.send_batch(requests)),
+            }
+        }
+    }
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(100),
+            request_timeout: Duration::from_millis(50),
+        }
+    }
+
+    fn rate_limited() -> web3::Error {
+        web3::Error::Transport(TransportError::Code(429))
+    }
+
+    async fn block_number(transport: &FlakyTransport, max_retries: u32) -> web3::Result<u64> {
+        let web3 = Web3::new(RetryTransport::new(transport.clone(), policy(max_retries)));
+        web3.eth().block_number().await.map(|number| number.as_u64())
+    }
+
+    #[test]
+    fn tells_transient_errors_from_permanent_ones() {
+        let rpc = |code: i64, message: &str| web3::Error::Rpc(RpcError { code: ErrorCode::from(code), message: message.to_string(), data: None });
+        let message = |message: &str| web3::Error::Transport(TransportError::Message(message.to_string()));
+        let retryable = [
+            rate_limited(),
+            web3::Error::Transport(TransportError::Code(503)),
+            web3::Error::Transport(TransportError::Code(408)),
+            web3::Error::Unreachable,
+            message("failed to send request: error trying to connect: tcp connect error: Connection refused"),
+            message("failed to read response bytes: connection closed before message completed"),
+            rpc(-32603, "internal error"),
+            rpc(-32005, "limit exceeded"),
+            rpc(-32000, "Too Many Requests, try again later"),
+            rpc(-32000, "request timed out"),
+        ];
+        for error in &retryable {
+            assert!(is_retryable(error), "{}", error);
+        }
+        let permanent = [
+            web3::Error::Transport(TransportError::Code(404)),
+            web3::Error::Transport(TransportError::Code(401)),
+            message("failed to deserialize response: expected value at line 1 column 1"),
+            message("the mock has no response for eth_chainId([])"),
+            rpc(-32602, "invalid params"),
+            rpc(-32601, "the method trace_block does not exist/is not available"),
+            rpc(-32000, "execution reverted"),
+            web3::Error::Decoder("invalid hex".to_string()),
+        ];
+        for error in &permanent {
+            assert!(!is_retryable(error), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn succeeds_once_the_failures_stop() {
+        let transport = FlakyTransport::new(2, Some(rate_limited));
+        assert_eq!(block_number(&transport, 3).await.unwrap(), 42);
+        assert_eq!(transport.attempts(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_retry() {
+        let transport = FlakyTransport::new(10, Some(rate_limited));
+        let error = block_number(&transport, 2).await.unwrap_err();
+        assert!(matches!(error, web3::Error::Transport(TransportError::Code(429))), "{}", error);
+        assert_eq!(transport.attempts(), 3);
+    }
+
+    #[tokio::test]
+    async fn permanent_errors_are_not_retried() {
+        let invalid_params: fn() -> web3::Error = || web3::Error::Rpc(RpcError::invalid_params("bad block tag"));
+        let method_not_found: fn() -> web3::Error = || web3::Error::Rpc(RpcError::method_not_found());
+        let unparseable: fn() -> web3::Error = || web3::Error::Transport(TransportError::Message("failed to deserialize response".to_string()));
+        for error in [invalid_params, method_not_found, unparseable] {
+            let transport = FlakyTransport::new(1, Some(error));
+            assert!(block_number(&transport, 3).await.is_err());
+            assert_eq!(transport.attempts(), 1, "{}", error());
+        }
+    }
+
+    #[tokio::test]
+    async fn waits_longer_before_each_retry() {
+        // At least half of 20ms, 40ms and 80ms
+        let transport = FlakyTransport::new(3, Some(rate_limited));
+        let started = Instant::now();
+        block_number(&transport, 3).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(70), "{:?}", started.elapsed());
+
+        let policy = policy(10);
+        for attempt in 0..6 {
+            let backoff = (policy.base_delay * 2u32.pow(attempt)).min(policy.max_delay);
+            for _ in 0..20 {
+                let delay = policy.delay(attempt);
+                assert!(backoff / 2 <= delay && delay <= backoff, "attempt {}: {:?}", attempt, delay);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_stalled_attempt_times_out_and_is_retried() {
+        let transport = FlakyTransport::new(1, None);
+        assert_eq!(block_number(&transport, 1).await.unwrap(), 42);
+        assert_eq!(transport.attempts(), 2);
+
+        let transport = FlakyTransport::new(10, None);
+        let error = block_number(&transport, 1).await.unwrap_err();
+        assert_eq!(transport.attempts(), 2);
+        let timeout = TimeoutError::find(&error).unwrap();
+        assert_eq!((timeout.call.as_str(), timeout.after), ("eth_blockNumber()", Duration::from_millis(50)));
+        assert!(error.to_string().contains("eth_blockNumber() timed out after 50ms"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn a_failed_batch_is_resent_whole() {
+        let transport = FlakyTransport::new(1, Some(rate_limited));
+        let retrying = RetryTransport::new(transport.clone(), policy(3));
+        let requests: Vec<_> = (0..3).map(|_| retrying.prepare("eth_blockNumber", vec![])).collect();
+        let responses = retrying.send_batch(requests).await.unwrap();
+        assert!(responses.iter().all(|response| response.as_ref().ok() == Some(&json!("0x2a"))));
+        assert_eq!((transport.attempts(), transport.inner.round_trips(), transport.inner.calls("eth_blockNumber")), (2, 1, 3));
+    }
+}
@@ -0,0 +1,71 @@
+use futures::stream::{self, StreamExt};
+use web3::ethabi::{self, ParamType, Token};
+use web3::types::{BlockId, BlockNumber, Bytes, CallRequest, U64};
+use web3::{Web3, Transport};
+
+use crate::{BlockInfo, TransactionStatus};
+
+/// Selector of Solidity's `Error(string)`
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of Solidity's `Panic(uint256)`
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Re-run each reverted transaction with `eth_call` against the parent block's state to recover
+/// a revert reason. State from earlier transactions in the same block isn't replayed, so a
+/// transaction that only reverts because of them will get no reason.
+pub(crate) async fn fill_revert_reasons<T: Transport>(web3: &Web3<T>, block_info: &mut BlockInfo, concurrency: usize) {
+    let parent = BlockId::Number(BlockNumber::Number(U64::from(block_info.block_number.saturating_sub(1))));
+
+    let reasons: Vec<(usize, Option<String>)> = stream::iter(block_info.transactions.iter().enumerate())
+        .filter(|(_, tx)| futures::future::ready(tx.status == TransactionStatus::Reverted))
+        .map(|(index, tx)| {
+            let request = CallRequest {
+                from: tx.from,
+                to: tx.to,
+                gas: Some(tx.gas),
+                value: Some(tx.value.0),
+                data: Some(tx.input.clone()),
+                ..CallRequest::default()
+            };
+            async move {
+                let reason = match web3.eth().call(request, Some(parent)).await {
+                    Ok(_) => None,
+                    Err(web3::Error::Rpc(e)) => {
+                        let data: Option<Bytes> = e.data.and_then(|data| serde_json::from_value(data).ok());
+                        Some(decode_revert(data.as_ref(), &e.message))
+                    }
+                    Err(_) => None,
+                };
+                (index, reason)
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    for (index, reason) in reasons {
+        block_info.transactions[index].revert_reason = reason;
+    }
+}
+
+/// Turn the revert payload of a failed call into something readable, falling back to the node's message
+fn decode_revert(data: Option<&Bytes>, message: &str) -> String {
+    if let Some(Bytes(bytes)) = data.filter(|data| data.0.len() >= 4) {
+        let (selector, payload) = bytes.split_at(4);
+        if selector == ERROR_SELECTOR {
+            if let Ok(tokens) = ethabi::decode(&[ParamType::String], payload) {
+                if let Some(Token::String(reason)) = tokens.into_iter().next() {
+                    return reason;
+                }
+            }
+        } else if selector == PANIC_SELECTOR {
+            if let Ok(tokens) = ethabi::decode(&[ParamType::Uint(256)], payload) {
+                if let Some(Token::Uint(code)) = tokens.into_iter().next() {
+                    return format!("panic code {:#x}", code);
+                }
+            }
+        }
+    }
+
+    message.to_string()
+}
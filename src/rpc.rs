@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use serde_json::json;
+use jsonrpc_core::Value;
+use web3::types::{Block, Transaction, TransactionReceipt, H160, H256, U256, U64};
+use web3::{Web3, Transport};
+use std::error::Error;
+
+use crate::trace::is_method_unsupported;
+use crate::BlockRef;
+
+/// Block fields newer than web3's `Block` type knows about, read from the same raw response
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BlockExtras {
+    /// Absent before Shanghai and on chains without beacon withdrawals
+    #[serde(default)]
+    pub withdrawals: Vec<RpcWithdrawal>,
+    /// Absent before Dencun
+    pub blob_gas_used: Option<U64>,
+    pub excess_blob_gas: Option<U64>,
+    /// Header roots and hashes only `--verify` uses, each absent before the fork that added it
+    pub withdrawals_root: Option<H256>,
+    pub parent_beacon_block_root: Option<H256>,
+    pub requests_hash: Option<H256>,
+    /// Only on Arbitrum chains
+    pub l1_block_number: Option<U64>,
+    #[serde(default)]
+    pub transactions: Vec<TransactionExtras>,
+}
+
+/// Transaction fields newer than web3's `Transaction` type knows about: the EIP-4844 blob fields, absent
+/// on every other transaction type, and what `--verify` needs to re-encode typed transactions
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TransactionExtras {
+    pub hash: H256,
+    pub max_fee_per_blob_gas: Option<U256>,
+    pub blob_versioned_hashes: Option<Vec<H256>>,
+    /// Absent on pre-EIP-155 legacy transactions
+    pub chain_id: Option<U256>,
+    /// Typed transactions' signature parity; some nodes only give it as `v`
+    pub y_parity: Option<U64>,
+    /// Only on EIP-7702 transactions
+    pub authorization_list: Option<Vec<RpcAuthorization>>,
+    /// Only on OP Stack deposits: ether credited to the sender from L1
+    pub mint: Option<U256>,
+    /// Only on OP Stack deposits: what on L1 the deposit came from, and whether it's exempt from the
+    /// L2 gas limit
+    pub source_hash: Option<H256>,
+    pub is_system_tx: Option<bool>,
+}
+
+/// An EIP-7702 authorization, signed by the account delegating its code
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RpcAuthorization {
+    pub chain_id: U256,
+    pub address: H160,
+    pub nonce: U256,
+    pub y_parity: U64,
+    pub r: U256,
+    pub s: U256,
+}
+
+/// Receipt fields web3's `TransactionReceipt` doesn't know: the blob gas of blob transactions, and the
+/// L1 data fee OP Stack chains charge their non-deposit transactions
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiptExtras {
+    pub blob_gas_used: Option<U256>,
+    pub blob_gas_price: Option<U256>,
+    pub l1_fee: Option<U256>,
+    pub l1_gas_used: Option<U256>,
+    pub l1_gas_price: Option<U256>,
+    /// Only on Arbitrum chains
+    pub gas_used_for_l1: Option<U256>,
+    /// Only on OP Stack deposits, since Regolith and Canyon
+    pub deposit_nonce: Option<U64>,
+    pub deposit_receipt_version: Option<U64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RpcWithdrawal {
+    pub index: U64,
+    pub validator_index: U64,
+    pub address: H160,
+    /// Denominated in Gwei
+    pub amount: U64,
+}
+
+/// Fetch a block with full transaction objects, keeping the fields web3 would drop.
+pub(crate) async fn fetch_block<T: Transport>(
+    web3: &Web3<T>,
+    block: BlockRef,
+) -> Result<Option<(Block<Transaction>, BlockExtras)>, Box<dyn Error>> {
+    let method = match block {
+        BlockRef::Hash(_) => "eth_getBlockByHash",
+        _ => "eth_getBlockByNumber",
+    };
+
+    let raw = web3.transport().execute(method, vec![json!(block), json!(true)]).await?;
+    if raw.is_null() {
+        return Ok(None);
+    }
+
+    let extras = serde_json::from_value(raw.clone())?;
+    let block = serde_json::from_value(raw)?;
+    Ok(Some((block, extras)))
+}
+
+/// Every receipt in the block in one `eth_getBlockReceipts` call.
+/// Returns `None` when the node doesn't support the method.
+pub(crate) async fn fetch_block_receipts<T: Transport>(
+    web3: &Web3<T>,
+    block_number: u64,
+) -> Result<Option<Vec<(TransactionReceipt, ReceiptExtras)>>, Box<dyn Error>> {
+    match web3.transport().execute("eth_getBlockReceipts", vec![json!(BlockRef::Number(block_number))]).await {
+        Ok(receipts) if receipts.is_null() => Ok(None),
+        Ok(receipts) => {
+            let receipts: Vec<Value> = serde_json::from_value(receipts)?;
+            receipts.into_iter().map(with_extras).collect::<Result<_, _>>().map(Some)
+        }
+        Err(e) if is_method_unsupported(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// One transaction's receipt, or `None` when the node has none
+pub(crate) async fn fetch_receipt<T: Transport>(
+    web3: &Web3<T>,
+    hash: H256,
+) -> Result<Option<(TransactionReceipt, ReceiptExtras)>, Box<dyn Error>> {
+    let raw = web3.transport().execute("eth_getTransactionReceipt", vec![json!(hash)]).await?;
+    if raw.is_null() {
+        return Ok(None);
+    }
+    with_extras(raw).map(Some)
+}
+
+fn with_extras(raw: Value) -> Result<(TransactionReceipt, ReceiptExtras), Box<dyn Error>> {
+    let extras = serde_json::from_value(raw.clone())?;
+    Ok((serde_json::from_value(raw)?, extras))
+}
@@ -0,0 +1,143 @@
+use web3::signing::keccak256;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use crate::BlockAnalysis;
+
+/// Signatures of widely used token, NFT, router and proxy functions, always available
+const BUNDLED_SIGNATURES: &[&str] = &[
+    "transfer(address,uint256)",
+    "transferFrom(address,address,uint256)",
+    "approve(address,uint256)",
+    "increaseAllowance(address,uint256)",
+    "decreaseAllowance(address,uint256)",
+    "permit(address,address,uint256,uint256,uint8,bytes32,bytes32)",
+    "deposit()",
+    "withdraw(uint256)",
+    "mint(address,uint256)",
+    "burn(uint256)",
+    "safeTransferFrom(address,address,uint256)",
+    "safeTransferFrom(address,address,uint256,bytes)",
+    "safeTransferFrom(address,address,uint256,uint256,bytes)",
+    "safeBatchTransferFrom(address,address,uint256[],uint256[],bytes)",
+    "setApprovalForAll(address,bool)",
+    "multicall(bytes[])",
+    "multicall(uint256,bytes[])",
+    "aggregate((address,bytes)[])",
+    "execute(bytes,bytes[],uint256)",
+    "execute(bytes,bytes[])",
+    "swapExactTokensForTokens(uint256,uint256,address[],address,uint256)",
+    "swapTokensForExactTokens(uint256,uint256,address[],address,uint256)",
+    "swapExactETHForTokens(uint256,address[],address,uint256)",
+    "swapExactTokensForETH(uint256,uint256,address[],address,uint256)",
+    "addLiquidity(address,address,uint256,uint256,uint256,uint256,address,uint256)",
+    "addLiquidityETH(address,uint256,uint256,uint256,address,uint256)",
+    "removeLiquidity(address,address,uint256,uint256,uint256,address,uint256)",
+    "exactInputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160))",
+    "exactInput((bytes,address,uint256,uint256,uint256))",
+    "upgradeTo(address)",
+    "upgradeToAndCall(address,bytes)",
+    "transferOwnership(address)",
+    "renounceOwnership()",
+    "execTransaction(address,uint256,bytes,uint8,uint256,uint256,uint256,address,address,bytes)",
+    "handleOps((address,uint256,bytes,bytes,bytes32,uint256,bytes32,bytes,bytes)[],address)",
+    "claim()",
+    "stake(uint256)",
+];
+
+/// Function signatures by 4-byte selector, used to name what each transaction called
+#[derive(Debug, Clone)]
+pub struct SelectorRegistry {
+    signatures: HashMap<[u8; 4], String>,
+}
+
+impl Default for SelectorRegistry {
+    /// Just the bundled signatures
+    fn default() -> Self {
+        let signatures = BUNDLED_SIGNATURES.iter().map(|signature| (selector(signature), signature.to_string())).collect();
+        SelectorRegistry { signatures }
+    }
+}
+
+impl SelectorRegistry {
+    /// The bundled signatures plus those in a `selector,signature` file, which take precedence
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+        let mut registry = Self::default();
+        registry.signatures.extend(Self::parse(&contents)?.signatures);
+        Ok(registry)
+    }
+
+    /// Blank lines, `#` comments and a `selector,signature` header are skipped; a selector listed twice is an error.
+    /// Unlike [`SelectorRegistry::load`], the result holds only the parsed signatures.
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let mut signatures = HashMap::new();
+        let mut seen_on: HashMap<[u8; 4], usize> = HashMap::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (hex, signature) = line
+                .split_once(',')
+                .ok_or_else(|| format!("selectors line {}: expected selector,signature", line_number))?;
+            let hex = hex.trim();
+            if line_number == 1 && hex.eq_ignore_ascii_case("selector") {
+                continue;
+            }
+
+            let selector = parse_selector(hex).ok_or_else(|| format!("selectors line {}: invalid selector '{}'", line_number, hex))?;
+            if let Some(first) = seen_on.insert(selector, line_number) {
+                return Err(format!("selectors: 0x{} is listed on both line {} and line {}", to_hex(&selector), first, line_number).into());
+            }
+            signatures.insert(selector, signature.trim().trim_matches('"').to_string());
+        }
+
+        Ok(SelectorRegistry { signatures })
+    }
+
+    pub fn get(&self, selector: &[u8; 4]) -> Option<&String> {
+        self.signatures.get(selector)
+    }
+
+    /// Fill in the method of every transaction in `analysis`: `transfer` for plain value transfers,
+    /// `create` for contract creations, otherwise the signature of the called selector if it is known
+    pub fn apply(&self, analysis: &mut BlockAnalysis) {
+        for tx in &mut analysis.block_info.transactions {
+            tx.method = if tx.to.is_none() || tx.created_contract.is_some() {
+                Some("create".to_string())
+            } else if tx.input.0.is_empty() {
+                Some("transfer".to_string())
+            } else {
+                tx.input.0.get(..4).and_then(|selector| self.get(selector.try_into().ok()?)).cloned()
+            };
+        }
+    }
+}
+
+/// First four bytes of the Keccak-256 hash of a canonical signature such as `transfer(address,uint256)`
+fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn parse_selector(hex: &str) -> Option<[u8; 4]> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if hex.len() != 8 || !hex.is_ascii() {
+        return None;
+    }
+    let mut selector = [0u8; 4];
+    for (index, byte) in selector.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(selector)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,61 @@
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::Serialize;
+use web3::types::{BlockNumber, H160, H256, U64};
+use web3::{Web3, Transport};
+use std::error::Error;
+
+use crate::{BlockInfo, StateChange, Wei};
+
+/// A contract that ran SELFDESTRUCT in the analyzed block. Since Cancun the code is only removed when
+/// the contract was created in the same transaction; otherwise only its balance moves.
+#[derive(Debug, Clone, Serialize)]
+pub struct DestroyedContract {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    /// Receiver of the contract's balance; unknown when found without traces
+    #[serde(serialize_with = "crate::address::serialize_option")]
+    pub beneficiary: Option<H160>,
+    /// Balance sent to the beneficiary; unknown when found without traces
+    pub balance: Option<Wei>,
+    /// Unknown when found without traces
+    pub transaction_hash: Option<H256>,
+}
+
+/// Contracts whose code was present at the parent block and is gone at the analyzed one, for nodes without
+/// traces. Needs the code sizes `classify_addresses` fills in; accounts created in this block can't qualify.
+pub(crate) async fn find_emptied_contracts<T: Transport>(
+    web3: &Web3<T>,
+    block_info: &BlockInfo,
+    state_changes: &[StateChange],
+    concurrency: usize,
+) -> Result<Vec<DestroyedContract>, Box<dyn Error>> {
+    let Some(parent) = block_info.block_number.checked_sub(1) else { return Ok(Vec::new()) };
+    let parent = Some(BlockNumber::Number(U64::from(parent)));
+
+    // A deleted account's nonce starts over at zero, so only accounts whose nonce dropped, or wasn't queried, qualify
+    let candidates: Vec<H160> = state_changes
+        .iter()
+        .filter(|change| change.code_size == Some(0))
+        .filter(|change| match (change.prev_nonce, change.new_nonce) {
+            (Some(prev), Some(new)) => new < prev,
+            _ => true,
+        })
+        .filter(|change| !block_info.transactions.iter().any(|tx| tx.created_contract == Some(change.address)))
+        .map(|change| change.address)
+        .collect();
+
+    let emptied: Vec<Option<H160>> = stream::iter(candidates)
+        .map(|address| async move {
+            let code = web3.eth().code(address, parent).await?;
+            Ok::<_, web3::Error>((!code.0.is_empty()).then_some(address))
+        })
+        .buffered(concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    Ok(emptied
+        .into_iter()
+        .flatten()
+        .map(|address| DestroyedContract { address, beneficiary: None, balance: None, transaction_hash: None })
+        .collect())
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use web3::types::{BlockId, BlockNumber, H160, H256, U256, U64};
+use web3::{BatchTransport, Web3};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+use crate::history::{fetch_accounts, net_change, AccountState};
+use crate::{serialize_opt_u256, sort_state_changes, with_deadline, AnalysisOptions, BlockNotFoundError, BlockRef, SortKey, StateChange, Wei};
+
+/// The balances and nonces of a set of addresses after one block, kept so two can be compared later
+/// without a node. The chain and block hash it was taken on are recorded so snapshots of different
+/// chains, or of different blocks at one height, aren't compared.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub chain_id: u64,
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub accounts: Vec<AccountSnapshot>,
+}
+
+/// One address in a [`Snapshot`]; a field left out with `--fields` is `null`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    pub balance: Option<Wei>,
+    #[serde(serialize_with = "serialize_opt_u256", deserialize_with = "deserialize_opt_u256")]
+    pub nonce: Option<U256>,
+}
+
+fn deserialize_opt_u256<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<U256>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| U256::from_dec_str(&s).map_err(|_| serde::de::Error::custom(format!("invalid nonce '{}'", s))))
+        .transpose()
+}
+
+impl Snapshot {
+    /// Query the state of `addresses` after `block`, batched. Only the fields in `options.fields` are queried.
+    pub async fn take<T: BatchTransport>(
+        web3: &Web3<T>,
+        block: u64,
+        addresses: &[H160],
+        options: &AnalysisOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        with_deadline(options.deadline, || format!("snapshot at block {}", block), async {
+            let chain_id = web3.eth().chain_id().await?.as_u64();
+            let block_hash = fetch_block_hash(web3, block).await?;
+            let states = fetch_accounts(web3, addresses, &[block], options).await?;
+            // The state is queried by number, so a reorg meanwhile would mix two blocks' state under one hash
+            if fetch_block_hash(web3, block).await? != block_hash {
+                return Err(format!("block {} was reorged while the snapshot was taken; try again", block).into());
+            }
+            let accounts = addresses
+                .iter()
+                .zip(states)
+                .map(|(address, states)| {
+                    let (balance, nonce) = states[0];
+                    AccountSnapshot { address: *address, balance, nonce }
+                })
+                .collect();
+            Ok(Snapshot { chain_id, block_number: block, block_hash, accounts })
+        })
+        .await
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?)
+    }
+}
+
+async fn fetch_block_hash<T: BatchTransport>(web3: &Web3<T>, block: u64) -> Result<H256, Box<dyn Error>> {
+    let header = web3.eth().block(BlockId::Number(BlockNumber::Number(U64::from(block)))).await?;
+    header
+        .and_then(|header| header.hash)
+        .ok_or_else(|| BlockNotFoundError { block: BlockRef::Number(block) }.into())
+}
+
+/// Net change of every address in either snapshot from `before` to `after`, as the `diff` command reports
+/// it. An address in only one of them has the other side's fields empty, as does a field only one recorded.
+pub fn compare_snapshots(before: &Snapshot, after: &Snapshot, sort: SortKey) -> Result<Vec<StateChange>, String> {
+    if before.chain_id != after.chain_id {
+        return Err(format!("the snapshots are of different chains: {} and {}", before.chain_id, after.chain_id));
+    }
+    if before.block_number > after.block_number {
+        return Err(format!(
+            "the first snapshot is of block {}, after the second's block {}; give them oldest first",
+            before.block_number, after.block_number
+        ));
+    }
+    if before.block_number == after.block_number && before.block_hash != after.block_hash {
+        return Err(format!(
+            "the snapshots are of different blocks at height {}: {:?} and {:?}",
+            before.block_number, before.block_hash, after.block_hash
+        ));
+    }
+
+    let states = |snapshot: &Snapshot| -> BTreeMap<H160, AccountState> {
+        snapshot.accounts.iter().map(|account| (account.address, (account.balance, account.nonce))).collect()
+    };
+    let (prev, new) = (states(before), states(after));
+    let mut changes: Vec<StateChange> = prev
+        .keys()
+        .chain(new.keys().filter(|address| !prev.contains_key(address)))
+        .map(|address| {
+            let state = |states: &BTreeMap<H160, AccountState>| states.get(address).copied().unwrap_or((None, None));
+            net_change(*address, state(&prev), state(&new))
+        })
+        .collect();
+    sort_state_changes(&mut changes, sort);
+    Ok(changes)
+}
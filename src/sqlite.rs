@@ -0,0 +1,161 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use std::error::Error;
+use std::path::Path;
+
+use crate::{BlockAnalysis, Reorg};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS blocks (
+    hash TEXT PRIMARY KEY,
+    number INTEGER NOT NULL,
+    parent_hash TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    miner TEXT NOT NULL,
+    gas_used INTEGER NOT NULL,
+    gas_limit INTEGER NOT NULL,
+    base_fee_per_gas TEXT,
+    burned_base_fee TEXT,
+    state_diff_source TEXT NOT NULL,
+    replaced INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS blocks_number ON blocks(number);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    block_hash TEXT NOT NULL REFERENCES blocks(hash),
+    hash TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    from_address TEXT,
+    to_address TEXT,
+    created_contract TEXT,
+    value TEXT NOT NULL,
+    status TEXT NOT NULL,
+    gas_used TEXT,
+    fee_paid TEXT,
+    PRIMARY KEY (block_hash, hash)
+);
+
+CREATE TABLE IF NOT EXISTS state_changes (
+    block_hash TEXT NOT NULL REFERENCES blocks(hash),
+    address TEXT NOT NULL,
+    prev_balance TEXT,
+    new_balance TEXT,
+    balance_change TEXT,
+    prev_nonce TEXT,
+    new_nonce TEXT,
+    nonce_change TEXT,
+    PRIMARY KEY (block_hash, address)
+);
+CREATE INDEX IF NOT EXISTS state_changes_address ON state_changes(address);
+";
+
+/// Writes analyses into a SQLite database, one row per block, transaction and state change.
+/// Hashes and addresses are stored as 0x-prefixed hex, and U256 amounts as decimal text.
+/// Blocks that were reorged out keep their rows but are flagged `replaced`.
+pub struct SqliteSink {
+    conn: Connection,
+}
+
+impl SqliteSink {
+    /// Open (or create) the database at `path`, creating the schema if it isn't there yet
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+
+        // Databases created before reorg tracking lack the flag
+        if conn.prepare("SELECT replaced FROM blocks LIMIT 0").is_err() {
+            conn.execute("ALTER TABLE blocks ADD COLUMN replaced INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+        Ok(SqliteSink { conn })
+    }
+
+    /// Highest canonical block number stored so far, if any
+    pub fn highest_block(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        let highest: Option<i64> = self
+            .conn
+            .query_row("SELECT MAX(number) FROM blocks WHERE replaced = 0", [], |row| row.get(0))
+            .optional()?
+            .flatten();
+        Ok(highest.map(|number| number as u64))
+    }
+
+    /// Store an analysis, replacing anything stored for the same block hash. Other blocks stored at
+    /// the same height have been reorged out, and are flagged as replaced.
+    pub fn write(&mut self, analysis: &BlockAnalysis) -> Result<(), Box<dyn Error>> {
+        let block = &analysis.block_info;
+        let hash = format!("{:?}", block.hash);
+        let tx = self.conn.transaction()?;
+
+        tx.execute("DELETE FROM state_changes WHERE block_hash = ?1", params![hash])?;
+        tx.execute("DELETE FROM transactions WHERE block_hash = ?1", params![hash])?;
+        tx.execute("DELETE FROM blocks WHERE hash = ?1", params![hash])?;
+        tx.execute("UPDATE blocks SET replaced = 1 WHERE number = ?1", params![block.block_number as i64])?;
+
+        tx.execute(
+            "INSERT INTO blocks (hash, number, parent_hash, timestamp, miner, gas_used, gas_limit, base_fee_per_gas, burned_base_fee, state_diff_source)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                hash,
+                block.block_number as i64,
+                format!("{:?}", block.parent_hash),
+                block.timestamp as i64,
+                block.miner,
+                block.gas_used as i64,
+                block.gas_limit as i64,
+                block.base_fee_per_gas.map(|fee| fee.to_string()),
+                block.burned_base_fee.map(|fee| fee.to_string()),
+                serde_json::to_value(analysis.state_diff_source)?.as_str(),
+            ],
+        )?;
+
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO transactions (block_hash, hash, position, from_address, to_address, created_contract, value, status, gas_used, fee_paid)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for (position, info) in block.transactions.iter().enumerate() {
+                insert.execute(params![
+                    hash,
+                    format!("{:?}", info.hash),
+                    position as i64,
+                    info.from.map(|from| format!("{:?}", from)),
+                    info.to.map(|to| format!("{:?}", to)),
+                    info.created_contract.map(|created| format!("{:?}", created)),
+                    info.value.to_string(),
+                    serde_json::to_value(info.status)?.as_str(),
+                    info.gas_used.map(|gas| gas.to_string()),
+                    info.fee_paid.map(|fee| fee.to_string()),
+                ])?;
+            }
+
+            let mut insert = tx.prepare(
+                "INSERT INTO state_changes (block_hash, address, prev_balance, new_balance, balance_change, prev_nonce, new_nonce, nonce_change)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for change in analysis.state_changes.iter().flatten() {
+                insert.execute(params![
+                    hash,
+                    format!("{:?}", change.address),
+                    change.prev_balance.map(|balance| balance.to_string()),
+                    change.new_balance.map(|balance| balance.to_string()),
+                    change.balance_change.map(|delta| delta.to_string()),
+                    change.prev_nonce.map(|nonce| nonce.to_string()),
+                    change.new_nonce.map(|nonce| nonce.to_string()),
+                    change.nonce_change.map(|delta| delta.to_string()),
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Flag the blocks a reorg invalidated, keeping their rows
+    pub fn mark_replaced(&mut self, reorg: &Reorg) -> Result<(), Box<dyn Error>> {
+        let tx = self.conn.transaction()?;
+        for block in &reorg.invalidated {
+            tx.execute("UPDATE blocks SET replaced = 1 WHERE hash = ?1", params![format!("{:?}", block.hash)])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::json;
+use web3::types::{Bytes, H160, H256, U256};
+use web3::{Web3, Transport};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+
+use crate::trace::is_method_unsupported;
+use crate::{BlockInfo, BlockRef, NonceDelta, StateChange, StorageChange, TransactionChanges, Wei};
+
+/// One entry of `trace_replayBlockTransactions` with the `stateDiff` trace type, or the result of
+/// `trace_replayTransaction`, which doesn't name the transaction
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplayedTransaction {
+    #[serde(default)]
+    transaction_hash: H256,
+    #[serde(default)]
+    state_diff: HashMap<H160, AccountDiff>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountDiff {
+    balance: Diff<U256>,
+    nonce: Diff<U256>,
+    code: Diff<IgnoredAny>,
+    #[serde(default)]
+    storage: HashMap<H256, Diff<H256>>,
+}
+
+/// A field in a parity-style state diff: `"="` when unchanged, otherwise created, removed or changed
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Diff<T> {
+    Delta(Delta<T>),
+    Same(IgnoredAny),
+}
+
+#[derive(Debug, Deserialize)]
+enum Delta<T> {
+    #[serde(rename = "+")]
+    Born(T),
+    #[serde(rename = "-")]
+    Died(T),
+    #[serde(rename = "*")]
+    Changed { from: T, to: T },
+}
+
+impl<T: Copy + Default> Diff<T> {
+    /// Values before and after the transaction, or `None` when the field didn't change
+    fn values(&self) -> Option<(T, T)> {
+        match self {
+            Diff::Same(_) => None,
+            Diff::Delta(Delta::Born(value)) => Some((T::default(), *value)),
+            Diff::Delta(Delta::Died(value)) => Some((*value, T::default())),
+            Diff::Delta(Delta::Changed { from, to }) => Some((*from, *to)),
+        }
+    }
+}
+
+impl<T> Diff<T> {
+    fn changed(&self) -> bool {
+        matches!(self, Diff::Delta(_))
+    }
+}
+
+/// Result of geth's prestate tracer in diff mode for one transaction
+#[derive(Debug, Default, Deserialize)]
+struct PrestateDiff {
+    #[serde(default)]
+    pre: HashMap<H160, PrestateAccount>,
+    #[serde(default)]
+    post: HashMap<H160, PrestateAccount>,
+}
+
+/// Fields are omitted when zero, and from "post" when unchanged
+#[derive(Debug, Default, Deserialize)]
+struct PrestateAccount {
+    balance: Option<U256>,
+    nonce: Option<u64>,
+    code: Option<Bytes>,
+    #[serde(default)]
+    storage: HashMap<H256, H256>,
+}
+
+/// State diffs of every transaction in the block from `trace_replayBlockTransactions`, in block order.
+/// Returns `None` when the node doesn't expose the trace_ namespace.
+pub(crate) async fn replay_block<T: Transport>(
+    web3: &Web3<T>,
+    block_number: u64,
+) -> Result<Option<Vec<TransactionChanges>>, Box<dyn Error>> {
+    let params = vec![json!(BlockRef::Number(block_number)), json!(["stateDiff"])];
+    match web3.transport().execute("trace_replayBlockTransactions", params).await {
+        Ok(replayed) => {
+            let replayed: Vec<ReplayedTransaction> = serde_json::from_value(replayed)?;
+            Ok(Some(replayed.into_iter().map(from_state_diff).collect()))
+        }
+        Err(e) if is_method_unsupported(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Storage slot changes per address across the whole block, folded from per-transaction diffs.
+/// Slots keep the value from before their first write and after their last write.
+pub(crate) fn merge_storage(per_tx: &[TransactionChanges]) -> HashMap<H160, Vec<StorageChange>> {
+    let mut slots: BTreeMap<(H160, H256), (H256, H256)> = BTreeMap::new();
+    for tx_changes in per_tx {
+        for change in &tx_changes.state_changes {
+            for storage in &change.storage_changes {
+                slots.entry((change.address, storage.slot)).or_insert((storage.before, storage.after)).1 = storage.after;
+            }
+        }
+    }
+
+    let mut changes: HashMap<H160, Vec<StorageChange>> = HashMap::new();
+    for ((address, slot), (before, after)) in slots {
+        if before != after {
+            changes.entry(address).or_default().push(StorageChange { slot, before, after });
+        }
+    }
+    changes
+}
+
+/// State changes caused by each transaction of the block, in block order, from the diff-mode
+/// prestate tracer. Returns `None` when the node doesn't expose `debug_traceTransaction`.
+pub(crate) async fn get_transaction_changes<T: Transport>(
+    web3: &Web3<T>,
+    block_info: &BlockInfo,
+    concurrency: usize,
+) -> Result<Option<Vec<TransactionChanges>>, Box<dyn Error>> {
+    let tracer = json!({ "tracer": "prestateTracer", "tracerConfig": { "diffMode": true } });
+
+    let traced = stream::iter(&block_info.transactions)
+        .map(|tx| {
+            let params = vec![json!(tx.hash), tracer.clone()];
+            async move { web3.transport().execute("debug_traceTransaction", params).await }
+        })
+        .buffered(concurrency.max(1))
+        .try_collect::<Vec<_>>()
+        .await;
+
+    let diffs = match traced {
+        Ok(diffs) => diffs,
+        Err(e) if is_method_unsupported(&e) => {
+            tracing::warn!(
+                error = %e,
+                "node supports neither trace_replayBlockTransactions nor debug_traceTransaction, skipping per-transaction changes"
+            );
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    block_info
+        .transactions
+        .iter()
+        .zip(diffs)
+        .map(|(tx, diff)| Ok(from_prestate_diff(tx.hash, serde_json::from_value(diff)?)))
+        .collect::<Result<_, Box<dyn Error>>>()
+        .map(Some)
+}
+
+/// State changes caused by one transaction, from `trace_replayTransaction` or else the diff-mode
+/// prestate tracer. Returns `None` when the node exposes neither.
+pub(crate) async fn trace_transaction<T: Transport>(web3: &Web3<T>, hash: H256) -> Result<Option<TransactionChanges>, Box<dyn Error>> {
+    let params = vec![json!(hash), json!(["stateDiff"])];
+    match web3.transport().execute("trace_replayTransaction", params).await {
+        Ok(replayed) => {
+            let replayed = ReplayedTransaction { transaction_hash: hash, ..serde_json::from_value(replayed)? };
+            return Ok(Some(from_state_diff(replayed)));
+        }
+        Err(e) if is_method_unsupported(&e) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let tracer = json!({ "tracer": "prestateTracer", "tracerConfig": { "diffMode": true } });
+    match web3.transport().execute("debug_traceTransaction", vec![json!(hash), tracer]).await {
+        Ok(diff) => Ok(Some(from_prestate_diff(hash, serde_json::from_value(diff)?))),
+        Err(e) if is_method_unsupported(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn from_state_diff(replayed: ReplayedTransaction) -> TransactionChanges {
+    let mut state_changes: Vec<StateChange> = replayed
+        .state_diff
+        .into_iter()
+        .map(|(address, diff)| {
+            let balance = diff.balance.values().map(|(prev, new)| (Wei(prev), Wei(new)));
+            let nonce = diff.nonce.values();
+            let mut storage_changes: Vec<StorageChange> = diff
+                .storage
+                .into_iter()
+                .filter_map(|(slot, diff)| diff.values().map(|(before, after)| StorageChange { slot, before, after }))
+                .collect();
+            storage_changes.sort_by_key(|change| change.slot);
+
+            StateChange {
+                address,
+                label: None,
+                prev_balance: balance.map(|(prev, _)| prev),
+                new_balance: balance.map(|(_, new)| new),
+                prev_nonce: nonce.map(|(prev, _)| prev),
+                new_nonce: nonce.map(|(_, new)| new),
+                balance_change: balance.map(|(prev, new)| Wei::checked_delta(prev, new)),
+                nonce_change: nonce.map(|(prev, new)| NonceDelta::between(prev, new)),
+                value_in: Wei::zero(),
+                value_out: Wei::zero(),
+                fees_paid: Wei::zero(),
+                priority_fees_received: Wei::zero(),
+                vault_fees_received: Wei::zero(),
+                code_changed: diff.code.changed(),
+                is_contract: None,
+                code_size: None,
+                storage_changes,
+                proof_verified: false,
+                first_activity: false,
+            }
+        })
+        .collect();
+    state_changes.sort_by_key(|change| change.address);
+
+    TransactionChanges { transaction_hash: replayed.transaction_hash, state_changes }
+}
+
+fn from_prestate_diff(transaction_hash: H256, diff: PrestateDiff) -> TransactionChanges {
+    let addresses: BTreeSet<H160> = diff.pre.keys().chain(diff.post.keys()).copied().collect();
+    let empty = PrestateAccount::default();
+
+    let state_changes = addresses
+        .into_iter()
+        .filter_map(|address| {
+            let pre = diff.pre.get(&address).unwrap_or(&empty);
+            let post = diff.post.get(&address);
+
+            let prev_balance = Wei(pre.balance.unwrap_or_default());
+            let prev_nonce = U256::from(pre.nonce.unwrap_or_default());
+            // An account listed only in "pre" was deleted by the transaction
+            let (new_balance, new_nonce) = match post {
+                Some(post) => (
+                    post.balance.map_or(prev_balance, Wei),
+                    post.nonce.map_or(prev_nonce, U256::from),
+                ),
+                None => (Wei::zero(), U256::zero()),
+            };
+
+            // Zeroed slots are omitted from "post", so a missing side reads as zero
+            let mut slots: BTreeMap<H256, (H256, H256)> = BTreeMap::new();
+            for (slot, value) in &pre.storage {
+                slots.entry(*slot).or_default().0 = *value;
+            }
+            for (slot, value) in post.map(|post| &post.storage).into_iter().flatten() {
+                slots.entry(*slot).or_default().1 = *value;
+            }
+            let storage_changes: Vec<StorageChange> = slots
+                .into_iter()
+                .filter(|(_, (before, after))| before != after)
+                .map(|(slot, (before, after))| StorageChange { slot, before, after })
+                .collect();
+
+            let code_changed = match post {
+                Some(post) => post.code.is_some() && post.code != pre.code,
+                None => pre.code.is_some(),
+            };
+
+            let changed = prev_balance != new_balance || prev_nonce != new_nonce || code_changed || !storage_changes.is_empty();
+            changed.then(|| StateChange {
+                address,
+                label: None,
+                prev_balance: Some(prev_balance),
+                new_balance: Some(new_balance),
+                prev_nonce: Some(prev_nonce),
+                new_nonce: Some(new_nonce),
+                balance_change: Some(Wei::checked_delta(prev_balance, new_balance)),
+                nonce_change: Some(NonceDelta::between(prev_nonce, new_nonce)),
+                value_in: Wei::zero(),
+                value_out: Wei::zero(),
+                fees_paid: Wei::zero(),
+                priority_fees_received: Wei::zero(),
+                vault_fees_received: Wei::zero(),
+                code_changed,
+                is_contract: None,
+                code_size: None,
+                storage_changes,
+                proof_verified: false,
+                first_activity: false,
+            })
+        })
+        .collect();
+
+    TransactionChanges { transaction_hash, state_changes }
+}
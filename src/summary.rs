@@ -0,0 +1,229 @@
+use web3::types::{H256, U256};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::fees::{base_fee_paid, burned_fee, DEPOSIT_TX_TYPE};
+use crate::{
+    BlockInfo, BlockSummary, GasPriceBucket, GasPriceStats, StateChange, TransactionChanges, TransactionStatus, Wei, WeiDelta,
+};
+
+/// Aggregate figures over data the analysis has already fetched; never touches the node.
+///
+/// `mev_candidates` come from [`mev_candidates`], since the per-transaction diffs they need aren't always kept.
+pub(crate) fn summarize(block_info: &BlockInfo, state_changes: &[StateChange], mev_candidates: Vec<H256>) -> BlockSummary {
+    let transactions = &block_info.transactions;
+    let senders: HashSet<_> = transactions.iter().filter_map(|tx| tx.from).collect();
+    let recipients: HashSet<_> = transactions.iter().filter_map(|tx| tx.to.or(tx.created_contract)).collect();
+
+    // Reverted transactions move no value, same as in the balance decomposition
+    let value_transferred: Wei = transactions
+        .iter()
+        .filter(|tx| tx.status != TransactionStatus::Reverted)
+        .map(|tx| tx.value)
+        .sum();
+    let fees_paid: Wei = transactions.iter().filter_map(|tx| tx.fee_paid).sum();
+    let total_base_fee_burned: Wei = transactions.iter().map(|tx| burned_fee(block_info, tx)).sum();
+    let total_blob_fees: Wei = transactions.iter().filter_map(|tx| tx.blob_fee_paid).sum();
+    let total_priority_fees: Wei = transactions
+        .iter()
+        .filter_map(|tx| tx.fee_paid.map(|fee| fee.saturating_sub(base_fee_paid(block_info, tx))))
+        .sum();
+    let total_l1_fees: Wei = transactions.iter().filter_map(|tx| tx.l1_fee).sum();
+
+    let mut transactions_by_type: BTreeMap<u64, usize> = BTreeMap::new();
+    for tx in transactions {
+        *transactions_by_type.entry(tx.tx_type.unwrap_or_default()).or_default() += 1;
+    }
+
+    let gas_used_percent = if block_info.gas_limit == 0 {
+        0.0
+    } else {
+        block_info.gas_used as f64 * 100.0 / block_info.gas_limit as f64
+    };
+
+    // Proof-of-work blocks pay a block reward to the coinbase too, which would look like a payment
+    let unexplained_coinbase_change = unexplained_coinbase_change(block_info, state_changes);
+    let proof_of_stake = block_info.is_post_merge();
+    let mev_payment = (proof_of_stake && !unexplained_coinbase_change.decreased && !unexplained_coinbase_change.magnitude.is_zero())
+        .then_some(unexplained_coinbase_change.magnitude);
+
+    BlockSummary {
+        transaction_count: transactions.len(),
+        unique_senders: senders.len(),
+        unique_recipients: recipients.len(),
+        value_transferred,
+        gas_used: block_info.gas_used,
+        gas_used_percent,
+        fees_paid,
+        total_base_fee_burned,
+        total_priority_fees,
+        total_blob_fees,
+        total_l1_fees,
+        unexplained_coinbase_change,
+        mev_payment,
+        mev_candidates,
+        transactions_by_type,
+        contract_creations: transactions.iter().filter(|tx| tx.created_contract.is_some()).count(),
+        reverted_transactions: transactions.iter().filter(|tx| tx.status == TransactionStatus::Reverted).count(),
+        changed_addresses: state_changes.len(),
+        filtered_state_changes: 0,
+        hidden_system_addresses: 0,
+        gas_price_stats: gas_price_stats(block_info),
+    }
+}
+
+/// Ranges in the gas price histogram
+const HISTOGRAM_BUCKETS: usize = 8;
+
+fn gas_price_stats(block_info: &BlockInfo) -> GasPriceStats {
+    // Deposits pay for their gas on L1
+    let mut prices: Vec<Wei> = block_info
+        .transactions
+        .iter()
+        .filter(|tx| tx.tx_type != Some(DEPOSIT_TX_TYPE))
+        .filter_map(|tx| tx.effective_gas_price.or(tx.gas_price))
+        .collect();
+    let (Some(&min), Some(&max)) = (prices.iter().min(), prices.iter().max()) else { return GasPriceStats::default() };
+    prices.sort();
+
+    // Nearest rank: the smallest price at least `percent` of the transactions paid no more than
+    let percentile = |percent: usize| prices[(prices.len() * percent).div_ceil(100).saturating_sub(1)];
+    let base_fee_only = match block_info.base_fee_per_gas {
+        Some(base_fee) => prices.iter().filter(|price| **price <= base_fee).count(),
+        None => 0,
+    };
+
+    // Ranges of whole wei, rounded up so they cover `min` to `max`; the top one is cut off at `max`,
+    // and ranges wholly above it are left out
+    let prices_spanned = (max.0 - min.0).saturating_add(U256::one());
+    let width = (prices_spanned + U256::from(HISTOGRAM_BUCKETS - 1)) / U256::from(HISTOGRAM_BUCKETS);
+    let mut histogram: Vec<GasPriceBucket> = (0..HISTOGRAM_BUCKETS)
+        .map(|i| min.0 + width * U256::from(i))
+        .take_while(|low| *low <= max.0)
+        .map(|low| GasPriceBucket { low: Wei(low), high: Wei((low + width - 1).min(max.0)), transactions: 0 })
+        .collect();
+    for price in &prices {
+        if let Some(bucket) = histogram.iter_mut().find(|bucket| *price <= bucket.high) {
+            bucket.transactions += 1;
+        }
+    }
+
+    GasPriceStats {
+        transactions: prices.len(),
+        min: Some(min),
+        p25: Some(percentile(25)),
+        median: Some(percentile(50)),
+        p75: Some(percentile(75)),
+        max: Some(max),
+        base_fee_only,
+        histogram,
+    }
+}
+
+/// How far the coinbase's balance change is from what its priority fees, the block's transactions and
+/// any withdrawals to it account for. An address missing from `state_changes` didn't change.
+fn unexplained_coinbase_change(block_info: &BlockInfo, state_changes: &[StateChange]) -> WeiDelta {
+    let coinbase = block_info.miner_address;
+    let change = state_changes.iter().find(|change| change.address == coinbase);
+    let observed = change.and_then(|change| change.balance_change).unwrap_or(WeiDelta { decreased: false, magnitude: Wei::zero() });
+    let withdrawn: Wei = block_info.withdrawals.iter().filter(|withdrawal| withdrawal.address == coinbase).map(|withdrawal| withdrawal.amount).sum();
+
+    let (gains, losses) = match change {
+        Some(change) => (
+            change.priority_fees_received.saturating_add(change.vault_fees_received).saturating_add(change.value_in).saturating_add(withdrawn),
+            change.value_out.saturating_add(change.fees_paid),
+        ),
+        None => (withdrawn, Wei::zero()),
+    };
+    let (observed_gain, observed_loss) =
+        if observed.decreased { (Wei::zero(), observed.magnitude) } else { (observed.magnitude, Wei::zero()) };
+    // observed - (gains - losses), kept unsigned on both sides
+    Wei::checked_delta(observed_loss.saturating_add(gains), observed_gain.saturating_add(losses))
+}
+
+/// Transactions whose own state diff credits the coinbase with more than their priority fee and any
+/// value they sent it, i.e. that paid it from inside a call
+pub(crate) fn mev_candidates(block_info: &BlockInfo, per_tx: &[TransactionChanges]) -> Vec<H256> {
+    let coinbase = block_info.miner_address;
+    per_tx
+        .iter()
+        .filter_map(|tx_changes| {
+            let tx = block_info.transactions.iter().find(|tx| tx.hash == tx_changes.transaction_hash)?;
+            let delta = tx_changes.state_changes.iter().find(|change| change.address == coinbase)?.balance_change?;
+            let priority_fee = tx.fee_paid.map_or(Wei::zero(), |fee| fee.saturating_sub(base_fee_paid(block_info, tx)));
+            let sent = if tx.to == Some(coinbase) && tx.status != TransactionStatus::Reverted { tx.value } else { Wei::zero() };
+            (!delta.decreased && delta.magnitude > priority_fee.saturating_add(sent)).then_some(tx.hash)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::Web3;
+
+    use crate::history::net_change;
+    use crate::{get_block_info, AnalysisOptions, BlockRef, MockTransport};
+
+    /// Block 3 of the fixture: a 0.5 ether transfer, a reverted token transfer and a creation sending
+    /// 0.1 ether, 131,000 gas in all at a base fee of 1 gwei and a priority fee of 2
+    async fn block_info() -> BlockInfo {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        get_block_info(&web3, BlockRef::Number(3), &AnalysisOptions::new()).await.unwrap().0
+    }
+
+    fn gwei(amount: u64) -> Wei {
+        Wei::from(U256::from(amount) * U256::exp10(9))
+    }
+
+    /// The coinbase going from `prev` to `new` gwei, having earned the block's priority fees
+    fn coinbase_change(block_info: &BlockInfo, prev: u64, new: u64) -> StateChange {
+        let state = |balance: u64| (Some(gwei(balance)), Some(U256::zero()));
+        let mut change = net_change(block_info.miner_address, state(prev), state(new));
+        change.priority_fees_received = gwei(131_000 * 2);
+        change
+    }
+
+    #[tokio::test]
+    async fn counts_over_the_block() {
+        let block_info = block_info().await;
+        let candidates = vec![H256::from_low_u64_be(0x408)];
+        let summary = summarize(&block_info, &[], candidates.clone());
+
+        assert_eq!((summary.transaction_count, summary.unique_senders, summary.unique_recipients), (3, 2, 3));
+        assert_eq!((summary.contract_creations, summary.reverted_transactions, summary.changed_addresses), (1, 1, 0));
+        // The reverted transaction's value never moved
+        assert_eq!(summary.value_transferred, gwei(600_000_000));
+        assert_eq!(summary.gas_used, 131_000);
+        assert!((summary.gas_used_percent - 131_000.0 * 100.0 / 30_000_000.0).abs() < 1e-9);
+        assert_eq!(summary.fees_paid, gwei(131_000 * 3));
+        assert_eq!(summary.total_base_fee_burned, gwei(131_000));
+        assert_eq!(summary.total_priority_fees, gwei(131_000 * 2));
+        assert_eq!(summary.transactions_by_type, BTreeMap::from([(0, 1), (2, 2)]));
+        assert_eq!(summary.mev_candidates, candidates);
+        // A coinbase missing from the state changes didn't change
+        assert!(summary.unexplained_coinbase_change.magnitude.is_zero());
+        assert_eq!(summary.gas_price_stats.median, Some(gwei(3)));
+    }
+
+    #[tokio::test]
+    async fn a_coinbase_earning_only_its_fees_got_no_mev_payment() {
+        let block_info = block_info().await;
+        let summary = summarize(&block_info, &[coinbase_change(&block_info, 1_000, 1_000 + 131_000 * 2)], Vec::new());
+        assert_eq!(summary.changed_addresses, 1);
+        assert_eq!(summary.unexplained_coinbase_change, WeiDelta { decreased: false, magnitude: Wei::zero() });
+        assert_eq!(summary.mev_payment, None);
+    }
+
+    #[tokio::test]
+    async fn a_coinbase_gain_beyond_its_fees_is_an_mev_payment() {
+        let block_info = block_info().await;
+        let summary = summarize(&block_info, &[coinbase_change(&block_info, 1_000, 1_000 + 131_000 * 2 + 5)], Vec::new());
+        assert_eq!(summary.unexplained_coinbase_change, WeiDelta { decreased: false, magnitude: gwei(5) });
+        assert_eq!(summary.mev_payment, Some(gwei(5)));
+
+        // Falling short of the fees isn't a payment
+        let summary = summarize(&block_info, &[coinbase_change(&block_info, 1_000, 1_000 + 131_000)], Vec::new());
+        assert_eq!(summary.unexplained_coinbase_change, WeiDelta { decreased: true, magnitude: gwei(131_000) });
+        assert_eq!(summary.mev_payment, None);
+    }
+}
@@ -0,0 +1,429 @@
+use web3::types::H160;
+use std::fmt::Write;
+
+use crate::markdown::method;
+use crate::{
+    format_address, AmountFormat, BlockAnalysis, StateChange, Timezone, TransactionAnalysis, TransactionFilter, TransactionInfo,
+    TransactionStatus,
+};
+
+/// Columns never shrink below this when fitting a table to the terminal
+const MIN_COLUMN_WIDTH: usize = 8;
+
+/// A column of the transaction or the state change table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Index,
+    Hash,
+    From,
+    To,
+    Method,
+    Value,
+    Fee,
+    GasUsed,
+    Status,
+    Type,
+    Address,
+    Balance,
+    BalanceChange,
+    Nonce,
+    NonceChange,
+    Storage,
+}
+
+const DEFAULT_TRANSACTION_COLUMNS: [Column; 9] = [
+    Column::Index,
+    Column::Hash,
+    Column::From,
+    Column::To,
+    Column::Method,
+    Column::Value,
+    Column::GasUsed,
+    Column::Fee,
+    Column::Status,
+];
+
+const DEFAULT_STATE_COLUMNS: [Column; 5] =
+    [Column::Address, Column::Balance, Column::BalanceChange, Column::NonceChange, Column::Storage];
+
+impl Column {
+    fn title(self) -> &'static str {
+        match self {
+            Column::Index => "#",
+            Column::Hash => "Hash",
+            Column::From => "From",
+            Column::To => "To",
+            Column::Method => "Method",
+            Column::Value => "Value",
+            Column::Fee => "Fee",
+            Column::GasUsed => "Gas Used",
+            Column::Status => "Status",
+            Column::Type => "Type",
+            Column::Address => "Address",
+            Column::Balance => "Balance",
+            Column::BalanceChange => "Balance Change",
+            Column::Nonce => "Nonce",
+            Column::NonceChange => "Nonce Change",
+            Column::Storage => "Storage Slots",
+        }
+    }
+
+    fn is_transaction(self) -> bool {
+        DEFAULT_TRANSACTION_COLUMNS.contains(&self) || self == Column::Type
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Column::Index
+                | Column::Value
+                | Column::Fee
+                | Column::GasUsed
+                | Column::Type
+                | Column::Balance
+                | Column::BalanceChange
+                | Column::Nonce
+                | Column::NonceChange
+                | Column::Storage
+        )
+    }
+}
+
+impl std::str::FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "index" => Ok(Column::Index),
+            "hash" => Ok(Column::Hash),
+            "from" => Ok(Column::From),
+            "to" => Ok(Column::To),
+            "method" => Ok(Column::Method),
+            "value" => Ok(Column::Value),
+            "fee" => Ok(Column::Fee),
+            "gas_used" => Ok(Column::GasUsed),
+            "status" => Ok(Column::Status),
+            "type" => Ok(Column::Type),
+            "address" => Ok(Column::Address),
+            "balance" => Ok(Column::Balance),
+            "balance_change" => Ok(Column::BalanceChange),
+            "nonce" => Ok(Column::Nonce),
+            "nonce_change" => Ok(Column::NonceChange),
+            "storage" => Ok(Column::Storage),
+            other => Err(format!(
+                "invalid column '{}': expected index, hash, from, to, method, value, fee, gas_used, status, type, \
+                 address, balance, balance_change, nonce, nonce_change or storage",
+                other
+            )),
+        }
+    }
+}
+
+/// Renders an analysis as aligned tables for reading in a terminal, or as tab-separated rows for other programs
+#[derive(Debug, Clone, Default)]
+pub struct TableReport {
+    pub amounts: AmountFormat,
+    /// Columns to show, in order; a table none of them belong to keeps its default columns
+    pub columns: Vec<Column>,
+    /// Width to fit tables into, shrinking the widest columns first; `None` writes tab-separated rows with
+    /// hashes and addresses in full
+    pub width: Option<usize>,
+    /// Don't shorten hashes and addresses to `0x1234…abcd` in aligned tables
+    pub full_ids: bool,
+    /// Color balance increases green and decreases red
+    pub color: bool,
+    pub timezone: Timezone,
+    /// Which transactions the transaction table lists
+    pub filter: TransactionFilter,
+}
+
+/// One table cell, with the color it's drawn in when colors are on
+struct Cell {
+    text: String,
+    tone: Tone,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tone {
+    Plain,
+    Gain,
+    Loss,
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Cell { text, tone: Tone::Plain }
+    }
+}
+
+impl TableReport {
+    pub fn render(&self, analysis: &BlockAnalysis) -> String {
+        let info = &analysis.block_info;
+        let summary = &analysis.summary;
+        let mut out = String::new();
+
+        // Writing to a String can't fail
+        let _ = writeln!(
+            out,
+            "Block {}  {}  {}  gas used {} of {} ({:.2}%)",
+            info.block_number,
+            self.id(format!("{:?}", info.hash)),
+            self.timezone.describe(info.timestamp),
+            info.gas_used,
+            info.gas_limit,
+            summary.gas_used_percent
+        );
+        let _ = writeln!(
+            out,
+            "{} transactions ({} reverted), {} transferred, {} in fees",
+            summary.transaction_count,
+            summary.reverted_transactions,
+            self.amounts.format(summary.value_transferred),
+            self.amounts.format(summary.fees_paid)
+        );
+
+        let (listed, hidden) = self.filter.apply(&info.transactions);
+        let _ = writeln!(out, "\nTransactions ({})", info.transactions.len());
+        if hidden > 0 {
+            let _ = writeln!(out, "{} of them hidden by the transaction filters", hidden);
+        }
+        let columns = self.columns(Column::is_transaction, &DEFAULT_TRANSACTION_COLUMNS);
+        let rows = listed
+            .into_iter()
+            .map(|(index, tx)| columns.iter().map(|column| self.transaction_cell(analysis, index, tx, *column)).collect())
+            .collect();
+        self.table(&mut out, &columns, rows);
+
+        match &analysis.state_changes {
+            Some(changes) => {
+                let _ = writeln!(out, "\nState Changes ({})", changes.len());
+                if analysis.summary.filtered_state_changes > 0 {
+                    let _ = writeln!(out, "{} smaller changes hidden by the delta thresholds", analysis.summary.filtered_state_changes);
+                }
+                if analysis.summary.hidden_system_addresses > 0 {
+                    let _ = writeln!(out, "{} ArbOS system addresses hidden", analysis.summary.hidden_system_addresses);
+                }
+                self.state_changes(&mut out, analysis, changes);
+            }
+            None => out.push_str("\nState Changes: not queried\n"),
+        }
+
+        if !analysis.slot_changes.is_empty() {
+            let _ = writeln!(out, "\nWatched Slot Changes ({})", analysis.slot_changes.len());
+            for change in &analysis.slot_changes {
+                let _ = writeln!(out, "  {} {:?}: {:?} -> {:?}", self.address(Some(analysis), change.address, None), change.slot, change.before, change.after);
+            }
+        }
+
+        if !analysis.proxy_upgrades.is_empty() {
+            let _ = writeln!(out, "\nProxy Upgrades ({})", analysis.proxy_upgrades.len());
+            for upgrade in &analysis.proxy_upgrades {
+                let (old, new) = (self.address(Some(analysis), upgrade.old, None), self.address(Some(analysis), upgrade.new, None));
+                let _ = writeln!(out, "  {} {}: {} -> {}", self.address(Some(analysis), upgrade.address, None), upgrade.slot, old, new);
+            }
+        }
+
+        if !analysis.code_changes.is_empty() {
+            let _ = writeln!(out, "\nCode Changes ({})", analysis.code_changes.len());
+            for change in &analysis.code_changes {
+                let address = self.address(Some(analysis), change.address, None);
+                let (old, new) = (change.old_code_hash, change.new_code_hash);
+                let _ = writeln!(out, "  {} {:?} ({} bytes) -> {:?} ({} bytes)", address, old, change.old_size, new, change.new_size);
+            }
+        }
+
+        if let Some(verification) = &analysis.verification {
+            out.push_str("\nVerification\n");
+            for (name, check) in verification.checks() {
+                let _ = writeln!(out, "  {}: {}", name, check.describe());
+            }
+        }
+
+        if !analysis.warnings.is_empty() {
+            out.push_str("\nWarnings\n");
+            for warning in &analysis.warnings {
+                let _ = writeln!(out, "  {}", warning);
+            }
+        }
+        out
+    }
+
+    /// The block tables for the transaction's block, followed by the changes the transaction made on its own
+    pub fn render_transaction(&self, analysis: &TransactionAnalysis) -> String {
+        let mut out = format!(
+            "Transaction {} at index {}\n",
+            self.id(format!("{:?}", analysis.transaction_hash)),
+            analysis.transaction_index
+        );
+        out.push_str(&self.render(&analysis.block));
+        if let Some(changes) = analysis.own_state_changes() {
+            let _ = writeln!(out, "\nChanges Made By This Transaction ({})", changes.len());
+            self.state_changes(&mut out, &analysis.block, changes);
+        }
+        out
+    }
+
+    /// The net changes from [`crate::diff_addresses`] between blocks `from` and `to`
+    pub fn render_address_diff(&self, from: u64, to: u64, changes: &[StateChange]) -> String {
+        let mut out = format!("State Changes from Block {} to Block {} ({})\n", from, to, changes.len());
+        let columns = self.columns(|column| !column.is_transaction(), &DEFAULT_STATE_COLUMNS);
+        let rows = changes
+            .iter()
+            .map(|change| columns.iter().map(|column| self.state_cell(None, change, *column)).collect())
+            .collect();
+        self.table(&mut out, &columns, rows);
+        out
+    }
+
+    fn state_changes(&self, out: &mut String, analysis: &BlockAnalysis, changes: &[StateChange]) {
+        let columns = self.columns(|column| !column.is_transaction(), &DEFAULT_STATE_COLUMNS);
+        let rows = changes
+            .iter()
+            .map(|change| columns.iter().map(|column| self.state_cell(Some(analysis), change, *column)).collect())
+            .collect();
+        self.table(out, &columns, rows);
+    }
+
+    /// The selected columns that belong to a table, or its defaults when none do
+    fn columns(&self, belongs: impl Fn(Column) -> bool, defaults: &[Column]) -> Vec<Column> {
+        let selected: Vec<Column> = self.columns.iter().copied().filter(|column| belongs(*column)).collect();
+        if selected.is_empty() {
+            defaults.to_vec()
+        } else {
+            selected
+        }
+    }
+
+    fn transaction_cell(&self, analysis: &BlockAnalysis, index: usize, tx: &TransactionInfo, column: Column) -> Cell {
+        let amounts = &self.amounts;
+        let text = match column {
+            Column::Index => index.to_string(),
+            Column::Hash => self.id(format!("{:?}", tx.hash)),
+            Column::From => tx.from.map_or(String::new(), |from| self.address(Some(analysis), from, tx.from_label.as_ref())),
+            Column::To => match (tx.created_contract, tx.to) {
+                (Some(created), _) => format!("{} (created)", self.address(Some(analysis), created, tx.to_label.as_ref())),
+                (None, Some(to)) => self.address(Some(analysis), to, tx.to_label.as_ref()),
+                (None, None) => String::new(),
+            },
+            Column::Method => method(tx),
+            Column::Value => amounts.format(tx.value),
+            Column::Fee => tx.fee_paid.map_or(String::new(), |fee| amounts.format(fee)),
+            Column::GasUsed => tx.gas_used.map_or(String::new(), |gas| gas.to_string()),
+            Column::Status => match tx.status {
+                TransactionStatus::Success => "success".to_string(),
+                TransactionStatus::Reverted => match &tx.revert_reason {
+                    Some(reason) => format!("reverted: {}", reason),
+                    None => "reverted".to_string(),
+                },
+                TransactionStatus::Unknown => "unknown".to_string(),
+            },
+            Column::Type => tx.tx_type.map_or(String::new(), |tx_type| tx_type.to_string()),
+            _ => String::new(),
+        };
+        text.into()
+    }
+
+    fn state_cell(&self, analysis: Option<&BlockAnalysis>, change: &StateChange, column: Column) -> Cell {
+        let amounts = &self.amounts;
+        match column {
+            Column::Address => {
+                let address = self.address(analysis, change.address, change.label.as_ref());
+                if change.first_activity { format!("{} NEW", address) } else { address }.into()
+            }
+            Column::Balance => change.new_balance.map_or(String::new(), |balance| amounts.format(balance)).into(),
+            Column::BalanceChange => match change.balance_change {
+                Some(delta) => Cell {
+                    text: format!("{} {}", amounts.delta(delta), amounts.symbol()),
+                    tone: match (delta.magnitude.is_zero(), delta.decreased) {
+                        (true, _) => Tone::Plain,
+                        (false, true) => Tone::Loss,
+                        (false, false) => Tone::Gain,
+                    },
+                },
+                None => String::new().into(),
+            },
+            Column::Nonce => change.new_nonce.map_or(String::new(), |nonce| nonce.to_string()).into(),
+            Column::NonceChange => change.nonce_change.map_or(String::new(), |delta| delta.to_string()).into(),
+            Column::Storage => change.storage_changes.len().to_string().into(),
+            _ => String::new().into(),
+        }
+    }
+
+    /// Aligned columns fitted into `width`, or tab-separated rows without one
+    fn table(&self, out: &mut String, columns: &[Column], rows: Vec<Vec<Cell>>) {
+        let Some(max_width) = self.width else {
+            let titles: Vec<&str> = columns.iter().map(|column| column.title()).collect();
+            let _ = writeln!(out, "{}", titles.join("\t"));
+            for row in rows {
+                let cells: Vec<String> = row.iter().map(|cell| cell.text.replace(['\t', '\n', '\r'], " ")).collect();
+                let _ = writeln!(out, "{}", cells.join("\t"));
+            }
+            return;
+        };
+        if rows.is_empty() {
+            out.push_str("  none\n");
+            return;
+        }
+
+        let mut widths: Vec<usize> = columns.iter().map(|column| column.title().chars().count()).collect();
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.text.chars().count());
+            }
+        }
+        // Two spaces between columns; take from the widest column until the table fits or nothing can give.
+        // Cut numbers are misleading, so text columns give first.
+        let gaps = 2 * columns.len().saturating_sub(1);
+        for numeric in [false, true] {
+            while widths.iter().sum::<usize>() + gaps > max_width {
+                let shrinkable = widths.iter_mut().zip(columns).filter(|(width, column)| **width > MIN_COLUMN_WIDTH && column.is_numeric() == numeric);
+                let Some((widest, _)) = shrinkable.max_by_key(|(width, _)| **width) else { break };
+                *widest -= 1;
+            }
+        }
+
+        let titles: Vec<Cell> = columns.iter().map(|column| column.title().to_string().into()).collect();
+        for row in std::iter::once(&titles).chain(&rows) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(columns.iter().zip(&widths))
+                .map(|(cell, (column, width))| {
+                    let text = truncate(&cell.text, *width);
+                    let padded = if column.is_numeric() { format!("{:>1$}", text, width) } else { format!("{:<1$}", text, width) };
+                    match cell.tone {
+                        Tone::Gain if self.color => format!("\x1b[32m{}\x1b[0m", padded),
+                        Tone::Loss if self.color => format!("\x1b[31m{}\x1b[0m", padded),
+                        _ => padded,
+                    }
+                })
+                .collect();
+            let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+        }
+    }
+
+    fn address(&self, analysis: Option<&BlockAnalysis>, address: H160, file_label: Option<&String>) -> String {
+        let id = self.id(format_address(&address));
+        match file_label.or_else(|| analysis.and_then(|analysis| analysis.ens.get(&address))) {
+            Some(name) => format!("{} ({})", name, id),
+            None => id,
+        }
+    }
+
+    /// A hash or address as `0x1234…abcd`, unless ids are shown in full
+    fn id(&self, hex: String) -> String {
+        if self.full_ids || self.width.is_none() || hex.len() <= 12 {
+            return hex;
+        }
+        format!("{}…{}", &hex[..6], &hex[hex.len() - 4..])
+    }
+}
+
+/// `text` cut to `width` characters, ending in an ellipsis when anything was cut
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
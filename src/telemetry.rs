@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{metrics, BlockRef};
+
+tokio::task_local! {
+    /// RPC traffic of the block analysis the current future belongs to
+    static BLOCK_RPC: Arc<Mutex<RpcStats>>;
+}
+
+/// RPC traffic of one block's analysis, as seen by the retry layer; cache hits never reach the node and aren't counted
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RpcStats {
+    /// Round trips, counting a batch once
+    pub requests: u64,
+    pub calls_by_method: BTreeMap<String, MethodStats>,
+    /// Attempts repeated after a transient failure
+    pub retries: u64,
+    /// Calls that failed for good, including failed entries of a delivered batch
+    pub failures: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct MethodStats {
+    pub count: u64,
+    /// A batch's time is split evenly between its calls, so the totals add up to the time spent waiting on the node
+    pub total_ms: f64,
+}
+
+impl RpcStats {
+    /// JSON-RPC calls, counting each call in a batch
+    pub fn calls(&self) -> u64 {
+        self.calls_by_method.values().map(|method| method.count).sum()
+    }
+
+    /// Time spent waiting on the node. Requests overlap, so this can exceed the wall-clock time.
+    pub fn total_ms(&self) -> f64 {
+        self.calls_by_method.values().map(|method| method.total_ms).sum()
+    }
+}
+
+fn with_stats(update: impl FnOnce(&mut RpcStats)) {
+    let _ = BLOCK_RPC.try_with(|stats| update(&mut stats.lock().expect("rpc stats lock poisoned")));
+}
+
+/// Count a round trip carrying `methods` against the block being analyzed, if any, and the watch metrics
+pub(crate) fn record_rpc(methods: &[String], elapsed: Duration) {
+    metrics::record_rpc_calls(methods);
+    with_stats(|stats| {
+        stats.requests += 1;
+        let share = elapsed.as_secs_f64() * 1000.0 / methods.len().max(1) as f64;
+        for method in methods {
+            let entry = stats.calls_by_method.entry(method.clone()).or_default();
+            entry.count += 1;
+            entry.total_ms += share;
+        }
+    });
+}
+
+pub(crate) fn record_retry() {
+    with_stats(|stats| stats.retries += 1);
+}
+
+/// Count calls that failed for good, by method
+pub(crate) fn record_failures<'a>(methods: impl IntoIterator<Item = &'a String> + Clone) {
+    metrics::record_rpc_errors(methods.clone());
+    with_stats(|stats| stats.failures += methods.into_iter().count() as u64);
+}
+
+/// Run one block's analysis, then log how many requests it made and how long they took in total.
+/// The counts are handed back alongside the result.
+pub(crate) async fn with_rpc_summary<R, E>(block: BlockRef, run: impl Future<Output = Result<R, E>>) -> (Result<R, E>, RpcStats) {
+    let stats = Arc::new(Mutex::new(RpcStats::default()));
+    let result = BLOCK_RPC.scope(stats.clone(), run).await;
+    let stats = stats.lock().expect("rpc stats lock poisoned").clone();
+    tracing::info!(
+        block = ?block,
+        ok = result.is_ok(),
+        requests = stats.requests,
+        calls = stats.calls(),
+        retries = stats.retries,
+        failures = stats.failures,
+        rpc_ms = stats.total_ms() as u64,
+        "block analysis finished"
+    );
+    (result, stats)
+}
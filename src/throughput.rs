@@ -0,0 +1,123 @@
+use serde::Serialize;
+use web3::types::{BlockId, BlockNumber, U64};
+use web3::{Web3, Transport};
+use std::error::Error;
+
+use crate::{BlockAnalysis, BlockNotFoundError, BlockRef};
+
+/// How fast one block of a range followed the one before it
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockTiming {
+    pub block_number: u64,
+    pub timestamp: u64,
+    /// Seconds since the previous block; `None` for block 0, or a first block whose parent could not be fetched
+    pub interval: Option<u64>,
+    pub transactions: usize,
+    pub gas_used: u64,
+    /// Transactions over the interval; `None` without one, or when both blocks share a timestamp
+    pub transactions_per_second: Option<f64>,
+    pub gas_per_second: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Spread {
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+}
+
+impl Spread {
+    /// `None` for no values
+    fn of(values: impl Iterator<Item = f64>) -> Option<Self> {
+        let mut values: Vec<f64> = values.collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let middle = values.len() / 2;
+        let median = if values.len().is_multiple_of(2) { (values[middle - 1] + values[middle]) / 2.0 } else { values[middle] };
+        Some(Spread { min: values[0], median, max: values[values.len() - 1] })
+    }
+}
+
+/// Block production over a range, from [`ThroughputTracker::summary`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ThroughputSummary {
+    pub from_block: u64,
+    pub to_block: u64,
+    pub blocks: usize,
+    pub transactions: usize,
+    pub gas_used: u64,
+    /// Seconds between consecutive blocks
+    pub block_time: Option<Spread>,
+    pub transactions_per_second: Option<Spread>,
+    pub gas_per_second: Option<Spread>,
+}
+
+/// Per-block timing across a range, fed each analysis in block order. A reorg is followed by
+/// [`ThroughputTracker::rewind`], so replaced blocks don't count twice.
+#[derive(Debug, Clone, Default)]
+pub struct ThroughputTracker {
+    /// Timestamp of the block before the range, for the first block's interval
+    start_timestamp: Option<u64>,
+    timings: Vec<BlockTiming>,
+}
+
+impl ThroughputTracker {
+    /// `parent_timestamp` is the timestamp of the block before the first one recorded, if there is one
+    pub fn new(parent_timestamp: Option<u64>) -> Self {
+        ThroughputTracker { start_timestamp: parent_timestamp, timings: Vec::new() }
+    }
+
+    pub fn record(&mut self, analysis: &BlockAnalysis) -> &BlockTiming {
+        let info = &analysis.block_info;
+        let previous = self.timings.last().map(|timing| timing.timestamp).or(self.start_timestamp);
+        let interval = previous.map(|previous| info.timestamp.saturating_sub(previous));
+        let rate = |amount: f64| interval.filter(|interval| *interval > 0).map(|interval| amount / interval as f64);
+        self.timings.push(BlockTiming {
+            block_number: info.block_number,
+            timestamp: info.timestamp,
+            interval,
+            transactions: info.transactions.len(),
+            gas_used: info.gas_used,
+            transactions_per_second: rate(info.transactions.len() as f64),
+            gas_per_second: rate(info.gas_used as f64),
+        });
+        &self.timings[self.timings.len() - 1]
+    }
+
+    /// Forget the blocks after `common_ancestor`; their replacements are recorded next
+    pub fn rewind(&mut self, common_ancestor: u64) {
+        self.timings.retain(|timing| timing.block_number <= common_ancestor);
+    }
+
+    /// Every block recorded so far, in block order
+    pub fn timings(&self) -> &[BlockTiming] {
+        &self.timings
+    }
+
+    /// `None` until a block has been recorded
+    pub fn summary(&self) -> Option<ThroughputSummary> {
+        let (first, last) = (self.timings.first()?, self.timings.last()?);
+        Some(ThroughputSummary {
+            from_block: first.block_number,
+            to_block: last.block_number,
+            blocks: self.timings.len(),
+            transactions: self.timings.iter().map(|timing| timing.transactions).sum(),
+            gas_used: self.timings.iter().map(|timing| timing.gas_used).sum(),
+            block_time: Spread::of(self.timings.iter().filter_map(|timing| timing.interval).map(|interval| interval as f64)),
+            transactions_per_second: Spread::of(self.timings.iter().filter_map(|timing| timing.transactions_per_second)),
+            gas_per_second: Spread::of(self.timings.iter().filter_map(|timing| timing.gas_per_second)),
+        })
+    }
+}
+
+/// Timestamp of block `number`, without its transactions
+pub async fn block_timestamp<T: Transport>(web3: &Web3<T>, number: u64) -> Result<u64, Box<dyn Error>> {
+    let block = web3
+        .eth()
+        .block(BlockId::Number(BlockNumber::Number(U64::from(number))))
+        .await?
+        .ok_or(BlockNotFoundError { block: BlockRef::Number(number) })?;
+    Ok(block.timestamp.as_u64())
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Blocks older than this are shown without an age; for them the date says enough
+const RECENT_SECONDS: u64 = 7 * 24 * 60 * 60;
+
+/// Time zone to render block timestamps in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timezone(Tz);
+
+impl Default for Timezone {
+    fn default() -> Self {
+        Timezone(Tz::UTC)
+    }
+}
+
+impl std::str::FromStr for Timezone {
+    type Err = String;
+
+    /// An IANA zone name such as `Europe/Berlin` or `UTC`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim()
+            .parse::<Tz>()
+            .map(Timezone)
+            .map_err(|_| format!("invalid time zone '{}': expected an IANA name such as UTC or Europe/Berlin", s))
+    }
+}
+
+impl Timezone {
+    /// `timestamp`, in seconds since the epoch, as RFC 3339 in this zone: `2023-11-10T00:37:11Z` in UTC,
+    /// `2023-11-10T01:37:11+01:00` in Europe/Berlin
+    pub fn format(self, timestamp: u64) -> String {
+        match DateTime::<Utc>::from_timestamp(i64::try_from(timestamp).unwrap_or(i64::MAX), 0) {
+            Some(time) => time.with_timezone(&self.0).to_rfc3339_opts(SecondsFormat::Secs, true),
+            None => format!("{} (out of range)", timestamp),
+        }
+    }
+
+    /// The formatted time followed by the raw epoch and, for a recent block, its age:
+    /// `2023-11-10T00:37:11Z (1699574231, 2 hours ago)`
+    pub fn describe(self, timestamp: u64) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs());
+        match format_age(timestamp, now) {
+            Some(age) => format!("{} ({}, {})", self.format(timestamp), timestamp, age),
+            None => format!("{} ({})", self.format(timestamp), timestamp),
+        }
+    }
+}
+
+/// `timestamp` as RFC 3339 UTC
+pub fn format_timestamp(timestamp: u64) -> String {
+    Timezone::default().format(timestamp)
+}
+
+/// How long before `now` the block at `timestamp` was made, e.g. `2 hours ago`, or `None` when it's
+/// older than a week. A timestamp ahead of `now` (clock skew) reads as `just now`.
+pub fn format_age(timestamp: u64, now: u64) -> Option<String> {
+    let age = now.saturating_sub(timestamp);
+    if age > RECENT_SECONDS {
+        return None;
+    }
+    let (count, unit) = match age {
+        0 => return Some("just now".to_string()),
+        1..=59 => (age, "second"),
+        60..=3599 => (age / 60, "minute"),
+        3600..=86399 => (age / 3600, "hour"),
+        _ => (age / 86400, "day"),
+    };
+    Some(format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" }))
+}
@@ -0,0 +1,137 @@
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use web3::ethabi::{self, ParamType, Token};
+use web3::types::{BlockId, BlockNumber, Bytes, CallRequest, H160, U256, U64};
+use web3::{Transport, Web3};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::format_units;
+
+/// `symbol()`
+const SYMBOL_SELECTOR: [u8; 4] = [0x95, 0xd8, 0x9b, 0x41];
+/// `name()`
+const NAME_SELECTOR: [u8; 4] = [0x06, 0xfd, 0xde, 0x03];
+/// `decimals()`
+const DECIMALS_SELECTOR: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+
+/// What a token contract says about itself; any field it doesn't implement (or reverts on) is `None`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TokenMetadata {
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+impl TokenMetadata {
+    /// `amount` in whole tokens with thousands separators, e.g. `1,234.56 USDC`; raw base units
+    /// when the token has no decimals
+    pub fn format_amount(&self, amount: U256) -> String {
+        let formatted = match self.decimals {
+            Some(decimals) => group_thousands(&format_units(amount, decimals.into())),
+            None => amount.to_string(),
+        };
+        match &self.symbol {
+            Some(symbol) => format!("{} {}", formatted, symbol),
+            None => formatted,
+        }
+    }
+}
+
+/// Token metadata by contract address, kept for as long as the cache (or any clone of it) lives,
+/// so a range scan asks each token only once
+#[derive(Debug, Clone, Default)]
+pub struct TokenMetadataCache {
+    tokens: Arc<Mutex<HashMap<H160, TokenMetadata>>>,
+}
+
+impl TokenMetadataCache {
+    pub fn get(&self, token: &H160) -> Option<TokenMetadata> {
+        self.tokens.lock().expect("token metadata lock poisoned").get(token).cloned()
+    }
+
+    /// Metadata for each of `tokens`, fetching whatever isn't cached yet at `block_number`.
+    /// A token whose calls fail for a reason other than a revert is left out and tried again next time.
+    pub(crate) async fn fetch_all<T: Transport>(
+        &self,
+        web3: &Web3<T>,
+        tokens: impl IntoIterator<Item = H160>,
+        block_number: u64,
+        concurrency: usize,
+    ) -> BTreeMap<H160, TokenMetadata> {
+        let tokens: Vec<H160> = tokens.into_iter().collect();
+        let pending: Vec<H160> = {
+            let cached = self.tokens.lock().expect("token metadata lock poisoned");
+            tokens.iter().filter(|token| !cached.contains_key(token)).copied().collect()
+        };
+
+        let block = BlockId::Number(BlockNumber::Number(U64::from(block_number)));
+        let fetched: Vec<(H160, TokenMetadata)> = stream::iter(pending)
+            .map(|token| async move {
+                let (symbol, name, decimals) = futures::join!(
+                    call(web3, token, SYMBOL_SELECTOR, block),
+                    call(web3, token, NAME_SELECTOR, block),
+                    call(web3, token, DECIMALS_SELECTOR, block),
+                );
+                let metadata = TokenMetadata {
+                    symbol: symbol.ok()?.as_ref().and_then(decode_text),
+                    name: name.ok()?.as_ref().and_then(decode_text),
+                    decimals: decimals.ok()?.as_ref().and_then(decode_decimals),
+                };
+                Some((token, metadata))
+            })
+            .buffer_unordered(concurrency.max(1))
+            .filter_map(futures::future::ready)
+            .collect()
+            .await;
+
+        let mut cached = self.tokens.lock().expect("token metadata lock poisoned");
+        cached.extend(fetched);
+        tokens.into_iter().filter_map(|token| cached.get(&token).cloned().map(|metadata| (token, metadata))).collect()
+    }
+}
+
+/// The raw return data, `None` if the call reverted
+async fn call<T: Transport>(web3: &Web3<T>, to: H160, selector: [u8; 4], block: BlockId) -> web3::Result<Option<Bytes>> {
+    let request = CallRequest { to: Some(to), data: Some(Bytes(selector.to_vec())), ..CallRequest::default() };
+    match web3.eth().call(request, Some(block)).await {
+        Ok(data) if data.0.is_empty() => Ok(None),
+        Ok(data) => Ok(Some(data)),
+        Err(web3::Error::Rpc(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// A `string`, or the `bytes32` some older tokens (MKR, SAI) return instead
+fn decode_text(data: &Bytes) -> Option<String> {
+    let text = match ethabi::decode(&[ParamType::String], &data.0).ok().and_then(|tokens| tokens.into_iter().next()) {
+        Some(Token::String(text)) => text,
+        _ if data.0.len() == 32 => String::from_utf8(data.0.iter().copied().take_while(|byte| *byte != 0).collect()).ok()?,
+        _ => return None,
+    };
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn decode_decimals(data: &Bytes) -> Option<u8> {
+    match ethabi::decode(&[ParamType::Uint(256)], &data.0).ok()?.into_iter().next()? {
+        Token::Uint(decimals) if decimals <= U256::from(u8::MAX) => Some(decimals.as_u32() as u8),
+        _ => None,
+    }
+}
+
+/// `1234567.5` becomes `1,234,567.5`
+fn group_thousands(amount: &str) -> String {
+    let (whole, fraction) = amount.split_once('.').map_or((amount, None), |(whole, fraction)| (whole, Some(fraction)));
+    let mut grouped = String::new();
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    match fraction {
+        Some(fraction) => format!("{}.{}", grouped, fraction),
+        None => grouped,
+    }
+}
@@ -0,0 +1,168 @@
+use web3::ethabi::{self, ParamType, Token};
+use web3::types::{Log, H160, H256, U256};
+
+use crate::{BlockInfo, NftStandard, NftTransfer, TokenTransfer};
+
+/// keccak256("Transfer(address,address,uint256)")
+pub(crate) const TRANSFER_TOPIC: H256 = H256([
+    0xdd, 0xf2, 0x52, 0xad, 0x1b, 0xe2, 0xc8, 0x9b, 0x69, 0xc2, 0xb0, 0x68, 0xfc, 0x37, 0x8d, 0xaa,
+    0x95, 0x2b, 0xa7, 0xf1, 0x63, 0xc4, 0xa1, 0x16, 0x28, 0xf5, 0x5a, 0x4d, 0xf5, 0x23, 0xb3, 0xef,
+]);
+
+/// keccak256("TransferSingle(address,address,address,uint256,uint256)")
+const TRANSFER_SINGLE_TOPIC: H256 = H256([
+    0xc3, 0xd5, 0x81, 0x68, 0xc5, 0xae, 0x73, 0x97, 0x73, 0x1d, 0x06, 0x3d, 0x5b, 0xbf, 0x3d, 0x65,
+    0x78, 0x54, 0x42, 0x73, 0x43, 0xf4, 0xc0, 0x83, 0x24, 0x0f, 0x7a, 0xac, 0xaa, 0x2d, 0x0f, 0x62,
+]);
+
+/// keccak256("TransferBatch(address,address,address,uint256[],uint256[])")
+const TRANSFER_BATCH_TOPIC: H256 = H256([
+    0x4a, 0x39, 0xdc, 0x06, 0xd4, 0xc0, 0xdb, 0xc6, 0x4b, 0x70, 0xaf, 0x90, 0xfd, 0x69, 0x8a, 0x23,
+    0x3a, 0x51, 0x8a, 0xa5, 0xd0, 0x7e, 0x59, 0x5d, 0x98, 0x3b, 0x8c, 0x05, 0x26, 0xc8, 0xf7, 0xfb,
+]);
+
+/// Decode ERC-20 `Transfer` events from every receipt in the block, in log order.
+pub(crate) fn extract_token_transfers(block_info: &BlockInfo) -> Vec<TokenTransfer> {
+    block_info
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.logs.iter().filter_map(move |log| decode_transfer(tx.hash, log)))
+        .collect()
+}
+
+fn decode_transfer(transaction_hash: H256, log: &Log) -> Option<TokenTransfer> {
+    // ERC-20 indexes `from` and `to` only; fewer topics is a non-indexed variant we can't attribute,
+    // and a fourth topic means an ERC-721 token id rather than an amount.
+    if log.topics.len() != 3 || log.topics[0] != TRANSFER_TOPIC || log.data.0.len() != 32 {
+        return None;
+    }
+
+    Some(TokenTransfer {
+        transaction_hash,
+        token: log.address,
+        from: topic_to_address(&log.topics[1]),
+        to: topic_to_address(&log.topics[2]),
+        amount: U256::from_big_endian(&log.data.0),
+    })
+}
+
+/// Decode ERC-721 `Transfer` and ERC-1155 `TransferSingle`/`TransferBatch` events from every receipt
+/// in the block, in log order.
+pub(crate) fn extract_nft_transfers(block_info: &BlockInfo) -> Vec<NftTransfer> {
+    block_info
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.logs.iter().flat_map(move |log| decode_nft_transfer(tx.hash, log)))
+        .collect()
+}
+
+fn decode_nft_transfer(transaction_hash: H256, log: &Log) -> Vec<NftTransfer> {
+    let topics = &log.topics;
+    let transfer = |standard, operator, from, to, token_id, amount| NftTransfer {
+        transaction_hash,
+        token: log.address,
+        standard,
+        operator,
+        from,
+        to,
+        token_id,
+        amount,
+    };
+
+    match topics.first() {
+        // Same signature as ERC-20, but with the token id as a fourth, indexed topic and no data
+        Some(topic) if *topic == TRANSFER_TOPIC && topics.len() == 4 => vec![transfer(
+            NftStandard::Erc721,
+            None,
+            topic_to_address(&topics[1]),
+            topic_to_address(&topics[2]),
+            U256::from_big_endian(topics[3].as_bytes()),
+            U256::one(),
+        )],
+        Some(topic) if *topic == TRANSFER_SINGLE_TOPIC && topics.len() == 4 && log.data.0.len() == 64 => vec![transfer(
+            NftStandard::Erc1155,
+            Some(topic_to_address(&topics[1])),
+            topic_to_address(&topics[2]),
+            topic_to_address(&topics[3]),
+            U256::from_big_endian(&log.data.0[..32]),
+            U256::from_big_endian(&log.data.0[32..]),
+        )],
+        Some(topic) if *topic == TRANSFER_BATCH_TOPIC && topics.len() == 4 => {
+            let array = ParamType::Array(Box::new(ParamType::Uint(256)));
+            let Ok(tokens) = ethabi::decode(&[array.clone(), array], &log.data.0) else {
+                return Vec::new();
+            };
+            let (ids, amounts) = match tokens.as_slice() {
+                [Token::Array(ids), Token::Array(amounts)] if ids.len() == amounts.len() => (ids, amounts),
+                _ => return Vec::new(),
+            };
+            let (operator, from, to) = (topic_to_address(&topics[1]), topic_to_address(&topics[2]), topic_to_address(&topics[3]));
+            ids.iter()
+                .zip(amounts)
+                .filter_map(|(id, amount)| Some((id.clone().into_uint()?, amount.clone().into_uint()?)))
+                .map(|(id, amount)| transfer(NftStandard::Erc1155, Some(operator), from, to, id, amount))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn topic_to_address(topic: &H256) -> H160 {
+    H160::from_slice(&topic.as_bytes()[12..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::Web3;
+
+    use crate::{get_block_info, AnalysisOptions, BlockRef, MockTransport};
+
+    const ALICE: u64 = 0xa11ce;
+    const BOB: u64 = 0xb0b;
+    const CAROL: u64 = 0xca401;
+
+    fn address(low: u64) -> H160 {
+        H160::from_low_u64_be(low)
+    }
+
+    /// Block 3 of the fixture with its first transaction emitting the logs of `transfer_logs.json`, in
+    /// order: an ERC-20 transfer of 1,500, an ERC-721 transfer of token 42, a `Transfer` that indexes
+    /// nothing, one with three topics but no amount, an ERC-1155 single transfer of 3 of token 7,
+    /// a batch of 10 of token 1 and 20 of token 2, and a batch whose arrays differ in length
+    async fn block_info() -> BlockInfo {
+        let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+        let (mut block_info, _) = get_block_info(&web3, BlockRef::Number(3), &AnalysisOptions::new()).await.unwrap();
+        block_info.transactions[0].logs = serde_json::from_str(include_str!("../tests/fixtures/transfer_logs.json")).unwrap();
+        block_info
+    }
+
+    #[tokio::test]
+    async fn only_three_topics_and_an_amount_make_an_erc20_transfer() {
+        let transfers = extract_token_transfers(&block_info().await);
+        let decoded: Vec<_> = transfers.iter().map(|transfer| (transfer.token, transfer.from, transfer.to, transfer.amount)).collect();
+        assert_eq!(decoded, [(address(0xe20), address(ALICE), address(BOB), U256::from(1500))]);
+        assert_eq!(transfers[0].transaction_hash, H256::from_low_u64_be(0x407));
+    }
+
+    #[tokio::test]
+    async fn a_fourth_topic_makes_an_erc721_transfer() {
+        let transfers = extract_nft_transfers(&block_info().await);
+        let decoded: Vec<_> = transfers
+            .iter()
+            .map(|transfer| (transfer.token, transfer.standard, transfer.operator, transfer.from, transfer.to, transfer.token_id, transfer.amount))
+            .collect();
+        let batch = |id: u64, amount: u64| {
+            (address(0x1155), NftStandard::Erc1155, Some(address(CAROL)), H160::zero(), address(BOB), U256::from(id), U256::from(amount))
+        };
+        assert_eq!(
+            decoded,
+            [
+                (address(0x721), NftStandard::Erc721, None, address(ALICE), address(BOB), U256::from(42), U256::one()),
+                (address(0x1155), NftStandard::Erc1155, Some(address(CAROL)), address(ALICE), address(BOB), U256::from(7), U256::from(3)),
+                batch(1, 10),
+                batch(2, 20),
+            ]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use futures::stream::{self, StreamExt, TryStreamExt};
+use jsonrpc_core::ErrorCode;
+use serde::Deserialize;
+use serde_json::json;
+use web3::types::{H160, H256};
+use web3::{Web3, Transport};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use crate::{BlockInfo, StorageChange};
+
+#[derive(Debug, Default, Deserialize)]
+struct PrestateDiff {
+    #[serde(default)]
+    pre: HashMap<H160, PrestateAccount>,
+    #[serde(default)]
+    post: HashMap<H160, PrestateAccount>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PrestateAccount {
+    #[serde(default)]
+    storage: HashMap<H256, H256>,
+}
+
+/// Whether an RPC error means the node doesn't expose the method at all,
+/// as opposed to the call itself failing.
+pub(crate) fn is_method_unsupported(error: &web3::Error) -> bool {
+    match error {
+        web3::Error::Rpc(e) => {
+            let message = e.message.to_lowercase();
+            e.code == ErrorCode::MethodNotFound
+                || message.contains("does not exist")
+                || message.contains("not available")
+                || message.contains("not supported")
+        }
+        _ => false,
+    }
+}
+
+/// Storage slot changes per address across the whole block, from the diff-mode prestate tracer.
+/// Returns `None` when the node doesn't expose `debug_traceTransaction`.
+pub(crate) async fn get_storage_changes<T: Transport>(
+    web3: &Web3<T>,
+    block_info: &BlockInfo,
+    concurrency: usize,
+) -> Result<Option<HashMap<H160, Vec<StorageChange>>>, Box<dyn Error>> {
+    let tracer = json!({ "tracer": "prestateTracer", "tracerConfig": { "diffMode": true } });
+
+    let traced = stream::iter(&block_info.transactions)
+        .map(|tx| {
+            let params = vec![json!(tx.hash), tracer.clone()];
+            async move { web3.transport().execute("debug_traceTransaction", params).await }
+        })
+        .buffered(concurrency.max(1))
+        .try_collect::<Vec<_>>()
+        .await;
+
+    let diffs = match traced {
+        Ok(diffs) => diffs,
+        Err(e) if is_method_unsupported(&e) => {
+            tracing::warn!(error = %e, "node does not support debug_traceTransaction, skipping storage diffs");
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Slots keep the value from before their first write and after their last write in the block.
+    // Zeroed slots are omitted from the tracer's "post" map, so a missing side reads as zero.
+    let mut slots: BTreeMap<(H160, H256), (H256, H256)> = BTreeMap::new();
+    for diff in diffs {
+        let diff: PrestateDiff = serde_json::from_value(diff)?;
+
+        let mut touched: BTreeMap<(H160, H256), (H256, H256)> = BTreeMap::new();
+        for (address, account) in &diff.pre {
+            for (slot, value) in &account.storage {
+                touched.entry((*address, *slot)).or_default().0 = *value;
+            }
+        }
+        for (address, account) in &diff.post {
+            for (slot, value) in &account.storage {
+                touched.entry((*address, *slot)).or_default().1 = *value;
+            }
+        }
+
+        for (key, (before, after)) in touched {
+            slots.entry(key).or_insert((before, after)).1 = after;
+        }
+    }
+
+    let mut changes: HashMap<H160, Vec<StorageChange>> = HashMap::new();
+    for ((address, slot), (before, after)) in slots {
+        if before != after {
+            changes.entry(address).or_default().push(StorageChange { slot, before, after });
+        }
+    }
+
+    Ok(Some(changes))
+}
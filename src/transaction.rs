@@ -0,0 +1,89 @@
+use serde::Serialize;
+use web3::types::{BlockId, TransactionId, H160, H256};
+use web3::{BatchTransport, Web3};
+use std::collections::HashSet;
+use std::error::Error;
+
+use crate::{
+    analyze_block_cached, fees, gas, sort_state_changes, state_diff, summary, with_deadline, AccountStateCache, AnalysisOptions,
+    BlockAnalysis, BlockRef, StateChange, Wei, WeiDelta,
+};
+
+/// One transaction's view of the block it was mined in, from [`analyze_transaction`]
+#[derive(Debug, Serialize)]
+pub struct TransactionAnalysis {
+    pub transaction_hash: H256,
+    /// Position of the transaction in its block
+    pub transaction_index: u64,
+    /// The containing block's analysis, narrowed to this transaction: it is the only transaction listed, the
+    /// summary covers it alone apart from the block's gas figures, and only the accounts it touched are queried.
+    /// Their state changes span the whole block, so other transactions' effects on them are included;
+    /// `per_tx_changes` holds this transaction's own changes when the node can trace it.
+    pub block: BlockAnalysis,
+}
+
+impl TransactionAnalysis {
+    /// Changes made by this transaction alone, or `None` when the node couldn't trace it
+    pub fn own_state_changes(&self) -> Option<&[StateChange]> {
+        self.block.per_tx_changes.first().map(|tx_changes| tx_changes.state_changes.as_slice())
+    }
+}
+
+/// Analyze the transaction `hash` within the block that contains it.
+///
+/// Its sender, recipient, any contract it created and the block's coinbase are compared between the parent
+/// block and the containing block. When the node can trace the transaction, every account the trace shows
+/// it touching is compared too, and its own contribution is reported separately. `options.per_transaction`
+/// is ignored; an address filter in `options` narrows the accounts further.
+pub async fn analyze_transaction<T: BatchTransport>(
+    web3: &Web3<T>,
+    hash: H256,
+    options: &AnalysisOptions,
+) -> Result<TransactionAnalysis, Box<dyn Error>> {
+    with_deadline(options.deadline, || format!("analysis of transaction {:?}", hash), async {
+        let tx = web3.eth().transaction(TransactionId::Hash(hash)).await?.ok_or_else(|| format!("transaction {:?} not found", hash))?;
+        let (Some(block_hash), Some(index)) = (tx.block_hash, tx.transaction_index) else {
+            return Err(format!("transaction {:?} is pending: not yet mined", hash).into());
+        };
+
+        // The trace names every account the transaction touched, internal transfers included
+        let own = state_diff::trace_transaction(web3, hash).await?;
+        let coinbase = web3.eth().block(BlockId::Hash(block_hash)).await?.ok_or(format!("block {:?} not found", block_hash))?.author;
+        let mut involved: HashSet<H160> = [tx.from, tx.to, Some(coinbase)].into_iter().flatten().collect();
+        if let Some(receipt) = web3.eth().transaction_receipt(hash).await? {
+            involved.extend(receipt.contract_address);
+        }
+        involved.extend(own.iter().flat_map(|own| &own.state_changes).map(|change| change.address));
+        involved.retain(|address| options.includes(address));
+
+        let scoped = options.clone().with_address_filter(involved).per_transaction(false);
+        let mut block = analyze_block_cached(web3, BlockRef::Hash(block_hash), &scoped, &mut AccountStateCache::default()).await?;
+
+        block.block_info.transactions.retain(|tx| tx.hash == hash);
+        block.token_transfers.retain(|transfer| transfer.transaction_hash == hash);
+        block.nft_transfers.retain(|transfer| transfer.transaction_hash == hash);
+        if let Some(internal_transfers) = &mut block.internal_transfers {
+            internal_transfers.retain(|transfer| transfer.transaction_hash == hash);
+        }
+        block.destroyed_contracts.retain(|destroyed| destroyed.transaction_hash == Some(hash));
+        if let Some(mut own) = own {
+            own.state_changes.retain(|change| options.includes(&change.address));
+            sort_state_changes(&mut own.state_changes, options.sort);
+            let internal_transfers = block.internal_transfers.iter().flatten();
+            fees::decompose_balance_changes(&mut own.state_changes, &block.block_info.transactions, internal_transfers, &block.block_info);
+            block.per_tx_changes = vec![own];
+        }
+
+        // The coinbase's change spans the whole block, so no single transaction explains it
+        let mev_candidates = summary::mev_candidates(&block.block_info, &block.per_tx_changes);
+        block.summary = summary::summarize(&block.block_info, block.state_changes.as_deref().unwrap_or_default(), mev_candidates);
+        block.summary.unexplained_coinbase_change = WeiDelta { decreased: false, magnitude: Wei::zero() };
+        block.summary.mev_payment = None;
+        if block.gas_report.is_some() {
+            block.gas_report = Some(gas::gas_report(&block.block_info));
+        }
+
+        Ok(TransactionAnalysis { transaction_hash: hash, transaction_index: index.as_u64(), block })
+    })
+    .await
+}
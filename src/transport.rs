@@ -0,0 +1,24 @@
+use web3::transports::{Either, Http, WebSocket};
+use web3::Web3;
+use std::error::Error;
+
+/// HTTP or WebSocket transport, picked from the RPC URL's scheme
+pub type RpcTransport = Either<Http, WebSocket>;
+
+const SUPPORTED_SCHEMES: &str = "http, https, ws, wss";
+
+/// Connect to an RPC endpoint, choosing the transport from the URL scheme.
+pub async fn connect(url: &str) -> Result<Web3<RpcTransport>, Box<dyn Error>> {
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+
+    let transport = match scheme.as_deref() {
+        Some("http") | Some("https") => Either::Left(Http::new(url)?),
+        Some("ws") | Some("wss") => Either::Right(WebSocket::new(url).await?),
+        Some(other) => {
+            return Err(format!("unsupported RPC URL scheme '{}' in {}; supported schemes: {}", other, url, SUPPORTED_SCHEMES).into())
+        }
+        None => return Err(format!("RPC URL {} has no scheme; supported schemes: {}", url, SUPPORTED_SCHEMES).into()),
+    };
+
+    Ok(Web3::new(transport))
+}
@@ -0,0 +1,188 @@
+use web3::types::U256;
+
+use crate::{Wei, WeiDelta};
+
+/// Denomination for displaying wei amounts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Unit {
+    /// 10^18 wei, shown with the chain's native symbol
+    Eth,
+    /// 10^9 wei
+    Gwei,
+    #[default]
+    Wei,
+}
+
+impl Unit {
+    fn decimals(self) -> usize {
+        match self {
+            Unit::Eth => 18,
+            Unit::Gwei => 9,
+            Unit::Wei => 0,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Unit::Eth => "eth",
+            Unit::Gwei => "gwei",
+            Unit::Wei => "wei",
+        }
+    }
+}
+
+impl std::str::FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eth" => Ok(Unit::Eth),
+            "gwei" => Ok(Unit::Gwei),
+            "wei" => Ok(Unit::Wei),
+            _ => Err(format!("invalid unit '{}': expected eth, gwei or wei", s)),
+        }
+    }
+}
+
+/// Render `value` with the decimal point shifted `decimals` places left, without trailing zeros.
+/// Works on the decimal string, so no precision is lost: `format_units(1_250_000_000_000_000_000, 18)` is `1.25`.
+pub fn format_units(value: U256, decimals: usize) -> String {
+    let digits = value.to_string();
+    if decimals == 0 {
+        return digits;
+    }
+
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// Parse an amount with an optional unit suffix: `1.5eth`, `250gwei`, `0.01 eth`, or bare wei such as
+/// `21000`. The suffix is one `--units` accepts. Like [`format_units`], this works on the decimal string,
+/// so no precision is lost; a fraction finer than one wei is an error.
+pub fn parse_units(s: &str) -> Result<Wei, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (number, unit) = (s[..split].trim(), s[split..].trim());
+    let unit: Unit = if unit.is_empty() { Unit::Wei } else { unit.to_ascii_lowercase().parse()? };
+    let decimals = unit.decimals();
+
+    let invalid = || format!("invalid amount '{}': expected a number with an optional eth, gwei or wei suffix, e.g. 0.01eth", s);
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > decimals {
+        return Err(format!("invalid amount '{}': more than {} decimal places for {}", s, decimals, unit.name()));
+    }
+    let digits = format!("{}{}{}", whole, fraction, "0".repeat(decimals - fraction.len()));
+    U256::from_dec_str(if digits.trim_start_matches('0').is_empty() { "0" } else { &digits })
+        .map(Wei)
+        .map_err(|_| format!("invalid amount '{}': too large", s))
+}
+
+/// How wei amounts are shown in human-readable output
+#[derive(Debug, Clone)]
+pub struct AmountFormat {
+    pub unit: Unit,
+    /// Symbol used for whole-token amounts, since not every chain's native token is ETH
+    pub native_symbol: String,
+}
+
+impl Default for AmountFormat {
+    fn default() -> Self {
+        AmountFormat { unit: Unit::Wei, native_symbol: "ETH".to_string() }
+    }
+}
+
+impl AmountFormat {
+    /// The amount in the chosen unit, without a symbol
+    pub fn amount(&self, wei: Wei) -> String {
+        wei.to_eth_string(self.unit.decimals())
+    }
+
+    /// A signed amount in the chosen unit, without a symbol
+    pub fn delta(&self, delta: WeiDelta) -> String {
+        delta.to_eth_string(self.unit.decimals())
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self.unit {
+            Unit::Eth => &self.native_symbol,
+            Unit::Gwei => "gwei",
+            Unit::Wei => "wei",
+        }
+    }
+
+    /// The amount with its symbol, e.g. `1.25 ETH`
+    pub fn format(&self, wei: Wei) -> String {
+        format!("{} {}", self.amount(wei), self.symbol())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wei(amount: &str) -> Wei {
+        Wei(U256::from_dec_str(amount).unwrap())
+    }
+
+    #[test]
+    fn parses_amounts_with_and_without_units() {
+        assert_eq!(parse_units("1.5eth"), Ok(wei("1500000000000000000")));
+        assert_eq!(parse_units("250gwei"), Ok(wei("250000000000")));
+        assert_eq!(parse_units("21000"), Ok(wei("21000")));
+        assert_eq!(parse_units(" 0.01 ETH "), Ok(wei("10000000000000000")));
+        assert_eq!(parse_units(".5gwei"), Ok(wei("500000000")));
+        assert_eq!(parse_units("2."), Ok(wei("2")));
+        assert_eq!(parse_units("0.000000000000000001eth"), Ok(wei("1")));
+        // Trailing zeros past the unit's precision are still exact
+        assert_eq!(parse_units("1.50000000000000000000eth"), Ok(wei("1500000000000000000")));
+        assert_eq!(parse_units("0wei"), Ok(Wei::zero()));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        for invalid in ["", ".", "eth", "1.2.3eth", "-1eth", "1,000", "0x10"] {
+            assert!(parse_units(invalid).is_err(), "{:?}", invalid);
+        }
+        assert_eq!(parse_units("5 ether"), Err("invalid unit 'ether': expected eth, gwei or wei".to_string()));
+    }
+
+    #[test]
+    fn rejects_more_decimal_places_than_the_unit_has() {
+        assert_eq!(parse_units("1.5wei"), Err("invalid amount '1.5wei': more than 0 decimal places for wei".to_string()));
+        assert_eq!(parse_units("1.0000000001gwei"), Err("invalid amount '1.0000000001gwei': more than 9 decimal places for gwei".to_string()));
+        assert!(parse_units("0.0000000000000000001eth").is_err());
+    }
+
+    #[test]
+    fn rejects_amounts_beyond_u256() {
+        let max = U256::MAX.to_string();
+        assert_eq!(parse_units(&max), Ok(Wei(U256::MAX)));
+        // One more than the largest U256
+        let over = "115792089237316195423570985008687907853269984665640564039457584007913129639936";
+        assert_eq!(parse_units(over), Err(format!("invalid amount '{}': too large", over)));
+        assert!(parse_units("1000000000000000000000000000000000000000000000000000000000000eth").is_err());
+    }
+
+    #[test]
+    fn formats_without_trailing_zeros_and_round_trips() {
+        assert_eq!(format_units(U256::from_dec_str("1250000000000000000").unwrap(), 18), "1.25");
+        assert_eq!(format_units(U256::from(1), 18), "0.000000000000000001");
+        assert_eq!(format_units(U256::from(7) * U256::exp10(9), 9), "7");
+        assert_eq!(format_units(U256::zero(), 18), "0");
+        assert_eq!(format_units(U256::MAX, 0), U256::MAX.to_string());
+        for (amount, unit) in [("1.25", "eth"), ("0.000000001", "gwei"), ("123456789", "wei"), ("0.1", "eth")] {
+            let parsed = parse_units(&format!("{}{}", amount, unit)).unwrap();
+            assert_eq!(format_units(parsed.0, unit.parse::<Unit>().unwrap().decimals()), amount);
+        }
+    }
+}
@@ -0,0 +1,1107 @@
+use rlp::{Rlp, RlpStream};
+use serde::Serialize;
+use web3::signing::keccak256;
+use web3::types::{AccessList, Block, Transaction, TransactionReceipt, H160, H256, U256, U64};
+
+use crate::arbitrum::UNSIGNED_TX_TYPES;
+use crate::fees::DEPOSIT_TX_TYPE;
+use crate::rpc::{BlockExtras, ReceiptExtras, TransactionExtras};
+
+/// One `--verify` check: a value the node reported, and the same value computed from the rest of its data
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationCheck {
+    pub passed: bool,
+    /// The check couldn't be made at all, such as for a block holding a transaction type that can't be
+    /// encoded. It hasn't passed, but isn't counted as failed either.
+    pub skipped: bool,
+    /// As the node reported it
+    pub reported: H256,
+    /// `None` when it couldn't be computed
+    pub computed: Option<H256>,
+    /// Why the check failed other than by the two values differing, such as a transaction that doesn't
+    /// hash to its own hash, or why the value couldn't be computed
+    pub detail: Option<String>,
+}
+
+impl VerificationCheck {
+    fn new(reported: H256, computed: Result<H256, String>) -> Self {
+        match computed {
+            Ok(computed) => VerificationCheck { passed: computed == reported, skipped: false, reported, computed: Some(computed), detail: None },
+            Err(detail) => VerificationCheck { passed: false, skipped: false, reported, computed: None, detail: Some(detail) },
+        }
+    }
+
+    pub(crate) fn skipped(reported: H256, reason: String) -> Self {
+        VerificationCheck { passed: false, skipped: true, reported, computed: None, detail: Some(reason) }
+    }
+
+    pub fn failed(&self) -> bool {
+        !self.passed && !self.skipped
+    }
+
+    /// `passed`, or why not
+    pub fn describe(&self) -> String {
+        match (self.passed, self.skipped) {
+            (true, _) => "passed".to_string(),
+            (false, true) => format!("skipped: {}", self.reason()),
+            (false, false) => format!("FAILED: {}", self.reason()),
+        }
+    }
+
+    fn reason(&self) -> String {
+        match (&self.detail, self.computed) {
+            (Some(detail), _) => detail.clone(),
+            (None, Some(computed)) => format!("computed {:?}, the node reported {:?}", computed, self.reported),
+            (None, None) => "not computed".to_string(),
+        }
+    }
+}
+
+/// The block's header and contents checked against each other: that its transactions and receipts give the
+/// roots in the header, and that the header gives the block hash
+#[derive(Debug, Clone, Serialize)]
+pub struct Verification {
+    pub transactions_root: VerificationCheck,
+    pub receipts_root: VerificationCheck,
+    pub block_hash: VerificationCheck,
+}
+
+impl Verification {
+    pub fn checks(&self) -> [(&'static str, &VerificationCheck); 3] {
+        [("Transactions root", &self.transactions_root), ("Receipts root", &self.receipts_root), ("Block hash", &self.block_hash)]
+    }
+
+    /// Whether no check failed; skipped ones don't count
+    pub fn passed(&self) -> bool {
+        self.checks().iter().all(|(_, check)| !check.failed())
+    }
+
+    /// One line per failed check
+    pub fn failures(&self) -> Vec<String> {
+        self.checks().iter().filter(|(_, check)| check.failed()).map(|(name, check)| format!("{}: {}", name, check.reason())).collect()
+    }
+}
+
+/// The transaction types [`encode_transaction`] and [`encode_receipt`] know: legacy, EIP-2930, EIP-1559,
+/// EIP-4844, EIP-7702 and OP Stack deposits
+const ENCODABLE_TX_TYPES: [u64; 6] = [0, 1, 2, 3, 4, DEPOSIT_TX_TYPE];
+
+/// Why the block's transactions and receipts can't be verified, if one of them is of a type that can't
+/// be encoded
+pub(crate) fn unencodable_transaction(transactions: &[Transaction]) -> Option<String> {
+    transactions.iter().find_map(|tx| {
+        let transaction_type = tx.transaction_type.map_or(0, |kind| kind.as_u64());
+        (!ENCODABLE_TX_TYPES.contains(&transaction_type))
+            .then(|| format!("transaction {:?} is of type {:#x}, which can't be encoded", tx.hash, transaction_type))
+    })
+}
+
+/// Re-encode the block's transactions from their fields and build their trie. A block holding a type that
+/// can't be encoded is skipped rather than failed.
+pub(crate) fn check_transactions_root(block: &Block<Transaction>, extras: &BlockExtras) -> VerificationCheck {
+    if let Some(reason) = unencodable_transaction(&block.transactions) {
+        return VerificationCheck::skipped(block.transactions_root, reason);
+    }
+    let mut encoded = Vec::with_capacity(block.transactions.len());
+    let mut altered_transaction = None;
+    for (index, tx) in block.transactions.iter().enumerate() {
+        // Both come from the same array of the block response
+        let tx_extras = extras.transactions.get(index).filter(|tx_extras| tx_extras.hash == tx.hash);
+        match encode_transaction(tx, tx_extras, Encoding::Signed) {
+            Ok(encoding) => {
+                if altered_transaction.is_none() && H256(keccak256(&encoding)) != tx.hash {
+                    altered_transaction = Some(tx.hash);
+                }
+                encoded.push(encoding);
+            }
+            Err(e) => return VerificationCheck::new(block.transactions_root, Err(e)),
+        }
+    }
+    let mut check = VerificationCheck::new(block.transactions_root, Ok(ordered_trie_root(encoded)));
+    if let Some(hash) = altered_transaction {
+        check.passed = false;
+        check.detail = Some(format!("transaction {:?} doesn't encode to its hash", hash));
+    }
+    check
+}
+
+/// A receipt as [`encode_receipt`] encodes it, or why it couldn't be
+pub(crate) type EncodedReceipt = Result<Vec<u8>, String>;
+
+/// Build the receipts trie from each transaction's encoded receipt, `None` where the node had no receipt
+pub(crate) fn check_receipts_root(reported: H256, receipts: Vec<Option<EncodedReceipt>>) -> VerificationCheck {
+    let missing = receipts.iter().filter(|receipt| receipt.is_none()).count();
+    if missing > 0 {
+        return VerificationCheck::new(reported, Err(format!("the node had no receipt for {} transactions", missing)));
+    }
+    let encoded: Result<Vec<Vec<u8>>, String> = receipts.into_iter().flatten().collect();
+    VerificationCheck::new(reported, encoded.map(ordered_trie_root))
+}
+
+/// Hash the header re-encoded from its fields, down to whichever of the fields added since London it has
+pub(crate) fn check_block_hash(block: &Block<Transaction>, extras: &BlockExtras) -> VerificationCheck {
+    let reported = block.hash.unwrap_or_default();
+    VerificationCheck::new(reported, encode_header(block, extras).map(|header| H256(keccak256(&header))))
+}
+
+fn encode_header(block: &Block<Transaction>, extras: &BlockExtras) -> Result<Vec<u8>, String> {
+    let missing = |field: &str| format!("the header has no {}", field);
+    let mut stream = RlpStream::new();
+    stream.begin_unbounded_list();
+    stream.append(&block.parent_hash);
+    stream.append(&block.uncles_hash);
+    stream.append(&block.author);
+    stream.append(&block.state_root);
+    stream.append(&block.transactions_root);
+    stream.append(&block.receipts_root);
+    stream.append(&block.logs_bloom.ok_or_else(|| missing("logsBloom"))?);
+    stream.append(&block.difficulty);
+    stream.append(&block.number.ok_or_else(|| missing("number"))?);
+    stream.append(&block.gas_limit);
+    stream.append(&block.gas_used);
+    stream.append(&block.timestamp);
+    stream.append(&block.extra_data.0);
+    stream.append(&block.mix_hash.ok_or_else(|| missing("mixHash"))?);
+    stream.append(&block.nonce.ok_or_else(|| missing("nonce"))?);
+
+    // Each fork appends fields, so a header has a prefix of these
+    let added: [(&str, Option<Vec<u8>>); 6] = [
+        ("baseFeePerGas", block.base_fee_per_gas.map(|fee| rlp::encode(&fee).to_vec())),
+        ("withdrawalsRoot", extras.withdrawals_root.map(|root| rlp::encode(&root).to_vec())),
+        ("blobGasUsed", extras.blob_gas_used.map(|gas| rlp::encode(&gas).to_vec())),
+        ("excessBlobGas", extras.excess_blob_gas.map(|gas| rlp::encode(&gas).to_vec())),
+        ("parentBeaconBlockRoot", extras.parent_beacon_block_root.map(|root| rlp::encode(&root).to_vec())),
+        ("requestsHash", extras.requests_hash.map(|hash| rlp::encode(&hash).to_vec())),
+    ];
+    let present = added.iter().take_while(|(_, field)| field.is_some()).count();
+    if let Some((name, _)) = added[present..].iter().find(|(_, field)| field.is_some()) {
+        return Err(format!("the header has {} but no {}", name, added[present].0));
+    }
+    for (_, field) in added.iter().take(present) {
+        stream.append_raw(field.as_deref().unwrap_or_default(), 1);
+    }
+    stream.finalize_unbounded_list();
+    Ok(stream.out().to_vec())
+}
+
+/// The receipt as it is stored in the trie: an RLP list of the status (or, before Byzantium, the state
+/// root), cumulative gas, bloom and logs, behind the transaction's type byte for a typed transaction. A
+/// deposit's receipt adds its deposit nonce since Regolith and its receipt version since Canyon.
+pub(crate) fn encode_receipt(receipt: &TransactionReceipt, extras: &ReceiptExtras) -> EncodedReceipt {
+    let transaction_type = receipt.transaction_type.map_or(0, |kind| kind.as_u64());
+    if !ENCODABLE_TX_TYPES.contains(&transaction_type) {
+        return Err(format!("transaction {:?} is of type {:#x}, whose receipt can't be encoded", receipt.transaction_hash, transaction_type));
+    }
+    let deposit_fields: Vec<U64> = match transaction_type {
+        DEPOSIT_TX_TYPE => extras.deposit_nonce.into_iter().chain(extras.deposit_receipt_version).collect(),
+        _ => Vec::new(),
+    };
+    if extras.deposit_nonce.is_none() && !deposit_fields.is_empty() {
+        return Err(format!("the deposit receipt of transaction {:?} has a receipt version but no deposit nonce", receipt.transaction_hash));
+    }
+    let mut stream = RlpStream::new_list(4 + deposit_fields.len());
+    match (receipt.root, receipt.status) {
+        (Some(root), _) => stream.append(&root),
+        // Failure is encoded as the empty string, success as 1
+        (None, Some(status)) => stream.append(&status.as_u64()),
+        (None, None) => return Err(format!("the receipt of transaction {:?} has neither status nor root", receipt.transaction_hash)),
+    };
+    stream.append(&receipt.cumulative_gas_used);
+    stream.append(&receipt.logs_bloom);
+    stream.begin_list(receipt.logs.len());
+    for log in &receipt.logs {
+        stream.begin_list(3);
+        stream.append(&log.address);
+        stream.append_list(&log.topics);
+        stream.append(&log.data.0);
+    }
+    for field in &deposit_fields {
+        stream.append(field);
+    }
+    let mut encoding = match transaction_type {
+        0 => Vec::new(),
+        kind => vec![kind as u8],
+    };
+    encoding.extend_from_slice(&stream.out());
+    Ok(encoding)
+}
+
+/// Recover each transaction's sender from its signature, with a warning for every one that isn't the
+/// `from` the node reported or can't be recovered
+pub(crate) fn check_senders(block: &Block<Transaction>, extras: &BlockExtras) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (index, tx) in block.transactions.iter().enumerate() {
+        // Deposits and Arbitrum's system transactions aren't signed; their sender is whoever sent them on L1
+        let tx_type = tx.transaction_type.map(|tx_type| tx_type.as_u64());
+        if tx_type == Some(DEPOSIT_TX_TYPE) || tx_type.is_some_and(|tx_type| UNSIGNED_TX_TYPES.contains(&tx_type)) {
+            continue;
+        }
+        let tx_extras = extras.transactions.get(index).filter(|tx_extras| tx_extras.hash == tx.hash);
+        match recover_sender(tx, tx_extras) {
+            Ok(signer) if Some(signer) == tx.from => {}
+            Ok(signer) => warnings.push(match tx.from {
+                Some(from) => format!("transaction {:?} is signed by {:?}, but the node gives its sender as {:?}", tx.hash, signer, from),
+                None => format!("transaction {:?} is signed by {:?}, but the node gives no sender", tx.hash, signer),
+            }),
+            Err(e) => warnings.push(format!("the sender of transaction {:?} couldn't be recovered: {}", tx.hash, e)),
+        }
+    }
+    warnings
+}
+
+/// The address whose key signed `tx`. Legacy transactions sign their fields alone before EIP-155 and the
+/// chain id with them after, which a `v` of 35 or more tells; typed ones sign their type byte and fields.
+fn recover_sender(tx: &Transaction, extras: Option<&TransactionExtras>) -> Result<H160, String> {
+    let payload = encode_transaction(tx, extras, Encoding::SigningPayload)?;
+    let missing = |field: &str| format!("transaction {:?} has no {}", tx.hash, field);
+    let recovery_id = match tx.transaction_type.map_or(0, |kind| kind.as_u64()) {
+        0 => match tx.v.ok_or_else(|| missing("v"))?.as_u64() {
+            v @ (27 | 28) => v - 27,
+            v if v >= 35 => (v - 35) % 2,
+            v => return Err(format!("its v of {} is neither 27 or 28 nor an EIP-155 value", v)),
+        },
+        _ => extras.and_then(|extras| extras.y_parity).or(tx.v).ok_or_else(|| missing("yParity"))?.as_u64(),
+    };
+    let mut signature = [0u8; 64];
+    tx.r.ok_or_else(|| missing("r"))?.to_big_endian(&mut signature[..32]);
+    tx.s.ok_or_else(|| missing("s"))?.to_big_endian(&mut signature[32..]);
+    web3::signing::recover(&keccak256(&payload), &signature, recovery_id as i32).map_err(|e| format!("invalid signature: {}", e))
+}
+
+/// A transaction with its signature, as it's hashed and stored in the trie, or without, as it's signed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Signed,
+    SigningPayload,
+}
+
+/// An RLP list for a legacy transaction, the type byte followed by an RLP list for an EIP-2718 typed one
+fn encode_transaction(tx: &Transaction, extras: Option<&TransactionExtras>, encoding: Encoding) -> Result<Vec<u8>, String> {
+    let missing = |field: &str| format!("transaction {:?} has no {}", tx.hash, field);
+    let transaction_type = tx.transaction_type.map_or(0, |kind| kind.as_u64());
+    if transaction_type == DEPOSIT_TX_TYPE {
+        return match encoding {
+            Encoding::Signed => encode_deposit(tx, extras),
+            Encoding::SigningPayload => Err(format!("transaction {:?} is a deposit, which isn't signed", tx.hash)),
+        };
+    }
+    let (r, s) = (tx.r.ok_or_else(|| missing("r"))?, tx.s.ok_or_else(|| missing("s"))?);
+    let signature_fields = match encoding {
+        Encoding::Signed => 3,
+        Encoding::SigningPayload => 0,
+    };
+    if transaction_type == 0 {
+        let v = tx.v.ok_or_else(|| missing("v"))?;
+        // An EIP-155 signature covers the chain id, and zeros where r and s go
+        let chain_id = (v >= U64::from(35)).then(|| (v - 35) / 2);
+        let mut stream = RlpStream::new_list(if encoding == Encoding::Signed || chain_id.is_some() { 9 } else { 6 });
+        stream.append(&tx.nonce);
+        stream.append(&tx.gas_price.ok_or_else(|| missing("gasPrice"))?);
+        stream.append(&tx.gas);
+        append_to(&mut stream, tx);
+        stream.append(&tx.value);
+        stream.append(&tx.input.0);
+        match (encoding, chain_id) {
+            (Encoding::Signed, _) => {
+                stream.append(&v);
+                stream.append(&r);
+                stream.append(&s);
+            }
+            (Encoding::SigningPayload, Some(chain_id)) => {
+                stream.append(&chain_id);
+                stream.append(&0u8);
+                stream.append(&0u8);
+            }
+            (Encoding::SigningPayload, None) => {}
+        }
+        return Ok(stream.out().to_vec());
+    }
+
+    let chain_id = extras.and_then(|extras| extras.chain_id).ok_or_else(|| missing("chainId"))?;
+    let y_parity = extras.and_then(|extras| extras.y_parity).or(tx.v).ok_or_else(|| missing("yParity"))?;
+    let max_fees = || -> Result<(U256, U256), String> {
+        Ok((
+            tx.max_priority_fee_per_gas.ok_or_else(|| missing("maxPriorityFeePerGas"))?,
+            tx.max_fee_per_gas.ok_or_else(|| missing("maxFeePerGas"))?,
+        ))
+    };
+    let mut stream = RlpStream::new();
+    match transaction_type {
+        1 => {
+            stream.begin_list(8 + signature_fields);
+            stream.append(&chain_id);
+            stream.append(&tx.nonce);
+            stream.append(&tx.gas_price.ok_or_else(|| missing("gasPrice"))?);
+            stream.append(&tx.gas);
+            append_to(&mut stream, tx);
+            stream.append(&tx.value);
+            stream.append(&tx.input.0);
+            append_access_list(&mut stream, tx.access_list.as_ref());
+        }
+        2 => {
+            let (max_priority_fee, max_fee) = max_fees()?;
+            stream.begin_list(9 + signature_fields);
+            stream.append(&chain_id);
+            stream.append(&tx.nonce);
+            stream.append(&max_priority_fee);
+            stream.append(&max_fee);
+            stream.append(&tx.gas);
+            append_to(&mut stream, tx);
+            stream.append(&tx.value);
+            stream.append(&tx.input.0);
+            append_access_list(&mut stream, tx.access_list.as_ref());
+        }
+        3 => {
+            let (max_priority_fee, max_fee) = max_fees()?;
+            let extras = extras.ok_or_else(|| missing("blob fields"))?;
+            let max_fee_per_blob_gas = extras.max_fee_per_blob_gas.ok_or_else(|| missing("maxFeePerBlobGas"))?;
+            let blob_hashes = extras.blob_versioned_hashes.as_deref().ok_or_else(|| missing("blobVersionedHashes"))?;
+            stream.begin_list(11 + signature_fields);
+            stream.append(&chain_id);
+            stream.append(&tx.nonce);
+            stream.append(&max_priority_fee);
+            stream.append(&max_fee);
+            stream.append(&tx.gas);
+            append_to(&mut stream, tx);
+            stream.append(&tx.value);
+            stream.append(&tx.input.0);
+            append_access_list(&mut stream, tx.access_list.as_ref());
+            stream.append(&max_fee_per_blob_gas);
+            stream.append_list(blob_hashes);
+        }
+        4 => {
+            let (max_priority_fee, max_fee) = max_fees()?;
+            let authorizations = extras.and_then(|extras| extras.authorization_list.as_deref()).ok_or_else(|| missing("authorizationList"))?;
+            stream.begin_list(10 + signature_fields);
+            stream.append(&chain_id);
+            stream.append(&tx.nonce);
+            stream.append(&max_priority_fee);
+            stream.append(&max_fee);
+            stream.append(&tx.gas);
+            append_to(&mut stream, tx);
+            stream.append(&tx.value);
+            stream.append(&tx.input.0);
+            append_access_list(&mut stream, tx.access_list.as_ref());
+            stream.begin_list(authorizations.len());
+            for authorization in authorizations {
+                stream.begin_list(6);
+                stream.append(&authorization.chain_id);
+                stream.append(&authorization.address);
+                stream.append(&authorization.nonce);
+                stream.append(&authorization.y_parity);
+                stream.append(&authorization.r);
+                stream.append(&authorization.s);
+            }
+        }
+        other => return Err(format!("transaction {:?} is of type {:#x}, which can't be encoded", tx.hash, other)),
+    }
+    if encoding == Encoding::Signed {
+        stream.append(&y_parity);
+        stream.append(&r);
+        stream.append(&s);
+    }
+
+    let mut encoded = vec![transaction_type as u8];
+    encoded.extend_from_slice(&stream.out());
+    Ok(encoded)
+}
+
+/// An OP Stack deposit: its type byte and an RLP list of the L1 source hash, sender, recipient, ether
+/// minted and sent, gas, system flag and input. Nodes may leave out a zero mint and a false system flag.
+fn encode_deposit(tx: &Transaction, extras: Option<&TransactionExtras>) -> Result<Vec<u8>, String> {
+    let missing = |field: &str| format!("deposit {:?} has no {}", tx.hash, field);
+    let extras = extras.ok_or_else(|| missing("deposit fields"))?;
+    let mut stream = RlpStream::new_list(8);
+    stream.append(&extras.source_hash.ok_or_else(|| missing("sourceHash"))?);
+    stream.append(&tx.from.ok_or_else(|| missing("from"))?);
+    append_to(&mut stream, tx);
+    stream.append(&extras.mint.unwrap_or_default());
+    stream.append(&tx.value);
+    stream.append(&tx.gas);
+    stream.append(&extras.is_system_tx.unwrap_or(false));
+    stream.append(&tx.input.0);
+
+    let mut encoded = vec![DEPOSIT_TX_TYPE as u8];
+    encoded.extend_from_slice(&stream.out());
+    Ok(encoded)
+}
+
+/// A contract creation has an empty recipient
+fn append_to(stream: &mut RlpStream, tx: &Transaction) {
+    match &tx.to {
+        Some(to) => stream.append(to),
+        None => stream.append_empty_data(),
+    };
+}
+
+fn append_access_list(stream: &mut RlpStream, access_list: Option<&AccessList>) {
+    let items = access_list.map_or(&[][..], Vec::as_slice);
+    stream.begin_list(items.len());
+    for item in items {
+        stream.begin_list(2);
+        stream.append(&item.address);
+        stream.append_list(&item.storage_keys);
+    }
+}
+
+/// Root of the Merkle-Patricia trie holding `values` under the RLP encodings of their indices, as for a
+/// block's transactions or receipts
+fn ordered_trie_root(values: Vec<Vec<u8>>) -> H256 {
+    let mut entries: Vec<(Vec<u8>, Vec<u8>)> =
+        values.into_iter().enumerate().map(|(index, value)| (nibbles(&rlp::encode(&(index as u64))), value)).collect();
+    // Nodes are built over entries sorted by key, which index order isn't: 0 encodes as 0x80
+    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    match entries.is_empty() {
+        // The hash of an empty RLP string
+        true => H256(keccak256(&rlp::NULL_RLP)),
+        false => H256(keccak256(&trie_node(&entries, 0))),
+    }
+}
+
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+/// The RLP of the node holding `entries`, whose keys all agree up to `depth` nibbles
+fn trie_node(entries: &[(Vec<u8>, Vec<u8>)], depth: usize) -> Vec<u8> {
+    let mut stream = RlpStream::new();
+    if let [(key, value)] = entries {
+        stream.begin_list(2);
+        stream.append(&hex_prefix(&key[depth..], true));
+        stream.append(value);
+        return stream.out().to_vec();
+    }
+
+    // Keys sharing more nibbles make an extension over a branch
+    let (first, last) = (&entries[0].0, &entries[entries.len() - 1].0);
+    let shared = first[depth..].iter().zip(&last[depth..]).take_while(|(a, b)| a == b).count();
+    if shared > 0 {
+        stream.begin_list(2);
+        stream.append(&hex_prefix(&first[depth..depth + shared], false));
+        append_child(&mut stream, trie_node(entries, depth + shared));
+        return stream.out().to_vec();
+    }
+
+    stream.begin_list(17);
+    let mut value = None;
+    let mut rest = entries;
+    if rest[0].0.len() == depth {
+        value = Some(&rest[0].1);
+        rest = &rest[1..];
+    }
+    for nibble in 0..16u8 {
+        let count = rest.iter().take_while(|(key, _)| key[depth] == nibble).count();
+        let (children, remaining) = rest.split_at(count);
+        match children.is_empty() {
+            true => {
+                stream.append_empty_data();
+            }
+            false => append_child(&mut stream, trie_node(children, depth + 1)),
+        }
+        rest = remaining;
+    }
+    match value {
+        Some(value) => stream.append(value),
+        None => stream.append_empty_data(),
+    };
+    stream.out().to_vec()
+}
+
+/// Children under 32 bytes are inlined; longer ones are referenced by hash
+fn append_child(stream: &mut RlpStream, node: Vec<u8>) {
+    match node.len() < 32 {
+        true => stream.append_raw(&node, 1),
+        false => stream.append(&keccak256(&node).as_slice()),
+    };
+}
+
+/// Nibbles packed two to a byte behind a flag nibble telling leaves from extensions and odd lengths from even
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 } + (nibbles.len() % 2) as u8;
+    let (first, rest) = match nibbles.len() % 2 {
+        1 => ((flag << 4) | nibbles[0], &nibbles[1..]),
+        _ => (flag << 4, nibbles),
+    };
+    std::iter::once(first).chain(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1])).collect()
+}
+
+/// The nonce and balance stored for an account in the state trie
+pub(crate) type ProvenAccount = (U256, U256);
+
+/// Walk an `eth_getProof` account proof, the trie nodes from `state_root` down to `address`, checking every
+/// node against the hash its parent references it by. Gives the account's nonce and balance, or `None`
+/// when the proof shows the address has no account.
+pub(crate) fn verify_account_proof(state_root: H256, address: H160, proof: &[Vec<u8>]) -> Result<Option<ProvenAccount>, String> {
+    let path = nibbles(&keccak256(address.as_bytes()));
+    let mut nodes = proof.iter().enumerate();
+    let mut depth = 0;
+    let mut reference = NodeRef::Hash(state_root);
+    loop {
+        let node = match reference {
+            NodeRef::Inline(node) => node,
+            NodeRef::Hash(hash) => {
+                let (index, node) = nodes.next().ok_or("the proof ends before reaching the account")?;
+                if H256(keccak256(node)) != hash {
+                    return Err(format!("proof node {} doesn't match the hash {:?} it's referenced by", index, hash));
+                }
+                node.clone()
+            }
+        };
+
+        let node = Rlp::new(&node);
+        // The only empty node is the root of an empty trie
+        let count = match node.is_data() && node.is_empty() {
+            true => 0,
+            false => node.item_count().map_err(|e| format!("undecodable proof node: {}", e))?,
+        };
+        let child = match count {
+            0 => None,
+            17 => {
+                let nibble = *path.get(depth).ok_or("the proof has a branch node where the account should be")?;
+                depth += 1;
+                Some(node.at(nibble as usize).map_err(|e| e.to_string())?)
+            }
+            2 => {
+                let (key, leaf) = decode_hex_prefix(node.at(0).and_then(|key| key.data()).map_err(|e| e.to_string())?)?;
+                match leaf {
+                    true if key == path[depth..] => {
+                        let account = node.at(1).and_then(|value| value.data()).map_err(|e| e.to_string())?;
+                        if nodes.next().is_some() {
+                            return Err("the proof continues past the account".into());
+                        }
+                        return decode_account(account).map(Some);
+                    }
+                    // A leaf for another key, or an extension leading elsewhere, proves the absence
+                    true => None,
+                    false if !path[depth..].starts_with(&key) => None,
+                    false => {
+                        depth += key.len();
+                        Some(node.at(1).map_err(|e| e.to_string())?)
+                    }
+                }
+            }
+            count => return Err(format!("a proof node has {} items; trie nodes have 2 or 17", count)),
+        };
+
+        reference = match child {
+            Some(child) if child.is_list() => NodeRef::Inline(child.as_raw().to_vec()),
+            Some(child) if !child.is_empty() => match child.data().map_err(|e| e.to_string())? {
+                hash if hash.len() == 32 => NodeRef::Hash(H256::from_slice(hash)),
+                hash => return Err(format!("a proof node references a child by {} bytes instead of a hash", hash.len())),
+            },
+            // An empty slot where the account's path leads
+            _ => {
+                if nodes.next().is_some() {
+                    return Err("the proof continues past where it shows the account is absent".into());
+                }
+                return Ok(None);
+            }
+        };
+    }
+}
+
+/// How a trie node refers to a child: nodes under 32 bytes sit inside their parent instead of having a hash,
+/// and a proof entry, of their own
+enum NodeRef {
+    Hash(H256),
+    Inline(Vec<u8>),
+}
+
+/// The nibbles behind a [`hex_prefix`] encoding, and whether it was a leaf's
+fn decode_hex_prefix(encoded: &[u8]) -> Result<(Vec<u8>, bool), String> {
+    let (&first, rest) = encoded.split_first().ok_or("a proof node has an empty path")?;
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(format!("a proof node's path has the invalid flag {}", flag));
+    }
+    let mut key = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag % 2 == 1 {
+        key.push(first & 0x0f);
+    }
+    key.extend(nibbles(rest));
+    Ok((key, flag >= 2))
+}
+
+/// Nonce and balance from an account's `[nonce, balance, storageRoot, codeHash]` leaf value
+fn decode_account(value: &[u8]) -> Result<ProvenAccount, String> {
+    let account = Rlp::new(value);
+    match account.item_count() {
+        Ok(4) => {}
+        _ => return Err("the proven account isn't a list of nonce, balance, storage root and code hash".into()),
+    }
+    let field = |index: usize| account.val_at::<U256>(index).map_err(|e| format!("undecodable account field: {}", e));
+    Ok((field(0)?, field(1)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::SecretKey;
+    use web3::signing::{Key, SecretKeyRef};
+    use web3::types::{AccessListItem, Log, Proof, TransactionParameters, H2048};
+    use web3::Web3;
+
+    use crate::rpc::RpcAuthorization;
+
+    /// The key of web3.js's `signTransaction` example
+    const KEY: [u8; 32] = [
+        0x4c, 0x08, 0x83, 0xa6, 0x91, 0x02, 0x93, 0x7d, 0x62, 0x31, 0x47, 0x1b, 0x5d, 0xbb, 0x62, 0x04,
+        0xfe, 0x51, 0x29, 0x61, 0x70, 0x82, 0x79, 0x2a, 0xe4, 0x68, 0xd0, 0x1a, 0x3f, 0x36, 0x23, 0x18,
+    ];
+
+    fn key() -> SecretKey {
+        SecretKey::from_slice(&KEY).unwrap()
+    }
+
+    fn signer() -> H160 {
+        SecretKeyRef::new(&key()).address()
+    }
+
+    fn h256(hex: &str) -> H256 {
+        hex.parse().unwrap()
+    }
+
+    fn transaction(transaction_type: u64) -> Transaction {
+        Transaction {
+            nonce: U256::from(7),
+            to: Some(H160::from_low_u64_be(0xb0b)),
+            value: U256::from(10).pow(U256::from(18)),
+            gas: U256::from(21_000),
+            gas_price: Some(U256::from(30_000_000_000u64)),
+            max_priority_fee_per_gas: (transaction_type >= 2).then(|| U256::from(1_000_000_000u64)),
+            max_fee_per_gas: (transaction_type >= 2).then(|| U256::from(30_000_000_000u64)),
+            access_list: (transaction_type >= 1).then(|| {
+                vec![AccessListItem { address: H160::from_low_u64_be(0x70ce), storage_keys: vec![H256::from_low_u64_be(1)] }]
+            }),
+            transaction_type: (transaction_type > 0).then(|| U64::from(transaction_type)),
+            ..Default::default()
+        }
+    }
+
+    fn extras(tx: &Transaction) -> TransactionExtras {
+        TransactionExtras {
+            hash: tx.hash,
+            max_fee_per_blob_gas: None,
+            blob_versioned_hashes: None,
+            chain_id: Some(U256::one()),
+            y_parity: None,
+            authorization_list: None,
+            mint: None,
+            source_hash: None,
+            is_system_tx: None,
+        }
+    }
+
+    /// Sign `tx` with [`KEY`] over the payload `recover_sender` will hash. A legacy transaction is signed
+    /// the pre-EIP-155 way.
+    fn sign(tx: &mut Transaction, extras: &mut TransactionExtras) {
+        // Encoding wants a signature even when it leaves it out
+        (tx.v, tx.r, tx.s) = (Some(U64::from(27)), Some(U256::zero()), Some(U256::zero()));
+        let payload = encode_transaction(tx, Some(extras), Encoding::SigningPayload).unwrap();
+        let signature = SecretKeyRef::new(&key()).sign(&keccak256(&payload), None).unwrap();
+        let v = match tx.transaction_type {
+            Some(_) => signature.v - 27,
+            None => signature.v,
+        };
+        tx.v = Some(U64::from(v));
+        tx.r = Some(U256::from_big_endian(signature.r.as_bytes()));
+        tx.s = Some(U256::from_big_endian(signature.s.as_bytes()));
+        if tx.transaction_type.is_some() {
+            extras.y_parity = tx.v;
+        }
+        tx.hash = H256(keccak256(&encode_transaction(tx, Some(extras), Encoding::Signed).unwrap()));
+        extras.hash = tx.hash;
+    }
+
+    #[test]
+    fn recovers_a_pre_eip155_legacy_sender() {
+        let mut tx = transaction(0);
+        let mut extras = extras(&tx);
+        extras.chain_id = None;
+        sign(&mut tx, &mut extras);
+        assert!(matches!(tx.v.unwrap().as_u64(), 27 | 28));
+        assert_eq!(recover_sender(&tx, Some(&extras)), Ok(signer()));
+    }
+
+    #[test]
+    fn recovers_an_eip155_sender_from_a_published_vector() {
+        let tx = Transaction {
+            nonce: U256::zero(),
+            to: Some("0xF0109fC8DF283027b6285cc889F5aA624EaC1F55".parse().unwrap()),
+            value: U256::from(1_000_000_000),
+            gas: U256::from(2_000_000),
+            gas_price: Some(U256::from(234_567_897_654_321u64)),
+            v: Some(U64::from(0x25)),
+            r: Some(U256::from_big_endian(h256("09ebb6ca057a0535d6186462bc0b465b561c94a295bdb0621fc19208ab149a9c").as_bytes())),
+            s: Some(U256::from_big_endian(h256("440ffd775ce91a833ab410777204d5341a6f9fa91216a6f3ee2c051fea6a0428").as_bytes())),
+            ..Default::default()
+        };
+        let encoded = encode_transaction(&tx, None, Encoding::Signed).unwrap();
+        assert_eq!(H256(keccak256(&encoded)), h256("d8f64a42b57be0d565f385378db2f6bf324ce14a594afc05de90436e9ce01f60"));
+        assert_eq!(recover_sender(&tx, None), Ok("0x2c7536E3605D9C16a7a3D7b1898e529396a65c23".parse().unwrap()));
+    }
+
+    /// web3's own signer encodes access-list and dynamic-fee transactions independently of ours
+    #[tokio::test]
+    async fn recovers_eip2930_and_eip1559_senders_signed_by_web3() {
+        let web3 = Web3::new(web3::transports::Http::new("http://127.0.0.1:1").unwrap());
+        for transaction_type in [1, 2] {
+            let mut tx = transaction(transaction_type);
+            let parameters = TransactionParameters {
+                nonce: Some(tx.nonce),
+                to: tx.to,
+                gas: tx.gas,
+                gas_price: tx.gas_price,
+                value: tx.value,
+                chain_id: Some(1),
+                transaction_type: tx.transaction_type,
+                access_list: tx.access_list.clone(),
+                max_fee_per_gas: tx.max_fee_per_gas,
+                max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+                ..Default::default()
+            };
+            let signed = web3.accounts().sign_transaction(parameters, &key()).await.unwrap();
+            tx.v = Some(U64::from(signed.v));
+            tx.r = Some(U256::from_big_endian(signed.r.as_bytes()));
+            tx.s = Some(U256::from_big_endian(signed.s.as_bytes()));
+            let extras = extras(&tx);
+
+            let encoded = encode_transaction(&tx, Some(&extras), Encoding::Signed).unwrap();
+            assert_eq!(encoded, signed.raw_transaction.0, "type {}", transaction_type);
+            assert_eq!(recover_sender(&tx, Some(&extras)), Ok(signer()), "type {}", transaction_type);
+        }
+    }
+
+    #[test]
+    fn recovers_an_eip4844_sender_over_its_blob_fields() {
+        let mut tx = transaction(3);
+        let mut extras = extras(&tx);
+        extras.max_fee_per_blob_gas = Some(U256::from(1_000_000));
+        extras.blob_versioned_hashes = Some(vec![H256::from_low_u64_be(0x0100)]);
+        sign(&mut tx, &mut extras);
+        assert_eq!(recover_sender(&tx, Some(&extras)), Ok(signer()));
+
+        extras.blob_versioned_hashes = Some(vec![H256::from_low_u64_be(0x0101)]);
+        assert_ne!(recover_sender(&tx, Some(&extras)), Ok(signer()));
+    }
+
+    #[test]
+    fn recovers_an_eip7702_sender_over_its_authorizations() {
+        let mut tx = transaction(4);
+        let mut extras = extras(&tx);
+        extras.authorization_list = Some(vec![RpcAuthorization {
+            chain_id: U256::one(),
+            address: H160::from_low_u64_be(0xde1e),
+            nonce: U256::from(8),
+            y_parity: U64::zero(),
+            r: U256::from(1),
+            s: U256::from(2),
+        }]);
+        sign(&mut tx, &mut extras);
+        assert_eq!(recover_sender(&tx, Some(&extras)), Ok(signer()));
+
+        extras.authorization_list.as_mut().unwrap()[0].address = H160::from_low_u64_be(0xbad);
+        assert_ne!(recover_sender(&tx, Some(&extras)), Ok(signer()));
+    }
+
+    #[test]
+    fn warns_of_a_sender_the_signature_doesnt_match() {
+        let mut tx = transaction(2);
+        let mut tx_extras = extras(&tx);
+        sign(&mut tx, &mut tx_extras);
+        tx.from = Some(signer());
+        let mut block = Block { transactions: vec![tx.clone()], ..Default::default() };
+        let extras = BlockExtras { transactions: vec![tx_extras], ..Default::default() };
+        assert!(check_senders(&block, &extras).is_empty());
+
+        block.transactions[0].from = Some(H160::from_low_u64_be(0xa11ce));
+        let warnings = check_senders(&block, &extras);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("but the node gives its sender as"), "{}", warnings[0]);
+    }
+
+    /// An L1 attributes deposit and a user deposit minting ether to create a contract, with their hashes
+    /// and transactions root as op-alloy computes them
+    fn deposits() -> (Block<Transaction>, BlockExtras) {
+        let deposit = |from: H160, to: Option<H160>, value: u64, gas: u64, input: &str, hash: &str| Transaction {
+            hash: h256(hash),
+            from: Some(from),
+            to,
+            value: U256::from(value),
+            gas: U256::from(gas),
+            input: serde_json::from_value(serde_json::json!(format!("0x{}", input))).unwrap(),
+            transaction_type: Some(U64::from(DEPOSIT_TX_TYPE)),
+            ..Default::default()
+        };
+        let attributes = deposit(
+            "0xdeaddeaddeaddeaddeaddeaddeaddeaddead0001".parse().unwrap(),
+            Some("0x4200000000000000000000000000000000000015".parse().unwrap()),
+            0,
+            1_000_000,
+            "440a5e20000008dd00101c1200000000000000030000000066f6ab",
+            "81a47d544623cb105beceda16c4183d1c6966289736e5a1528bbba147c3523c9",
+        );
+        let user = deposit(H160::from_low_u64_be(0xa11ce), None, 500_000_000_000_000_000, 100_000, "6000", "54841c9cb5f1e4df453fd5b319745d6e8fb91b731abfbae56b86c47888180317");
+        let mut attributes_extras = extras(&attributes);
+        attributes_extras.source_hash = Some(H256::repeat_byte(1));
+        let mut user_extras = extras(&user);
+        (user_extras.source_hash, user_extras.mint, user_extras.is_system_tx) = (Some(H256::repeat_byte(2)), Some(U256::exp10(18)), Some(true));
+
+        let block = Block {
+            transactions_root: h256("b3b3dc0ce130695290a46ad16a991912a5563d280a6d1de75391ac0b8a1e01bc"),
+            transactions: vec![attributes, user],
+            ..Default::default()
+        };
+        (block, BlockExtras { transactions: vec![attributes_extras, user_extras], ..Default::default() })
+    }
+
+    /// The bloom filter of a log's address and topics
+    fn bloom(log: &Log) -> H2048 {
+        let mut bloom = H2048::zero();
+        for item in std::iter::once(log.address.as_bytes()).chain(log.topics.iter().map(H256::as_bytes)) {
+            let hash = keccak256(item);
+            for pair in hash[..6].chunks(2) {
+                let bit = (usize::from(pair[0]) << 8 | usize::from(pair[1])) & 2047;
+                bloom.0[255 - bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        bloom
+    }
+
+    #[test]
+    fn encodes_op_stack_deposits() {
+        let (block, extras) = deposits();
+        for (tx, tx_extras) in block.transactions.iter().zip(&extras.transactions) {
+            let encoded = encode_transaction(tx, Some(tx_extras), Encoding::Signed).unwrap();
+            assert_eq!(H256(keccak256(&encoded)), tx.hash);
+        }
+        let check = check_transactions_root(&block, &extras);
+        assert!(check.passed, "{}", check.describe());
+
+        // Deposits come unsigned, so there is no sender to recover
+        assert!(check_senders(&block, &extras).is_empty());
+        assert!(encode_transaction(&block.transactions[0], Some(&extras.transactions[0]), Encoding::SigningPayload).is_err());
+    }
+
+    #[test]
+    fn encodes_deposit_receipts_with_their_nonce_and_version() {
+        let log: Log = serde_json::from_value(serde_json::json!({
+            "address": "0x4200000000000000000000000000000000000015",
+            "topics": [H256::from_low_u64_be(1)],
+            "data": "0x07070707",
+        }))
+        .unwrap();
+        // Logs, cumulative gas, deposit nonce and receipt version. Canyon adds the receipt version to the
+        // nonce Regolith added, and older deposits have neither.
+        type DepositReceipt = (Vec<Log>, u64, Option<u64>, Option<u64>);
+        let receipts: [DepositReceipt; 3] = [(vec![log], 45_000, Some(7), Some(1)), (vec![], 90_000, Some(8), None), (vec![], 135_000, None, None)];
+        let encode = |receipts: &[DepositReceipt]| {
+            let encoded = receipts.iter().map(|(logs, cumulative_gas_used, deposit_nonce, deposit_receipt_version)| {
+                let receipt = TransactionReceipt {
+                    status: Some(U64::one()),
+                    cumulative_gas_used: U256::from(*cumulative_gas_used),
+                    logs_bloom: logs.first().map(bloom).unwrap_or_default(),
+                    logs: logs.clone(),
+                    transaction_type: Some(U64::from(DEPOSIT_TX_TYPE)),
+                    ..Default::default()
+                };
+                let extras = ReceiptExtras { deposit_nonce: deposit_nonce.map(U64::from), deposit_receipt_version: deposit_receipt_version.map(U64::from), ..Default::default() };
+                Some(encode_receipt(&receipt, &extras))
+            });
+            check_receipts_root(h256("92d87a63a335060c87aa061b7335846324e32d0bdad9885e170dd056f63c687e"), encoded.collect())
+        };
+        let check = encode(&receipts);
+        assert!(check.passed, "{}", check.describe());
+
+        let mut without_version = receipts.clone();
+        without_version[0].3 = None;
+        assert!(encode(&without_version).failed());
+    }
+
+    #[test]
+    fn a_transaction_type_that_cant_be_encoded_skips_the_checks() {
+        let (mut block, extras) = deposits();
+        // An Arbitrum internal transaction
+        block.transactions[1].transaction_type = Some(U64::from(0x6a));
+        let transactions_root = check_transactions_root(&block, &extras);
+        assert!(transactions_root.skipped && !transactions_root.failed());
+        assert!(transactions_root.describe().starts_with("skipped: "), "{}", transactions_root.describe());
+
+        let reason = unencodable_transaction(&block.transactions).unwrap();
+        let receipts_root = VerificationCheck::skipped(block.receipts_root, reason);
+        let verification = Verification { transactions_root, receipts_root, block_hash: VerificationCheck::new(H256::zero(), Ok(H256::zero())) };
+        assert!(verification.passed());
+        assert!(verification.failures().is_empty());
+    }
+
+    /// The state root of a trie of 49 accounts built with alloy-trie, and an `eth_getProof` response
+    /// from it: `present` for an account in it, `absent` for an address that isn't
+    fn account_proof(which: &str) -> (H256, H160, Vec<Vec<u8>>) {
+        let fixture: serde_json::Value = serde_json::from_str(include_str!("../tests/fixtures/account_proofs.json")).unwrap();
+        let proof: Proof = serde_json::from_value(fixture[which].clone()).unwrap();
+        let state_root = serde_json::from_value(fixture["stateRoot"].clone()).unwrap();
+        let address = serde_json::from_value(fixture[which]["address"].clone()).unwrap();
+        (state_root, address, proof.account_proof.into_iter().map(|node| node.0).collect())
+    }
+
+    #[test]
+    fn proves_an_existing_account() {
+        let (state_root, address, proof) = account_proof("present");
+        assert_eq!(proof.len(), 3);
+        assert_eq!(verify_account_proof(state_root, address, &proof), Ok(Some((U256::from(3), U256::from(125) * U256::exp10(17)))));
+    }
+
+    #[test]
+    fn proves_an_absent_account() {
+        let (state_root, address, proof) = account_proof("absent");
+        assert_eq!(verify_account_proof(state_root, address, &proof), Ok(None));
+
+        // The same nodes don't prove the absence of the account they lead to
+        let (_, present, _) = account_proof("present");
+        assert!(verify_account_proof(state_root, present, &proof).is_err());
+    }
+
+    #[test]
+    fn rejects_a_tampered_proof() {
+        let (state_root, address, proof) = account_proof("present");
+        // Raising the balance in the leaf changes its hash, which the branch above it references
+        let mut tampered = proof.clone();
+        // The leaf ends with the balance's last byte before the storage root and code hash
+        let leaf = tampered.last_mut().unwrap();
+        let balance = leaf.len() - 67;
+        leaf[balance] += 1;
+        let error = verify_account_proof(state_root, address, &tampered).unwrap_err();
+        assert!(error.starts_with("proof node 2 doesn't match the hash"), "{}", error);
+
+        let error = verify_account_proof(H256::repeat_byte(0xab), address, &proof).unwrap_err();
+        assert!(error.starts_with("proof node 0 doesn't match the hash"), "{}", error);
+
+        let error = verify_account_proof(state_root, address, &proof[..2]).unwrap_err();
+        assert_eq!(error, "the proof ends before reaching the account");
+    }
+
+    /// A block built and hashed with alloy, as a node returns it, with its receipts when the file has them.
+    /// Those named for a fork have its header fields and hold one transaction of each type and a pre-EIP-155
+    /// legacy one; `many_transactions` holds twenty, enough for the trie to need extension nodes.
+    fn block_fixture(name: &str) -> (Block<Transaction>, BlockExtras, Vec<(TransactionReceipt, ReceiptExtras)>) {
+        let fixture = std::fs::read_to_string(format!("tests/fixtures/verify/{}.json", name)).unwrap();
+        let fixture: serde_json::Value = serde_json::from_str(&fixture).unwrap();
+        fn parse<T: serde::de::DeserializeOwned>(value: &serde_json::Value) -> T {
+            serde_json::from_value(value.clone()).unwrap()
+        }
+        let receipts = fixture["receipts"].as_array().map_or(Vec::new(), |receipts| receipts.iter().map(|receipt| (parse(receipt), parse(receipt))).collect());
+        (parse(&fixture["block"]), parse(&fixture["block"]), receipts)
+    }
+
+    #[test]
+    fn encodes_the_eip155_example_transaction() {
+        let tx = Transaction {
+            nonce: U256::from(9),
+            to: Some(H160::repeat_byte(0x35)),
+            value: U256::exp10(18),
+            gas: U256::from(21_000),
+            gas_price: Some(U256::from(20_000_000_000u64)),
+            v: Some(U64::from(37)),
+            r: Some(U256::from_big_endian(h256("28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276").as_bytes())),
+            s: Some(U256::from_big_endian(h256("67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").as_bytes())),
+            ..Default::default()
+        };
+        let signed = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+        let encoded = encode_transaction(&tx, None, Encoding::Signed).unwrap();
+        assert_eq!(encoded.iter().map(|byte| format!("{:02x}", byte)).collect::<String>(), signed);
+
+        // The example's signing payload is its fields, chain id 1 and two zeros
+        let payload = encode_transaction(&tx, None, Encoding::SigningPayload).unwrap();
+        assert_eq!(H256(keccak256(&payload)), h256("daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"));
+    }
+
+    #[test]
+    fn encodes_each_transaction_type_to_its_hash() {
+        let (block, extras, _) = block_fixture("cancun");
+        let types: Vec<u64> = block.transactions.iter().map(|tx| tx.transaction_type.unwrap().as_u64()).collect();
+        assert_eq!(types, [0, 1, 2, 3, 4, 0]);
+        for (tx, tx_extras) in block.transactions.iter().zip(&extras.transactions) {
+            let encoded = encode_transaction(tx, Some(tx_extras), Encoding::Signed).unwrap();
+            assert_eq!(H256(keccak256(&encoded)), tx.hash, "type {:?}", tx.transaction_type);
+            assert_eq!(recover_sender(tx, Some(tx_extras)).ok(), tx.from, "type {:?}", tx.transaction_type);
+        }
+    }
+
+    #[test]
+    fn rebuilds_the_transactions_root() {
+        for name in ["cancun", "many_transactions"] {
+            let (block, extras, _) = block_fixture(name);
+            let check = check_transactions_root(&block, &extras);
+            assert!(check.passed, "{}: {}", name, check.describe());
+        }
+
+        // Transactions in another order give another root
+        let (mut block, mut extras, _) = block_fixture("cancun");
+        block.transactions.swap(0, 1);
+        extras.transactions.swap(0, 1);
+        let check = check_transactions_root(&block, &extras);
+        assert!(check.failed() && check.detail.is_none(), "{}", check.describe());
+
+        // A changed field no longer hashes to the transaction's hash
+        block.transactions.swap(0, 1);
+        extras.transactions.swap(0, 1);
+        block.transactions[2].value += U256::one();
+        let check = check_transactions_root(&block, &extras);
+        assert_eq!(check.detail, Some(format!("transaction {:?} doesn't encode to its hash", block.transactions[2].hash)));
+    }
+
+    const ERAS: [&str; 5] = ["berlin", "london", "shanghai", "cancun", "prague"];
+
+    fn receipts_root(block: &Block<Transaction>, receipts: &[(TransactionReceipt, ReceiptExtras)]) -> VerificationCheck {
+        let encoded = receipts.iter().map(|(receipt, extras)| Some(encode_receipt(receipt, extras))).collect();
+        check_receipts_root(block.receipts_root, encoded)
+    }
+
+    #[test]
+    fn rebuilds_the_receipts_root_in_every_era() {
+        for era in ERAS {
+            let (block, _, receipts) = block_fixture(era);
+            let check = receipts_root(&block, &receipts);
+            assert!(check.passed, "{}: {}", era, check.describe());
+        }
+
+        let (block, _, mut receipts) = block_fixture("cancun");
+        receipts[0].0.cumulative_gas_used += U256::one();
+        assert!(receipts_root(&block, &receipts).failed());
+
+        let mut encoded: Vec<_> = receipts.iter().map(|(receipt, extras)| Some(encode_receipt(receipt, extras))).collect();
+        encoded[1] = None;
+        let check = check_receipts_root(block.receipts_root, encoded);
+        assert_eq!(check.detail.as_deref(), Some("the node had no receipt for 1 transactions"));
+    }
+
+    #[test]
+    fn rehashes_headers_of_every_era() {
+        for era in ERAS {
+            let (block, extras, _) = block_fixture(era);
+            let check = check_block_hash(&block, &extras);
+            assert!(check.passed, "{}: {}", era, check.describe());
+        }
+
+        let (mut block, mut extras, _) = block_fixture("cancun");
+        block.gas_used += U256::one();
+        assert!(check_block_hash(&block, &extras).failed());
+
+        // A later field without an earlier one can't be encoded
+        block.gas_used -= U256::one();
+        extras.blob_gas_used = None;
+        let check = check_block_hash(&block, &extras);
+        assert_eq!(check.detail.as_deref(), Some("the header has excessBlobGas but no blobGasUsed"));
+    }
+
+    /// Proof-of-work headers from before London, with their fields and hashes as mainnet has them
+    #[test]
+    fn rehashes_mainnet_genesis_and_its_first_block() {
+        let headers: Vec<Block<Transaction>> = serde_json::from_str(include_str!("../tests/fixtures/verify/mainnet_headers.json")).unwrap();
+        for block in &headers {
+            let check = check_block_hash(block, &BlockExtras::default());
+            assert!(check.passed, "block {:?}: {}", block.number, check.describe());
+        }
+        assert_eq!(headers[1].parent_hash, headers[0].hash.unwrap());
+    }
+}
@@ -0,0 +1,95 @@
+use futures::stream::{self, LocalBoxStream, Stream, StreamExt};
+use web3::transports::Either;
+use web3::{BatchTransport, Web3};
+use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::reorg::{self, ChainTracker};
+use crate::{analyze_block_cached, AccountStateCache, AnalysisOptions, BlockEvent, BlockRef, RpcTransport};
+
+/// New head block numbers: a `newHeads` subscription over WebSocket, `eth_blockNumber` polling over HTTP.
+/// The stream may repeat or skip numbers; `watch_blocks` dedupes and catches up.
+pub async fn new_heads(
+    web3: &Web3<RpcTransport>,
+    poll_interval: Duration,
+) -> Result<LocalBoxStream<'static, web3::Result<u64>>, Box<dyn Error>> {
+    match web3.transport() {
+        Either::Right(ws) => {
+            let heads = Web3::new(ws.clone()).eth_subscribe().subscribe_new_heads().await?;
+            Ok(heads
+                .filter_map(|header| async move {
+                    match header {
+                        Ok(header) => header.number.map(|n| Ok(n.as_u64())),
+                        Err(e) => Some(Err(e)),
+                    }
+                })
+                .boxed_local())
+        }
+        Either::Left(_) => {
+            let web3 = web3.clone();
+            let mut interval = tokio::time::interval(poll_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            Ok(stream::unfold((web3, interval), |(web3, mut interval)| async move {
+                interval.tick().await;
+                let head = web3.eth().block_number().await.map(|n| n.as_u64());
+                Some((head, (web3, interval)))
+            })
+            .boxed_local())
+        }
+    }
+}
+
+/// Analyze every new block announced by `heads` until the stream ends or `shutdown` resolves.
+/// A block already being analyzed when `shutdown` resolves is finished and handed to `on_block` first.
+/// As with ranges, a failed block only stops the watch if `on_block` returns the error, and reorgs are
+/// reported before the new canonical blocks are re-analyzed.
+pub async fn watch_blocks<T, S, F>(
+    web3: &Web3<T>,
+    mut heads: S,
+    options: &AnalysisOptions,
+    shutdown: impl Future<Output = ()>,
+    mut on_block: F,
+) -> Result<(), Box<dyn Error>>
+where
+    T: BatchTransport,
+    S: Stream<Item = web3::Result<u64>> + Unpin,
+    F: FnMut(u64, Result<BlockEvent, Box<dyn Error>>) -> Result<(), Box<dyn Error>>,
+{
+    tokio::pin!(shutdown);
+
+    let mut cache = AccountStateCache::default();
+    let mut tracker = ChainTracker::new(options.reorg_depth);
+    let mut last_analyzed: Option<u64> = None;
+
+    loop {
+        let head = tokio::select! {
+            biased;
+            _ = &mut shutdown => return Ok(()),
+            head = heads.next() => head,
+        };
+        let head = match head {
+            Some(head) => head?,
+            None => return Ok(()),
+        };
+
+        // Pollers see the same head repeatedly; skip anything already analyzed.
+        // When the head jumps ahead, analyze the skipped blocks in order.
+        let start = match last_analyzed {
+            Some(last) if head <= last => continue,
+            Some(last) => last + 1,
+            None => head,
+        };
+
+        for block_number in start..=head {
+            let analysis = analyze_block_cached(web3, BlockRef::Number(block_number), options, &mut cache).await;
+            reorg::deliver(web3, options, &mut tracker, &mut cache, block_number, analysis, &mut on_block).await?;
+            last_analyzed = Some(block_number);
+
+            if futures::poll!(&mut shutdown).is_ready() {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use serde::Serialize;
+use serde_json::json;
+use web3::types::{BlockNumber, H160, H256, U256, U64};
+use web3::{BatchTransport, Web3};
+use std::error::Error;
+
+use crate::{batch, AnalysisOptions};
+
+/// A watched storage slot whose value differs between the baseline and the analyzed block
+#[derive(Debug, Clone, Serialize)]
+pub struct SlotChange {
+    #[serde(serialize_with = "crate::address::serialize")]
+    pub address: H160,
+    pub slot: H256,
+    pub before: H256,
+    pub after: H256,
+}
+
+/// An `ADDRESS:SLOT` pair, the slot as 0x-prefixed hex of up to 32 bytes or as a decimal index
+pub fn parse_watched_slot(s: &str) -> Result<(H160, H256), String> {
+    let (address, slot) = s.split_once(':').ok_or_else(|| format!("'{}' isn't ADDRESS:SLOT", s))?;
+    let address = address.trim().to_lowercase().parse().map_err(|_| format!("invalid address '{}'", address))?;
+    let slot = slot.trim();
+    let index = match slot.strip_prefix("0x").or_else(|| slot.strip_prefix("0X")) {
+        Some(hex) if !hex.is_empty() && hex.len() <= 64 => U256::from_str_radix(hex, 16).ok(),
+        Some(_) => None,
+        None => U256::from_dec_str(slot).ok(),
+    };
+    let index = index.ok_or_else(|| format!("invalid slot '{}': expected hex such as 0x36 or a decimal index", slot))?;
+    let mut bytes = [0u8; 32];
+    index.to_big_endian(&mut bytes);
+    Ok((address, H256(bytes)))
+}
+
+/// Read each of `options.watched_slots` at `baseline` and at `block` with `eth_getStorageAt`, batched,
+/// and keep those that changed. Without a baseline, the genesis block's slots start at zero.
+pub(crate) async fn read_watched_slots<T: BatchTransport>(
+    web3: &Web3<T>,
+    baseline: Option<u64>,
+    block: u64,
+    options: &AnalysisOptions,
+) -> Result<Vec<SlotChange>, Box<dyn Error>> {
+    if options.watched_slots.is_empty() {
+        return Ok(Vec::new());
+    }
+    let blocks: Vec<u64> = baseline.into_iter().chain([block]).collect();
+    let calls = options
+        .watched_slots
+        .iter()
+        .flat_map(|(address, slot)| {
+            blocks.iter().map(move |block| {
+                let tag = json!(BlockNumber::Number(U64::from(*block)));
+                ("eth_getStorageAt", vec![json!(address), json!(U256::from_big_endian(slot.as_bytes())), tag])
+            })
+        })
+        .collect();
+    let values = batch::call_batched(web3, calls, options.batch_size, options.concurrency, |_| {}).await?;
+
+    let mut changes = Vec::new();
+    for ((address, slot), values) in options.watched_slots.iter().zip(values.chunks(blocks.len())) {
+        let values: Vec<H256> = values.iter().map(storage_word).collect::<Result<_, _>>()?;
+        let (before, after) = match values[..] {
+            [before, after] => (before, after),
+            [after] => (H256::zero(), after),
+            _ => unreachable!("a value per block"),
+        };
+        if before != after {
+            changes.push(SlotChange { address: *address, slot: *slot, before, after });
+        }
+    }
+    Ok(changes)
+}
+
+/// Nodes give the word as 32 bytes of hex, though some drop the leading zeros
+pub(crate) fn storage_word(value: &serde_json::Value) -> Result<H256, String> {
+    let hex = value.as_str().and_then(|hex| hex.strip_prefix("0x")).ok_or_else(|| format!("invalid storage value {}", value))?;
+    let word = U256::from_str_radix(if hex.is_empty() { "0" } else { hex }, 16).map_err(|_| format!("invalid storage value {}", value))?;
+    let mut bytes = [0u8; 32];
+    word.to_big_endian(&mut bytes);
+    Ok(H256(bytes))
+}
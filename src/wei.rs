@@ -0,0 +1,205 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use web3::types::U256;
+use std::fmt;
+use std::iter::Sum;
+
+use crate::format_units;
+
+/// An amount of the chain's native token in wei. RPC quantities are wrapped at the fetch boundary,
+/// so gas amounts, nonces and token amounts (which stay plain `U256`) can't be mixed up with it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Wei(pub U256);
+
+impl Wei {
+    pub fn zero() -> Self {
+        Wei(U256::zero())
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn saturating_add(self, other: Wei) -> Wei {
+        Wei(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Wei) -> Wei {
+        Wei(self.0.saturating_sub(other.0))
+    }
+
+    /// The cost of `gas` units at this price per unit
+    pub fn saturating_mul_gas(self, gas: U256) -> Wei {
+        Wei(self.0.saturating_mul(gas))
+    }
+
+    /// Signed change from `prev` to `new`
+    pub fn checked_delta(prev: Wei, new: Wei) -> WeiDelta {
+        if new >= prev {
+            WeiDelta { decreased: false, magnitude: Wei(new.0 - prev.0) }
+        } else {
+            WeiDelta { decreased: true, magnitude: Wei(prev.0 - new.0) }
+        }
+    }
+
+    /// The amount with the decimal point shifted `decimals` places left, e.g. 18 for ETH
+    pub fn to_eth_string(self, decimals: usize) -> String {
+        format_units(self.0, decimals)
+    }
+}
+
+impl From<U256> for Wei {
+    fn from(value: U256) -> Self {
+        Wei(value)
+    }
+}
+
+impl Sum for Wei {
+    fn sum<I: Iterator<Item = Wei>>(iter: I) -> Self {
+        iter.fold(Wei::zero(), Wei::saturating_add)
+    }
+}
+
+// Debug matches Display so comparisons and logs read the same as before the newtype
+impl fmt::Debug for Wei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Wei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// Written as decimal strings so consumers like jq don't lose precision
+impl Serialize for Wei {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// Read back from the decimal strings it's written as
+impl<'de> Deserialize<'de> for Wei {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        // `from_dec_str` reads an empty string as zero
+        let parsed = if s.is_empty() { None } else { U256::from_dec_str(&s).ok() };
+        parsed.map(Wei).ok_or_else(|| serde::de::Error::custom(format!("invalid wei amount '{}'", s)))
+    }
+}
+
+/// Signed difference between two wei amounts, kept as sign + magnitude since U256 has no sign bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeiDelta {
+    pub decreased: bool,
+    pub magnitude: Wei,
+}
+
+impl WeiDelta {
+    /// A zero change is never negative
+    pub fn is_negative(self) -> bool {
+        self.decreased && !self.magnitude.is_zero()
+    }
+
+    /// Decimal string with the point shifted `decimals` places left and a leading `-` when negative
+    pub fn to_eth_string(self, decimals: usize) -> String {
+        let magnitude = self.magnitude.to_eth_string(decimals);
+        if self.is_negative() {
+            format!("-{}", magnitude)
+        } else {
+            magnitude
+        }
+    }
+}
+
+impl fmt::Display for WeiDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_eth_string(0))
+    }
+}
+
+impl Serialize for WeiDelta {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: &str = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+
+    fn wei(amount: u64) -> Wei {
+        Wei(amount.into())
+    }
+
+    #[test]
+    fn deltas_at_the_edges() {
+        let max = Wei(U256::MAX);
+        let table = [
+            (wei(0), wei(0), false, wei(0)),
+            (wei(0), wei(1), false, wei(1)),
+            (wei(1), wei(0), true, wei(1)),
+            (wei(5), wei(3), true, wei(2)),
+            (wei(0), max, false, max),
+            (max, wei(0), true, max),
+            (max, max, false, wei(0)),
+            (wei(1), max, false, Wei(U256::MAX - 1)),
+        ];
+        for (prev, new, decreased, magnitude) in table {
+            let delta = Wei::checked_delta(prev, new);
+            assert_eq!(delta, WeiDelta { decreased, magnitude }, "{} to {}", prev, new);
+            // Applying the delta to the old amount gives the new one
+            let applied = if delta.decreased { prev.saturating_sub(delta.magnitude) } else { prev.saturating_add(delta.magnitude) };
+            assert_eq!(applied, new);
+        }
+    }
+
+    #[test]
+    fn arithmetic_saturates() {
+        let max = Wei(U256::MAX);
+        assert_eq!(max.saturating_add(wei(1)), max);
+        assert_eq!(wei(0).saturating_sub(wei(1)), wei(0));
+        assert_eq!(max.saturating_mul_gas(2.into()), max);
+        assert_eq!(wei(3).saturating_mul_gas(21_000.into()), wei(63_000));
+        assert_eq!([max, wei(1)].into_iter().sum::<Wei>(), max);
+        assert_eq!(std::iter::empty::<Wei>().sum::<Wei>(), Wei::zero());
+    }
+
+    #[test]
+    fn signed_strings() {
+        let table = [
+            (true, wei(1), 18, "-0.000000000000000001"),
+            (false, wei(1), 18, "0.000000000000000001"),
+            (true, wei(5), 0, "-5"),
+            // A decrease of nothing is no decrease
+            (true, wei(0), 18, "0"),
+            (true, Wei(U256::MAX), 0, &format!("-{}", MAX)),
+            (false, Wei(U256::MAX), 18, "115792089237316195423570985008687907853269984665640564039457.584007913129639935"),
+        ];
+        for (decreased, magnitude, decimals, expected) in table {
+            let delta = WeiDelta { decreased, magnitude };
+            assert_eq!(delta.to_eth_string(decimals), expected);
+            assert_eq!(delta.is_negative(), expected.starts_with('-'));
+        }
+        assert_eq!(WeiDelta { decreased: true, magnitude: wei(7) }.to_string(), "-7");
+    }
+
+    #[test]
+    fn serializes_as_decimal_strings_and_reads_them_back() {
+        for amount in [wei(0), wei(1), Wei(U256::MAX)] {
+            let json = serde_json::to_value(amount).unwrap();
+            assert_eq!(json, serde_json::Value::String(amount.0.to_string()));
+            assert_eq!(serde_json::from_value::<Wei>(json).unwrap(), amount);
+        }
+        assert_eq!(serde_json::to_value(Wei(U256::MAX)).unwrap(), MAX);
+        assert_eq!(serde_json::to_value(WeiDelta { decreased: true, magnitude: wei(1) }).unwrap(), "-1");
+
+        for invalid in ["", "-1", "0x10", "1.5", "115792089237316195423570985008687907853269984665640564039457584007913129639936"] {
+            let error = serde_json::from_value::<Wei>(serde_json::json!(invalid)).unwrap_err();
+            assert!(error.to_string().contains("invalid wei amount"), "{}: {}", invalid, error);
+        }
+    }
+}
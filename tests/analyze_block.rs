@@ -0,0 +1,176 @@
+//! A whole analysis of a small recorded block, served by [`MockTransport`]. Block 3 of the fixture holds
+//! a value transfer, a reverted token transfer and a contract creation that sends the new contract
+//! 0.1 ether, and credits a beacon withdrawal.
+
+use ethereum_block_analyzer::{
+    analyze_block, analyze_block_with, AnalysisOptions, BlockAnalysis, BlockRef, MissingReceiptPolicy, MissingReceiptsError, MockTransport,
+    ReceiptSource, StateChange, TransactionStatus, Wei,
+};
+use serde_json::{json, Value};
+use std::error::Error;
+use web3::types::{H160, H256, U256};
+use web3::Web3;
+
+const ALICE: u64 = 0xa11ce;
+const BOB: u64 = 0xb0b;
+const TOKEN: u64 = 0x70ce;
+const CREATED: u64 = 0xc0ffee03;
+const MINER: &str = "0x00a329c0648769a73afac7f9381e08fb43dbea72";
+
+async fn analyze() -> BlockAnalysis {
+    let web3 = Web3::new(MockTransport::load("tests/fixtures/block_3.json").unwrap());
+    analyze_block(&web3, Some(3), 4).await.unwrap()
+}
+
+fn address(low: u64) -> H160 {
+    H160::from_low_u64_be(low)
+}
+
+fn ether(tenths: u64) -> Wei {
+    Wei::from(U256::from(tenths) * U256::exp10(17))
+}
+
+/// Every transaction pays 3 gwei a gas: the base fee of 1 and a priority fee of 2
+fn gwei(amount: u64) -> Wei {
+    Wei::from(U256::from(amount) * U256::exp10(9))
+}
+
+fn change(analysis: &BlockAnalysis, address: H160) -> &StateChange {
+    analysis.state_changes.iter().flatten().find(|change| change.address == address).unwrap()
+}
+
+#[tokio::test]
+async fn analysis_matches_the_expected_report() {
+    let analysis = analyze().await;
+    let expected: serde_json::Value = serde_json::from_str(include_str!("fixtures/block_3.analysis.json")).unwrap();
+    assert_eq!(serde_json::to_value(&analysis).unwrap(), expected);
+}
+
+#[tokio::test]
+async fn value_transfer_moves_ether_and_charges_gas() {
+    let analysis = analyze().await;
+    let tx = &analysis.block_info.transactions[0];
+    assert_eq!((tx.from, tx.to, tx.value, tx.status), (Some(address(ALICE)), Some(address(BOB)), ether(5), TransactionStatus::Success));
+    assert_eq!(tx.fee_paid, Some(gwei(21_000 * 3)));
+
+    // Bob received the transfer and paid for his reverted transaction
+    let bob = change(&analysis, address(BOB));
+    assert_eq!(bob.value_in, ether(5));
+    assert_eq!(bob.fees_paid, gwei(50_000 * 3));
+    assert_eq!(bob.balance_change.map(|delta| (delta.decreased, delta.magnitude)), Some((false, ether(5).saturating_sub(gwei(50_000 * 3)))));
+}
+
+#[tokio::test]
+async fn reverted_transaction_pays_gas_but_moves_nothing() {
+    let analysis = analyze().await;
+    let tx = &analysis.block_info.transactions[1];
+    assert_eq!((tx.from, tx.to, tx.status), (Some(address(BOB)), Some(address(TOKEN)), TransactionStatus::Reverted));
+    assert_eq!(tx.fee_paid, Some(gwei(50_000 * 3)));
+    assert!(analysis.token_transfers.is_empty());
+    assert_eq!(analysis.summary.reverted_transactions, 1);
+    // The token contract's own state is untouched
+    assert!(analysis.state_changes.iter().flatten().all(|change| change.address != address(TOKEN)));
+}
+
+#[tokio::test]
+async fn contract_creation_reports_the_deployed_address() {
+    let analysis = analyze().await;
+    let tx = &analysis.block_info.transactions[2];
+    assert_eq!((tx.from, tx.to, tx.created_contract), (Some(address(ALICE)), None, Some(address(CREATED))));
+    assert_eq!(analysis.summary.contract_creations, 1);
+
+    let created = change(&analysis, address(CREATED));
+    assert_eq!((created.prev_balance, created.new_balance), (Some(Wei::zero()), Some(ether(1))));
+    assert_eq!(created.nonce_change.map(|delta| (delta.decreased, delta.magnitude)), Some((false, U256::one())));
+
+    // Alice sent both transactions, so her nonce moved by two
+    let alice = change(&analysis, address(ALICE));
+    assert_eq!(alice.value_out, ether(6));
+    assert_eq!(alice.fees_paid, gwei((21_000 + 60_000) * 3));
+    assert_eq!(alice.nonce_change.map(|delta| delta.magnitude), Some(U256::from(2)));
+}
+
+#[tokio::test]
+async fn miner_is_credited_the_priority_fees() {
+    let analysis = analyze().await;
+    assert_eq!(analysis.block_info.miner_address, MINER.parse().unwrap());
+    let miner = change(&analysis, analysis.block_info.miner_address);
+    let priority_fees = gwei((21_000 + 50_000 + 60_000) * 2);
+    assert_eq!(miner.priority_fees_received, priority_fees);
+    assert_eq!(miner.balance_change.map(|delta| (delta.decreased, delta.magnitude)), Some((false, priority_fees)));
+    assert_eq!(analysis.summary.mev_payment, None);
+}
+
+#[tokio::test]
+async fn without_state_the_block_is_not_traced() {
+    let mock = MockTransport::load("tests/fixtures/block_3.json").unwrap();
+    let options = AnalysisOptions::new().state_changes(false);
+    let analysis = analyze_block_with(&Web3::new(mock.clone()), BlockRef::Number(3), &options).await.unwrap();
+    assert!(analysis.state_changes.is_none() && analysis.internal_transfers.is_none());
+    assert_eq!((mock.calls("trace_block"), mock.calls("debug_traceBlockByNumber")), (0, 0));
+}
+
+/// The fixture's receipts for block 3, from its `eth_getBlockReceipts` exchange
+fn block_receipts() -> Vec<Value> {
+    let exchanges = include_str!("fixtures/block_3.json").lines().map(|line| serde_json::from_str::<Value>(line).unwrap());
+    let exchange = exchanges.into_iter().find(|exchange| exchange["method"] == "eth_getBlockReceipts").unwrap();
+    serde_json::from_value(exchange["result"].clone()).unwrap()
+}
+
+/// Block 3 analyzed under `policy` with the node having no receipt for Bob's reverted transaction,
+/// fetched all at once or, when `per_transaction`, one receipt at a time
+async fn analyze_without_a_receipt(policy: MissingReceiptPolicy, per_transaction: bool) -> Result<BlockAnalysis, Box<dyn Error>> {
+    let mut mock = MockTransport::load("tests/fixtures/block_3.json").unwrap();
+    let mut receipts = block_receipts();
+    let missing = receipts.remove(1);
+    if per_transaction {
+        let unsupported = jsonrpc_core::Error::method_not_found();
+        mock = mock.with_error("eth_getBlockReceipts", json!(["0x3"]), unsupported);
+        for receipt in receipts {
+            mock = mock.with_response("eth_getTransactionReceipt", json!([receipt["transactionHash"]]), receipt);
+        }
+        mock = mock.with_response("eth_getTransactionReceipt", json!([missing["transactionHash"]]), Value::Null);
+    } else {
+        mock = mock.with_response("eth_getBlockReceipts", json!(["0x3"]), json!(receipts));
+    }
+    let options = AnalysisOptions::new().with_missing_receipts(policy);
+    analyze_block_with(&Web3::new(mock), BlockRef::Number(3), &options).await
+}
+
+fn missing_receipt_warnings(analysis: &BlockAnalysis) -> Vec<&String> {
+    analysis.warnings.iter().filter(|warning| warning.starts_with("no receipt for transaction")).collect()
+}
+
+#[tokio::test]
+async fn a_missing_receipt_fails_the_analysis_under_the_error_policy() {
+    for per_transaction in [false, true] {
+        let error = analyze_without_a_receipt(MissingReceiptPolicy::Error, per_transaction).await.unwrap_err();
+        let missing = error.downcast_ref::<MissingReceiptsError>().unwrap();
+        assert_eq!((missing.block_number, missing.transactions.clone()), (3, vec![H256::from_low_u64_be(0x408)]));
+    }
+}
+
+#[tokio::test]
+async fn a_missing_receipt_is_warned_about_under_the_warn_policy() {
+    for per_transaction in [false, true] {
+        let analysis = analyze_without_a_receipt(MissingReceiptPolicy::Warn, per_transaction).await.unwrap();
+        let source = if per_transaction { ReceiptSource::PerTransaction } else { ReceiptSource::Block };
+        assert_eq!(analysis.receipt_source, source);
+        let expected = format!("no receipt for transaction {:?}; its gas, fee and status are unknown", H256::from_low_u64_be(0x408));
+        assert_eq!(missing_receipt_warnings(&analysis), [&expected]);
+
+        let tx = &analysis.block_info.transactions[1];
+        assert_eq!((tx.status, tx.gas_used, tx.fee_paid), (TransactionStatus::Unknown, None, None));
+        // The transactions that have receipts are unaffected
+        assert_eq!(analysis.block_info.transactions[0].fee_paid, Some(gwei(21_000 * 3)));
+    }
+}
+
+#[tokio::test]
+async fn a_missing_receipt_goes_unmentioned_under_the_skip_policy() {
+    for per_transaction in [false, true] {
+        let analysis = analyze_without_a_receipt(MissingReceiptPolicy::Skip, per_transaction).await.unwrap();
+        assert!(missing_receipt_warnings(&analysis).is_empty(), "{:?}", analysis.warnings);
+        assert_eq!(analysis.block_info.transactions[1].status, TransactionStatus::Unknown);
+    }
+}
@@ -0,0 +1,116 @@
+//! End to end against a real node: a value transfer, a contract deployment and a call that has the
+//! contract pass its ether on are mined into one block, which is then analyzed
+
+mod common;
+
+use common::Anvil;
+use ethereum_block_analyzer::{analyze_block, BlockAnalysis, StateChange, TransactionStatus, Wei, WeiDelta};
+use rlp::RlpStream;
+use web3::signing::keccak256;
+use web3::types::{BlockId, BlockNumber, Bytes, TransactionReceipt, TransactionRequest, H160, H256, U256};
+use web3::transports::Http;
+use web3::Web3;
+
+/// Init code returning a runtime that sends whatever it's called with to `beneficiary`:
+/// `CALL(gas, beneficiary, callvalue, 0, 0, 0, 0)`
+fn forwarder(beneficiary: H160) -> Bytes {
+    let mut runtime = vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x34, 0x73];
+    runtime.extend_from_slice(beneficiary.as_bytes());
+    runtime.extend_from_slice(&[0x5a, 0xf1, 0x50, 0x00]);
+    // CODECOPY the runtime, which follows these 11 bytes, to memory and RETURN it
+    let mut init = vec![0x60, runtime.len() as u8, 0x80, 0x60, 0x0b, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3];
+    init.extend(runtime);
+    Bytes(init)
+}
+
+/// Where a creation from `sender` at `nonce` deploys: the last 20 bytes of `keccak256(rlp([sender, nonce]))`
+fn creation_address(sender: H160, nonce: u64) -> H160 {
+    let mut stream = RlpStream::new_list(2);
+    stream.append(&sender);
+    stream.append(&nonce);
+    H160::from_slice(&keccak256(&stream.out())[12..])
+}
+
+fn ether(hundredths: u64) -> U256 {
+    U256::from(hundredths) * U256::exp10(16)
+}
+
+async fn send(web3: &Web3<Http>, request: TransactionRequest) -> H256 {
+    web3.eth().send_transaction(request).await.expect("eth_sendTransaction failed")
+}
+
+async fn receipt(web3: &Web3<Http>, hash: H256) -> TransactionReceipt {
+    web3.eth().transaction_receipt(hash).await.unwrap().expect("transaction wasn't mined")
+}
+
+fn change(analysis: &BlockAnalysis, address: H160) -> &StateChange {
+    let changes = analysis.state_changes.as_ref().expect("state wasn't queried");
+    changes.iter().find(|change| change.address == address).unwrap_or_else(|| panic!("no state change for {:?}", address))
+}
+
+fn fee(receipt: &TransactionReceipt) -> U256 {
+    receipt.gas_used.unwrap() * receipt.effective_gas_price.unwrap()
+}
+
+fn delta(increase: U256, decrease: U256) -> WeiDelta {
+    Wei::checked_delta(Wei::from(decrease), Wei::from(increase))
+}
+
+#[tokio::test]
+#[ignore = "needs anvil; run with cargo test -- --ignored"]
+async fn analyzes_a_block_mined_by_anvil() {
+    // In the order sent, so the call comes after the deployment
+    let anvil = Anvil::spawn(&["--order", "fifo"]).await;
+    let web3 = anvil.web3();
+    let accounts = web3.eth().accounts().await.unwrap();
+    let (deployer, payer, beneficiary) = (accounts[0], accounts[1], accounts[2]);
+    let contract = creation_address(deployer, 1);
+
+    let transfer = send(&web3, TransactionRequest { from: deployer, to: Some(payer), value: Some(ether(100)), gas: Some(21_000.into()), ..Default::default() }).await;
+    let deployment = send(&web3, TransactionRequest { from: deployer, data: Some(forwarder(beneficiary)), gas: Some(200_000.into()), ..Default::default() }).await;
+    let call = send(&web3, TransactionRequest { from: payer, to: Some(contract), value: Some(ether(25)), gas: Some(100_000.into()), ..Default::default() }).await;
+    anvil.mine().await;
+    let receipts = [receipt(&web3, transfer).await, receipt(&web3, deployment).await, receipt(&web3, call).await];
+    let block_number = receipts[0].block_number.unwrap().as_u64();
+    assert!(receipts.iter().all(|receipt| receipt.block_number == Some(block_number.into())), "the transactions weren't mined together");
+
+    let analysis = analyze_block(&web3, Some(block_number), 4).await.unwrap();
+
+    let block = web3.eth().block(BlockId::Number(BlockNumber::Number(block_number.into()))).await.unwrap().unwrap();
+    let block_info = &analysis.block_info;
+    assert_eq!((block_info.block_number, block_info.hash, block_info.parent_hash), (block_number, block.hash.unwrap(), block.parent_hash));
+    assert_eq!(block_info.miner_address, block.author);
+    assert_eq!(block_info.gas_used, block.gas_used.as_u64());
+    assert_eq!(analysis.baseline_block, Some(block_number - 1));
+
+    let hashes: Vec<H256> = block_info.transactions.iter().map(|tx| tx.hash).collect();
+    assert_eq!(hashes, [transfer, deployment, call]);
+    assert!(block_info.transactions.iter().all(|tx| tx.status == TransactionStatus::Success));
+    assert_eq!(block_info.transactions[1].created_contract, Some(contract));
+    assert_eq!(receipts[1].contract_address, Some(contract));
+    assert_eq!(analysis.summary.contract_creations, 1);
+
+    // The deployer paid the transfer and two fees, and sent two transactions
+    let fees = fee(&receipts[0]) + fee(&receipts[1]);
+    let deployer_change = change(&analysis, deployer);
+    assert_eq!(deployer_change.balance_change, Some(delta(U256::zero(), ether(100) + fees)));
+    assert_eq!(deployer_change.nonce_change.map(|nonce| nonce.magnitude), Some(U256::from(2)));
+
+    // The payer received the transfer and passed a quarter of it on through the contract
+    let payer_change = change(&analysis, payer);
+    assert_eq!(payer_change.balance_change, Some(delta(ether(100), ether(25) + fee(&receipts[2]))));
+
+    // The contract kept nothing, and the beneficiary got the call's value from it
+    let contract_change = change(&analysis, contract);
+    assert_eq!((contract_change.prev_balance, contract_change.new_balance), (Some(Wei::zero()), Some(Wei::zero())));
+    assert_eq!(contract_change.nonce_change.map(|nonce| nonce.magnitude), Some(U256::one()));
+    assert_eq!(change(&analysis, beneficiary).balance_change, Some(delta(ether(25), U256::zero())));
+    if let Some(internal) = &analysis.internal_transfers {
+        let forwarded: Vec<(H160, H160, Wei)> = internal.iter().map(|transfer| (transfer.from, transfer.to, transfer.value)).collect();
+        assert_eq!(forwarded, [(contract, beneficiary, Wei::from(ether(25)))]);
+    }
+
+    // The fee recipient's change is the priority fees
+    let miner = change(&analysis, block_info.miner_address);
+    assert_eq!(miner.balance_change.map(|delta| delta.magnitude), Some(analysis.summary.total_priority_fees));
+}
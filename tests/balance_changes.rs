@@ -0,0 +1,110 @@
+//! Signed balance changes of block 2 of a recorded fixture, where one address only pays for gas, one
+//! only receives, and the miner earns priority fees, and of the first two blocks of a devnet whose
+//! genesis funds Alice and Bob
+
+use ethereum_block_analyzer::{analyze_block, BlockAnalysis, MockTransport, Wei, WeiDelta};
+use web3::types::{H160, U256};
+use web3::Web3;
+
+const ALICE: u64 = 0xa11ce;
+const BOB: u64 = 0xb0b;
+const CAROL: u64 = 0xca401;
+
+async fn analyze() -> BlockAnalysis {
+    let web3 = Web3::new(MockTransport::load("tests/fixtures/block_2.json").unwrap());
+    analyze_block(&web3, Some(2), 4).await.unwrap()
+}
+
+fn balance_change(analysis: &BlockAnalysis, address: H160) -> WeiDelta {
+    let change = analysis.state_changes.iter().flatten().find(|change| change.address == address).unwrap();
+    change.balance_change.unwrap()
+}
+
+fn wei(amount: u64) -> Wei {
+    Wei::from(U256::from(amount))
+}
+
+#[tokio::test]
+async fn gas_only_payer_decreases_by_its_fee() {
+    let analysis = analyze().await;
+    // A zero-value token transfer of 50,000 gas at 3 gwei
+    let carol = balance_change(&analysis, H160::from_low_u64_be(CAROL));
+    assert_eq!(carol, WeiDelta { decreased: true, magnitude: wei(150_000_000_000_000) });
+    assert!(carol.is_negative());
+    assert_eq!(format!("{} wei", carol), "-150000000000000 wei");
+    assert_eq!(serde_json::to_value(carol).unwrap(), serde_json::json!("-150000000000000"));
+}
+
+#[tokio::test]
+async fn receiver_increases_by_the_value() {
+    let analysis = analyze().await;
+    let bob = balance_change(&analysis, H160::from_low_u64_be(BOB));
+    assert_eq!(bob, WeiDelta { decreased: false, magnitude: wei(500_000_000_000_000_000) });
+    assert_eq!(serde_json::to_value(bob).unwrap(), serde_json::json!("500000000000000000"));
+
+    // The sender pays the value and its gas
+    let alice = balance_change(&analysis, H160::from_low_u64_be(ALICE));
+    assert_eq!(alice, WeiDelta { decreased: true, magnitude: wei(500_063_000_000_000_000) });
+}
+
+#[tokio::test]
+async fn miner_increases_by_the_priority_fees() {
+    let analysis = analyze().await;
+    // 71,000 gas at a priority fee of 2 gwei
+    let miner = balance_change(&analysis, analysis.block_info.miner_address);
+    assert_eq!(miner, WeiDelta { decreased: false, magnitude: wei(142_000_000_000_000) });
+    assert!(!miner.is_negative());
+}
+
+/// The genesis allocates Alice, its etherbase, 100 ether and Bob 5; block 1 has Alice send Bob 1 ether
+/// at 3 gwei a gas
+async fn analyze_devnet(block: u64) -> (BlockAnalysis, MockTransport) {
+    let mock = MockTransport::load("tests/fixtures/genesis.json").unwrap();
+    let analysis = analyze_block(&Web3::new(mock.clone()), Some(block), 4).await.unwrap();
+    (analysis, mock)
+}
+
+fn ether(amount: u64) -> Wei {
+    Wei::from(U256::from(amount) * U256::exp10(18))
+}
+
+/// The blocks balance and nonce queries were made at
+fn queried_blocks(mock: &MockTransport) -> Vec<String> {
+    let mut blocks: Vec<String> = mock
+        .requests()
+        .into_iter()
+        .filter(|(method, _)| method == "eth_getBalance" || method == "eth_getTransactionCount")
+        .map(|(_, params)| params[1].as_str().unwrap().to_string())
+        .collect();
+    blocks.sort();
+    blocks.dedup();
+    blocks
+}
+
+#[tokio::test]
+async fn genesis_allocations_are_changes_from_empty_accounts() {
+    let (analysis, mock) = analyze_devnet(0).await;
+    assert_eq!(analysis.baseline_block, None);
+    // Only the genesis state is read; there's no block before it to ask about
+    assert_eq!(queried_blocks(&mock), ["0x0"]);
+
+    let alice = analysis.state_changes.iter().flatten().find(|change| change.address == H160::from_low_u64_be(ALICE)).unwrap();
+    assert_eq!((alice.prev_balance, alice.new_balance), (Some(Wei::zero()), Some(ether(100))));
+    assert_eq!(alice.balance_change, Some(WeiDelta { decreased: false, magnitude: ether(100) }));
+    assert_eq!(alice.nonce_change.map(|delta| delta.magnitude), Some(U256::zero()));
+}
+
+#[tokio::test]
+async fn the_first_block_is_measured_from_the_genesis_state() {
+    let (analysis, mock) = analyze_devnet(1).await;
+    assert_eq!(analysis.baseline_block, Some(0));
+    assert_eq!(queried_blocks(&mock), ["0x0", "0x1"]);
+
+    // Bob's genesis allocation is his starting balance, not zero
+    let bob = analysis.state_changes.iter().flatten().find(|change| change.address == H160::from_low_u64_be(BOB)).unwrap();
+    assert_eq!((bob.prev_balance, bob.new_balance), (Some(ether(5)), Some(ether(6))));
+    let alice = balance_change(&analysis, H160::from_low_u64_be(ALICE));
+    assert_eq!(alice, WeiDelta { decreased: true, magnitude: ether(1).saturating_add(wei(21_000 * 3_000_000_000)) });
+    let miner = balance_change(&analysis, analysis.block_info.miner_address);
+    assert_eq!(miner, WeiDelta { decreased: false, magnitude: wei(21_000 * 2_000_000_000) });
+}
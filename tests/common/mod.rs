@@ -0,0 +1,58 @@
+//! Spawning and tearing down a local `anvil` node for end-to-end tests. The binary is `$ANVIL`, or
+//! `anvil` on the `PATH`; tests that need it are `#[ignore]`d, so run them with `cargo test -- --ignored`.
+
+use web3::transports::Http;
+use web3::{Transport, Web3};
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long a freshly spawned node gets to start answering
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running `anvil`, killed when dropped
+pub struct Anvil {
+    child: Child,
+    pub url: String,
+}
+
+impl Anvil {
+    /// Start a node with automatic mining off, so transactions wait for [`Anvil::mine`] and land in one
+    /// block. `args` are passed on after the port.
+    pub async fn spawn(args: &[&str]) -> Anvil {
+        let program = std::env::var("ANVIL").unwrap_or_else(|_| "anvil".to_string());
+        // Ask the OS for a free port rather than guess one other tests may hold
+        let port = TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).expect("no free port").port();
+        let child = Command::new(&program)
+            .args(["--port", &port.to_string(), "--no-mining", "--silent"])
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .unwrap_or_else(|e| panic!("couldn't start {} (install Foundry, or point $ANVIL at it): {}", program, e));
+        let anvil = Anvil { child, url: format!("http://127.0.0.1:{}", port) };
+
+        let started = Instant::now();
+        while anvil.web3().eth().chain_id().await.is_err() {
+            assert!(started.elapsed() < STARTUP_TIMEOUT, "{} didn't answer on {} within {:?}", program, anvil.url, STARTUP_TIMEOUT);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        anvil
+    }
+
+    pub fn web3(&self) -> Web3<Http> {
+        Web3::new(Http::new(&self.url).expect("invalid anvil URL"))
+    }
+
+    /// Mine one block of every pending transaction
+    pub async fn mine(&self) {
+        self.web3().transport().execute("evm_mine", Vec::new()).await.expect("evm_mine failed");
+    }
+}
+
+impl Drop for Anvil {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,28 @@
+//! `set_lowercase_addresses` changes a process-wide flag, so it's tested in a binary of its own, in a
+//! single test, where no other test can print an address while it's set
+
+use ethereum_block_analyzer::{format_address, set_lowercase_addresses, to_checksum_address, Alert, AlertValue};
+use web3::types::H160;
+
+const CHECKSUMMED: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+fn alert(address: H160) -> serde_json::Value {
+    let alert = Alert { rule: "nonce_change>1".to_string(), block_number: 1, address: Some(address), field: "nonce_change", value: AlertValue::Count(2) };
+    serde_json::to_value(alert).unwrap()
+}
+
+#[test]
+fn the_lowercase_flag_applies_to_printed_and_serialized_addresses() {
+    let address: H160 = CHECKSUMMED.to_lowercase().parse().unwrap();
+    assert_eq!(format_address(&address), CHECKSUMMED);
+    assert_eq!(alert(address)["address"], CHECKSUMMED);
+
+    set_lowercase_addresses(true);
+    assert_eq!(format_address(&address), CHECKSUMMED.to_lowercase());
+    assert_eq!(alert(address)["address"], CHECKSUMMED.to_lowercase());
+    // Asking for the checksummed form directly still gets it
+    assert_eq!(to_checksum_address(&address), CHECKSUMMED);
+
+    set_lowercase_addresses(false);
+    assert_eq!(format_address(&address), CHECKSUMMED);
+}
@@ -0,0 +1,117 @@
+//! Blocks 1 to 6 of a recorded fixture written with [`ParquetExport`] and read back with the parquet crate
+#![cfg(feature = "parquet")]
+
+use arrow_array::{Array, FixedSizeBinaryArray, Int64Array, RecordBatch, StringArray, UInt64Array};
+use ethereum_block_analyzer::{analyze_block_range, BlockAnalysis, InvalidatedBlock, MockTransport, ParquetExport, Reorg};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use web3::Web3;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+async fn analyses() -> Vec<BlockAnalysis> {
+    let web3 = Web3::new(MockTransport::load("tests/fixtures/blocks_1_6.json").unwrap());
+    analyze_block_range(&web3, 1, 6, 4).await.unwrap()
+}
+
+/// An empty directory of its own for each test
+fn output_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("state-diff-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn export(dir: &Path, analyses: &[BlockAnalysis], replaced: Option<&Reorg>) {
+    let mut export = ParquetExport::create(dir, 2).unwrap();
+    for analysis in analyses {
+        export.write(analysis).unwrap();
+    }
+    if let Some(reorg) = replaced {
+        export.mark_replaced(reorg);
+    }
+    export.finish().unwrap();
+}
+
+/// Every row group of a file, checking it has the schema it's written with
+fn read(dir: &Path, name: &str, schema: arrow_schema::SchemaRef) -> Vec<RecordBatch> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(dir.join(name)).unwrap()).unwrap();
+    assert_eq!(reader.schema().fields(), schema.fields(), "{}", name);
+    reader.build().unwrap().collect::<Result<_, _>>().unwrap()
+}
+
+fn column<'a, A: Array + 'static>(batches: &'a [RecordBatch], name: &str) -> Vec<&'a A> {
+    batches.iter().map(|batch| batch.column_by_name(name).unwrap().as_any().downcast_ref::<A>().unwrap()).collect()
+}
+
+fn numbers(batches: &[RecordBatch], name: &str) -> Vec<Option<u64>> {
+    column::<UInt64Array>(batches, name).into_iter().flat_map(|array| array.iter().collect::<Vec<_>>()).collect()
+}
+
+fn signed_numbers(batches: &[RecordBatch], name: &str) -> Vec<Option<i64>> {
+    column::<Int64Array>(batches, name).into_iter().flat_map(|array| array.iter().collect::<Vec<_>>()).collect()
+}
+
+fn strings(batches: &[RecordBatch], name: &str) -> Vec<Option<String>> {
+    column::<StringArray>(batches, name).into_iter().flat_map(|array| array.iter().map(|value| value.map(String::from)).collect::<Vec<_>>()).collect()
+}
+
+fn bytes(batches: &[RecordBatch], name: &str) -> Vec<Option<Vec<u8>>> {
+    column::<FixedSizeBinaryArray>(batches, name).into_iter().flat_map(|array| array.iter().map(|value| value.map(<[u8]>::to_vec)).collect::<Vec<_>>()).collect()
+}
+
+#[tokio::test]
+async fn files_read_back_as_the_analyses_they_were_written_from() {
+    let analyses = analyses().await;
+    let dir = output_dir("parquet-round-trip");
+    export(&dir, &analyses, None);
+
+    let blocks = read(&dir, "blocks.parquet", ethereum_block_analyzer::blocks_schema());
+    let infos: Vec<_> = analyses.iter().map(|analysis| &analysis.block_info).collect();
+    assert_eq!(numbers(&blocks, "number"), infos.iter().map(|block| Some(block.block_number)).collect::<Vec<_>>());
+    assert_eq!(bytes(&blocks, "hash"), infos.iter().map(|block| Some(block.hash.as_bytes().to_vec())).collect::<Vec<_>>());
+    assert_eq!(bytes(&blocks, "parent_hash"), infos.iter().map(|block| Some(block.parent_hash.as_bytes().to_vec())).collect::<Vec<_>>());
+    assert_eq!(bytes(&blocks, "miner"), infos.iter().map(|block| Some(block.miner_address.as_bytes().to_vec())).collect::<Vec<_>>());
+    assert_eq!(numbers(&blocks, "gas_used"), infos.iter().map(|block| Some(block.gas_used)).collect::<Vec<_>>());
+    assert_eq!(strings(&blocks, "base_fee_per_gas"), infos.iter().map(|block| block.base_fee_per_gas.map(|fee| fee.to_string())).collect::<Vec<_>>());
+    assert_eq!(numbers(&blocks, "transaction_count"), infos.iter().map(|block| Some(block.transactions.len() as u64)).collect::<Vec<_>>());
+
+    let transactions = read(&dir, "transactions.parquet", ethereum_block_analyzer::transactions_schema());
+    let txs: Vec<_> = infos.iter().flat_map(|block| &block.transactions).collect();
+    assert!(!txs.is_empty());
+    assert_eq!(bytes(&transactions, "hash"), txs.iter().map(|tx| Some(tx.hash.as_bytes().to_vec())).collect::<Vec<_>>());
+    assert_eq!(bytes(&transactions, "from_address"), txs.iter().map(|tx| tx.from.map(|from| from.as_bytes().to_vec())).collect::<Vec<_>>());
+    assert_eq!(bytes(&transactions, "to_address"), txs.iter().map(|tx| tx.to.map(|to| to.as_bytes().to_vec())).collect::<Vec<_>>());
+    assert_eq!(strings(&transactions, "value"), txs.iter().map(|tx| Some(tx.value.to_string())).collect::<Vec<_>>());
+    let statuses = txs.iter().map(|tx| serde_json::to_value(tx.status).unwrap().as_str().map(String::from));
+    assert_eq!(strings(&transactions, "status"), statuses.collect::<Vec<_>>());
+    assert_eq!(strings(&transactions, "fee_paid"), txs.iter().map(|tx| tx.fee_paid.map(|fee| fee.to_string())).collect::<Vec<_>>());
+
+    let state_changes = read(&dir, "state_changes.parquet", ethereum_block_analyzer::state_changes_schema());
+    let changes: Vec<_> = analyses.iter().flat_map(|analysis| analysis.state_changes.iter().flatten()).collect();
+    assert_eq!(bytes(&state_changes, "address"), changes.iter().map(|change| Some(change.address.as_bytes().to_vec())).collect::<Vec<_>>());
+    assert_eq!(strings(&state_changes, "new_balance"), changes.iter().map(|change| change.new_balance.map(|balance| balance.to_string())).collect::<Vec<_>>());
+    assert_eq!(strings(&state_changes, "balance_change"), changes.iter().map(|change| change.balance_change.map(|delta| delta.to_string())).collect::<Vec<_>>());
+    assert!(strings(&state_changes, "balance_change").iter().flatten().any(|delta| delta.starts_with('-')));
+    let nonce_changes = changes.iter().map(|change| change.nonce_change.map(|delta| delta.magnitude.low_u64() as i64 * if delta.decreased { -1 } else { 1 }));
+    assert_eq!(signed_numbers(&state_changes, "nonce_change"), nonce_changes.collect::<Vec<_>>());
+
+    // Only the final names are left behind
+    let mut names: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+    names.sort();
+    assert_eq!(names, ["blocks.parquet", "state_changes.parquet", "transactions.parquet"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_block_replaced_by_a_reorg_leaves_no_rows() {
+    let analyses = analyses().await;
+    let dir = output_dir("parquet-reorg");
+    let replaced = &analyses[5].block_info;
+    let reorg = Reorg { common_ancestor: 5, invalidated: vec![InvalidatedBlock { number: replaced.block_number, hash: replaced.hash }] };
+    export(&dir, &analyses, Some(&reorg));
+
+    let blocks = read(&dir, "blocks.parquet", ethereum_block_analyzer::blocks_schema());
+    assert_eq!(numbers(&blocks, "number"), (1..=5).map(Some).collect::<Vec<_>>());
+    let transactions = read(&dir, "transactions.parquet", ethereum_block_analyzer::transactions_schema());
+    assert!(numbers(&transactions, "block_number").iter().all(|number| *number != Some(6)));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,132 @@
+//! Range analysis over blocks 1 to 6 of a recorded fixture, with later blocks fetched faster than earlier
+//! ones so they finish out of order
+
+use futures::future::BoxFuture;
+use jsonrpc_core::{Call, Params, Value};
+use ethereum_block_analyzer::{analyze_block_range_streaming, AnalysisOptions, BlockEvent, MockTransport};
+use web3::error::TransportError;
+use web3::{BatchTransport, RequestId, Transport, Web3};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const FIXTURE: &str = "tests/fixtures/blocks_1_6.json";
+
+/// Serves the fixture, holding each block's `eth_getBlockByNumber` longer the earlier the block is, and
+/// failing it for `failing`
+#[derive(Debug, Clone)]
+struct SlowTransport {
+    inner: MockTransport,
+    failing: Option<u64>,
+    in_flight: Arc<AtomicUsize>,
+    most_in_flight: Arc<AtomicUsize>,
+}
+
+impl SlowTransport {
+    fn new(failing: Option<u64>) -> Self {
+        let inner = MockTransport::load(FIXTURE).unwrap();
+        SlowTransport { inner, failing, in_flight: Arc::default(), most_in_flight: Arc::default() }
+    }
+}
+
+fn block_fetched(request: &Call) -> Option<u64> {
+    let Call::MethodCall(call) = request else { return None };
+    let Params::Array(params) = &call.params else { return None };
+    match (call.method.as_str(), params.first().and_then(Value::as_str)) {
+        ("eth_getBlockByNumber", Some(block)) => u64::from_str_radix(block.trim_start_matches("0x"), 16).ok(),
+        _ => None,
+    }
+}
+
+impl Transport for SlowTransport {
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        let this = self.clone();
+        Box::pin(async move {
+            let Some(block) = block_fetched(&request) else { return this.inner.send(id, request).await };
+            let in_flight = this.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            this.most_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20 * (7 - block.min(7)))).await;
+            this.in_flight.fetch_sub(1, Ordering::SeqCst);
+            if this.failing == Some(block) {
+                return Err(web3::Error::Transport(TransportError::Message(format!("block {} is unavailable", block))));
+            }
+            this.inner.send(id, request).await
+        })
+    }
+}
+
+impl BatchTransport for SlowTransport {
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        Box::pin(self.inner.send_batch(requests))
+    }
+}
+
+/// Each block's analysis as JSON, or its error, in the order they were handed over
+async fn run<T: BatchTransport>(web3: &Web3<T>, block_concurrency: usize) -> Vec<(u64, Result<Value, String>)> {
+    let options = AnalysisOptions::new().with_block_concurrency(block_concurrency);
+    let mut delivered = Vec::new();
+    analyze_block_range_streaming(web3, 1, 6, &options, |block_number, event| {
+        let outcome = match event {
+            Ok(BlockEvent::Analyzed(analysis)) => Ok(serde_json::to_value(&analysis).unwrap()),
+            Ok(BlockEvent::Reorg(reorg)) => panic!("unexpected reorg at {}", reorg.common_ancestor),
+            Err(e) => Err(e.to_string()),
+        };
+        delivered.push((block_number, outcome));
+        Ok(())
+    })
+    .await
+    .unwrap();
+    delivered
+}
+
+#[tokio::test]
+async fn out_of_order_finishes_are_delivered_in_block_order() {
+    let transport = SlowTransport::new(None);
+    let delivered = run(&Web3::new(transport.clone()), 4).await;
+
+    let order: Vec<u64> = delivered.iter().map(|(block_number, _)| *block_number).collect();
+    assert_eq!(order, [1, 2, 3, 4, 5, 6]);
+    assert_eq!(transport.most_in_flight.load(Ordering::SeqCst), 4);
+
+    // Analyzing blocks side by side gives what analyzing them one after another does
+    let sequential = run(&Web3::new(MockTransport::load(FIXTURE).unwrap()), 1).await;
+    assert_eq!(delivered, sequential);
+}
+
+#[tokio::test]
+async fn blocks_started_later_reuse_what_finished_blocks_learned() {
+    // With all six at once, none has finished when another starts
+    let all_at_once = SlowTransport::new(None);
+    run(&Web3::new(all_at_once.clone()), 6).await;
+    let windowed = SlowTransport::new(None);
+    run(&Web3::new(windowed.clone()), 4).await;
+
+    assert_eq!(all_at_once.inner.calls("eth_chainId"), 6);
+    assert_eq!(windowed.inner.calls("eth_chainId"), 4);
+    // Block 4 finishes first, so block 5 starts with its states as the baseline
+    let balances_at = |transport: &SlowTransport, block: &str| {
+        transport.inner.requests().iter().filter(|(method, params)| method == "eth_getBalance" && params[1] == block).count()
+    };
+    assert!(balances_at(&windowed, "0x4") < balances_at(&all_at_once, "0x4"));
+}
+
+#[tokio::test]
+async fn a_failed_block_is_reported_in_its_place() {
+    let delivered = run(&Web3::new(SlowTransport::new(Some(3))), 4).await;
+
+    let outcomes: Vec<(u64, bool)> = delivered.iter().map(|(block_number, outcome)| (*block_number, outcome.is_ok())).collect();
+    assert_eq!(outcomes, [(1, true), (2, true), (3, false), (4, true), (5, true), (6, true)]);
+    let error = delivered[2].1.as_ref().unwrap_err();
+    assert!(error.contains("block 3 is unavailable"), "{}", error);
+}
@@ -0,0 +1,29 @@
+//! An analysis replayed offline from a `--record` fixture, recorded from block 7 of a test node, whose
+//! second transaction reverts
+
+use ethereum_block_analyzer::{analyze_block, FixtureTransport, MockTransport, TransactionStatus};
+use web3::Web3;
+
+const FIXTURE: &str = "tests/fixtures/block_7.json";
+
+/// The inner transport has no responses, so anything the fixture lacks fails
+fn replay() -> Web3<FixtureTransport<MockTransport>> {
+    Web3::new(FixtureTransport::replay(MockTransport::new(), FIXTURE).unwrap())
+}
+
+#[tokio::test]
+async fn replays_a_recorded_analysis() {
+    let analysis = analyze_block(&replay(), Some(7), 4).await.unwrap();
+    assert_eq!((analysis.chain_id, analysis.block_info.block_number, analysis.baseline_block), (1, 7, Some(6)));
+    let statuses: Vec<TransactionStatus> = analysis.block_info.transactions.iter().map(|tx| tx.status).collect();
+    assert_eq!(statuses, [TransactionStatus::Success, TransactionStatus::Reverted]);
+    assert_eq!(analysis.summary.reverted_transactions, 1);
+    assert_eq!(analysis.state_changes.as_ref().map(Vec::len), Some(4));
+    assert!(analysis.warnings.is_empty(), "{:?}", analysis.warnings);
+}
+
+#[tokio::test]
+async fn an_unrecorded_request_fails() {
+    let error = analyze_block(&replay(), Some(8), 4).await.unwrap_err().to_string();
+    assert!(error.contains(&format!("{} has no recorded response for eth_getBlockByNumber", FIXTURE)), "{}", error);
+}